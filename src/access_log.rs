@@ -0,0 +1,255 @@
+//! Access log, with a line per request in the Combined Log Format used by Apache and nginx,
+//! so that traffic can be analyzed with standard tools.
+//!
+//! ```text
+//! ::1 - - [16/Oct/2026:02:13:03 +0000] "GET /index.html HTTP/1.1" 200 2326 "-" "curl/8.5.0"
+//! ```
+//!
+//! Times are in UTC. The number of bytes is that of the response body as sent,
+//! so it is the compressed size for compressed responses, and it is logged once the body
+//! has been sent, or the client has gone away.
+//!
+//! Requests to the project server are also sent to the status web-ui, to show in its request log.
+
+use crate::event::{self, Event};
+use bytes::Buf;
+use hyper::{
+    body::{Body, Frame, SizeHint},
+    header, HeaderMap, Method, Uri, Version,
+};
+use std::{
+    fmt::Write as _,
+    fs::OpenOptions,
+    io::{self, LineWriter, Write},
+    net::IpAddr,
+    path::Path,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+/// Where access log lines are written.
+pub struct AccessLog {
+    writer: Mutex<LineWriter<Box<dyn Write + Send>>>,
+}
+
+impl AccessLog {
+    /// Open the access log, appending to the file at `target`, or writing to stdout if it is `-`.
+    pub fn open(target: &Path) -> io::Result<Self> {
+        let writer: Box<dyn Write + Send> = if target == Path::new("-") {
+            Box::new(io::stdout())
+        } else {
+            Box::new(OpenOptions::new().create(true).append(true).open(target)?)
+        };
+        Ok(Self {
+            writer: Mutex::new(LineWriter::new(writer)),
+        })
+    }
+
+    fn write(&self, entry: &AccessLogEntry, bytes_sent: u64) {
+        let line = entry.format(bytes_sent);
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writeln!(writer, "{line}") {
+            warn!(err = ?e, "Failed to write to access log.");
+        }
+    }
+}
+
+/// What is logged about a request, apart from the size of the response body.
+#[derive(Debug, Clone)]
+pub struct AccessLogEntry {
+    pub peer_ip: IpAddr,
+    pub time: SystemTime,
+    /// When the request came in, to tell how long it took to handle and send the response.
+    pub started: Instant,
+    pub method: String,
+    /// Path and query of the request.
+    pub target: String,
+    pub request_line: String,
+    pub status: u16,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl AccessLogEntry {
+    /// Collect what is logged about a request, before it is handled.
+    pub fn for_request(
+        peer_ip: IpAddr,
+        method: &Method,
+        uri: &Uri,
+        version: Version,
+        headers: &HeaderMap,
+    ) -> Self {
+        let target = uri.path_and_query().map_or("/", |pq| pq.as_str());
+        let header_text = |name| {
+            headers
+                .get(name)
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        };
+        Self {
+            peer_ip,
+            time: SystemTime::now(),
+            started: Instant::now(),
+            method: method.to_string(),
+            target: target.to_string(),
+            request_line: format!("{method} {target} {version:?}"),
+            status: 0,
+            referer: header_text(header::REFERER),
+            user_agent: header_text(header::USER_AGENT),
+        }
+    }
+
+    /// Format as a line in the Combined Log Format.
+    pub fn format(&self, bytes_sent: u64) -> String {
+        let mut line = format!(
+            "{} - - [{}] {} {} ",
+            self.peer_ip,
+            clf_time(self.time),
+            quoted(&self.request_line),
+            self.status
+        );
+        if bytes_sent == 0 {
+            line.push('-');
+        } else {
+            let _ = write!(line, "{bytes_sent}");
+        }
+        let _ = write!(
+            line,
+            " {} {}",
+            quoted(self.referer.as_deref().unwrap_or("-")),
+            quoted(self.user_agent.as_deref().unwrap_or("-"))
+        );
+        line
+    }
+}
+
+/// Quote a field, escaping what could otherwise break up the line or the field.
+fn quoted(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\x{:02x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Format a time like `16/Oct/2026:02:13:03 +0000`.
+fn clf_time(time: SystemTime) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+    // Convert days since the epoch to a civil date,
+    // per <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{day:02}/{}/{year}:{:02}:{:02}:{:02} +0000",
+        MONTHS[month as usize - 1],
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// A response body that counts the bytes sent, and writes the access log line
+/// once it has been sent in full, or is dropped before that.
+pub struct LoggedBody<B> {
+    inner: B,
+    entry: Option<AccessLogEntry>,
+    access_log: Option<&'static AccessLog>,
+    publish: bool,
+    bytes_sent: u64,
+}
+
+impl<B> LoggedBody<B> {
+    /// Wrap a response body. The entry is written to `access_log`, if there is one,
+    /// and sent to the status web-ui if `publish` is set. Nothing is logged if `entry` is `None`.
+    pub fn new(
+        inner: B,
+        entry: Option<AccessLogEntry>,
+        access_log: Option<&'static AccessLog>,
+        publish: bool,
+    ) -> Self {
+        Self {
+            inner,
+            entry,
+            access_log,
+            publish,
+            bytes_sent: 0,
+        }
+    }
+
+    fn finish(&mut self) {
+        let Some(entry) = self.entry.take() else {
+            return;
+        };
+        if let Some(access_log) = self.access_log {
+            access_log.write(&entry, self.bytes_sent);
+        }
+        if self.publish {
+            event::publish_status(Event::Request {
+                duration_ms: entry.started.elapsed().as_millis() as u64,
+                method: entry.method,
+                path: entry.target,
+                status: entry.status,
+                bytes: self.bytes_sent,
+            });
+        }
+    }
+}
+
+impl<B: Body + Unpin> Body for LoggedBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let res = Pin::new(&mut self.inner).poll_frame(cx);
+        match &res {
+            Poll::Ready(Some(Ok(frame))) => {
+                if let Some(data) = frame.data_ref() {
+                    self.bytes_sent += data.remaining() as u64;
+                }
+            }
+            Poll::Ready(None) => self.finish(),
+            _ => {}
+        }
+        res
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl<B> Drop for LoggedBody<B> {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
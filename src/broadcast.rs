@@ -0,0 +1,59 @@
+//! Broadcasting of messages to any number of subscribers, like the event streams
+//! of the pages that are open in browsers.
+//!
+//! Subscribers that fall too far behind miss messages rather than hold up the others,
+//! and subscribers that have gone away are dropped on the next broadcast.
+
+use smol::channel::{bounded, Receiver, Sender, TrySendError};
+use std::{fmt::Debug, sync::Mutex};
+use tracing::{debug, warn};
+
+/// Sends each message to all subscribers.
+#[derive(Debug)]
+pub struct Broadcast<T> {
+    /// What is being broadcast, for logging.
+    name: &'static str,
+    /// How many messages a subscriber can fall behind by before it starts missing messages.
+    capacity: usize,
+    subscribers: Mutex<Vec<Sender<T>>>,
+}
+
+impl<T: Clone + Debug> Broadcast<T> {
+    pub const fn new(name: &'static str, capacity: usize) -> Self {
+        Self {
+            name,
+            capacity,
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Subscribe to messages. The subscription ends when the receiver is dropped.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let (tx, rx) = bounded(self.capacity);
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        subscribers.push(tx);
+        debug!(
+            name = self.name,
+            n_subscribers = subscribers.len(),
+            "New subscriber to broadcast."
+        );
+        rx
+    }
+
+    /// Send a message to all subscribers, dropping subscribers that have gone away.
+    pub fn send(&self, message: T) {
+        let mut subscribers = self.subscribers.lock().unwrap_or_else(|e| e.into_inner());
+        subscribers.retain(|tx| match tx.try_send(message.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                warn!(
+                    name = self.name,
+                    ?message,
+                    "Broadcast subscriber is lagging behind. Dropping message."
+                );
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        });
+    }
+}
@@ -0,0 +1,1042 @@
+//! The build runner runs a build pipeline when source files change, so that editing, building
+//! and reloading happen in one loop. The pipeline is either a single build command, as given
+//! with `--exec`, or named steps from the config file, which are run in order:
+//!
+//! ```toml
+//! [build]
+//! policy = "cancel"
+//!
+//! [[build.steps]]
+//! name = "compile"
+//! command = "tsc"
+//!
+//! [[build.steps]]
+//! name = "bundle"
+//! command = "esbuild src/main.js --bundle --outdir=out"
+//!
+//! [[build.steps]]
+//! name = "content"
+//! command = "node fetch-content.js | tee fetched.log"
+//! dir = "tools"
+//! shell = "bash -eo pipefail"
+//! env = { CMS_SPACE = "staging" }
+//! secrets = ["CMS_TOKEN"]
+//! ```
+//!
+//! Each step can be run in a directory and with a shell of its own, with environment variables
+//! set on top of those of http-horse. The values of the environment variables named as secrets,
+//! whether set for the step or by the environment of http-horse, are masked in the output of
+//! the step, wherever it goes.
+//!
+//! Reloads are gated on builds while there is a build pipeline, as with a build marker, so that
+//! changes to build output are held for as long as it runs. Once every step has exited
+//! successfully, a marker tempfile is created in each project directory, and the held reloads are
+//! released when the FS events for the marker tempfiles have come through. The FS events for what
+//! the steps wrote came before them, so those changes are among the held reloads by then.
+//! See [`crate::fs::marker`].
+//!
+//! When a step fails, the steps after it are skipped, and the reloads stay held, so that pages
+//! are not reloaded with the output of a broken build. The failure is shown in the error overlay.
+//! See [`crate::overlay`].
+//!
+//! Each command runs in a process group of its own, which is stopped along with http-horse,
+//! so that no watch-mode compilers or the like are left running after it. See
+//! [`BuildRunner::shutdown`].
+//!
+//! What the steps write to stdout and stderr is passed through to the terminal, sent to the
+//! status web-ui as it comes, and kept for each run, in memory and in a log file in the cache
+//! directory.
+//!
+//! Finished runs are recorded in a build history file in the cache directory, with what triggered
+//! them, how long they took, how they exited and how much output they wrote, so that the build
+//! timeline and success rate in the status web-ui survive restarts of http-horse. Like the event
+//! history journal, the build history is specific to the set of project directories being served,
+//! and compacted when opened. See [`crate::journal`].
+
+use crate::{
+    diagnostics,
+    event::{self, Event},
+    fs::marker::create_marker_tempfile,
+    journal::project_set_file_stem,
+    overlay::{ErrorReport, ERROR_REPORTS},
+    reload::RELOAD_CHANNEL,
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet, VecDeque},
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Condvar, Mutex, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, error, info, warn};
+
+/// How often to check whether the build command has exited, while waiting for source changes
+/// that come in as it runs.
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long to wait for more source changes before running the build pipeline, by default.
+pub const DEFAULT_BUILD_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// How long a cancelled build command gets to stop after being asked to,
+/// before it is killed.
+const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// How long the build command gets to stop when http-horse shuts down, before it is killed.
+/// Short, since shutdown waits for it.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// How long to wait for the FS events of the marker tempfiles after a successful build,
+/// before releasing the held reloads anyway.
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Shell that the commands of steps are run in, unless they have another one.
+const DEFAULT_SHELL: &str = "sh";
+
+/// What the values of secrets are replaced by in the output of steps.
+const SECRET_MASK: &str = "********";
+
+/// Key of build failures in the error overlay.
+const OVERLAY_KEY: &str = "build";
+
+/// Number of runs whose output is kept in memory, and of log files kept in the cache directory.
+pub const MAX_BUILD_RUNS: usize = 50;
+
+/// Number of finished runs kept in the build history, both on disk and in memory.
+pub const MAX_BUILD_HISTORY: usize = 1000;
+
+/// Most output kept in memory for a run, in bytes. The log file has all of it.
+const MAX_OUTPUT_LEN: usize = 1024 * 1024;
+
+/// Output is sent to the status web-ui as it comes, but once this much has piled up
+/// at the latest, so that a chatty command does not send an event per line.
+const OUTPUT_BATCH_LEN: usize = 16 * 1024;
+
+/// Number of lines at the end of the output of a failed run to show in the error overlay.
+const OVERLAY_OUTPUT_LINES: usize = 40;
+
+/// Name of the subdirectory of the cache directory that build logs are kept in.
+pub const BUILD_LOG_SUBDIR_NAME: &str = "builds";
+
+/// The build runner, if there is a build pipeline.
+pub static BUILD_RUNNER: OnceLock<BuildRunner> = OnceLock::new();
+
+/// What to do about source changes that come in while the build pipeline is running.
+#[derive(ValueEnum, Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildPolicy {
+    /// Run the pipeline once more when it is done
+    #[default]
+    Queue,
+    /// Stop the pipeline, and run it anew
+    Cancel,
+    /// Let the pipeline finish, and do not run it again for the changes
+    Ignore,
+}
+
+/// A step of the build pipeline.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildStep {
+    pub name: String,
+    /// Command to run in a shell.
+    pub command: String,
+    /// Directory to run the command in, relative to the working directory of http-horse,
+    /// or `None` for the working directory of http-horse itself. For steps from the project
+    /// config file, relative to the directory of that. See [`crate::config::Config::load_project`].
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    /// Shell to run the command in, with any arguments to it, like `bash -eo pipefail`,
+    /// or `None` for [`DEFAULT_SHELL`]. The command is passed to it with `-c`.
+    #[serde(default)]
+    pub shell: Option<String>,
+    /// Environment variables to set for the command, on top of those of http-horse.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Names of environment variables whose values are masked in the output of the command.
+    #[serde(default)]
+    pub secrets: Vec<String>,
+}
+
+impl BuildStep {
+    /// The values of the secrets of the step, from its environment variables,
+    /// or else from those of http-horse.
+    fn secret_values(&self) -> Vec<String> {
+        self.secrets
+            .iter()
+            .filter_map(|name| {
+                self.env
+                    .get(name)
+                    .cloned()
+                    .or_else(|| std::env::var(name).ok())
+            })
+            .filter(|value| !value.is_empty())
+            .collect()
+    }
+
+    /// The command, with the values of secrets in it masked, for showing.
+    fn masked_command(&self) -> String {
+        mask_secrets(&self.command, &self.secret_values()).into_owned()
+    }
+}
+
+/// The build pipeline as written in the config file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildConfig {
+    pub steps: Vec<BuildStep>,
+    pub policy: Option<BuildPolicy>,
+    /// Duration, like `200ms`. See [`crate::throttle::parse_duration`].
+    pub debounce: Option<String>,
+}
+
+/// How the build pipeline is run.
+#[derive(Debug, Clone)]
+pub struct BuildSettings {
+    /// Steps to run in order, until one of them fails.
+    pub steps: Vec<BuildStep>,
+    pub policy: BuildPolicy,
+    /// How long to wait for more source changes before running the pipeline,
+    /// since editors and version control tools often change several files at once.
+    pub debounce: Duration,
+}
+
+/// Output stream of the build command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// How far a step of the build pipeline got in a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StepState {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    /// Not run, for an earlier step having failed or been cancelled.
+    Skipped,
+}
+
+/// A step in a run of the build pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepRun {
+    pub name: String,
+    pub state: StepState,
+    /// How long the step ran for, or `None` if it has not finished.
+    pub duration_ms: Option<u64>,
+    pub exit_code: Option<i32>,
+}
+
+/// A run of the build pipeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildRun {
+    /// Runs are numbered from one. Numbers keep increasing across restarts,
+    /// for as long as the build history is kept.
+    pub run: u64,
+    /// Path of the source file whose change had the pipeline run.
+    pub trigger: String,
+    /// Milliseconds since the Unix epoch.
+    pub started_ms: u64,
+    /// How long the pipeline ran for, or `None` while it is running.
+    pub duration_ms: Option<u64>,
+    pub success: bool,
+    /// Whether the pipeline was stopped for source changes that came in as it ran.
+    pub cancelled: bool,
+    /// Exit code of the last step that was run, or `None` if it is running, could not be run,
+    /// or was killed by a signal.
+    pub exit_code: Option<i32>,
+    pub steps: Vec<StepRun>,
+    /// Bytes of output, including what is not kept in memory.
+    pub output_len: u64,
+    /// Output of the steps, with stdout and stderr interleaved as they came.
+    #[serde(skip)]
+    pub output: String,
+    /// Log file that the output was written to, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct BuildRunner {
+    settings: BuildSettings,
+    project_dirs: Vec<PathBuf>,
+    /// Directory to write a log file for each run to, if any.
+    log_dir: Option<PathBuf>,
+    triggers: Sender<PathBuf>,
+    /// The most recent runs, oldest first, with those from the build history.
+    runs: Mutex<VecDeque<BuildRun>>,
+    /// Build history file that finished runs are appended to, if any.
+    history: Mutex<Option<File>>,
+    /// Process group of the command that is running, if any.
+    running: Mutex<Option<u32>>,
+    /// Set on shutdown, after which no more commands are run.
+    stopping: AtomicBool,
+    /// Paths of the marker tempfiles created after the last successful build,
+    /// whose FS events have not come through yet.
+    settling: Mutex<HashSet<PathBuf>>,
+    settled: Condvar,
+}
+
+impl BuildRunner {
+    /// A build runner for a pipeline that writes to the given project directories,
+    /// and the receiving end of its triggers, to be passed to [`Self::run`].
+    /// Logs of runs and the build history are written to `log_dir`, if given.
+    pub fn new(
+        settings: BuildSettings,
+        project_dirs: Vec<PathBuf>,
+        log_dir: Option<PathBuf>,
+    ) -> (Self, Receiver<PathBuf>) {
+        let (triggers, trigger_rx) = mpsc::channel();
+        // The build history is a nice-to-have, like the logs, so failures are only logged.
+        let (runs, history) = match &log_dir {
+            Some(log_dir) => match open_history(log_dir, &project_dirs) {
+                Ok((runs, file)) => (runs, Some(file)),
+                Err(e) => {
+                    warn!(err = ?e, ?log_dir, "Failed to open build history.");
+                    (VecDeque::new(), None)
+                }
+            },
+            None => (VecDeque::new(), None),
+        };
+        let runner = Self {
+            settings,
+            project_dirs,
+            log_dir,
+            triggers,
+            runs: Mutex::new(runs),
+            history: Mutex::new(history),
+            running: Mutex::new(None),
+            stopping: AtomicBool::new(false),
+            settling: Mutex::new(HashSet::new()),
+            settled: Condvar::new(),
+        };
+        (runner, trigger_rx)
+    }
+
+    pub fn settings(&self) -> &BuildSettings {
+        &self.settings
+    }
+
+    /// The most recent runs, oldest first, with those from the build history.
+    pub fn runs(&self) -> Vec<BuildRun> {
+        let runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+        runs.iter().cloned().collect()
+    }
+
+    /// A run, if it is among the most recent ones.
+    pub fn get(&self, run: u64) -> Option<BuildRun> {
+        let runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+        runs.iter().find(|r| r.run == run).cloned()
+    }
+
+    /// Output of a run, from memory for the most recent runs, and from its log file
+    /// for older ones, for as long as it is kept.
+    pub fn output(&self, run: u64) -> Option<String> {
+        let build_run = self.get(run)?;
+        if build_run.output.len() as u64 == build_run.output_len {
+            return Some(build_run.output);
+        }
+        let log_file = build_run.log_file?;
+        let mut output = Vec::new();
+        match File::open(&log_file)
+            .and_then(|file| file.take(MAX_OUTPUT_LEN as u64).read_to_end(&mut output))
+        {
+            Ok(_) => Some(String::from_utf8_lossy(&output).into_owned()),
+            Err(e) => {
+                debug!(run, err = ?e, ?log_file, "Build log file is gone.");
+                None
+            }
+        }
+    }
+
+    /// Have the build pipeline run, for a change to a source file at the given path.
+    pub fn trigger(&self, path: &Path) {
+        if self.triggers.send(path.to_path_buf()).is_err() {
+            warn!(?path, "Build runner has stopped. Not running build.");
+        }
+    }
+
+    /// Run the build pipeline each time it is triggered, until every trigger is gone.
+    /// Triggers that come in while it runs are dealt with according to the policy.
+    pub fn run(&self, trigger_rx: &Receiver<PathBuf>) {
+        let mut n_runs = {
+            let runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+            runs.back().map_or(0, |build_run| build_run.run)
+        };
+        let mut next = None;
+        loop {
+            let trigger = match next.take() {
+                Some(trigger) => trigger,
+                None => match trigger_rx.recv() {
+                    Ok(trigger) => trigger,
+                    Err(_) => return,
+                },
+            };
+            let mut n_triggers = 1;
+            while trigger_rx.recv_timeout(self.settings.debounce).is_ok() {
+                n_triggers += 1;
+            }
+            if self.stopping.load(Ordering::SeqCst) {
+                return;
+            }
+            n_runs += 1;
+            debug!(run = n_runs, n_triggers, "Build triggered.");
+            next = self.run_once(n_runs, &trigger, trigger_rx);
+            if self.stopping.load(Ordering::SeqCst) {
+                return;
+            }
+        }
+    }
+
+    /// Stop the command that is running, if any, along with what it started, and run no more.
+    /// Waits for up to [`SHUTDOWN_GRACE_PERIOD`] for the process group of the command to be gone
+    /// after asking it to stop, and kills what is left of it then.
+    pub fn shutdown(&self) {
+        let pgid = {
+            // Held while stopping is set, so that no command can be started after it without being seen.
+            let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+            self.stopping.store(true, Ordering::SeqCst);
+            *running
+        };
+        let Some(pgid) = pgid else {
+            return;
+        };
+        info!(pgid, "Stopping build command.");
+        signal_process_group(pgid, libc::SIGTERM);
+        let t_start = Instant::now();
+        while process_group_exists(pgid) {
+            if t_start.elapsed() >= SHUTDOWN_GRACE_PERIOD {
+                warn!(pgid, "Build command did not stop in time. Killing it.");
+                signal_process_group(pgid, libc::SIGKILL);
+                return;
+            }
+            std::thread::sleep(CHILD_POLL_INTERVAL);
+        }
+        debug!(pgid, "Build command stopped.");
+    }
+
+    /// Run the steps of the build pipeline once, in order, until one of them fails,
+    /// and return the source change to run the pipeline for next, if any.
+    fn run_once(
+        &self,
+        run: u64,
+        trigger: &Path,
+        trigger_rx: &Receiver<PathBuf>,
+    ) -> Option<PathBuf> {
+        info!(run, ?trigger, "Running build.");
+        let started_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let log = self.create_log_file(run, started_ms);
+        {
+            let mut runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+            if runs.len() == MAX_BUILD_HISTORY {
+                runs.pop_front();
+            }
+            // Output is kept in memory for the most recent runs only. Older runs have it
+            // in their log files, for as long as those are kept.
+            if let Some(i) = runs.len().checked_sub(MAX_BUILD_RUNS) {
+                runs[i].output = String::new();
+            }
+            runs.push_back(BuildRun {
+                run,
+                trigger: trigger.display().to_string(),
+                started_ms,
+                duration_ms: None,
+                success: false,
+                cancelled: false,
+                exit_code: None,
+                steps: self
+                    .settings
+                    .steps
+                    .iter()
+                    .map(|step| StepRun {
+                        name: step.name.clone(),
+                        state: StepState::Pending,
+                        duration_ms: None,
+                        exit_code: None,
+                    })
+                    .collect(),
+                output_len: 0,
+                output: String::new(),
+                log_file: log.as_ref().map(|(path, _)| path.clone()),
+            });
+        }
+        event::publish_status(Event::BuildRunStarted {
+            run,
+            trigger: trigger.display().to_string(),
+            steps: self
+                .settings
+                .steps
+                .iter()
+                .map(|step| step.name.clone())
+                .collect(),
+        });
+        let log = log.map(|(_, file)| Mutex::new(file));
+        let t_start = Instant::now();
+        let mut next = None;
+        let mut outcome = None;
+        for (i, step) in self.settings.steps.iter().enumerate() {
+            info!(
+                run,
+                step = step.name,
+                command = %step.masked_command(),
+                "Running build step."
+            );
+            self.update_run(run, |build_run| {
+                build_run.steps[i].state = StepState::Running
+            });
+            event::publish_status(Event::BuildStepStarted {
+                run,
+                step: i,
+                name: step.name.clone(),
+                command: step.masked_command(),
+            });
+            let t_step_start = Instant::now();
+            let (status, cancelled) = self.run_step(run, step, log.as_ref(), trigger_rx, &mut next);
+            let duration_ms = t_step_start.elapsed().as_millis() as u64;
+            let state = match &status {
+                _ if cancelled => StepState::Cancelled,
+                Ok(status) if status.success() => StepState::Succeeded,
+                _ => StepState::Failed,
+            };
+            let exit_code = status.as_ref().ok().and_then(ExitStatus::code);
+            self.update_run(run, |build_run| {
+                let step_run = &mut build_run.steps[i];
+                step_run.state = state;
+                step_run.duration_ms = Some(duration_ms);
+                step_run.exit_code = exit_code;
+            });
+            event::publish_status(Event::BuildStepFinished {
+                run,
+                step: i,
+                state,
+                exit_code,
+                duration_ms,
+            });
+            debug!(
+                run,
+                step = step.name,
+                ?state,
+                duration_ms,
+                "Build step finished."
+            );
+            if state != StepState::Succeeded {
+                outcome = Some((step, status, cancelled));
+                break;
+            }
+        }
+        let duration_ms = t_start.elapsed().as_millis() as u64;
+        let (success, cancelled, exit_code) = match &outcome {
+            None => (true, false, Some(0)),
+            Some((_, status, cancelled)) => (
+                false,
+                *cancelled,
+                status.as_ref().ok().and_then(ExitStatus::code),
+            ),
+        };
+        let finished = self.update_run(run, |build_run| {
+            build_run.duration_ms = Some(duration_ms);
+            build_run.success = success;
+            build_run.cancelled = cancelled;
+            build_run.exit_code = exit_code;
+            for step_run in &mut build_run.steps {
+                if step_run.state == StepState::Pending {
+                    step_run.state = StepState::Skipped;
+                }
+            }
+            build_run.clone()
+        });
+        if let Some(finished) = &finished {
+            self.record(finished);
+        }
+        event::publish_status(Event::BuildRunFinished {
+            run,
+            success,
+            cancelled,
+            exit_code,
+            duration_ms,
+        });
+        // Relative paths in the output are taken to be relative to the directory
+        // that the failed step was run in.
+        let step_dir = outcome.as_ref().and_then(|(step, _, _)| step.dir.clone());
+        let failure = match outcome {
+            None => {
+                info!(run, duration_ms, "Build succeeded.");
+                ERROR_REPORTS.set(OVERLAY_KEY, None);
+                self.settle();
+                RELOAD_CHANNEL.build_finished();
+                return next;
+            }
+            // The reloads stay held for the run that replaces it.
+            Some(_) if cancelled => {
+                info!(run, duration_ms, "Build was cancelled.");
+                return next;
+            }
+            Some((step, Ok(status), _)) => {
+                warn!(
+                    run,
+                    step = step.name,
+                    ?status,
+                    "Build step failed. Reloads are held until a build succeeds."
+                );
+                format!(
+                    "Step {} (`{}`) failed with {status}.",
+                    step.name,
+                    step.masked_command()
+                )
+            }
+            Some((step, Err(e), _)) => {
+                error!(run, step = step.name, err = ?e, "Failed to run build step.");
+                format!(
+                    "Failed to run step {} (`{}`): {e}",
+                    step.name,
+                    step.masked_command()
+                )
+            }
+        };
+        let output = finished
+            .map(|build_run| build_run.output)
+            .unwrap_or_default();
+        let diagnostics = std::env::current_dir()
+            .map(|dir| match step_dir {
+                Some(step_dir) => dir.join(step_dir),
+                None => dir,
+            })
+            .map(|dir| diagnostics::parse(&output, &dir))
+            .unwrap_or_default();
+        debug!(
+            run,
+            n_diagnostics = diagnostics.len(),
+            "Parsed errors in build output."
+        );
+        let lines = output.lines().collect::<Vec<_>>();
+        let tail = lines[lines.len().saturating_sub(OVERLAY_OUTPUT_LINES)..].join("\n");
+        ERROR_REPORTS.set(
+            OVERLAY_KEY,
+            Some(ErrorReport {
+                title: "Build failed".to_string(),
+                url_path: None,
+                output: if tail.is_empty() {
+                    failure
+                } else {
+                    format!("{failure}\n\n{tail}")
+                },
+                diagnostics,
+            }),
+        );
+        next
+    }
+
+    /// Run the command of a build step, and return its exit status, and whether it was cancelled.
+    fn run_step(
+        &self,
+        run: u64,
+        step: &BuildStep,
+        log: Option<&Mutex<File>>,
+        trigger_rx: &Receiver<PathBuf>,
+        next: &mut Option<PathBuf>,
+    ) -> (io::Result<ExitStatus>, bool) {
+        let mut command = shell_command(
+            &step.command,
+            step.shell.as_deref(),
+            step.dir.as_deref(),
+            &step.env,
+        );
+        let secrets = step.secret_values();
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        if self.stopping.load(Ordering::SeqCst) {
+            return (Err(io::Error::other("shutting down")), true);
+        }
+        // The command gets a process group of its own, so that it can be stopped along with
+        // whatever it starts.
+        let mut child = match command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => return (Err(e), false),
+        };
+        // The shell leads the process group, so its id is that of the group.
+        *running = Some(child.id());
+        drop(running);
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        std::thread::scope(|scope| {
+            if let Some(stdout) = stdout {
+                scope.spawn(|| self.capture(run, OutputStream::Stdout, stdout, &secrets, log));
+            }
+            if let Some(stderr) = stderr {
+                scope.spawn(|| self.capture(run, OutputStream::Stderr, stderr, &secrets, log));
+            }
+            let waited = self.wait(run, &mut child, trigger_rx, next);
+            *self.running.lock().unwrap_or_else(|e| e.into_inner()) = None;
+            waited
+        })
+    }
+
+    /// Wait for the command of a build step to exit, dealing with source changes that come in
+    /// meanwhile according to the policy. Returns its exit status, and whether it was cancelled.
+    fn wait(
+        &self,
+        run: u64,
+        child: &mut Child,
+        trigger_rx: &Receiver<PathBuf>,
+        next: &mut Option<PathBuf>,
+    ) -> (io::Result<ExitStatus>, bool) {
+        let mut t_cancelled = None;
+        // Commands stopped on shutdown count as cancelled, rather than as failed.
+        let cancelled = |t_cancelled: Option<Instant>| {
+            t_cancelled.is_some() || self.stopping.load(Ordering::SeqCst)
+        };
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) => return (Ok(status), cancelled(t_cancelled)),
+                Ok(None) => {}
+                Err(e) => return (Err(e), cancelled(t_cancelled)),
+            }
+            if t_cancelled.is_some_and(|t: Instant| t.elapsed() >= CANCEL_GRACE_PERIOD) {
+                warn!(
+                    run,
+                    "Cancelled build command did not stop in time. Killing it."
+                );
+                signal_process_group(child.id(), libc::SIGKILL);
+                return (child.wait(), true);
+            }
+            let trigger = match trigger_rx.recv_timeout(CHILD_POLL_INTERVAL) {
+                Ok(trigger) => trigger,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return (child.wait(), cancelled(t_cancelled))
+                }
+            };
+            match self.settings.policy {
+                BuildPolicy::Queue => {
+                    debug!(
+                        run,
+                        ?trigger,
+                        "Source changed while build is running. Running it again afterwards."
+                    );
+                    *next = Some(trigger);
+                }
+                BuildPolicy::Ignore => {
+                    debug!(
+                        run,
+                        ?trigger,
+                        "Source changed while build is running. Ignoring the change."
+                    );
+                }
+                BuildPolicy::Cancel => {
+                    if t_cancelled.is_none() {
+                        info!(
+                            run,
+                            ?trigger,
+                            "Source changed while build is running. Cancelling it."
+                        );
+                        signal_process_group(child.id(), libc::SIGTERM);
+                        t_cancelled = Some(Instant::now());
+                    }
+                    *next = Some(trigger);
+                }
+            }
+        }
+    }
+
+    /// Apply a change to a run that is among the most recent ones.
+    fn update_run<T>(&self, run: u64, f: impl FnOnce(&mut BuildRun) -> T) -> Option<T> {
+        let mut runs = self.runs.lock().unwrap_or_else(|e| e.into_inner());
+        runs.iter_mut().find(|r| r.run == run).map(f)
+    }
+
+    /// Append a finished run to the build history file.
+    fn record(&self, build_run: &BuildRun) {
+        let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(file) = history.as_mut() else {
+            return;
+        };
+        let written = serde_json::to_vec(build_run)
+            .map_err(io::Error::from)
+            .and_then(|mut line| {
+                line.push(b'\n');
+                file.write_all(&line)
+            });
+        if let Err(e) = written {
+            warn!(run = build_run.run, err = ?e, "Failed to record build run in build history.");
+        }
+    }
+
+    /// Create the log file for a run, and remove the oldest ones, past [`MAX_BUILD_RUNS`].
+    /// Logs are a nice-to-have, so failures are only logged.
+    fn create_log_file(&self, run: u64, started_ms: u64) -> Option<(PathBuf, File)> {
+        let log_dir = self.log_dir.as_ref()?;
+        let created = std::fs::create_dir_all(log_dir).and_then(|()| {
+            // Names start with the time of the run, so that they sort by it, across restarts.
+            let path = log_dir.join(format!("{started_ms:016}-{run}.log"));
+            let file = File::create(&path)?;
+            Ok((path, file))
+        });
+        match created {
+            Ok(log) => {
+                prune_log_files(log_dir);
+                Some(log)
+            }
+            Err(e) => {
+                warn!(err = ?e, ?log_dir, "Failed to create build log file.");
+                None
+            }
+        }
+    }
+
+    /// Pass through, send, and keep what the command writes to one of its output streams,
+    /// with the values of secrets masked.
+    fn capture(
+        &self,
+        run: u64,
+        stream: OutputStream,
+        output: impl Read,
+        secrets: &[String],
+        log: Option<&Mutex<File>>,
+    ) {
+        let mut reader = BufReader::new(output);
+        let mut line = Vec::new();
+        let mut pending = String::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!(run, ?stream, err = ?e, "Failed to read output of build command.");
+                    break;
+                }
+            }
+            // Output is read by the line, so that secrets are not split across reads.
+            let text = String::from_utf8_lossy(&line);
+            let text = mask_secrets(&text, secrets);
+            // Output without secrets in it is passed through as it is, even if it is not UTF-8.
+            let passed_through = match &text {
+                Cow::Borrowed(_) => &line[..],
+                Cow::Owned(masked) => masked.as_bytes(),
+            };
+            let _ = match stream {
+                OutputStream::Stdout => io::stdout().write_all(passed_through),
+                OutputStream::Stderr => io::stderr().write_all(passed_through),
+            };
+            pending.push_str(&text);
+            // Output that is already buffered is sent along with what is being sent.
+            if reader.buffer().is_empty() || pending.len() >= OUTPUT_BATCH_LEN {
+                self.append_output(run, stream, std::mem::take(&mut pending), log);
+            }
+        }
+        if !pending.is_empty() {
+            self.append_output(run, stream, pending, log);
+        }
+    }
+
+    fn append_output(
+        &self,
+        run: u64,
+        stream: OutputStream,
+        text: String,
+        log: Option<&Mutex<File>>,
+    ) {
+        if let Some(log) = log {
+            let mut log = log.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = log.write_all(text.as_bytes()) {
+                warn!(run, err = ?e, "Failed to write to build log file.");
+            }
+        }
+        self.update_run(run, |build_run| {
+            build_run.output_len += text.len() as u64;
+            if build_run.output.len() + text.len() <= MAX_OUTPUT_LEN {
+                build_run.output.push_str(&text);
+            }
+        });
+        event::publish_status(Event::BuildOutput { run, stream, text });
+    }
+
+    /// Wait until the FS events for what the build wrote have come through,
+    /// by creating a marker tempfile in each project directory and waiting for their FS events.
+    fn settle(&self) {
+        // The lock is held while the marker tempfiles are created, so that their FS events
+        // cannot come through before we are waiting for them.
+        let mut settling = self.settling.lock().unwrap_or_else(|e| e.into_inner());
+        let markers = self
+            .project_dirs
+            .iter()
+            .filter_map(|project_dir| {
+                create_marker_tempfile(project_dir)
+                    .inspect_err(|e| {
+                        warn!(
+                            err = ?e,
+                            ?project_dir,
+                            "Failed to create marker tempfile after build."
+                        )
+                    })
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+        settling.extend(markers.iter().map(|marker| marker.path().to_path_buf()));
+        let (mut settling, wait) = self
+            .settled
+            .wait_timeout_while(settling, SETTLE_TIMEOUT, |settling| !settling.is_empty())
+            .unwrap_or_else(|e| e.into_inner());
+        if wait.timed_out() {
+            warn!(
+                n_pending = settling.len(),
+                "Timed out waiting for FS events of marker tempfiles after build. Releasing reloads anyway."
+            );
+            settling.clear();
+        }
+    }
+
+    /// Check whether an FS event is for one of the marker tempfiles created after a build.
+    pub fn is_settling(&self, path: &Path) -> bool {
+        let settling = self.settling.lock().unwrap_or_else(|e| e.into_inner());
+        settling.contains(path)
+    }
+
+    /// Note that the FS event for a marker tempfile created after a build has come through.
+    /// Called for each FS event for a marker tempfile in a project directory.
+    pub fn marker_seen(&self, path: &Path) {
+        let mut settling = self.settling.lock().unwrap_or_else(|e| e.into_inner());
+        if settling.remove(path) && settling.is_empty() {
+            self.settled.notify_all();
+        }
+    }
+}
+
+/// A command that runs `command` in a shell, with any arguments to it, like `bash -eo pipefail`,
+/// or in [`DEFAULT_SHELL`]. It is run in `dir`, if given, and with `env` set on top of
+/// the environment of http-horse.
+pub fn shell_command(
+    command: &str,
+    shell: Option<&str>,
+    dir: Option<&Path>,
+    env: &BTreeMap<String, String>,
+) -> Command {
+    let mut shell = shell.unwrap_or(DEFAULT_SHELL).split_whitespace();
+    let mut shell_command = Command::new(shell.next().unwrap_or(DEFAULT_SHELL));
+    shell_command.args(shell).arg("-c").arg(command).envs(env);
+    if let Some(dir) = dir {
+        shell_command.current_dir(dir);
+    }
+    shell_command
+}
+
+/// Text with the values of secrets in it replaced by [`SECRET_MASK`].
+pub fn mask_secrets<'a>(text: &'a str, secrets: &[String]) -> Cow<'a, str> {
+    let mut masked = Cow::Borrowed(text);
+    for secret in secrets {
+        if masked.contains(secret.as_str()) {
+            masked = Cow::Owned(masked.replace(secret.as_str(), SECRET_MASK));
+        }
+    }
+    masked
+}
+
+/// Send a signal to the process group of a command, which is led by the shell that runs it,
+/// so that what the shell started gets the signal too.
+pub fn signal_process_group(pgid: u32, signal: libc::c_int) {
+    // SAFETY: kill has no preconditions, other than for the process group id to be ours to signal.
+    if unsafe { libc::kill(-(pgid as libc::pid_t), signal) } != 0 {
+        warn!(err = ?io::Error::last_os_error(), pgid, signal, "Failed to signal process group.");
+    }
+}
+
+/// Check whether any process is left in a process group, including what the shell that led it
+/// started, after the shell itself has exited.
+pub fn process_group_exists(pgid: u32) -> bool {
+    // SAFETY: As above. Signal 0 only checks whether the processes can be signalled.
+    unsafe { libc::kill(-(pgid as libc::pid_t), 0) == 0 }
+}
+
+/// Open the build history for a set of project directories, creating it if it does not exist,
+/// and load the runs in it, oldest first.
+///
+/// The history file is compacted if it has grown too long. Lines that cannot be parsed,
+/// like a last line that was only partially written, are skipped.
+fn open_history(
+    log_dir: &Path,
+    project_dirs: &[PathBuf],
+) -> io::Result<(VecDeque<BuildRun>, File)> {
+    std::fs::create_dir_all(log_dir)?;
+    let path = log_dir.join(format!("{}.jsonl", project_set_file_stem(project_dirs)));
+    let mut runs = VecDeque::new();
+    let mut n_lines = 0;
+    match File::open(&path) {
+        Ok(file) => {
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                n_lines += 1;
+                match serde_json::from_str::<BuildRun>(&line) {
+                    Ok(build_run) => {
+                        if runs.len() == MAX_BUILD_HISTORY {
+                            runs.pop_front();
+                        }
+                        runs.push_back(build_run);
+                    }
+                    Err(e) => warn!(err = ?e, ?path, "Skipping unparseable build history entry."),
+                }
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    if n_lines > runs.len() {
+        debug!(
+            n_lines,
+            n_kept = runs.len(),
+            ?path,
+            "Compacting build history file."
+        );
+        let mut compacted = tempfile::NamedTempFile::new_in(log_dir)?;
+        for build_run in &runs {
+            serde_json::to_writer(&mut compacted, build_run)?;
+            compacted.write_all(b"\n")?;
+        }
+        compacted.as_file().sync_all()?;
+        compacted.persist(&path).map_err(|e| e.error)?;
+    }
+
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    debug!(?path, n_runs = runs.len(), "Opened build history.");
+    Ok((runs, file))
+}
+
+/// Remove the oldest log files in a directory, past [`MAX_BUILD_RUNS`].
+fn prune_log_files(log_dir: &Path) {
+    let Ok(dir_entries) = std::fs::read_dir(log_dir) else {
+        return;
+    };
+    let mut log_files = dir_entries
+        .filter_map(|dir_entry| dir_entry.ok().map(|dir_entry| dir_entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .collect::<Vec<_>>();
+    log_files.sort();
+    let n_excess = log_files.len().saturating_sub(MAX_BUILD_RUNS);
+    for path in &log_files[..n_excess] {
+        if let Err(e) = std::fs::remove_file(path) {
+            warn!(err = ?e, ?path, "Failed to remove old build log file.");
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Errors that happened in the pages open in browsers, as reported by the reload client script:
+//! uncaught exceptions, unhandled promise rejections, and calls to `console.error`.
+//!
+//! They are kept in memory, for the status web-ui to show in its history,
+//! next to the changes that preceded them.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Number of errors kept.
+const MAX_CLIENT_ERRORS: usize = 1_000;
+
+/// Longest message, stack trace or URL kept, in bytes. Longer ones are truncated.
+const MAX_TEXT_LEN: usize = 8 * 1024;
+
+/// Largest report body accepted from the client script, in bytes.
+pub const MAX_REPORT_LEN: usize = 32 * 1024;
+
+/// What kind of error happened in the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClientErrorKind {
+    UncaughtException,
+    UnhandledRejection,
+    ConsoleError,
+}
+
+/// An error that happened in a page, as reported by the reload client script.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientError {
+    /// Milliseconds since the Unix epoch, when the error was received.
+    /// Set by us, rather than by the client, whose clock may be off.
+    #[serde(default)]
+    pub timestamp_ms: u64,
+    pub kind: ClientErrorKind,
+    /// URL of the page.
+    pub page: String,
+    pub message: String,
+    /// URL of the script that the error happened in, with line and column, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack: Option<String>,
+}
+
+/// The most recent errors reported by the pages open in browsers.
+#[derive(Debug)]
+pub struct ClientErrors {
+    entries: Mutex<VecDeque<ClientError>>,
+}
+
+/// Errors reported by the pages served by the project server.
+pub static CLIENT_ERRORS: ClientErrors = ClientErrors::new();
+
+impl ClientErrors {
+    pub const fn new() -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Record an error reported by a page.
+    pub fn record(&self, mut error: ClientError) {
+        error.timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let texts = [
+            Some(&mut error.page),
+            Some(&mut error.message),
+            error.source.as_mut(),
+            error.stack.as_mut(),
+        ];
+        for text in texts.into_iter().flatten() {
+            truncate(text, MAX_TEXT_LEN);
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == MAX_CLIENT_ERRORS {
+            entries.pop_front();
+        }
+        entries.push_back(error);
+    }
+
+    /// The most recent errors, oldest first.
+    pub fn recent(&self, n: usize) -> Vec<ClientError> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let skip = entries.len().saturating_sub(n);
+        entries.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Default for ClientErrors {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn truncate(text: &mut String, max_len: usize) {
+    if text.len() > max_len {
+        let mut len = max_len;
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+        text.truncate(len);
+        text.push('…');
+    }
+}
@@ -0,0 +1,496 @@
+//! Companion processes are long-running commands that are started along with http-horse and
+//! kept running for as long as it is, like `cargo watch`, or a dev server for an API that the
+//! pages talk to. They are declared in the config file:
+//!
+//! ```toml
+//! [[companions]]
+//! name = "api"
+//! command = "cargo run -p api"
+//! env = { PORT = "8081" }
+//!
+//! [[companions]]
+//! name = "css"
+//! command = "tailwindcss -i src/main.css -o out/main.css --watch"
+//! restart = "always"
+//! ```
+//!
+//! A companion that exits is restarted according to its restart policy, after a delay that
+//! grows for as long as it keeps exiting soon after being started, so that one that cannot
+//! start does not spin. Like build steps, each runs in a process group of its own, which is
+//! stopped along with http-horse, and can have a directory, shell, environment and secrets
+//! of its own. See [`crate::build`].
+//!
+//! What companions write to stdout and stderr is passed through to the terminal, with their
+//! names in front of each line, sent to the status web-ui as it comes, and the end of it kept
+//! in memory.
+
+use crate::{
+    build::{
+        mask_secrets, process_group_exists, shell_command, signal_process_group, OutputStream,
+    },
+    event::{self, Event},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::{Child, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Condvar, Mutex, OnceLock,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{debug, info, warn};
+
+/// How often to check whether a companion has exited.
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Delay before restarting a companion the first time in a row that it exits soon after
+/// being started. Doubled each time after, up to [`MAX_RESTART_DELAY`].
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+/// Companions that ran for at least this long before exiting are restarted right away,
+/// and have their restart delay reset.
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+/// How long companions get to stop when they are restarted or http-horse shuts down,
+/// before they are killed.
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
+/// Most output kept in memory for each companion, in bytes. The oldest lines go first.
+const MAX_OUTPUT_LEN: usize = 256 * 1024;
+
+/// The companion processes, if any are declared in the config file.
+pub static COMPANIONS: OnceLock<Companions> = OnceLock::new();
+
+/// When to restart a companion that has exited.
+#[derive(Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// Restart it if it exited with an error, or was killed by a signal.
+    #[default]
+    OnFailure,
+    /// Restart it however it exited.
+    Always,
+    /// Leave it exited.
+    Never,
+}
+
+/// A companion process as declared in the config file.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompanionConfig {
+    pub name: String,
+    /// Command to run in a shell.
+    pub command: String,
+    /// As for build steps. See [`crate::build::BuildStep`].
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub secrets: Vec<String>,
+    #[serde(default)]
+    pub restart: RestartPolicy,
+}
+
+/// What a companion is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CompanionState {
+    /// Not started yet.
+    Starting,
+    Running,
+    /// Exited, and waiting to be restarted.
+    Restarting,
+    /// Exited, and not to be restarted.
+    Exited,
+}
+
+/// The status of a companion, as shown in the status web-ui.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompanionStatus {
+    pub name: String,
+    /// The command, with the values of secrets in it masked.
+    pub command: String,
+    pub state: CompanionState,
+    /// Process id of the shell that runs the command, while it runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// When it was last started, in milliseconds since the Unix epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_ms: Option<u64>,
+    /// Times it has been restarted since http-horse was started.
+    pub restarts: u64,
+    /// Exit code of the last time it exited, if it has, and was not killed by a signal.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+#[derive(Debug)]
+struct Companion {
+    config: CompanionConfig,
+    secrets: Vec<String>,
+    status: Mutex<CompanionStatus>,
+    /// The end of the output, with stdout and stderr interleaved as they came.
+    output: Mutex<String>,
+    /// Set to have the companion restarted right away, whatever its restart policy.
+    restart_requested: AtomicBool,
+    /// Notified when a restart is requested, or on shutdown, to cut the restart delay short.
+    wake: Condvar,
+}
+
+/// The companion processes, each kept running by a thread of its own.
+#[derive(Debug)]
+pub struct Companions {
+    companions: Vec<Companion>,
+    /// Set on shutdown, after which companions are not restarted.
+    stopping: AtomicBool,
+}
+
+/// Milliseconds since the Unix epoch.
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl Companions {
+    pub fn new(configs: Vec<CompanionConfig>) -> Self {
+        let companions = configs
+            .into_iter()
+            .map(|config| {
+                let secrets = config
+                    .secrets
+                    .iter()
+                    .filter_map(|name| {
+                        config
+                            .env
+                            .get(name)
+                            .cloned()
+                            .or_else(|| std::env::var(name).ok())
+                    })
+                    .filter(|value| !value.is_empty())
+                    .collect::<Vec<_>>();
+                let status = CompanionStatus {
+                    name: config.name.clone(),
+                    command: mask_secrets(&config.command, &secrets).into_owned(),
+                    state: CompanionState::Starting,
+                    pid: None,
+                    started_ms: None,
+                    restarts: 0,
+                    exit_code: None,
+                };
+                Companion {
+                    config,
+                    secrets,
+                    status: Mutex::new(status),
+                    output: Mutex::new(String::new()),
+                    restart_requested: AtomicBool::new(false),
+                    wake: Condvar::new(),
+                }
+            })
+            .collect();
+        Self {
+            companions,
+            stopping: AtomicBool::new(false),
+        }
+    }
+
+    /// Start each companion, and keep it running, on a thread of its own.
+    /// Must be called on the [`COMPANIONS`] static, for the threads to borrow it.
+    pub fn start(&'static self) {
+        for companion in &self.companions {
+            let name = companion.config.name.clone();
+            let spawned = std::thread::Builder::new()
+                .name(format!("companion {name}"))
+                .spawn(move || self.supervise(companion));
+            if let Err(e) = spawned {
+                warn!(name, err = ?e, "Failed to spawn thread for companion process.");
+            }
+        }
+    }
+
+    /// The status of each companion, in the order that they are declared in.
+    pub fn statuses(&self) -> Vec<CompanionStatus> {
+        self.companions
+            .iter()
+            .map(|companion| {
+                let status = companion.status.lock().unwrap_or_else(|e| e.into_inner());
+                status.clone()
+            })
+            .collect()
+    }
+
+    /// The end of the output of a companion, if there is one by the name.
+    pub fn output(&self, name: &str) -> Option<String> {
+        let companion = self.get(name)?;
+        let output = companion.output.lock().unwrap_or_else(|e| e.into_inner());
+        Some(output.clone())
+    }
+
+    /// Have a companion stopped and started anew, or started again if it has exited.
+    /// Returns `false` if there is none by the name.
+    pub fn restart(&self, name: &str) -> bool {
+        let Some(companion) = self.get(name) else {
+            return false;
+        };
+        info!(name, "Restart of companion process requested.");
+        // Held while the flag is set, so that the notification cannot come in between
+        // the supervising thread checking the flag and waiting.
+        let _status = companion.status.lock().unwrap_or_else(|e| e.into_inner());
+        companion.restart_requested.store(true, Ordering::SeqCst);
+        companion.wake.notify_all();
+        true
+    }
+
+    /// Stop every companion, along with what it started, and restart none of them.
+    /// Waits for up to [`STOP_GRACE_PERIOD`] for their process groups to be gone after asking
+    /// them to stop, and kills what is left of them then.
+    pub fn shutdown(&self) {
+        let mut pgids = Vec::new();
+        for companion in &self.companions {
+            // Held while stopping is set, so that no companion can be started after it without being seen.
+            let status = companion.status.lock().unwrap_or_else(|e| e.into_inner());
+            self.stopping.store(true, Ordering::SeqCst);
+            pgids.extend(status.pid);
+            companion.wake.notify_all();
+        }
+        for &pgid in &pgids {
+            info!(pgid, "Stopping companion process.");
+            signal_process_group(pgid, libc::SIGTERM);
+        }
+        let t_start = Instant::now();
+        loop {
+            pgids.retain(|&pgid| process_group_exists(pgid));
+            if pgids.is_empty() {
+                debug!("Companion processes stopped.");
+                return;
+            }
+            if t_start.elapsed() >= STOP_GRACE_PERIOD {
+                for &pgid in &pgids {
+                    warn!(pgid, "Companion process did not stop in time. Killing it.");
+                    signal_process_group(pgid, libc::SIGKILL);
+                }
+                return;
+            }
+            std::thread::sleep(CHILD_POLL_INTERVAL);
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&Companion> {
+        self.companions
+            .iter()
+            .find(|companion| companion.config.name == name)
+    }
+
+    /// Run a companion, and run it again each time it exits, for as long as its restart policy
+    /// says to, until shutdown.
+    fn supervise(&self, companion: &Companion) {
+        let name = companion.config.name.as_str();
+        let mut restart_delay = MIN_RESTART_DELAY;
+        loop {
+            let t_start = Instant::now();
+            let exited = self.run(companion);
+            let uptime = t_start.elapsed();
+            if self.stopping.load(Ordering::SeqCst) {
+                return;
+            }
+            let requested = companion.restart_requested.swap(false, Ordering::SeqCst);
+            let failed = !exited.as_ref().is_ok_and(ExitStatus::success);
+            let restart = requested
+                || match companion.config.restart {
+                    RestartPolicy::Always => true,
+                    RestartPolicy::OnFailure => failed,
+                    RestartPolicy::Never => false,
+                };
+            let exit_code = exited.as_ref().ok().and_then(ExitStatus::code);
+            match &exited {
+                Ok(status) => info!(name, ?status, ?uptime, restart, "Companion process exited."),
+                Err(e) => warn!(name, err = ?e, restart, "Failed to run companion process."),
+            }
+            // Companions that keep exiting soon after being started are restarted ever more slowly.
+            let delay = if requested || uptime >= STABLE_UPTIME {
+                restart_delay = MIN_RESTART_DELAY;
+                Duration::ZERO
+            } else {
+                let delay = restart_delay;
+                restart_delay = (restart_delay * 2).min(MAX_RESTART_DELAY);
+                delay
+            };
+            {
+                let mut status = companion.status.lock().unwrap_or_else(|e| e.into_inner());
+                status.state = if restart {
+                    CompanionState::Restarting
+                } else {
+                    CompanionState::Exited
+                };
+                status.pid = None;
+                status.exit_code = exit_code;
+            }
+            event::publish_status(Event::CompanionExited {
+                name: name.to_string(),
+                exit_code,
+                restart_in_ms: restart.then_some(delay.as_millis() as u64),
+            });
+            // Wait for the delay to pass, or, for companions that are not to be restarted,
+            // until a restart is requested, if it ever is.
+            let status = companion.status.lock().unwrap_or_else(|e| e.into_inner());
+            let keep_waiting = |_: &mut CompanionStatus| {
+                !self.stopping.load(Ordering::SeqCst)
+                    && !companion.restart_requested.load(Ordering::SeqCst)
+            };
+            if restart {
+                let (status, _) = companion
+                    .wake
+                    .wait_timeout_while(status, delay, keep_waiting)
+                    .unwrap_or_else(|e| e.into_inner());
+                drop(status);
+            } else {
+                let status = companion
+                    .wake
+                    .wait_while(status, keep_waiting)
+                    .unwrap_or_else(|e| e.into_inner());
+                drop(status);
+            }
+            if self.stopping.load(Ordering::SeqCst) {
+                return;
+            }
+            companion.restart_requested.store(false, Ordering::SeqCst);
+            let mut status = companion.status.lock().unwrap_or_else(|e| e.into_inner());
+            status.restarts += 1;
+        }
+    }
+
+    /// Run a companion once, until it exits or is stopped, and return how it exited.
+    fn run(&self, companion: &Companion) -> io::Result<ExitStatus> {
+        let config = &companion.config;
+        let mut command = shell_command(
+            &config.command,
+            config.shell.as_deref(),
+            config.dir.as_deref(),
+            &config.env,
+        );
+        let mut child = {
+            let mut status = companion.status.lock().unwrap_or_else(|e| e.into_inner());
+            if self.stopping.load(Ordering::SeqCst) {
+                return Err(io::Error::other("shutting down"));
+            }
+            // The command gets a process group of its own, so that it can be stopped along with
+            // whatever it starts.
+            let child = command
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .process_group(0)
+                .spawn()?;
+            status.state = CompanionState::Running;
+            status.pid = Some(child.id());
+            status.started_ms = Some(now_ms());
+            status.exit_code = None;
+            child
+        };
+        info!(
+            name = config.name,
+            pid = child.id(),
+            "Started companion process."
+        );
+        event::publish_status(Event::CompanionStarted {
+            name: config.name.clone(),
+            pid: child.id(),
+        });
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        std::thread::scope(|scope| {
+            if let Some(stdout) = stdout {
+                scope.spawn(|| capture(companion, OutputStream::Stdout, stdout));
+            }
+            if let Some(stderr) = stderr {
+                scope.spawn(|| capture(companion, OutputStream::Stderr, stderr));
+            }
+            wait(companion, &mut child)
+        })
+    }
+}
+
+/// Wait for a companion to exit, stopping it if a restart is requested meanwhile.
+/// On shutdown, companions are stopped by [`Companions::shutdown`].
+fn wait(companion: &Companion, child: &mut Child) -> io::Result<ExitStatus> {
+    let mut t_stopped = None;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if companion.restart_requested.load(Ordering::SeqCst) {
+            match t_stopped {
+                None => {
+                    signal_process_group(child.id(), libc::SIGTERM);
+                    t_stopped = Some(Instant::now());
+                }
+                Some(t) if t.elapsed() >= STOP_GRACE_PERIOD => {
+                    warn!(
+                        name = companion.config.name,
+                        "Companion process did not stop in time for restart. Killing it."
+                    );
+                    signal_process_group(child.id(), libc::SIGKILL);
+                    return child.wait();
+                }
+                Some(_) => {}
+            }
+        }
+        std::thread::sleep(CHILD_POLL_INTERVAL);
+    }
+}
+
+/// Pass through, send, and keep what a companion writes to one of its output streams,
+/// with the values of secrets masked.
+fn capture(companion: &Companion, stream: OutputStream, output: impl Read) {
+    let name = companion.config.name.as_str();
+    let mut reader = BufReader::new(output);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                warn!(name, ?stream, err = ?e, "Failed to read output of companion process.");
+                break;
+            }
+        }
+        let text = String::from_utf8_lossy(&line);
+        let text = mask_secrets(&text, &companion.secrets).into_owned();
+        // Lines are told apart from those of other companions and of http-horse by the name.
+        let prefixed = format!("[{name}] {text}");
+        let _ = match stream {
+            OutputStream::Stdout => io::stdout().write_all(prefixed.as_bytes()),
+            OutputStream::Stderr => io::stderr().write_all(prefixed.as_bytes()),
+        };
+        {
+            let mut output = companion.output.lock().unwrap_or_else(|e| e.into_inner());
+            output.push_str(&text);
+            if output.len() > MAX_OUTPUT_LEN {
+                // Whole lines go, so that the first line kept is not cut off.
+                let excess = output.len() - MAX_OUTPUT_LEN;
+                let cut = output.as_bytes()[excess..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(output.len(), |i| excess + i + 1);
+                output.drain(..cut);
+            }
+        }
+        event::publish_status(Event::CompanionOutput {
+            name: name.to_string(),
+            stream,
+            text,
+        });
+    }
+}
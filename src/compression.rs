@@ -0,0 +1,151 @@
+//! On-the-fly compression of response bodies, negotiated with the `Accept-Encoding` request header.
+//!
+//! Large files are compressed as they are streamed, so that they never have to be buffered in full.
+
+use async_compression::{
+    futures::bufread::{BrotliEncoder, GzipEncoder, ZstdEncoder},
+    Level,
+};
+use smol::io::{AsyncBufRead, AsyncRead, AsyncReadExt, Cursor};
+use std::{pin::Pin, sync::OnceLock};
+
+/// Bodies smaller than this are not worth compressing.
+pub const MIN_COMPRESS_SIZE: u64 = 1024;
+
+/// Brotli quality level used unless configured otherwise. The highest levels are too slow
+/// for compressing on the fly, and the default level of the brotli encoder is the highest one.
+const DEFAULT_BROTLI_LEVEL: i32 = 4;
+
+/// Compression levels for each encoding. `None` means the default level of the encoding.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct CompressionLevels {
+    pub gzip: Option<i32>,
+    pub brotli: Option<i32>,
+    pub zstd: Option<i32>,
+}
+
+/// Compression levels to use. Set once, at program startup.
+pub static COMPRESSION_LEVELS: OnceLock<CompressionLevels> = OnceLock::new();
+
+/// Content codings that we can compress response bodies with,
+/// in order of increasing preference for when the client has no preference of its own.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Encoding {
+    Gzip,
+    Brotli,
+    Zstd,
+}
+
+impl Encoding {
+    /// Value of the `Content-Encoding` header for this encoding.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+        }
+    }
+
+    /// File name extension of precompressed files with this encoding.
+    pub fn file_extension(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gz",
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zst",
+        }
+    }
+
+    fn level(self) -> Level {
+        let levels = COMPRESSION_LEVELS.get().copied().unwrap_or_default();
+        let level = match self {
+            Encoding::Gzip => levels.gzip,
+            Encoding::Brotli => levels.brotli.or(Some(DEFAULT_BROTLI_LEVEL)),
+            Encoding::Zstd => levels.zstd,
+        };
+        level.map(Level::Precise).unwrap_or(Level::Default)
+    }
+}
+
+/// Pick the encoding to use, given the value of an `Accept-Encoding` request header.
+///
+/// Returns `None` if the client does not accept any of the encodings that we support.
+pub fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+    accepted_encodings(accept_encoding).into_iter().next()
+}
+
+/// List the encodings that we support that are accepted according to the value
+/// of an `Accept-Encoding` request header, most preferred first.
+pub fn accepted_encodings(accept_encoding: &str) -> Vec<Encoding> {
+    let mut accepted: Vec<(Encoding, f32)> = vec![];
+    for item in accept_encoding.split(',') {
+        let mut params = item.split(';').map(str::trim);
+        let coding = params.next().unwrap_or_default();
+        let q = params
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if q <= 0.0 {
+            continue;
+        }
+        let encoding = match coding.to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" | "*" => Encoding::Gzip,
+            "br" => Encoding::Brotli,
+            "zstd" => Encoding::Zstd,
+            _ => continue,
+        };
+        if !accepted.iter().any(|(e, _)| *e == encoding) {
+            accepted.push((encoding, q));
+        }
+    }
+    // Highest q-value first, and our own preference among equal q-values.
+    accepted.sort_by(|(a, a_q), (b, b_q)| b_q.total_cmp(a_q).then(b.cmp(a)));
+    accepted.into_iter().map(|(encoding, _)| encoding).collect()
+}
+
+/// Check whether content of a given MIME type benefits from compression.
+///
+/// Formats that are already compressed, like most image, audio and video formats, do not.
+pub fn is_compressible(content_type: &str) -> bool {
+    let mime_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime_type.starts_with("text/")
+        || mime_type.ends_with("+json")
+        || mime_type.ends_with("+xml")
+        || matches!(
+            mime_type.as_str(),
+            "application/json"
+                | "application/xml"
+                | "application/wasm"
+                | "image/svg+xml"
+                | "image/x-icon"
+                | "image/bmp"
+                | "font/ttf"
+                | "font/otf"
+        )
+}
+
+/// Wrap a reader so that reading from it yields the compressed contents of the inner reader.
+pub fn encode_reader<R>(reader: R, encoding: Encoding) -> Pin<Box<dyn AsyncRead + Send + Sync>>
+where
+    R: AsyncBufRead + Unpin + Send + Sync + 'static,
+{
+    let level = encoding.level();
+    match encoding {
+        Encoding::Gzip => Box::pin(GzipEncoder::with_quality(reader, level)),
+        Encoding::Brotli => Box::pin(BrotliEncoder::with_quality(reader, level)),
+        Encoding::Zstd => Box::pin(ZstdEncoder::with_quality(reader, level)),
+    }
+}
+
+/// Compress a body that we already have in memory.
+pub async fn encode_bytes(data: Vec<u8>, encoding: Encoding) -> std::io::Result<Vec<u8>> {
+    let mut encoded = vec![];
+    encode_reader(Cursor::new(data), encoding)
+        .read_to_end(&mut encoded)
+        .await?;
+    Ok(encoded)
+}
@@ -0,0 +1,46 @@
+//! Conditional requests, for letting clients revalidate what they have cached.
+//!
+//! See <https://www.rfc-editor.org/rfc/rfc9110#name-conditional-requests>.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Make a strong entity tag from the content hash of a tracked file.
+///
+/// Compressed representations of a file get entity tags of their own, as they differ
+/// byte for byte from the uncompressed one.
+pub fn etag_for_hash(hash: u64, content_encoding: Option<&str>) -> String {
+    match content_encoding {
+        Some(content_encoding) => format!("\"{hash:016x}-{content_encoding}\""),
+        None => format!("\"{hash:016x}\""),
+    }
+}
+
+/// Check whether the value of an `If-None-Match` request header matches an entity tag.
+///
+/// Uses the weak comparison function, as mandated for `If-None-Match`.
+pub fn if_none_match(header_value: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    header_value
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Check whether the value of an `If-Modified-Since` request header is at or after a modification time.
+///
+/// HTTP dates have a resolution of one second, so the modification time is truncated to seconds.
+/// Invalid dates are ignored, as mandated for `If-Modified-Since`.
+pub fn if_modified_since_is_fresh(header_value: &str, mtime: SystemTime) -> bool {
+    let Ok(since) = httpdate::parse_http_date(header_value) else {
+        return false;
+    };
+    truncate_to_seconds(mtime) <= since
+}
+
+/// Truncate a time to whole seconds, which is the resolution of HTTP dates.
+fn truncate_to_seconds(t: SystemTime) -> SystemTime {
+    match t.duration_since(UNIX_EPOCH) {
+        Ok(d) => UNIX_EPOCH + Duration::from_secs(d.as_secs()),
+        Err(_) => t,
+    }
+}
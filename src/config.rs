@@ -0,0 +1,172 @@
+//! Config file for settings that are impractical to give as command-line arguments.
+//!
+//! Settings are read from the config file given with `--config`, and, with `--project-config`,
+//! from a project config file named [`PROJECT_CONFIG_FILE_NAME`], in the project directory or the
+//! nearest directory above it that has one, up to the root of the git repo. Projects can thus pin
+//! their own settings in their repos, while those of the config file given with `--config` take
+//! precedence. See [`Config::merge`].
+//!
+//! The project config file is only read when asked for, since the build steps and companion
+//! processes in it are run as shell commands, and a repo that was just downloaded should not
+//! get to run commands by being served.
+//!
+//! Both are in TOML format:
+//!
+//! ```toml
+//! allowed_hosts = ["my-laptop.local"]
+//! exclude = ["drafts/", "*.map"]
+//! proxies = ["/api=http://localhost:8000"]
+//!
+//! [[headers]]
+//! for = "/*"
+//! values = { Cross-Origin-Opener-Policy = "same-origin", Cross-Origin-Embedder-Policy = "require-corp" }
+//!
+//! [[redirects]]
+//! from = "/old-blog/*"
+//! to = "/blog/:splat"
+//! status = 301
+//!
+//! [auth]
+//! users = ["alice:correct-horse"]
+//! servers = "project"
+//!
+//! [[build.steps]]
+//! name = "compile"
+//! command = "tsc"
+//!
+//! [[companions]]
+//! name = "api"
+//! command = "cargo run -p api"
+//! ```
+
+use crate::auth::AuthConfig;
+use crate::build::BuildConfig;
+use crate::companion::CompanionConfig;
+use crate::headers::HeaderRuleConfig;
+use crate::redirects::RedirectRuleConfig;
+use crate::throttle::ThrottleRuleConfig;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Name of the project config file, which is looked for in the project directory
+/// and the directories above it, up to the root of the git repo.
+pub const PROJECT_CONFIG_FILE_NAME: &str = ".http-horse.toml";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("TOML: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("`{0}` can only be set in the config file given with --config, not in a project config file")]
+    NotInProjectConfig(&'static str),
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Host names that requests may be for, in addition to `localhost` and the IP addresses
+    /// that the servers are reachable at.
+    pub allowed_hosts: Vec<String>,
+    /// Patterns in `.gitignore` syntax for files to exclude from being tracked and served,
+    /// in every project directory, along with those of its `.http-horse-ignore` file.
+    pub exclude: Vec<String>,
+    /// Proxy rules, like `/api=http://localhost:8000`, evaluated after those given on the
+    /// command line. See [`crate::proxy`].
+    pub proxies: Vec<String>,
+    /// Extra response headers for URL paths matching a pattern.
+    /// Patterns are matched against the full URL path, including any mount point.
+    pub headers: Vec<HeaderRuleConfig>,
+    /// Redirect and rewrite rules, evaluated before those of the project directories.
+    /// Patterns and targets are full URL paths, including any mount point.
+    pub redirects: Vec<RedirectRuleConfig>,
+    /// Users that may access the servers with HTTP Basic authentication,
+    /// in addition to those given on the command line.
+    pub auth: AuthConfig,
+    /// Network conditions for URL paths matching a pattern, overriding those given on
+    /// the command line. Patterns are matched against the full URL path.
+    pub throttle: Vec<ThrottleRuleConfig>,
+    /// Build pipeline to run when source files change. See [`crate::build`].
+    pub build: BuildConfig,
+    /// Long-running processes to keep running alongside http-horse. See [`crate::companion`].
+    pub companions: Vec<CompanionConfig>,
+}
+
+impl Config {
+    pub fn load(fpath: &Path) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(fpath)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    /// Load a project config file. Build steps and companion processes in it run in the
+    /// directory of the file, and their relative directories are resolved against it,
+    /// so that the file works the same wherever http-horse is started from.
+    ///
+    /// Allowed host names and authentication are refused, as they are for whoever runs
+    /// http-horse to decide, not for the repo being served.
+    pub fn load_project(fpath: &Path) -> Result<Self, Error> {
+        let mut config = Self::load(fpath)?;
+        if !config.allowed_hosts.is_empty() {
+            return Err(Error::NotInProjectConfig("allowed_hosts"));
+        }
+        if !config.auth.users.is_empty() || config.auth.servers.is_some() {
+            return Err(Error::NotInProjectConfig("auth"));
+        }
+        let config_dir = fpath.parent().unwrap_or(Path::new("."));
+        for step in &mut config.build.steps {
+            step.dir = Some(config_dir.join(step.dir.take().unwrap_or_default()));
+        }
+        for companion in &mut config.companions {
+            companion.dir = Some(config_dir.join(companion.dir.take().unwrap_or_default()));
+        }
+        Ok(config)
+    }
+
+    /// Merge the settings of a project config file under these, so that these take precedence.
+    ///
+    /// Lists of rules are concatenated, with these first, as the first matching rule wins.
+    /// Build steps are not, as a pipeline is one whole, so those of the project config file
+    /// are only used if there are none here. Other settings are taken from here if set here,
+    /// except for allowed host names and authentication, which are only taken from here.
+    pub fn merge(self, project: Self) -> Self {
+        let build = if self.build.steps.is_empty() {
+            BuildConfig {
+                steps: project.build.steps,
+                policy: self.build.policy.or(project.build.policy),
+                debounce: self.build.debounce.or(project.build.debounce),
+            }
+        } else {
+            self.build
+        };
+        Self {
+            allowed_hosts: self.allowed_hosts,
+            exclude: [self.exclude, project.exclude].concat(),
+            proxies: [self.proxies, project.proxies].concat(),
+            headers: [self.headers, project.headers].concat(),
+            redirects: [self.redirects, project.redirects].concat(),
+            auth: self.auth,
+            throttle: [self.throttle, project.throttle].concat(),
+            build,
+            companions: [self.companions, project.companions].concat(),
+        }
+    }
+}
+
+/// Find the project config file for a project directory, in it or the nearest directory
+/// above it that has one.
+///
+/// The search stops at the root of the git repo that the project directory is in, which is the
+/// first directory with a `.git` in it. Project directories outside of git repos are only
+/// looked in themselves, so that files further up, like in the home directory, are not picked up.
+pub fn find_project_config(project_dir: &Path) -> Option<PathBuf> {
+    let repo_root = project_dir
+        .ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .unwrap_or(project_dir);
+    project_dir
+        .ancestors()
+        .take_while(|dir| dir.starts_with(repo_root))
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE_NAME))
+        .find(|fpath| fpath.is_file())
+}
@@ -0,0 +1,216 @@
+//! Dependency graph of the pages served by the project server, so that a change to a file
+//! only reloads the pages that use it, rather than every open page.
+//!
+//! HTML pages are parsed as they are served, for the stylesheets, scripts, images and other
+//! resources that they reference, and stylesheets for the images, fonts and stylesheets that
+//! they reference in turn. Nodes are keyed by URL path, and are dropped when their file changes,
+//! to be parsed anew when the file is next served.
+//!
+//! What scripts load at runtime, like modules that they import or data that they fetch, cannot be
+//! seen this way. So a change to a file that no parsed page or stylesheet references is taken to
+//! affect every page, as is any change for a page that has not been parsed.
+
+use crate::html::start_tags;
+use crate::url::{decode_path, encode_path_segment};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Mutex,
+};
+
+/// The dependency graph of the project server.
+pub static DEPENDENCY_GRAPH: DependencyGraph = DependencyGraph::new();
+
+/// Maps the URL paths of pages and stylesheets to the URL paths of the files that they reference.
+#[derive(Debug)]
+pub struct DependencyGraph {
+    edges: Mutex<BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl DependencyGraph {
+    pub const fn new() -> Self {
+        Self {
+            edges: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Set the files that the file at a URL path references.
+    pub fn set_dependencies(&self, url_path: String, deps: BTreeSet<String>) {
+        let mut edges = self.edges.lock().unwrap_or_else(|e| e.into_inner());
+        edges.insert(url_path, deps);
+    }
+
+    /// Forget what the file at a URL path references, until it is parsed anew.
+    pub fn remove(&self, url_path: &str) {
+        let mut edges = self.edges.lock().unwrap_or_else(|e| e.into_inner());
+        edges.remove(url_path);
+    }
+
+    /// Whether a change to the file at one URL path affects the page at another.
+    pub fn affects(&self, page: &str, changed: &str) -> bool {
+        if page == changed {
+            return true;
+        }
+        let edges = self.edges.lock().unwrap_or_else(|e| e.into_inner());
+        if !edges.contains_key(page) {
+            return true;
+        }
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![page];
+        while let Some(node) = stack.pop() {
+            for dep in edges.get(node).into_iter().flatten() {
+                if dep == changed {
+                    return true;
+                }
+                if seen.insert(dep.as_str()) {
+                    stack.push(dep);
+                }
+            }
+        }
+        // Other pages are only affected through the files that they reference.
+        let referenced = edges.values().any(|deps| deps.contains(changed));
+        !referenced && !is_page(changed)
+    }
+}
+
+impl Default for DependencyGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_page(url_path: &str) -> bool {
+    let url_path = url_path.to_ascii_lowercase();
+    url_path.ends_with('/') || url_path.ends_with(".html") || url_path.ends_with(".htm")
+}
+
+/// Find the URL paths of the same-origin files that an HTML page references.
+pub fn html_dependencies(url_path: &str, html: &[u8]) -> BTreeSet<String> {
+    let tags = start_tags(html, None);
+    let base = tags
+        .iter()
+        .find(|tag| tag.name == "base")
+        .and_then(|tag| tag.attr("href"))
+        .and_then(|href| resolve_reference(url_path, href))
+        .unwrap_or_else(|| url_path.to_string());
+    let mut hrefs = vec![];
+    for tag in &tags {
+        let attrs: &[&str] = match tag.name.as_str() {
+            "link" => {
+                let rel = tag.attr("rel").unwrap_or_default().to_ascii_lowercase();
+                let is_resource = rel.split_ascii_whitespace().any(|rel| {
+                    matches!(
+                        rel,
+                        "stylesheet" | "icon" | "preload" | "modulepreload" | "manifest"
+                    )
+                });
+                if is_resource {
+                    &["href"]
+                } else {
+                    &[]
+                }
+            }
+            "script" | "iframe" | "embed" | "track" | "input" => &["src"],
+            "img" | "source" => &["src", "srcset"],
+            "video" => &["src", "poster"],
+            "audio" => &["src"],
+            "object" => &["data"],
+            "image" | "use" => &["href", "xlink:href"],
+            _ => &[],
+        };
+        for &attr in attrs {
+            let Some(value) = tag.attr(attr) else {
+                continue;
+            };
+            if attr == "srcset" {
+                hrefs.extend(
+                    value
+                        .split(',')
+                        .filter_map(|candidate| candidate.split_whitespace().next()),
+                );
+            } else {
+                hrefs.push(value);
+            }
+        }
+    }
+    let mut deps: BTreeSet<String> = hrefs
+        .into_iter()
+        .filter_map(|href| resolve_reference(&base, href))
+        .collect();
+    for style in tags.iter().filter_map(|tag| tag.attr("style")) {
+        deps.extend(css_dependencies(&base, style.as_bytes()));
+    }
+    deps.remove(url_path);
+    deps
+}
+
+/// Find the URL paths of the same-origin files that a stylesheet references,
+/// with `url()` and `@import`.
+pub fn css_dependencies(url_path: &str, css: &[u8]) -> BTreeSet<String> {
+    let css = String::from_utf8_lossy(css);
+    let lowercase = css.to_ascii_lowercase();
+    let mut hrefs = vec![];
+    for (start, _) in lowercase.match_indices("url(") {
+        let rest = &css[start + "url(".len()..];
+        if let Some(end) = rest.find(')') {
+            hrefs.push(rest[..end].trim().trim_matches(['"', '\'']));
+        }
+    }
+    for (start, _) in lowercase.match_indices("@import") {
+        let rest = css[start + "@import".len()..].trim_start();
+        if let Some(quote @ ('"' | '\'')) = rest.chars().next() {
+            if let Some(end) = rest[1..].find(quote) {
+                hrefs.push(&rest[1..1 + end]);
+            }
+        }
+    }
+    hrefs
+        .into_iter()
+        .filter_map(|href| resolve_reference(url_path, href))
+        .filter(|dep| dep != url_path)
+        .collect()
+}
+
+/// Resolve a reference in the file at a URL path to the URL path that it refers to, normalized
+/// to the percent-encoding that the project server uses for URL paths in reload events.
+/// Returns `None` for references to other origins, and to things other than files, like `data:`.
+pub fn resolve_reference(url_path: &str, href: &str) -> Option<String> {
+    let href = href.trim();
+    let href = href.split(['#', '?']).next().unwrap_or_default();
+    if href.is_empty() || href.starts_with("//") {
+        return None;
+    }
+    // A colon before any slash is the end of a scheme, like in `https:` or `data:`.
+    if href
+        .split('/')
+        .next()
+        .is_some_and(|first| first.contains(':'))
+    {
+        return None;
+    }
+    let joined = if href.starts_with('/') {
+        href.to_string()
+    } else {
+        let dir = &url_path[..url_path.rfind('/').map_or(0, |pos| pos + 1)];
+        format!("{dir}{href}")
+    };
+    let raw_segments = joined.split('/').skip(1).collect::<Vec<_>>();
+    let mut segments = vec![];
+    for segment in &raw_segments {
+        match *segment {
+            "." | "" => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => {
+                let decoded = decode_path(segment).unwrap_or_else(|| segment.to_string());
+                segments.push(encode_path_segment(&decoded));
+            }
+        }
+    }
+    let mut resolved = format!("/{}", segments.join("/"));
+    // References to directories keep their trailing slash, like the URL paths of index pages.
+    if !segments.is_empty() && matches!(raw_segments.last(), Some(&("" | "." | ".."))) {
+        resolved.push('/');
+    }
+    Some(resolved)
+}
@@ -0,0 +1,238 @@
+//! Errors and warnings in the output of build commands, parsed into the file, line and column
+//! that they are at and what they say, so that the error overlay can list them by where they are,
+//! and open them in an editor. See [`crate::overlay`] and [`crate::editor`].
+//!
+//! The output of these compilers is understood:
+//!
+//! - rustc, with `--error-format=json`, and Cargo, with `--message-format=json`
+//! - tsc, with and without `--pretty`
+//! - esbuild
+//!
+//! Along with lines like `file:line:column: error: message`, which many other tools write.
+//! Output that is not understood is still shown in the overlay as it is.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+/// Most diagnostics parsed from the output of a run, so that a build with thousands
+/// of errors does not flood the overlay.
+pub const MAX_DIAGNOSTICS: usize = 100;
+
+/// Lines after an esbuild error header to look for where the error is at.
+const ESBUILD_LOCATION_LOOKAHEAD: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An error or warning at a place in a source file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Absolute path of the file.
+    pub file: PathBuf,
+    /// Line in the file, counted from one.
+    pub line: u32,
+    /// Column in the line, counted from one.
+    pub column: u32,
+    pub message: String,
+    /// Error code, like `E0308` or `TS2322`, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+/// Parse the errors and warnings in the output of a build command that was run in `dir`,
+/// up to [`MAX_DIAGNOSTICS`] of them. Relative paths are resolved against `dir`.
+pub fn parse(output: &str, dir: &Path) -> Vec<Diagnostic> {
+    let lines = output.lines().map(strip_ansi).collect::<Vec<_>>();
+    let mut diagnostics = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if diagnostics.len() == MAX_DIAGNOSTICS {
+            break;
+        }
+        let following = &lines[i + 1..lines.len().min(i + 1 + ESBUILD_LOCATION_LOOKAHEAD)];
+        let diagnostic = parse_rustc_json(line)
+            .or_else(|| parse_tsc(line))
+            .or_else(|| parse_esbuild(line, following))
+            .or_else(|| parse_plain(line));
+        if let Some(mut diagnostic) = diagnostic {
+            diagnostic.file = dir.join(&diagnostic.file);
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics
+}
+
+/// A diagnostic of rustc, or a compiler message of Cargo, which holds one.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RustcLine {
+    Cargo { message: RustcDiagnostic },
+    Rustc(RustcDiagnostic),
+}
+
+#[derive(Deserialize)]
+struct RustcDiagnostic {
+    message: String,
+    code: Option<RustcCode>,
+    level: String,
+    spans: Vec<RustcSpan>,
+}
+
+#[derive(Deserialize)]
+struct RustcCode {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct RustcSpan {
+    file_name: String,
+    line_start: u32,
+    column_start: u32,
+    is_primary: bool,
+}
+
+fn parse_rustc_json(line: &str) -> Option<Diagnostic> {
+    if !line.starts_with('{') {
+        return None;
+    }
+    let diagnostic = match serde_json::from_str::<RustcLine>(line).ok()? {
+        RustcLine::Cargo { message } | RustcLine::Rustc(message) => message,
+    };
+    let severity = match diagnostic.level.as_str() {
+        "error" | "error: internal compiler error" => Severity::Error,
+        "warning" => Severity::Warning,
+        _ => return None,
+    };
+    // Summaries, like that of how many errors there were, are not at any place.
+    let span = diagnostic.spans.into_iter().find(|span| span.is_primary)?;
+    Some(Diagnostic {
+        severity,
+        file: PathBuf::from(span.file_name),
+        line: span.line_start,
+        column: span.column_start,
+        message: diagnostic.message,
+        code: diagnostic.code.map(|code| code.code),
+    })
+}
+
+/// `file(line,column): error TS1234: message`, or with `--pretty`,
+/// `file:line:column - error TS1234: message`.
+fn parse_tsc(line: &str) -> Option<Diagnostic> {
+    let (file, line_no, column, rest) = match line.split_once("): ") {
+        Some((location, rest)) if location.ends_with(|c: char| c.is_ascii_digit()) => {
+            let (file, position) = location.rsplit_once('(')?;
+            let (line_no, column) = position.split_once(',')?;
+            (file, line_no.parse().ok()?, column.parse().ok()?, rest)
+        }
+        _ => {
+            let (location, rest) = line.split_once(" - ")?;
+            let (file, line_no, column) = split_location(location)?;
+            (file, line_no, column, rest)
+        }
+    };
+    let (severity, rest) = split_severity(rest)?;
+    let (code, message) = rest.split_once(": ")?;
+    if !code.starts_with("TS") {
+        return None;
+    }
+    Some(Diagnostic {
+        severity,
+        file: PathBuf::from(file),
+        line: line_no,
+        column,
+        message: message.to_string(),
+        code: Some(code.to_string()),
+    })
+}
+
+/// `✘ [ERROR] message`, followed by `file:line:column:` on one of the next lines.
+fn parse_esbuild(line: &str, following: &[Cow<'_, str>]) -> Option<Diagnostic> {
+    let (severity, message) = if let Some((_, message)) = line.split_once("[ERROR] ") {
+        (Severity::Error, message)
+    } else if let Some((_, message)) = line.split_once("[WARNING] ") {
+        (Severity::Warning, message)
+    } else {
+        return None;
+    };
+    let (file, line_no, column) = following.iter().find_map(|location| {
+        let location = location.trim().strip_suffix(':')?;
+        split_location(location)
+    })?;
+    Some(Diagnostic {
+        severity,
+        file: PathBuf::from(file),
+        line: line_no,
+        // esbuild counts columns from zero.
+        column: column + 1,
+        message: message.to_string(),
+        code: None,
+    })
+}
+
+/// `file:line:column: error: message`, as written by many tools, esbuild with
+/// `--log-level` and older versions of it among them.
+fn parse_plain(line: &str) -> Option<Diagnostic> {
+    let (location, rest) = line.split_once(": ")?;
+    let (file, line_no, column) = split_location(location)?;
+    let (severity, rest) = split_severity(rest)?;
+    let message = rest.strip_prefix(':')?.trim_start();
+    Some(Diagnostic {
+        severity,
+        file: PathBuf::from(file),
+        line: line_no,
+        column,
+        message: message.to_string(),
+        code: None,
+    })
+}
+
+/// Split `file:line:column` into its parts.
+fn split_location(location: &str) -> Option<(&str, u32, u32)> {
+    let mut parts = location.rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line_no = parts.next()?.parse().ok()?;
+    let file = parts.next().filter(|file| !file.is_empty())?;
+    Some((file, line_no, column))
+}
+
+/// Split the severity off the start of a message, in any case.
+fn split_severity(text: &str) -> Option<(Severity, &str)> {
+    [("error", Severity::Error), ("warning", Severity::Warning)]
+        .into_iter()
+        .find_map(|(word, severity)| {
+            let head = text.get(..word.len())?;
+            head.eq_ignore_ascii_case(word)
+                .then(|| (severity, text[word.len()..].trim_start_matches(' ')))
+        })
+}
+
+/// A line with the ANSI escape sequences that color it taken out.
+fn strip_ansi(line: &str) -> Cow<'_, str> {
+    if !line.contains('\x1b') {
+        return Cow::Borrowed(line);
+    }
+    let mut stripped = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        if chars.next() == Some('[') {
+            // Parameters and intermediate bytes, up to the final byte, which is a letter or the like.
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    Cow::Owned(stripped)
+}
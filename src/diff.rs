@@ -0,0 +1,313 @@
+//! Capture of what changed in text files, as unified diffs, so that the status web-ui
+//! can show what changed in each event, not just that it changed.
+//!
+//! The contents of text files are kept as snapshots, up to a total size, starting with
+//! the files found by the initial scan. When a file changes, its new contents are diffed
+//! against the snapshot, and the diff is kept along with the id of the event history
+//! journal entry for the change. Files that are too large, or that are not UTF-8 text,
+//! are not captured. Whether a file is UTF-8 text goes by the same classification of its
+//! contents as tracked files get in scans. See [`crate::sniff`].
+
+use crate::{
+    fs::project_dir::{ChangeKind, TrackedChange, TrackedProjectDir},
+    journal::JournalEntry,
+    sniff::{classify, ContentClass},
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write as _,
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::debug;
+
+/// Largest file that is captured, in bytes.
+pub const MAX_SNAPSHOT_LEN: u64 = 1024 * 1024;
+
+/// Most bytes of snapshots kept in all. Files beyond that are not captured
+/// until others have been removed.
+pub const MAX_SNAPSHOTS_LEN: usize = 64 * 1024 * 1024;
+
+/// Number of diffs kept.
+const MAX_DIFFS: usize = 1_000;
+
+/// Lines of context around changes in diffs.
+const CONTEXT_LINES: usize = 3;
+
+/// Most lines that may be added and removed between two versions of a file for them
+/// to be diffed line by line. Beyond that, the diff removes the one and adds the other.
+const MAX_EDITS: usize = 4_000;
+
+/// Snapshots of the contents of text files, and the diffs between them.
+#[derive(Debug, Default)]
+pub struct DiffCapture {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    snapshots: HashMap<PathBuf, String>,
+    snapshots_len: usize,
+    /// By journal entry id, oldest first.
+    diffs: VecDeque<(u64, String)>,
+}
+
+impl Inner {
+    fn take_snapshot(&mut self, path: &Path) -> Option<String> {
+        let snapshot = self.snapshots.remove(path)?;
+        self.snapshots_len -= snapshot.len();
+        Some(snapshot)
+    }
+
+    fn put_snapshot(&mut self, path: PathBuf, contents: String) {
+        if self.snapshots_len + contents.len() > MAX_SNAPSHOTS_LEN {
+            debug!(?path, "Snapshots are full. Not keeping snapshot of file.");
+            return;
+        }
+        self.snapshots_len += contents.len();
+        self.snapshots.insert(path, contents);
+    }
+}
+
+impl DiffCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take snapshots of the text files in a tracked project directory tree.
+    pub fn seed(&self, tree: &TrackedProjectDir) {
+        for file in &tree.tracked_files {
+            if file.class != ContentClass::Utf8Text || file.size > MAX_SNAPSHOT_LEN {
+                continue;
+            }
+            if let Some(contents) = read_text(&file.fpath) {
+                let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+                inner.put_snapshot(file.fpath.clone(), contents);
+            }
+        }
+        for dir in &tree.tracked_dirs {
+            self.seed(dir);
+        }
+    }
+
+    /// Diff the new contents of a file that changed against its snapshot, keeping the diff
+    /// under the id of the journal entry for the change, and keep the new contents as the snapshot.
+    ///
+    /// A file that is created is diffed against nothing. A file that another file is renamed over,
+    /// as build tools that write files atomically do, is diffed against what was there before.
+    pub fn record(&self, change: &TrackedChange, entry: &JournalEntry) {
+        if change.is_dir {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let old = match &change.kind {
+            ChangeKind::Removed => {
+                inner.take_snapshot(&change.path);
+                return;
+            }
+            ChangeKind::Created => inner.take_snapshot(&change.path).or(Some(String::new())),
+            ChangeKind::Modified => inner.take_snapshot(&change.path),
+            ChangeKind::Renamed { from } => {
+                let moved = inner.take_snapshot(from);
+                inner.take_snapshot(&change.path).or(moved)
+            }
+        };
+        drop(inner);
+        // The file is read without holding the lock, since it may take a while.
+        let Some(new) = read_text(&change.path) else {
+            return;
+        };
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(old) = old {
+            // Journal entries of changes that are not recorded all have id zero.
+            if entry.id != 0 && old != new {
+                let label = change.path.display();
+                let diff = unified_diff(&old, &new, &format!("a{label}"), &format!("b{label}"));
+                if inner.diffs.len() == MAX_DIFFS {
+                    inner.diffs.pop_front();
+                }
+                inner.diffs.push_back((entry.id, diff));
+            }
+        }
+        inner.put_snapshot(change.path.clone(), new);
+    }
+
+    /// The diff for the journal entry with the given id, if one was captured and is still kept.
+    pub fn diff(&self, id: u64) -> Option<String> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner
+            .diffs
+            .iter()
+            .find(|(diff_id, _)| *diff_id == id)
+            .map(|(_, diff)| diff.clone())
+    }
+}
+
+/// Contents of a file, if it is small enough to capture and is text.
+fn read_text(path: &Path) -> Option<String> {
+    let read = || -> io::Result<Vec<u8>> {
+        let mut contents = vec![];
+        File::open(path)?
+            .take(MAX_SNAPSHOT_LEN + 1)
+            .read_to_end(&mut contents)?;
+        Ok(contents)
+    };
+    let contents = read()
+        .inspect_err(|e| debug!(err = ?e, ?path, "Failed to read file to capture."))
+        .ok()?;
+    if contents.len() as u64 > MAX_SNAPSHOT_LEN || classify(&contents) != ContentClass::Utf8Text {
+        return None;
+    }
+    String::from_utf8(contents).ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    /// Line of both versions, by index in the old and in the new version.
+    Equal(usize, usize),
+    /// Line of the old version only.
+    Delete(usize),
+    /// Line of the new version only.
+    Insert(usize),
+}
+
+/// Shortest edit script turning `a` into `b`, per Myers' O(ND) diff algorithm,
+/// or `None` if it takes more than [`MAX_EDITS`] edits.
+fn edit_script(a: &[&str], b: &[&str]) -> Option<Vec<Op>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    // For each number of edits d, the furthest x reached on each diagonal k = x - y,
+    // for k in -d..=d, at index k + d.
+    let mut trace: Vec<Vec<isize>> = vec![];
+    for d in 0..=(n + m).min(MAX_EDITS as isize) {
+        let mut v = vec![0; 2 * d as usize + 1];
+        let prev = |k: isize| {
+            trace
+                .last()
+                .map_or(0, |v: &Vec<isize>| v[(k + d - 1) as usize])
+        };
+        for k in (-d..=d).step_by(2) {
+            let mut x = if d == 0 {
+                0
+            } else if k == -d || (k != d && prev(k - 1) < prev(k + 1)) {
+                prev(k + 1)
+            } else {
+                prev(k - 1) + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(k + d) as usize] = x;
+            if x >= n && y >= m {
+                trace.push(v);
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+        trace.push(v);
+    }
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Op> {
+    let (mut x, mut y) = (n, m);
+    let mut ops = vec![];
+    for d in (1..trace.len() as isize).rev() {
+        let prev = |k: isize| trace[d as usize - 1][(k + d - 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && prev(k - 1) < prev(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = prev(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            ops.push(Op::Equal(x as usize, y as usize));
+        }
+        if x == prev_x {
+            y -= 1;
+            ops.push(Op::Insert(y as usize));
+        } else {
+            x -= 1;
+            ops.push(Op::Delete(x as usize));
+        }
+    }
+    while x > 0 && y > 0 {
+        x -= 1;
+        y -= 1;
+        ops.push(Op::Equal(x as usize, y as usize));
+    }
+    ops.reverse();
+    ops
+}
+
+/// Unified diff between two versions of a text, like `diff -u` makes, or an empty string
+/// if they are the same.
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    let a = old.split_inclusive('\n').collect::<Vec<_>>();
+    let b = new.split_inclusive('\n').collect::<Vec<_>>();
+    let ops = edit_script(&a, &b).unwrap_or_else(|| {
+        (0..a.len())
+            .map(Op::Delete)
+            .chain((0..b.len()).map(Op::Insert))
+            .collect()
+    });
+
+    // Hunks, as ranges of ops, with changes that are close together in the same hunk.
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for (i, op) in ops.iter().enumerate() {
+        if matches!(op, Op::Equal(..)) {
+            continue;
+        }
+        let start = i.saturating_sub(CONTEXT_LINES);
+        let end = (i + 1 + CONTEXT_LINES).min(ops.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    let mut diff = format!("--- {old_label}\n+++ {new_label}\n");
+    for (start, end) in hunks {
+        let hunk = &ops[start..end];
+        let is_old = |op: &&Op| !matches!(op, Op::Insert(_));
+        let is_new = |op: &&Op| !matches!(op, Op::Delete(_));
+        let (a_before, b_before) = (
+            ops[..start].iter().filter(is_old).count(),
+            ops[..start].iter().filter(is_new).count(),
+        );
+        let (a_len, b_len) = (
+            hunk.iter().filter(is_old).count(),
+            hunk.iter().filter(is_new).count(),
+        );
+        // Empty ranges start at the line before them, like `diff -u` has it.
+        let range_start = |before: usize, len: usize| if len == 0 { before } else { before + 1 };
+        let _ = writeln!(
+            diff,
+            "@@ -{},{a_len} +{},{b_len} @@",
+            range_start(a_before, a_len),
+            range_start(b_before, b_len)
+        );
+        for op in hunk {
+            let (prefix, line) = match *op {
+                Op::Equal(i, _) => (' ', a[i]),
+                Op::Delete(i) => ('-', a[i]),
+                Op::Insert(j) => ('+', b[j]),
+            };
+            diff.push(prefix);
+            diff.push_str(line);
+            if !line.ends_with('\n') {
+                diff.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    diff
+}
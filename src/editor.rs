@@ -0,0 +1,72 @@
+//! Opening files in an editor from the error overlay, at the place of an error in the output
+//! of the build. The editor is opened by a command given with `--editor`, like
+//! `code --goto {file}:{line}:{column}`, which is run in a shell, with the placeholders
+//! replaced by where the error is.
+//!
+//! The reload client script posts where to open to a reserved URL path. Only files that the
+//! errors in the overlay are in can be opened, so that pages cannot have just any file opened.
+//! See [`crate::diagnostics`].
+
+use serde::Deserialize;
+use std::{
+    io,
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+use tracing::{debug, warn};
+
+/// URL path, under the reserved path prefix, that the client script posts what to open to.
+pub const OPEN_IN_EDITOR_URL_PATH: &str = "open-in-editor";
+
+/// Largest request body accepted from the client script, in bytes.
+pub const MAX_REQUEST_LEN: usize = 4 * 1024;
+
+/// Command to open files in the editor with, if one was given.
+pub static EDITOR_COMMAND: OnceLock<String> = OnceLock::new();
+
+/// A place in a file to open the editor at.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenRequest {
+    pub file: PathBuf,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// Whether files can be opened in an editor, making the error overlay offer to.
+pub fn is_enabled() -> bool {
+    EDITOR_COMMAND.get().is_some()
+}
+
+/// Open a file in the editor, at a line and column, without waiting for the editor.
+pub fn open(request: &OpenRequest) -> io::Result<()> {
+    let Some(command) = EDITOR_COMMAND.get() else {
+        return Err(io::Error::other("no editor command"));
+    };
+    // The placeholders become positional parameters of the shell, so that paths
+    // with spaces and quotes in them need no quoting.
+    let script = command
+        .replace("{file}", "\"$1\"")
+        .replace("{line}", "\"$2\"")
+        .replace("{column}", "\"$3\"");
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .arg("sh")
+        .arg(&request.file)
+        .arg(request.line.to_string())
+        .arg(request.column.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    debug!(?request, "Opening file in editor.");
+    // Editors that are already running return right away, but others keep running,
+    // so the command is waited on elsewhere.
+    std::thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => warn!(?status, "Editor command failed."),
+        Ok(_) => {}
+        Err(e) => warn!(err = ?e, "Failed to wait for editor command."),
+    });
+    Ok(())
+}
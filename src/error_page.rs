@@ -0,0 +1,95 @@
+//! Content negotiation for the bodies of error responses, so that browsers get an HTML page,
+//! API clients get JSON, and curl and the like get plain text.
+
+use hyper::StatusCode;
+use serde_json::json;
+
+/// Format of the body of an error response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorFormat {
+    // Variants are in order of our own preference, least preferred first.
+    Html,
+    Json,
+    Text,
+}
+
+impl ErrorFormat {
+    const ALL: [Self; 3] = [Self::Text, Self::Json, Self::Html];
+
+    fn media_type(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Html => ("text", "html"),
+            Self::Json => ("application", "json"),
+            Self::Text => ("text", "plain"),
+        }
+    }
+}
+
+/// Choose the format of an error response body according to the value of an `Accept`
+/// request header. Among formats that are equally acceptable, those that the client names
+/// are preferred over those that it accepts by wildcard, and then those that it names first.
+/// Plain text is the fallback, including when the client accepts none of the formats.
+pub fn negotiate_error_format(accept: &str) -> ErrorFormat {
+    let ranges: Vec<(String, String, f32)> = accept
+        .split(',')
+        .filter_map(|item| {
+            let mut params = item.split(';').map(str::trim);
+            let (type_, subtype) = params.next()?.split_once('/')?;
+            let q = params
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some((
+                type_.trim().to_ascii_lowercase(),
+                subtype.trim().to_ascii_lowercase(),
+                q,
+            ))
+        })
+        .collect();
+    ErrorFormat::ALL
+        .into_iter()
+        .filter_map(|format| {
+            let (type_, subtype) = format.media_type();
+            // The most specific matching range decides the q-value of a format.
+            let (specificity, position, q) = ranges
+                .iter()
+                .enumerate()
+                .filter_map(|(position, (range_type, range_subtype, q))| {
+                    let specificity = match (range_type.as_str(), range_subtype.as_str()) {
+                        ("*", "*") => 0,
+                        (t, "*") if t == type_ => 1,
+                        (t, s) if t == type_ && s == subtype => 2,
+                        _ => return None,
+                    };
+                    Some((specificity, position, *q))
+                })
+                .max_by_key(|(specificity, position, _)| (*specificity, usize::MAX - position))?;
+            (q > 0.0).then_some((format, q, specificity, position))
+        })
+        .max_by(|(a, a_q, a_spec, a_pos), (b, b_q, b_spec, b_pos)| {
+            a_q.total_cmp(b_q)
+                .then(a_spec.cmp(b_spec))
+                .then(b_pos.cmp(a_pos))
+                .then(a.cmp(b))
+        })
+        .map_or(ErrorFormat::Text, |(format, ..)| format)
+}
+
+/// The message of a plain text error body, without the status code that it starts with.
+pub fn error_message(body_text: &str) -> &str {
+    body_text
+        .strip_prefix("HTTP ")
+        .and_then(|rest| rest.split_once(". "))
+        .map_or(body_text, |(_, message)| message)
+        .trim()
+}
+
+/// The JSON body of an error response.
+pub fn error_json(status: StatusCode, message: &str) -> String {
+    json!({
+        "status": status.as_u16(),
+        "error": status.canonical_reason().unwrap_or_default(),
+        "message": message,
+    })
+    .to_string()
+}
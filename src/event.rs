@@ -0,0 +1,289 @@
+//! The events that http-horse sends over its event streams, to the status web-ui
+//! and, over the reload channel, to the pages that are open in browsers.
+//!
+//! Events are serialized as JSON objects with a `type` field, like
+//! `{"type": "file-modified", "path": "/index.html", "is_dir": false}`.
+
+use crate::{
+    broadcast::Broadcast,
+    build::{OutputStream, StepState},
+    fs::{
+        limits::ScanLimit,
+        project_dir::{ChangeKind, TrackedChange},
+        tree::TreeNode,
+    },
+    journal::{EntryKind, JournalEntry},
+    usage::ResourceUsage,
+};
+use serde::Serialize;
+use smol::channel::Receiver;
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// How many events a subscriber to the status events can fall behind by before it starts missing events.
+const STATUS_SUBSCRIBER_CAPACITY: usize = 256;
+
+/// Something that happened, as sent over an event stream.
+///
+/// The paths of file events are URL paths on the reload channel,
+/// and file system paths on the status event stream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Event {
+    FileCreated {
+        path: String,
+        is_dir: bool,
+    },
+    FileModified {
+        path: String,
+        is_dir: bool,
+    },
+    FileDeleted {
+        path: String,
+        is_dir: bool,
+    },
+    Renamed {
+        from: String,
+        path: String,
+        is_dir: bool,
+    },
+    /// Reload of the page at a URL path, or of every page, as requested through the status server.
+    ReloadRequested {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        page: Option<String>,
+    },
+    /// Build output started changing, while reloads are gated on builds.
+    BuildStarted,
+    /// The build marker was touched, or the build pipeline succeeded,
+    /// releasing the reloads that were held during the build.
+    BuildFinished,
+    /// The build pipeline was run, for the `run`th time, for a change to the source file at
+    /// `trigger`. `steps` are the names of its steps. See [`crate::build`].
+    BuildRunStarted {
+        run: u64,
+        trigger: String,
+        steps: Vec<String>,
+    },
+    /// The command of the step at index `step` of the build pipeline was run.
+    BuildStepStarted {
+        run: u64,
+        step: usize,
+        name: String,
+        command: String,
+    },
+    /// The command of a step of the build pipeline exited, or could not be run.
+    BuildStepFinished {
+        run: u64,
+        step: usize,
+        state: StepState,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exit_code: Option<i32>,
+        duration_ms: u64,
+    },
+    /// The build pipeline finished, with the exit code of the last step that was run, if any.
+    /// Cancelled runs were stopped for source changes that came in as they ran.
+    BuildRunFinished {
+        run: u64,
+        success: bool,
+        #[serde(skip_serializing_if = "is_false")]
+        cancelled: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exit_code: Option<i32>,
+        duration_ms: u64,
+    },
+    /// The build command wrote to one of its output streams, in the given run.
+    /// The text is one or more whole lines, except for at the end of the output.
+    BuildOutput {
+        run: u64,
+        stream: OutputStream,
+        text: String,
+    },
+    /// The companion process by the name was started, with its shell at `pid`.
+    /// See [`crate::companion`].
+    CompanionStarted {
+        name: String,
+        pid: u32,
+    },
+    /// A companion process exited, or could not be started. `restart_in_ms` is how long until
+    /// it is restarted, or is left out if it is not to be.
+    CompanionExited {
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exit_code: Option<i32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        restart_in_ms: Option<u64>,
+    },
+    /// A companion process wrote a line to one of its output streams.
+    CompanionOutput {
+        name: String,
+        stream: OutputStream,
+        text: String,
+    },
+    /// A page was opened in a browser. See [`crate::presence`].
+    ClientConnected {
+        client: String,
+        url: String,
+    },
+    ClientDisconnected {
+        client: String,
+    },
+    /// A file or directory in the tracked tree of the project directory at `root` changed.
+    /// `node` is what is tracked at the path now, or is left out if nothing is.
+    /// Renames come as an update for the old path and one for the new path.
+    TreeUpdated {
+        root: String,
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        node: Option<TreeNode>,
+    },
+    /// The tracked tree of the project directory at `root` was rescanned in full.
+    /// Changes found by the rescan do not come as updates, so the tree has to be fetched anew.
+    TreeRescanned {
+        root: String,
+    },
+    /// The color scheme of the status web-ui was switched, to the one with the given
+    /// command-line argument value.
+    ColorSchemeChanged {
+        color_scheme: String,
+    },
+    /// A full scan of the project directory at `root` is in progress, having gone through
+    /// `dirs` directories and found `files` files so far. Sent every second or so for as long
+    /// as the scan goes on, and once more with `done` set when it is over.
+    /// `limits_reached` lists the limits of the tracked tree that the scan ran into, if any,
+    /// and `hardlinked` is the number of files found with more than one hard link.
+    ScanProgress {
+        root: String,
+        dirs: u64,
+        files: u64,
+        done: bool,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        limits_reached: Vec<ScanLimit>,
+        #[serde(skip_serializing_if = "is_zero")]
+        hardlinked: u64,
+    },
+    /// Resource usage of http-horse was sampled.
+    ResourceUsage(ResourceUsage),
+    /// A request to the project server was handled, and its response sent.
+    /// The path includes the query, if any.
+    Request {
+        method: String,
+        path: String,
+        status: u16,
+        duration_ms: u64,
+        bytes: u64,
+    },
+}
+
+impl Event {
+    /// Event for a change to a file or directory, with its paths mapped by `path_of`,
+    /// or `None` if the path that changed does not map to anything.
+    /// Renames from paths that do not map to anything are creations, as far as anyone can tell.
+    pub fn file_change(
+        change: &TrackedChange,
+        path_of: impl Fn(&Path) -> Option<String>,
+    ) -> Option<Self> {
+        let path = path_of(&change.path)?;
+        let is_dir = change.is_dir;
+        Some(match &change.kind {
+            ChangeKind::Created => Self::FileCreated { path, is_dir },
+            ChangeKind::Modified => Self::FileModified { path, is_dir },
+            ChangeKind::Removed => Self::FileDeleted { path, is_dir },
+            ChangeKind::Renamed { from } => match path_of(from) {
+                Some(from) => Self::Renamed { from, path, is_dir },
+                None => Self::FileCreated { path, is_dir },
+            },
+        })
+    }
+
+    /// The paths that a file event is about: the path, and the previous path for renames.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        let (path, from) = match self {
+            Self::FileCreated { path, .. }
+            | Self::FileModified { path, .. }
+            | Self::FileDeleted { path, .. } => (Some(path), None),
+            Self::Renamed { from, path, .. } => (Some(path), Some(from)),
+            _ => (None, None),
+        };
+        path.into_iter().chain(from).map(String::as_str)
+    }
+
+    /// Whether the event is about a directory.
+    pub fn is_dir(&self) -> bool {
+        match self {
+            Self::FileCreated { is_dir, .. }
+            | Self::FileModified { is_dir, .. }
+            | Self::FileDeleted { is_dir, .. }
+            | Self::Renamed { is_dir, .. } => *is_dir,
+            _ => false,
+        }
+    }
+}
+
+/// An event on the status event stream, with when it happened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StampedEvent {
+    /// Id of the event history journal entry for the change, or zero for events that are
+    /// not recorded in the journal. See [`crate::journal`].
+    #[serde(skip_serializing_if = "is_zero")]
+    pub id: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+fn is_zero(id: &u64) -> bool {
+    *id == 0
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+impl From<&JournalEntry> for StampedEvent {
+    fn from(entry: &JournalEntry) -> Self {
+        let path = entry.path.display().to_string();
+        let is_dir = entry.is_dir;
+        let event = match (entry.kind, &entry.from) {
+            (EntryKind::Created, _) => Event::FileCreated { path, is_dir },
+            (EntryKind::Modified, _) => Event::FileModified { path, is_dir },
+            (EntryKind::Removed, _) => Event::FileDeleted { path, is_dir },
+            (EntryKind::Renamed, Some(from)) => Event::Renamed {
+                from: from.display().to_string(),
+                path,
+                is_dir,
+            },
+            (EntryKind::Renamed, None) => Event::FileCreated { path, is_dir },
+        };
+        Self {
+            id: entry.id,
+            timestamp_ms: entry.timestamp_ms,
+            event,
+        }
+    }
+}
+
+/// Events for the status event stream.
+pub static STATUS_EVENTS: Broadcast<StampedEvent> =
+    Broadcast::new("status event stream", STATUS_SUBSCRIBER_CAPACITY);
+
+/// Send an event that is not recorded in the event history journal to the status web-ui.
+pub fn publish_status(event: Event) {
+    let timestamp_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+    STATUS_EVENTS.send(StampedEvent {
+        id: 0,
+        timestamp_ms,
+        event,
+    });
+}
+
+/// Subscribe to the events for the status event stream.
+/// The subscription ends when the receiver is dropped.
+pub fn subscribe_status() -> Receiver<StampedEvent> {
+    STATUS_EVENTS.subscribe()
+}
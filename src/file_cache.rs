@@ -0,0 +1,124 @@
+//! In-memory cache of small files, as they are sent, so that the burst of requests that follows
+//! a reload does not have every file reopened, reread, and compressed anew.
+//!
+//! Entries are keyed by the path of the file and the encoding it is sent in, and hold the hash of
+//! the file contents that they were read with. A lookup only succeeds if the hash matches that of
+//! the tracked file, so a stale entry is never served, even if its invalidation were missed.
+//! Entries are also invalidated when the FS event pipeline reports a change to their file, so that
+//! memory is freed right away. When the cache is full, the least recently used entries are evicted.
+
+use bytes::Bytes;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tracing::trace;
+
+/// Identifies a cached file, in one of the encodings that it is sent in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub path: PathBuf,
+    /// Content coding of the cached body, or `None` if it is not compressed.
+    pub encoding: Option<&'static str>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    hash: u64,
+    body: Bytes,
+    /// Value of the use counter when the entry was last used, for LRU eviction.
+    last_used: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Total size of the cached bodies.
+    size: usize,
+    /// Incremented on every use of an entry.
+    uses: u64,
+}
+
+/// A size-bounded cache of file bodies.
+#[derive(Debug)]
+pub struct FileCache {
+    max_size: usize,
+    max_file_size: u64,
+    state: Mutex<CacheState>,
+}
+
+impl FileCache {
+    /// Create a cache holding at most `max_size` bytes, of files of at most `max_file_size` bytes.
+    pub fn new(max_size: usize, max_file_size: u64) -> Self {
+        Self {
+            max_size,
+            max_file_size,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Whether a file of the given size is small enough to be cached.
+    pub fn accepts(&self, len: u64) -> bool {
+        len <= self.max_file_size && len <= self.max_size as u64
+    }
+
+    /// Get the cached body of a file, if it was cached with the given hash of its contents.
+    pub fn get(&self, key: &CacheKey, hash: u64) -> Option<Bytes> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.uses += 1;
+        let uses = state.uses;
+        let entry = state
+            .entries
+            .get_mut(key)
+            .filter(|entry| entry.hash == hash)?;
+        entry.last_used = uses;
+        Some(entry.body.clone())
+    }
+
+    /// Cache the body of a file, read with the given hash of its contents.
+    pub fn insert(&self, key: CacheKey, hash: u64, body: Bytes) {
+        if body.len() > self.max_size {
+            return;
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.uses += 1;
+        let entry = CacheEntry {
+            hash,
+            last_used: state.uses,
+            body,
+        };
+        state.size += entry.body.len();
+        if let Some(replaced) = state.entries.insert(key, entry) {
+            state.size -= replaced.body.len();
+        }
+        while state.size > self.max_size {
+            let Some(lru_key) = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            if let Some(evicted) = state.entries.remove(&lru_key) {
+                trace!(?lru_key, "Evicting file from cache.");
+                state.size -= evicted.body.len();
+            }
+        }
+    }
+
+    /// Remove the entries for a file, or for all files in a directory.
+    pub fn invalidate(&self, path: &Path) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let mut freed = 0;
+        state.entries.retain(|key, entry| {
+            let keep = !key.path.starts_with(path);
+            if !keep {
+                freed += entry.body.len();
+            }
+            keep
+        });
+        state.size -= freed;
+    }
+}
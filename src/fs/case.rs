@@ -0,0 +1,113 @@
+//! The file systems of macOS and Windows are case-insensitive by default, so a request for
+//! `/INDEX.HTML` finds `index.html` on disk. Anything that goes by the path of the request,
+//! rather than by the path of what was found, then has to see past the case of it too:
+//! the check that a path is inside the project directory, the exclusion rules that go by
+//! file name, and lookups in the tracked project directory tree.
+//!
+//! Whether a project directory is on a case-insensitive file system is found out once,
+//! when the project root is set up, by looking up a name in it with its case swapped.
+
+use crate::fs::unicode::nfc;
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    io,
+    os::unix::fs::MetadataExt,
+    path::{Component, Path, PathBuf},
+};
+use tracing::debug;
+
+/// Check whether a directory is on a case-insensitive file system.
+///
+/// Looks up a name with its case swapped, from among the entries of the directory, or else
+/// from the path of the directory itself. Where no name has letters with case to them,
+/// goes by what is the default of the platform.
+pub fn is_case_insensitive(dir: &Path) -> bool {
+    let entry_paths = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|dir_entry| dir_entry.ok().map(|dir_entry| dir_entry.path()));
+    for path in entry_paths.chain(dir.ancestors().map(Path::to_path_buf)) {
+        let Some(swapped) = path.file_name().and_then(swap_case) else {
+            continue;
+        };
+        let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        let case_insensitive = std::fs::symlink_metadata(path.with_file_name(swapped))
+            .is_ok_and(|other| other.dev() == metadata.dev() && other.ino() == metadata.ino());
+        debug!(
+            ?dir,
+            ?path,
+            case_insensitive,
+            "Probed case sensitivity of file system."
+        );
+        return case_insensitive;
+    }
+    cfg!(any(target_os = "macos", target_os = "windows"))
+}
+
+/// A file name with the case of its letters swapped, if it has any letters with case to them.
+fn swap_case(name: &OsStr) -> Option<String> {
+    let name = name.to_str()?;
+    let swapped = name
+        .chars()
+        .map(|c| {
+            if c.is_lowercase() {
+                c.to_uppercase().collect::<String>()
+            } else {
+                c.to_lowercase().collect::<String>()
+            }
+        })
+        .collect::<String>();
+    (swapped != name && swapped.to_lowercase() == name.to_lowercase()).then_some(swapped)
+}
+
+/// A path in lowercase, for comparing paths without regard to case.
+/// Paths that are not valid UTF-8 are left as they are.
+pub fn fold_path(path: &Path) -> Cow<'_, Path> {
+    match path.to_str() {
+        Some(s) if s.chars().any(char::is_uppercase) => Cow::Owned(PathBuf::from(s.to_lowercase())),
+        _ => Cow::Borrowed(path),
+    }
+}
+
+/// Check whether two file names are the same without regard to case,
+/// in whichever normalization forms they are.
+fn same_name_folded(a: &OsStr, b: &OsStr) -> bool {
+    a == b || fold_path(Path::new(&nfc(a))) == fold_path(Path::new(&nfc(b)))
+}
+
+/// The path as it is on disk, with the case of each name below `dir` as it is in the directory
+/// it is in, for a path on a case-insensitive file system that was found by a name in another case.
+///
+/// Paths that are not inside of `dir`, even without regard to case, are returned as they are.
+pub fn on_disk_path(dir: &Path, path: &Path) -> io::Result<PathBuf> {
+    let mut components = path.components();
+    for dir_component in dir.components() {
+        match (dir_component, components.next()) {
+            (Component::Normal(a), Some(Component::Normal(b))) if same_name_folded(a, b) => {}
+            (a, Some(b)) if a == b => {}
+            _ => return Ok(path.to_path_buf()),
+        }
+    }
+    let mut on_disk = dir.to_path_buf();
+    for component in components {
+        let Component::Normal(name) = component else {
+            return Ok(path.to_path_buf());
+        };
+        let mut found = None;
+        for dir_entry in std::fs::read_dir(&on_disk)? {
+            let file_name = dir_entry?.file_name();
+            if file_name == name {
+                found = Some(file_name);
+                break;
+            }
+            if found.is_none() && same_name_folded(&file_name, name) {
+                found = Some(file_name);
+            }
+        }
+        on_disk.push(found.as_deref().unwrap_or(name));
+    }
+    Ok(on_disk)
+}
@@ -1,4 +1,11 @@
-use std::sync::OnceLock;
+use crate::fs::{
+    gitignore::is_gitignored, ignore_file::is_ignored, marker::is_marker_file_name, unicode::nfc,
+};
+use std::{
+    os::unix::ffi::OsStrExt,
+    path::{Component, Path},
+    sync::OnceLock,
+};
 use trie_hard::TrieHard;
 
 pub static EXCLUDE_FILES_BY_NAME: OnceLock<TrieHard<'static, &'static str>> = OnceLock::new();
@@ -30,7 +37,37 @@ pub fn exclude() -> TrieHard<'static, &'static str> {
         ".htaccess",
         // .gitignore files are for .git, no point in serving those.
         ".gitignore",
+        // The ignore file of http-horse is for http-horse, not for serving either.
+        ".http-horse-ignore",
+        // Nor is the project config file, which may hold passwords and secrets.
+        ".http-horse.toml",
     ]
     .into_iter()
     .collect::<TrieHard<'_, _>>()
 }
+
+/// Check whether a path inside the project directory is excluded from being tracked and served.
+///
+/// A path is excluded if any of its components relative to the project directory is excluded
+/// by name, or is a marker tempfile, or if the path is ignored by `.gitignore` rules
+/// (when those are respected).
+///
+/// Paths outside the project directory are always considered excluded, as are all paths
+/// if the exclusion rules have not been initialized.
+pub fn is_excluded(project_dir: &Path, path: &Path, is_dir: bool) -> bool {
+    let Some(exclude) = EXCLUDE_FILES_BY_NAME.get() else {
+        return true;
+    };
+    let Ok(rel_path) = path.strip_prefix(project_dir) else {
+        return true;
+    };
+    for component in rel_path.components() {
+        let Component::Normal(name) = component else {
+            return true;
+        };
+        if exclude.get(nfc(name).as_bytes()).is_some() || is_marker_file_name(name.as_bytes()) {
+            return true;
+        }
+    }
+    is_gitignored(path, is_dir) || is_ignored(path, is_dir)
+}
@@ -0,0 +1,137 @@
+//! Optional support for respecting `.gitignore` files found in the project directory tree.
+//!
+//! When enabled, paths matched by `.gitignore` files are excluded from the tracked tree,
+//! from the FS event pipeline, and from the URL space served by the project server.
+//! This keeps build caches and dependency directories from triggering reloads.
+//!
+//! Only `.gitignore` files inside of the project directory are taken into account.
+//! (The project directory itself is frequently ignored by a `.gitignore` further up,
+//! since it usually contains build output.)
+
+use crate::fs::{exclude::EXCLUDE_FILES_BY_NAME, marker::is_marker_file_name, unicode::nfc};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::{
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    sync::{OnceLock, RwLock},
+};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+pub const GITIGNORE_FILE_NAME: &str = ".gitignore";
+
+/// Whether `.gitignore` files are respected. Set once, at program startup.
+pub static RESPECT_GITIGNORE: OnceLock<bool> = OnceLock::new();
+
+/// Parsed `.gitignore` files from all project directories, deepest directories last.
+static GITIGNORES: RwLock<Vec<Gitignore>> = RwLock::new(vec![]);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Exclusion rules not initialized")]
+    ExcludeRulesNotInitialized,
+}
+
+/// Check whether `.gitignore` files are to be respected.
+pub fn respect_gitignore() -> bool {
+    RESPECT_GITIGNORE.get().copied().unwrap_or(false)
+}
+
+/// (Re)load all `.gitignore` files in the project directory tree.
+///
+/// Directories that are excluded, or ignored by a `.gitignore` file further up,
+/// are not descended into. Previously loaded `.gitignore` files from the same
+/// project directory are replaced, while those from other project directories are kept.
+/// Returns the number of `.gitignore` files loaded.
+pub fn load_gitignores(project_dir: &Path) -> Result<usize, Error> {
+    let mut loaded = vec![];
+    collect_gitignores(project_dir, &mut loaded)?;
+    let n_loaded = loaded.len();
+    info!(
+        n_loaded,
+        ?project_dir,
+        "Loaded .gitignore files from project directory."
+    );
+    let mut gitignores = GITIGNORES.write().unwrap_or_else(|e| e.into_inner());
+    gitignores.retain(|gitignore| !gitignore.path().starts_with(project_dir));
+    gitignores.extend(loaded);
+    // Keep deeper directories last, so that they take precedence when matching.
+    gitignores.sort_by_key(|gitignore| gitignore.path().components().count());
+    Ok(n_loaded)
+}
+
+fn collect_gitignores(dpath: &Path, gitignores: &mut Vec<Gitignore>) -> Result<(), Error> {
+    let exclude = EXCLUDE_FILES_BY_NAME
+        .get()
+        .ok_or(Error::ExcludeRulesNotInitialized)?;
+
+    let gitignore_fpath = dpath.join(GITIGNORE_FILE_NAME);
+    if gitignore_fpath.is_file() {
+        let mut builder = GitignoreBuilder::new(dpath);
+        if let Some(e) = builder.add(&gitignore_fpath) {
+            // Partially invalid files still produce a matcher for the valid lines.
+            warn!(err = ?e, ?gitignore_fpath, "Problem parsing .gitignore file.");
+        }
+        match builder.build() {
+            Ok(gitignore) => {
+                debug!(?gitignore_fpath, "Parsed .gitignore file.");
+                gitignores.push(gitignore);
+            }
+            Err(e) => warn!(err = ?e, ?gitignore_fpath, "Failed to parse .gitignore file."),
+        }
+    }
+
+    for dir_entry in std::fs::read_dir(dpath)? {
+        let dir_entry = dir_entry?;
+        let file_name = dir_entry.file_name();
+        if exclude.get(nfc(&file_name).as_bytes()).is_some()
+            || is_marker_file_name(file_name.as_bytes())
+        {
+            continue;
+        }
+        // Symlinks are not followed, same as in the scan of the project directory.
+        if !dir_entry.file_type()?.is_dir() {
+            continue;
+        }
+        let child_dpath = dir_entry.path();
+        if matched(gitignores, &child_dpath, true) {
+            continue;
+        }
+        collect_gitignores(&child_dpath, gitignores)?;
+    }
+    Ok(())
+}
+
+fn matched(gitignores: &[Gitignore], path: &Path, is_dir: bool) -> bool {
+    // The most deeply nested .gitignore file that has something to say about the path wins.
+    for gitignore in gitignores.iter().rev() {
+        if !path.starts_with(gitignore.path()) || path == gitignore.path() {
+            continue;
+        }
+        match gitignore.matched_path_or_any_parents(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+    }
+    false
+}
+
+/// Check whether a path is ignored by `.gitignore` files in the project directory tree.
+///
+/// Always returns `false` when `.gitignore` files are not respected.
+pub fn is_gitignored(path: &Path, is_dir: bool) -> bool {
+    if !respect_gitignore() {
+        return false;
+    }
+    let gitignores = GITIGNORES.read().unwrap_or_else(|e| e.into_inner());
+    matched(&gitignores, path, is_dir)
+}
+
+/// Check whether an FS event concerns a `.gitignore` file.
+pub fn is_gitignore_event(fs_ev: &fsevent::Event) -> bool {
+    Path::new(&fs_ev.path).file_name() == Some(GITIGNORE_FILE_NAME.as_ref())
+}
@@ -0,0 +1,147 @@
+//! Support for a `.http-horse-ignore` file at the top of each project directory.
+//!
+//! The file uses `.gitignore` syntax, negation patterns included, and is for excluding files
+//! from being tracked and served by http-horse in particular, whether or not `.gitignore` files
+//! are respected. Unlike `.gitignore` files, only the one at the top of the project directory
+//! is taken into account.
+//!
+//! Patterns can also be given in the config file, with `exclude`. These apply to every project
+//! directory, along with the rules of its ignore file.
+//!
+//! Patterns can also be added and removed at runtime, through the status API, for when a noisy
+//! directory turns up that is not worth a restart. These apply to every project directory,
+//! along with the rules of its ignore file, and are forgotten on exit.
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::{
+    path::Path,
+    sync::{OnceLock, RwLock},
+};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+pub const IGNORE_FILE_NAME: &str = ".http-horse-ignore";
+
+/// Parsed `.http-horse-ignore` files, of the project directories that have one,
+/// each with the runtime patterns added to it.
+static IGNORE_FILES: RwLock<Vec<Gitignore>> = RwLock::new(vec![]);
+
+/// Patterns from the config file, in `.gitignore` syntax. Set once, at program startup.
+pub static CONFIG_PATTERNS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Patterns added at runtime, in `.gitignore` syntax, in the order they were added.
+static RUNTIME_PATTERNS: RwLock<Vec<String>> = RwLock::new(vec![]);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid pattern {0:?}: {1}")]
+    InvalidPattern(String, ignore::Error),
+}
+
+/// (Re)load the `.http-horse-ignore` file of a project directory, along with the patterns from
+/// the config file and the runtime patterns, replacing the rules previously loaded for it.
+/// Returns whether the project directory has an ignore file.
+pub fn load_ignore_file(project_dir: &Path) -> bool {
+    let ignore_fpath = project_dir.join(IGNORE_FILE_NAME);
+    let has_ignore_file = ignore_fpath.is_file();
+    let config_patterns = CONFIG_PATTERNS.get().map(Vec::as_slice).unwrap_or_default();
+    let runtime_patterns = runtime_patterns();
+    let has_rules = has_ignore_file || !config_patterns.is_empty() || !runtime_patterns.is_empty();
+    let loaded = has_rules.then(|| {
+        let mut builder = GitignoreBuilder::new(project_dir);
+        if has_ignore_file {
+            if let Some(e) = builder.add(&ignore_fpath) {
+                // Partially invalid files still produce a matcher for the valid lines.
+                warn!(err = ?e, ?ignore_fpath, "Problem parsing ignore file.");
+            }
+        }
+        for pattern in config_patterns {
+            // Config patterns are checked at startup.
+            if let Err(e) = builder.add_line(None, pattern) {
+                warn!(err = ?e, pattern, "Problem parsing exclusion pattern from config file.");
+            }
+        }
+        for pattern in &runtime_patterns {
+            // Runtime patterns are checked when they are added.
+            if let Err(e) = builder.add_line(None, pattern) {
+                warn!(err = ?e, pattern, "Problem parsing runtime exclusion pattern.");
+            }
+        }
+        builder
+            .build()
+            .inspect_err(|e| warn!(err = ?e, ?ignore_fpath, "Failed to parse ignore file."))
+            .ok()
+    });
+    let loaded = loaded.flatten();
+    match &loaded {
+        Some(ignore_file) => info!(
+            n_rules = ignore_file.num_ignores() + ignore_file.num_whitelists(),
+            n_config_patterns = config_patterns.len(),
+            n_runtime_patterns = runtime_patterns.len(),
+            ?ignore_fpath,
+            "Loaded ignore file of project directory."
+        ),
+        None => debug!(?ignore_fpath, "Project directory has no ignore file."),
+    }
+    let mut ignore_files = IGNORE_FILES.write().unwrap_or_else(|e| e.into_inner());
+    ignore_files.retain(|ignore_file| ignore_file.path() != project_dir);
+    ignore_files.extend(loaded);
+    has_ignore_file
+}
+
+/// The patterns added at runtime, in the order they were added.
+pub fn runtime_patterns() -> Vec<String> {
+    RUNTIME_PATTERNS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Add and remove runtime patterns, returning the patterns as they are after. Patterns that are
+/// already there are not added twice, and patterns to remove that are not there are passed over.
+///
+/// Nothing is changed if any of the patterns to add is invalid. The rules of the project
+/// directories are left for the caller to reload with [`load_ignore_file`].
+pub fn update_runtime_patterns(add: &[String], remove: &[String]) -> Result<Vec<String>, Error> {
+    check_patterns(add)?;
+    let mut patterns = RUNTIME_PATTERNS.write().unwrap_or_else(|e| e.into_inner());
+    patterns.retain(|pattern| !remove.contains(pattern));
+    for pattern in add {
+        if !pattern.trim().is_empty() && !patterns.contains(pattern) {
+            patterns.push(pattern.clone());
+        }
+    }
+    Ok(patterns.clone())
+}
+
+/// Check that patterns are valid `.gitignore` syntax.
+pub fn check_patterns(patterns: &[String]) -> Result<(), Error> {
+    let mut builder = GitignoreBuilder::new("/");
+    for pattern in patterns {
+        builder
+            .add_line(None, pattern)
+            .map_err(|e| Error::InvalidPattern(pattern.clone(), e))?;
+    }
+    Ok(())
+}
+
+/// Check whether a path is ignored by the `.http-horse-ignore` file of its project directory.
+pub fn is_ignored(path: &Path, is_dir: bool) -> bool {
+    let ignore_files = IGNORE_FILES.read().unwrap_or_else(|e| e.into_inner());
+    ignore_files
+        .iter()
+        .filter(|ignore_file| path.starts_with(ignore_file.path()) && path != ignore_file.path())
+        .any(|ignore_file| {
+            matches!(
+                ignore_file.matched_path_or_any_parents(path, is_dir),
+                Match::Ignore(_)
+            )
+        })
+}
+
+/// Check whether a path is that of the `.http-horse-ignore` file of a project directory.
+pub fn is_ignore_file(project_dir: &Path, path: &Path) -> bool {
+    path.strip_prefix(project_dir)
+        .is_ok_and(|rel_path| rel_path == Path::new(IGNORE_FILE_NAME))
+}
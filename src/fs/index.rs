@@ -0,0 +1,121 @@
+//! A flat index of the tracked project directory tree by path, so that looking up whether
+//! a path is tracked, as the request handlers do for every file they serve, does not take
+//! a walk of the tree.
+//!
+//! The exclusion rules use a trie, but that is built once from a fixed set of names, whereas
+//! the tracked tree changes all the time. So the index is a hash map, kept up to date
+//! along with the tree by [`crate::fs::rescan::GenerationalTree::apply_fs_change`].
+//!
+//! Paths are indexed in NFC, so that they are found by their path in either normalization form.
+//! See [`crate::fs::unicode`]. On case-insensitive file systems, they are indexed in lowercase
+//! as well, so that they are found by their path in any case. See [`crate::fs::case`].
+
+use crate::fs::{
+    case::fold_path,
+    project_dir::{TrackedProjectDir, TrackedProjectFile},
+    unicode::nfc_path,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// What is tracked at a path.
+#[derive(Debug, Clone)]
+pub enum IndexEntry {
+    File(TrackedProjectFile),
+    Dir {
+        /// Absolute path of the directory, as it is on disk.
+        dpath: PathBuf,
+        /// Inode number of the directory.
+        ino: u64,
+    },
+}
+
+impl IndexEntry {
+    /// Absolute path of what is tracked, as it is on disk.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::File(file) => &file.fpath,
+            Self::Dir { dpath, .. } => dpath,
+        }
+    }
+}
+
+/// Everything in a tracked project directory tree, by absolute path in NFC.
+#[derive(Debug, Default)]
+pub struct PathIndex {
+    entries: HashMap<PathBuf, IndexEntry>,
+    /// Whether the tree is on a case-insensitive file system, and indexed in lowercase.
+    case_insensitive: bool,
+}
+
+impl PathIndex {
+    /// Index a tree, including the directory at the top of it.
+    pub fn new(tree: &TrackedProjectDir, case_insensitive: bool) -> Self {
+        let mut index = Self {
+            entries: HashMap::new(),
+            case_insensitive,
+        };
+        index.insert_dir(tree);
+        index
+    }
+
+    /// The key that a path is indexed by.
+    fn key(&self, path: &Path) -> PathBuf {
+        let path = nfc_path(path);
+        if self.case_insensitive {
+            fold_path(&path).into_owned()
+        } else {
+            path.into_owned()
+        }
+    }
+
+    fn insert_dir(&mut self, dir: &TrackedProjectDir) {
+        self.entries.insert(
+            self.key(&dir.dpath),
+            IndexEntry::Dir {
+                dpath: dir.dpath.clone(),
+                ino: dir.ino,
+            },
+        );
+        for file in &dir.tracked_files {
+            self.entries
+                .insert(self.key(&file.fpath), IndexEntry::File(file.clone()));
+        }
+        for subdir in &dir.tracked_dirs {
+            self.insert_dir(subdir);
+        }
+    }
+
+    /// Bring the index up to date with what the tree has at a path, after a change to it.
+    ///
+    /// Changes to files only touch their own entry. A directory that is removed takes a pass
+    /// over the whole index to remove what was in it, which is fine for how seldom that happens.
+    pub fn reindex(&mut self, tree: &TrackedProjectDir, path: &Path) {
+        let key = self.key(path);
+        if let Some(IndexEntry::Dir { .. }) = self.entries.remove(&key) {
+            self.entries
+                .retain(|entry_path, _| !entry_path.starts_with(&key));
+        }
+        if let Some(file) = tree.find_file(path) {
+            self.entries.insert(key, IndexEntry::File(file.clone()));
+        } else if let Some(dir) = tree.find_dir(path) {
+            self.insert_dir(dir);
+        }
+    }
+
+    /// What is tracked at an absolute path, in either normalization form, if anything.
+    /// On case-insensitive file systems, the path may be in any case.
+    pub fn get(&self, path: &Path) -> Option<&IndexEntry> {
+        self.entries.get(&self.key(path))
+    }
+
+    /// The tracked file at an absolute path, if there is one.
+    pub fn file(&self, path: &Path) -> Option<&TrackedProjectFile> {
+        match self.get(path)? {
+            IndexEntry::File(file) => Some(file),
+            IndexEntry::Dir { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Limits on how much of a project directory is tracked, so that pointing http-horse at
+//! a huge tree (like a home directory, or one with `node_modules` in it) does not make it
+//! read and hash everything in it, and hold on to all of it.
+//!
+//! What is past a limit is left out of the tracked tree, but is still served, by looking it up
+//! on disk when it is asked for. Changes to it go unnoticed, so pages are not reloaded for them.
+
+use serde::Serialize;
+use std::sync::OnceLock;
+use thiserror::Error;
+
+/// Limits of the tracked trees. Set once, at program startup.
+pub static SCAN_LIMITS: OnceLock<ScanLimits> = OnceLock::new();
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Expected a size like `500M` or `2G`, got {0:?}")]
+    InvalidSize(String),
+}
+
+/// Limits of a tracked tree. `None` is no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanLimits {
+    /// Number of files.
+    pub max_files: Option<u64>,
+    /// Total size of the files, in bytes.
+    pub max_total_size: Option<u64>,
+    /// Depth of directories below the project directory. At 0, only the files at the top
+    /// of the project directory are tracked.
+    pub max_depth: Option<usize>,
+}
+
+/// The limits of the tracked trees, or no limits if they have not been set.
+pub fn scan_limits() -> ScanLimits {
+    SCAN_LIMITS.get().copied().unwrap_or_default()
+}
+
+/// A limit that a scan ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScanLimit {
+    Files,
+    TotalSize,
+    Depth,
+}
+
+impl ScanLimit {
+    /// What running into the limit means, for warnings.
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Files => "Project directory has more files than the limit on number of files.",
+            Self::TotalSize => "Project directory has more in it than the limit on total size.",
+            Self::Depth => "Project directory has directories nested deeper than the depth limit.",
+        }
+    }
+}
+
+/// Parse a size like `500M` or `2G`, in bytes, or in KiB, MiB or GiB with a `K`, `M` or `G`
+/// suffix. A trailing `B` or `iB` is allowed, as in `500MB` or `500MiB`.
+pub fn parse_size(s: &str) -> Result<u64, Error> {
+    let invalid = || Error::InvalidSize(s.to_string());
+    let text = s.trim().to_ascii_lowercase();
+    let text = text
+        .strip_suffix("ib")
+        .or_else(|| text.strip_suffix('b'))
+        .unwrap_or(&text);
+    let (number, unit) = match text.char_indices().last() {
+        Some((i, 'k')) => (&text[..i], 1u64 << 10),
+        Some((i, 'm')) => (&text[..i], 1 << 20),
+        Some((i, 'g')) => (&text[..i], 1 << 30),
+        _ => (text, 1),
+    };
+    let number = number.trim().parse::<f64>().map_err(|_| invalid())?;
+    let bytes = number * unit as f64;
+    if !bytes.is_finite() || bytes < 0.0 {
+        return Err(invalid());
+    }
+    Ok(bytes as u64)
+}
@@ -0,0 +1,138 @@
+//! Marker tempfiles let us line up our picture of the project directory with the
+//! stream of FS events coming from the FS event observer.
+//!
+//! The idea is as follows: We create a uniquely named temporary file in the project
+//! directory, and then (re)scan the project directory. Any FS event that we receive
+//! before the event corresponding to the creation of our marker tempfile describes
+//! something that happened before we began scanning, and is therefore already reflected
+//! in the result of the scan. So we skip ("fast-forward" past) those events.
+//!
+//! See also the block comment about FS events in `main.rs`.
+
+use crate::fs::project_dir;
+use fsevent::StreamFlags;
+use std::{
+    os::unix::ffi::OsStrExt,
+    path::Path,
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+use tempfile::NamedTempFile;
+use thiserror::Error;
+use tracing::{debug, trace, warn};
+
+/// File name prefix of marker tempfiles. Files with names starting with this prefix
+/// are never tracked nor served, and FS events for them are not forwarded.
+pub const MARKER_TEMPFILE_PREFIX: &str = ".http-horse-marker-";
+
+/// How long we wait for the creation event of a marker tempfile the first time around.
+pub const INITIAL_MARKER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Upper limit for how long we wait for the creation event of a marker tempfile.
+/// Each time we give up waiting, the wait time is doubled, up to this limit.
+pub const MAX_MARKER_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Scan: {0}")]
+    Scan(#[from] project_dir::Error),
+    #[error("FS Event Observer has disconnected")]
+    FsEventObserverDisconnected,
+}
+
+/// Create a new marker tempfile in the given directory.
+///
+/// The marker tempfile is deleted when the returned value is dropped.
+pub fn create_marker_tempfile<P: AsRef<Path>>(dir: P) -> std::io::Result<NamedTempFile> {
+    tempfile::Builder::new()
+        .prefix(MARKER_TEMPFILE_PREFIX)
+        .tempfile_in(dir)
+}
+
+/// Check whether a file name is that of a marker tempfile.
+pub fn is_marker_file_name(file_name: &[u8]) -> bool {
+    file_name.starts_with(MARKER_TEMPFILE_PREFIX.as_bytes())
+}
+
+/// Check whether an FS event concerns a marker tempfile (any marker tempfile, not just ours).
+pub fn is_marker_event(fs_ev: &fsevent::Event) -> bool {
+    Path::new(&fs_ev.path)
+        .file_name()
+        .is_some_and(|file_name| is_marker_file_name(file_name.as_bytes()))
+}
+
+/// Check whether an FS event tells us that our picture of the project directory
+/// can no longer be trusted, so that a full rescan is needed.
+///
+/// This is the case when the FS event API tells us that events were dropped
+/// or coalesced to the point where subdirectories must be rescanned.
+///
+/// Moves into, within, and out of the project directory do not require a full rescan,
+/// as each half of a move is applied to the tracked tree by looking at what is on disk
+/// at the path of the event. See [`crate::fs::project_dir::TrackedProjectDir::apply_fs_change`].
+pub fn requires_rescan(fs_ev: &fsevent::Event) -> bool {
+    fs_ev.flag.intersects(
+        StreamFlags::MUST_SCAN_SUBDIRS | StreamFlags::USER_DROPPED | StreamFlags::KERNEL_DROPPED,
+    )
+}
+
+fn is_creation_of(fs_ev: &fsevent::Event, marker: &NamedTempFile) -> bool {
+    fs_ev.flag.contains(StreamFlags::ITEM_CREATED) && Path::new(&fs_ev.path) == marker.path()
+}
+
+/// Skip FS events up to and including the creation of the given marker tempfile.
+///
+/// The caller is expected to have created the marker tempfile *before* scanning
+/// the project directory. If the creation event of the marker tempfile does not show up
+/// within the time limit, we create a new marker tempfile, call `rescan`, and skip events
+/// up to the creation of the new marker tempfile instead. The time limit is exponentially
+/// increased each time this happens, up to [`MAX_MARKER_TIMEOUT`].
+pub fn fast_forward_to_marker<P, F>(
+    fs_event_rx: &Receiver<fsevent::Event>,
+    marker: NamedTempFile,
+    project_dir: P,
+    mut rescan: F,
+) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    F: FnMut() -> Result<(), Error>,
+{
+    let mut marker = marker;
+    let mut timeout = INITIAL_MARKER_TIMEOUT;
+    let mut t_marker_created = Instant::now();
+    let mut n_skipped = 0usize;
+    loop {
+        let remaining = timeout.saturating_sub(t_marker_created.elapsed());
+        match fs_event_rx.recv_timeout(remaining) {
+            Ok(fs_ev) if is_creation_of(&fs_ev, &marker) => {
+                debug!(
+                    marker = ?marker.path(),
+                    n_skipped,
+                    "Fast-forwarded FS event stream up to creation of marker tempfile."
+                );
+                return Ok(());
+            }
+            Ok(fs_ev) => {
+                trace!(
+                    ?fs_ev,
+                    "Skipping FS event preceding creation of marker tempfile."
+                );
+                n_skipped += 1;
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                warn!(
+                    marker = ?marker.path(),
+                    ?timeout,
+                    "Timed out waiting for creation event of marker tempfile. Creating new marker tempfile and rescanning."
+                );
+                timeout = (timeout * 2).min(MAX_MARKER_TIMEOUT);
+                marker = create_marker_tempfile(project_dir.as_ref())?;
+                t_marker_created = Instant::now();
+                rescan()?;
+            }
+            Err(RecvTimeoutError::Disconnected) => return Err(Error::FsEventObserverDisconnected),
+        }
+    }
+}
@@ -1,2 +1,13 @@
+pub mod case;
 pub mod exclude;
+pub mod gitignore;
+pub mod ignore_file;
+pub mod index;
+pub mod limits;
+pub mod marker;
 pub mod project_dir;
+pub mod project_root;
+pub mod rename;
+pub mod rescan;
+pub mod tree;
+pub mod unicode;
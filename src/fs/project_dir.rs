@@ -2,18 +2,46 @@
 //! The project directory is the user-provided directory containing the built files
 //! which will be served by the http-horse web server, and which will be watched
 //! for changes by http-horse.
+//!
+//! This is the one scanner of project directories. It scans them in full with
+//! [`scan_project_dir`], keeps the tracked tree up to date between full scans with
+//! [`TrackedProjectDir::apply_fs_change`], and tells what differs between two full scans with
+//! [`TrackedProjectDir::changes_since`]. Full rescans go through
+//! [`crate::fs::rescan::RescanManager`], which owns the tree.
 
-use crate::fs::exclude::EXCLUDE_FILES_BY_NAME;
+use crate::{
+    fs::{
+        exclude::EXCLUDE_FILES_BY_NAME,
+        gitignore::is_gitignored,
+        ignore_file::is_ignored,
+        limits::{scan_limits, ScanLimit},
+        marker::is_marker_file_name,
+        unicode::{nfc, same_name},
+    },
+    mime::mime_type_for_path,
+    sniff::{classify, content_type_for, ContentClass},
+};
 use futures_util::future::join_all;
 use smol::fs::{read_dir, File};
+use smol::io::AsyncReadExt;
 use smol::stream::StreamExt;
+use std::collections::HashMap;
 use std::fmt::Debug;
-use std::os::unix::ffi::OsStrExt;
-use std::path::PathBuf;
-use std::sync::OnceLock;
+use std::io::ErrorKind;
+use std::os::unix::{
+    ffi::OsStrExt,
+    fs::{MetadataExt, PermissionsExt},
+};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Mutex,
+};
+use std::time::SystemTime;
 use thiserror::Error;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use trie_hard::TrieHard;
+use xxhash_rust::xxh3::Xxh3;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -21,55 +49,217 @@ pub enum Error {
     IO(#[from] smol::io::Error),
     #[error("Exclusion rules not initialized")]
     ExcludeRulesNotInitialized,
-    #[error("A full re-scan of the project directory was attempted")]
-    FullRescanOfProjectDirWasAttempted,
 }
 
-static I_HAVE_ALREADY_BEEN_RUN: OnceLock<bool> = OnceLock::new();
-
-/// Call this function once, at program startup.
+/// Scan the project directory in full.
 ///
-/// Subsequent calls to this function should not be made. For staying up to date
-/// with file system changes, file system event monitoring should be used.
-pub async fn scan_project_dir(project_dir: PathBuf) -> Result<TrackedProjectDir, Error> {
+/// For staying up to date with file system changes, file system event monitoring should be used.
+/// Full scans are owned by [`crate::fs::rescan::RescanManager`], which keeps track of generations
+/// and swaps in the result of each full scan.
+pub async fn scan_project_dir(
+    project_dir: PathBuf,
+    progress: &ScanProgress,
+) -> Result<TrackedProjectDir, Error> {
     let exclude = EXCLUDE_FILES_BY_NAME
         .get()
         .ok_or(Error::ExcludeRulesNotInitialized)?;
 
-    // HEED THE RULES, OR SUFFER THE CONSEQUENCES!
-    I_HAVE_ALREADY_BEEN_RUN
-        .set(true)
-        .map_err(|_| Error::FullRescanOfProjectDirWasAttempted)?;
+    progress.start();
+    let res = scan_dir(project_dir, 0, exclude, progress).await;
+    progress.finish();
+    res
+}
 
-    scan_dir(project_dir, exclude).await
+/// How far along a full scan is, for reporting progress of scans of big trees,
+/// which otherwise give no sign of life until they are done.
+/// Also what the [`crate::fs::limits`] of the tracked tree are checked against.
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+    scanning: AtomicBool,
+    dirs: AtomicU64,
+    files: AtomicU64,
+    bytes: AtomicU64,
+    hardlinked: AtomicU64,
+    limits_reached: Mutex<Vec<ScanLimit>>,
+}
+
+impl ScanProgress {
+    fn start(&self) {
+        self.dirs.store(0, Ordering::Relaxed);
+        self.files.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+        self.hardlinked.store(0, Ordering::Relaxed);
+        self.limits_reached
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        self.scanning.store(true, Ordering::SeqCst);
+    }
+
+    /// Take note of the scan having run into a limit, warning about it the first time.
+    fn reach(&self, limit: ScanLimit, path: &Path) {
+        let mut limits_reached = self
+            .limits_reached
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if !limits_reached.contains(&limit) {
+            warn!(
+                ?path,
+                ?limit,
+                "{} What is past the limit is served, but not tracked, so changes to it do not reload pages.",
+                limit.description()
+            );
+            limits_reached.push(limit);
+        }
+    }
+
+    /// Count a file of the given size, unless that would take the scan past a limit.
+    fn admit_file(&self, fpath: &Path, size: u64) -> bool {
+        let limits = scan_limits();
+        if let Some(max_files) = limits.max_files {
+            if self.files.fetch_add(1, Ordering::Relaxed) >= max_files {
+                self.files.fetch_sub(1, Ordering::Relaxed);
+                self.reach(ScanLimit::Files, fpath);
+                return false;
+            }
+        } else {
+            self.files.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(max_total_size) = limits.max_total_size {
+            if self.bytes.fetch_add(size, Ordering::Relaxed) + size > max_total_size {
+                self.bytes.fetch_sub(size, Ordering::Relaxed);
+                self.files.fetch_sub(1, Ordering::Relaxed);
+                self.reach(ScanLimit::TotalSize, fpath);
+                return false;
+            }
+        } else {
+            self.bytes.fetch_add(size, Ordering::Relaxed);
+        }
+        true
+    }
+
+    fn finish(&self) {
+        self.scanning.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether a full scan is in progress.
+    pub fn is_scanning(&self) -> bool {
+        self.scanning.load(Ordering::SeqCst)
+    }
+
+    /// Directories scanned so far by the current full scan, or by the last one if none is
+    /// in progress.
+    pub fn dirs(&self) -> u64 {
+        self.dirs.load(Ordering::Relaxed)
+    }
+
+    /// Files found so far by the current full scan, or by the last one if none is in progress.
+    pub fn files(&self) -> u64 {
+        self.files.load(Ordering::Relaxed)
+    }
+
+    /// Total size of the files found so far, in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Files found so far that have more than one hard link. See [`TrackedProjectFile::nlink`].
+    pub fn hardlinked(&self) -> u64 {
+        self.hardlinked.load(Ordering::Relaxed)
+    }
+
+    /// The limits that the current full scan, or the last one, ran into.
+    pub fn limits_reached(&self) -> Vec<ScanLimit> {
+        self.limits_reached
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
 }
 
 /// A regular file that we are tracking updates and changes for,
 /// from the project directory tree.
-#[derive(Debug)]
+///
+/// Tracked files are not held open, so that large projects do not run out of file descriptors.
+/// Files are opened by path whenever they are read, and the path and inode number identify them.
+#[derive(Debug, Clone)]
 pub struct TrackedProjectFile {
     /// Absolute path to file.
     pub fpath: PathBuf,
-    /// Open file handle.
-    pub file: File,
+    /// Inode number of file.
+    pub ino: u64,
+    /// XXH3 hash of the file contents, as of when the file was last read.
+    pub hash: u64,
+    /// Last modification time of the file contents.
+    pub mtime: SystemTime,
+    /// Size of the file, in bytes, as of when the file was last read.
+    pub size: u64,
+    /// Permission bits of the file, like `0o644`.
+    pub mode: u32,
+    /// Number of hard links to the file. Changes made to a file through one of its other links,
+    /// which may well be outside of the project directory, do not result in FS events for the
+    /// path that we track it by, so files with more than one link are polled for changes.
+    pub nlink: u64,
+    /// What the contents of the file are, going by the start of them.
+    pub class: ContentClass,
+    /// MIME type that the file is served with, going by its file name extension,
+    /// and by its class where the extension leaves it open. See [`content_type_for`].
+    pub mime_type: &'static str,
+}
+
+/// Read a file for tracking, hashing its contents and classifying it by the start of them.
+/// The file is closed again once it has been read.
+///
+/// Hashing lets us tell apart actual changes from tools that only touch the mtime of files,
+/// like `touch`, or rsync and build tools that rewrite files with identical contents.
+async fn read_tracked_file(fpath: PathBuf) -> Result<TrackedProjectFile, Error> {
+    let mut file = File::open(&fpath).await?;
+    let metadata = file.metadata().await?;
+    let mut hasher = Xxh3::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut n = file.read(&mut buf).await?;
+    let class = classify(&buf[..n]);
+    while n > 0 {
+        hasher.update(&buf[..n]);
+        n = file.read(&mut buf).await?;
+    }
+    Ok(TrackedProjectFile {
+        ino: metadata.ino(),
+        hash: hasher.digest(),
+        mtime: metadata.modified()?,
+        size: metadata.len(),
+        mode: metadata.permissions().mode() & 0o7777,
+        nlink: metadata.nlink(),
+        class,
+        mime_type: content_type_for(mime_type_for_path(&fpath), class),
+        fpath,
+    })
 }
 
 /// A directory that we are tracking updates and changes for,
 /// from the project directory tree.
 #[derive(Debug)]
 pub struct TrackedProjectDir {
+    /// Absolute path to directory.
+    pub dpath: PathBuf,
+    /// Inode number of directory.
+    pub ino: u64,
     /// Regular files in this directory.
     pub tracked_files: Vec<TrackedProjectFile>,
     /// Subdirectories in this directory.
     pub tracked_dirs: Vec<TrackedProjectDir>,
 }
 
+/// Scan a directory, at the given depth below the project directory, and everything in it.
 async fn scan_dir(
     dpath: PathBuf,
+    depth: usize,
     exclude: &TrieHard<'static, &str>,
+    progress: &ScanProgress,
 ) -> Result<TrackedProjectDir, Error> {
     info!(?dpath, "Scanning directory");
 
+    let ino = smol::fs::metadata(&dpath).await?.ino();
     let mut read_dir = read_dir(&dpath).await?;
 
     let mut tracked_files = vec![];
@@ -80,7 +270,7 @@ async fn scan_dir(
     while let Some(dir_entry) = read_dir.try_next().await? {
         let file_name = dir_entry.file_name();
         debug!(?file_name, ?dpath, "A dir entry was read from directory.");
-        if let Some(matched) = exclude.get(dir_entry.file_name().as_bytes()) {
+        if let Some(matched) = exclude.get(nfc(&file_name).as_bytes()) {
             info!(
                 file_name = matched,
                 ?dpath,
@@ -88,6 +278,10 @@ async fn scan_dir(
             );
             continue;
         }
+        if is_marker_file_name(file_name.as_bytes()) {
+            debug!(?file_name, ?dpath, "Skipping marker tempfile.");
+            continue;
+        }
 
         // Symlinks are actually super useful, but because we want http-horse
         // to never serve files from outside the project directory, it is
@@ -118,28 +312,415 @@ async fn scan_dir(
         if file_type.is_symlink() {
             info!(?file_name, ?dpath, "Skipping file because it is a symlink.");
             continue;
+        } else if is_gitignored(&dpath.join(&file_name), file_type.is_dir()) {
+            info!(
+                ?file_name,
+                ?dpath,
+                "Skipping file based on .gitignore rules."
+            );
+            continue;
+        } else if is_ignored(&dpath.join(&file_name), file_type.is_dir()) {
+            info!(
+                ?file_name,
+                ?dpath,
+                "Skipping file based on ignore file rules."
+            );
+            continue;
         } else if file_type.is_dir() {
             let mut child_dpath = dpath.clone();
             child_dpath.push(file_name);
-            subdir_futs.push(scan_dir(child_dpath, exclude));
+            if scan_limits().max_depth.is_some_and(|max| depth >= max) {
+                progress.reach(ScanLimit::Depth, &child_dpath);
+                continue;
+            }
+            subdir_futs.push(scan_dir(child_dpath, depth + 1, exclude, progress));
         } else if file_type.is_file() {
             let mut fpath = dpath.clone();
             fpath.push(file_name);
-            let file = File::open(&fpath).await?;
-            let tracked_file = TrackedProjectFile { fpath, file };
+            let size = dir_entry.metadata().await?.len();
+            if !progress.admit_file(&fpath, size) {
+                continue;
+            }
+            let tracked_file = read_tracked_file(fpath).await?;
+            if tracked_file.nlink > 1 {
+                info!(
+                    fpath = ?tracked_file.fpath,
+                    nlink = tracked_file.nlink,
+                    "File has multiple hard links. It will be polled for changes."
+                );
+                progress.hardlinked.fetch_add(1, Ordering::Relaxed);
+            }
             tracked_files.push(tracked_file);
         } else {
-            unreachable!("The only three kinds of file type we know of is directory, symlink and regular file.");
+            // FIFOs, sockets and device files can turn up at any time, also during rescans,
+            // and are neither tracked nor served.
+            warn!(
+                ?file_name,
+                ?dpath,
+                ?file_type,
+                "Skipping file that is neither a directory, a symlink nor a regular file."
+            );
         }
     }
 
+    progress.dirs.fetch_add(1, Ordering::Relaxed);
+
     let res: Result<Vec<_>, _> = join_all(subdir_futs).await.into_iter().collect();
     let tracked_dirs = res?;
 
     let tracked_dir = TrackedProjectDir {
+        dpath,
+        ino,
         tracked_files,
         tracked_dirs,
     };
 
     Ok(tracked_dir)
 }
+
+/// The kind of change that was applied to the tracked project directory tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// A file or directory that we were not tracking was found, and is now tracked.
+    Created,
+    /// The contents of a tracked file changed.
+    Modified,
+    /// A tracked file or directory no longer exists, and is no longer tracked.
+    Removed,
+    /// A tracked file or directory was moved from another path within the project directory.
+    /// See [`crate::fs::rename`].
+    Renamed { from: PathBuf },
+}
+
+/// A change that was applied to the tracked project directory tree.
+#[derive(Debug, Clone)]
+pub struct TrackedChange {
+    pub kind: ChangeKind,
+    /// Absolute path of the file or directory that changed.
+    pub path: PathBuf,
+    pub is_dir: bool,
+    /// Inode number of the file or directory that changed.
+    pub ino: u64,
+}
+
+impl TrackedProjectDir {
+    /// Find a tracked directory by its absolute path. The directory itself is found by its own path.
+    pub fn find_dir(&self, dpath: &Path) -> Option<&TrackedProjectDir> {
+        let rel_path = dpath.strip_prefix(&self.dpath).ok()?;
+        let mut dir = self;
+        for component in rel_path.components() {
+            let Component::Normal(name) = component else {
+                return None;
+            };
+            dir = dir
+                .tracked_dirs
+                .iter()
+                .find(|d| d.dpath.file_name().is_some_and(|n| same_name(n, name)))?;
+        }
+        Some(dir)
+    }
+
+    /// Find a tracked file by its absolute path.
+    pub fn find_file(&self, fpath: &Path) -> Option<&TrackedProjectFile> {
+        let rel_path = fpath.strip_prefix(&self.dpath).ok()?;
+        let mut components = rel_path.components().collect::<Vec<_>>();
+        let Component::Normal(file_name) = components.pop()? else {
+            return None;
+        };
+        let mut dir = self;
+        for component in components {
+            let Component::Normal(name) = component else {
+                return None;
+            };
+            dir = dir
+                .tracked_dirs
+                .iter()
+                .find(|d| d.dpath.file_name().is_some_and(|n| same_name(n, name)))?;
+        }
+        dir.tracked_files
+            .iter()
+            .find(|f| f.fpath.file_name().is_some_and(|n| same_name(n, file_name)))
+    }
+
+    /// Absolute paths of the tracked files that have more than one hard link.
+    pub fn hardlinked_files(&self) -> Vec<PathBuf> {
+        let mut fpaths = vec![];
+        self.collect_hardlinked_files(&mut fpaths);
+        fpaths
+    }
+
+    fn collect_hardlinked_files(&self, fpaths: &mut Vec<PathBuf>) {
+        fpaths.extend(
+            self.tracked_files
+                .iter()
+                .filter(|f| f.nlink > 1)
+                .map(|f| f.fpath.clone()),
+        );
+        for dir in &self.tracked_dirs {
+            dir.collect_hardlinked_files(fpaths);
+        }
+    }
+
+    /// The changes that turn an earlier tree of the same directory into this one, as found by
+    /// comparing the two: what is only in this tree was created, what is only in the earlier one
+    /// was removed, and files whose contents hash differently were modified.
+    ///
+    /// Renames are not told apart, and come as a removal and a creation. A directory that was
+    /// created or removed comes as a single change, like it does when applying FS changes.
+    pub fn changes_since(&self, earlier: &TrackedProjectDir) -> Vec<TrackedChange> {
+        let mut changes = vec![];
+        self.collect_changes_since(earlier, &mut changes);
+        changes
+    }
+
+    fn collect_changes_since(&self, earlier: &TrackedProjectDir, changes: &mut Vec<TrackedChange>) {
+        let change = |kind, path: &Path, is_dir, ino| TrackedChange {
+            kind,
+            path: path.to_path_buf(),
+            is_dir,
+            ino,
+        };
+
+        let earlier_files = earlier
+            .tracked_files
+            .iter()
+            .map(|f| (f.fpath.as_path(), f))
+            .collect::<HashMap<_, _>>();
+        let files = self
+            .tracked_files
+            .iter()
+            .map(|f| (f.fpath.as_path(), f))
+            .collect::<HashMap<_, _>>();
+        for f in &earlier.tracked_files {
+            if !files.contains_key(f.fpath.as_path()) {
+                changes.push(change(ChangeKind::Removed, &f.fpath, false, f.ino));
+            }
+        }
+        for f in &self.tracked_files {
+            match earlier_files.get(f.fpath.as_path()) {
+                None => changes.push(change(ChangeKind::Created, &f.fpath, false, f.ino)),
+                Some(earlier_f) if earlier_f.hash != f.hash => {
+                    changes.push(change(ChangeKind::Modified, &f.fpath, false, f.ino))
+                }
+                Some(_) => {}
+            }
+        }
+
+        let earlier_dirs = earlier
+            .tracked_dirs
+            .iter()
+            .map(|d| (d.dpath.as_path(), d))
+            .collect::<HashMap<_, _>>();
+        let dirs = self
+            .tracked_dirs
+            .iter()
+            .map(|d| (d.dpath.as_path(), d))
+            .collect::<HashMap<_, _>>();
+        for d in &earlier.tracked_dirs {
+            if !dirs.contains_key(d.dpath.as_path()) {
+                changes.push(change(ChangeKind::Removed, &d.dpath, true, d.ino));
+            }
+        }
+        for d in &self.tracked_dirs {
+            match earlier_dirs.get(d.dpath.as_path()) {
+                None => changes.push(change(ChangeKind::Created, &d.dpath, true, d.ino)),
+                Some(earlier_d) => d.collect_changes_since(earlier_d, changes),
+            }
+        }
+    }
+
+    /// Update the tracked tree to match what is currently on disk at the given path.
+    ///
+    /// FS events are advisory and are often coalesced, so rather than trusting the flags
+    /// of an event, we look at what is actually there at the path that the event was for.
+    /// This way, a single code path handles creation, modification, deletion, and both
+    /// halves of a rename (the old path no longer exists, and the new path does).
+    ///
+    /// Returns `None` if the path is outside the project directory, is excluded,
+    /// or if the tree already matched what is on disk. Files whose contents hash the same
+    /// as before are not considered modified.
+    pub async fn apply_fs_change(&mut self, path: &Path) -> Result<Option<TrackedChange>, Error> {
+        let exclude = EXCLUDE_FILES_BY_NAME
+            .get()
+            .ok_or(Error::ExcludeRulesNotInitialized)?;
+
+        let Ok(rel_path) = path.strip_prefix(&self.dpath) else {
+            debug!(?path, "Path is outside of tracked tree.");
+            return Ok(None);
+        };
+        let mut rel_components = vec![];
+        for component in rel_path.components() {
+            let Component::Normal(name) = component else {
+                debug!(?path, "Path has unexpected components.");
+                return Ok(None);
+            };
+            if exclude.get(nfc(name).as_bytes()).is_some() || is_marker_file_name(name.as_bytes()) {
+                debug!(?path, "Path is excluded from tracked tree.");
+                return Ok(None);
+            }
+            rel_components.push(name);
+        }
+        let Some((&file_name, parent_components)) = rel_components.split_last() else {
+            // The project directory itself. Its contents are dealt with through events for them.
+            return Ok(None);
+        };
+        // Directories past the depth limit are not tracked, and neither is anything in them.
+        let max_depth = scan_limits().max_depth;
+        if max_depth.is_some_and(|max| parent_components.len() > max) {
+            debug!(?path, "Path is past the depth limit of the tracked tree.");
+            return Ok(None);
+        }
+
+        let metadata = match smol::fs::symlink_metadata(path).await {
+            Ok(metadata) => Some(metadata),
+            Err(e) if e.kind() == ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        let is_dir = metadata.as_ref().is_some_and(|m| m.is_dir());
+        if is_gitignored(path, is_dir) {
+            debug!(?path, "Path is ignored by .gitignore rules.");
+            return Ok(None);
+        }
+        if is_ignored(path, is_dir) {
+            debug!(?path, "Path is ignored by ignore file rules.");
+            return Ok(None);
+        }
+
+        // Find the deepest tracked directory along the path of the parent directory.
+        let mut dir = self;
+        let mut n_tracked_components = 0;
+        for name in parent_components {
+            match dir
+                .tracked_dirs
+                .iter()
+                .position(|d| d.dpath.file_name().is_some_and(|n| same_name(n, name)))
+            {
+                Some(idx) => {
+                    dir = &mut dir.tracked_dirs[idx];
+                    n_tracked_components += 1;
+                }
+                None => break,
+            }
+        }
+
+        if n_tracked_components < parent_components.len() {
+            // An ancestor directory is not tracked. If the ancestor exists on disk,
+            // we scan it in full, which brings everything below it up to date,
+            // including the path that this change was for.
+            let untracked_dpath = dir.dpath.join(parent_components[n_tracked_components]);
+            return match smol::fs::symlink_metadata(&untracked_dpath).await {
+                Ok(m) if m.is_dir() => {
+                    let tracked_dir = scan_dir(
+                        untracked_dpath.clone(),
+                        n_tracked_components + 1,
+                        exclude,
+                        &ScanProgress::default(),
+                    )
+                    .await?;
+                    dir.tracked_dirs.push(tracked_dir);
+                    Ok(Some(TrackedChange {
+                        kind: ChangeKind::Created,
+                        path: untracked_dpath,
+                        is_dir: true,
+                        ino: m.ino(),
+                    }))
+                }
+                Ok(_) => Ok(None),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            };
+        }
+
+        let file_idx = dir
+            .tracked_files
+            .iter()
+            .position(|f| f.fpath.file_name().is_some_and(|n| same_name(n, file_name)));
+        let dir_idx = dir
+            .tracked_dirs
+            .iter()
+            .position(|d| d.dpath.file_name().is_some_and(|n| same_name(n, file_name)));
+        let path = dir.dpath.join(file_name);
+
+        let change = match (metadata, file_idx, dir_idx) {
+            // Symlinks are not tracked. See the comment about symlinks in `scan_dir`.
+            (None, None, None) => None,
+            (Some(m), None, None) if m.is_symlink() => None,
+            (None, Some(idx), _) => {
+                let tracked_file = dir.tracked_files.swap_remove(idx);
+                Some((ChangeKind::Removed, false, tracked_file.ino))
+            }
+            (None, None, Some(idx)) => {
+                let tracked_dir = dir.tracked_dirs.swap_remove(idx);
+                Some((ChangeKind::Removed, true, tracked_dir.ino))
+            }
+            (Some(m), Some(idx), _) if m.is_file() => {
+                // The file may have been replaced by an atomic save, with a new inode,
+                // so we always read it anew by its path.
+                let mut reread = read_tracked_file(path.clone()).await?;
+                let tracked_file = &mut dir.tracked_files[idx];
+                let unchanged = tracked_file.hash == reread.hash;
+                if unchanged {
+                    // The mtime is kept as is, so that it keeps reflecting the last time
+                    // the contents actually changed.
+                    reread.mtime = tracked_file.mtime;
+                }
+                let ino = reread.ino;
+                *tracked_file = reread;
+                if unchanged {
+                    debug!(?path, "File contents are unchanged.");
+                    None
+                } else {
+                    Some((ChangeKind::Modified, false, ino))
+                }
+            }
+            (Some(m), None, Some(_)) if m.is_dir() => None,
+            (Some(m), None, None)
+                if m.is_dir() && max_depth.is_some_and(|max| rel_components.len() > max) =>
+            {
+                debug!(
+                    ?path,
+                    "Directory is past the depth limit of the tracked tree."
+                );
+                None
+            }
+            (Some(m), file_idx, dir_idx) => {
+                // The kind of thing that is at this path is different from what we were tracking,
+                // or we were not tracking anything at this path. Start over for this path.
+                let mut removed_ino = m.ino();
+                if let Some(idx) = file_idx {
+                    removed_ino = dir.tracked_files.swap_remove(idx).ino;
+                }
+                if let Some(idx) = dir_idx {
+                    removed_ino = dir.tracked_dirs.swap_remove(idx).ino;
+                }
+                if m.is_dir() && max_depth.is_none_or(|max| rel_components.len() <= max) {
+                    let tracked_dir = scan_dir(
+                        path.clone(),
+                        rel_components.len(),
+                        exclude,
+                        &ScanProgress::default(),
+                    )
+                    .await?;
+                    dir.tracked_dirs.push(tracked_dir);
+                    Some((ChangeKind::Created, true, m.ino()))
+                } else if m.is_file() {
+                    let tracked_file = read_tracked_file(path.clone()).await?;
+                    let ino = tracked_file.ino;
+                    dir.tracked_files.push(tracked_file);
+                    Some((ChangeKind::Created, false, ino))
+                } else {
+                    // Symlink, or directory past the depth limit, replacing something that
+                    // we were tracking.
+                    Some((ChangeKind::Removed, dir_idx.is_some(), removed_ino))
+                }
+            }
+        };
+
+        Ok(change.map(|(kind, is_dir, ino)| TrackedChange {
+            kind,
+            path,
+            is_dir,
+            ino,
+        }))
+    }
+}
@@ -0,0 +1,210 @@
+//! http-horse can watch and serve multiple project directories at once.
+//!
+//! Each project directory is a "project root", served by the project server
+//! under its own URL path prefix (its "mount point"), and watched by its own
+//! FS event observer.
+//!
+//! Mount points are derived from the names of the project directories, or given explicitly
+//! with mounts like `/docs=./build/docs`. Project roots can also be tied to a host name, like
+//! `admin.localhost=./admin`, in which case they serve requests for that host name at `/`,
+//! in place of the project roots that serve requests for all other host names.
+
+use crate::fs::{case::is_case_insensitive, project_dir::ScanProgress, rescan::RescanManager};
+use crate::headers::{load_headers_file, HeaderRule};
+use crate::redirects::{load_redirects_file, RedirectRule};
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, OnceLock, RwLock},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Expected a mount on the form `/prefix=DIR`, got {0:?}")]
+    InvalidMount(String),
+    #[error("Expected a virtual host on the form `HOST=DIR`, got {0:?}")]
+    InvalidVirtualHost(String),
+}
+
+/// A project directory, and the URL path prefix that it is served under.
+#[derive(Debug)]
+pub struct ProjectRoot {
+    /// URL path prefix that the project directory is served under,
+    /// without leading or trailing slashes. Empty when served at `/`.
+    pub mount: String,
+    /// Canonical path to the project directory.
+    pub dir: PathBuf,
+    /// Host name, in lowercase, that the project directory serves requests for.
+    /// `None` for project directories that serve requests for other host names.
+    pub host: Option<String>,
+    /// Whether the project directory is on a case-insensitive file system.
+    /// See [`crate::fs::case`].
+    pub case_insensitive: bool,
+    /// The tracked project directory tree. Set once the initial full scan is done.
+    pub tree: OnceLock<RescanManager>,
+    /// Progress of full scans of the project directory, including the initial one.
+    pub scan_progress: Arc<ScanProgress>,
+    /// Rules from the `_headers` file of the project directory.
+    pub header_rules: RwLock<Vec<HeaderRule>>,
+    /// Rules from the `_redirects` file of the project directory.
+    pub redirect_rules: RwLock<Vec<RedirectRule>>,
+}
+
+impl ProjectRoot {
+    pub fn new(mount: String, dir: PathBuf) -> Self {
+        Self {
+            mount,
+            case_insensitive: is_case_insensitive(&dir),
+            dir,
+            host: None,
+            tree: OnceLock::new(),
+            scan_progress: Arc::default(),
+            header_rules: RwLock::new(vec![]),
+            redirect_rules: RwLock::new(vec![]),
+        }
+    }
+
+    /// Serve requests for the given host name only.
+    pub fn with_host(mut self, host: String) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    /// (Re)load the rules from the `_headers` and `_redirects` files of the project directory.
+    pub fn load_rules(&self) {
+        let header_rules = load_headers_file(&self.dir);
+        *self.header_rules.write().unwrap_or_else(|e| e.into_inner()) = header_rules;
+        let redirect_rules = load_redirects_file(&self.dir);
+        *self
+            .redirect_rules
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = redirect_rules;
+    }
+
+    /// Check whether a file or directory exists at a URL path below the mount point.
+    pub fn has_path(&self, uri_path: &str) -> bool {
+        let uri_path = uri_path.trim_start_matches('/');
+        uri_path.is_empty() || self.dir.join(uri_path).exists()
+    }
+
+    /// URL path (with leading and trailing slash) that the project directory is served at.
+    pub fn mount_url_path(&self) -> String {
+        if self.mount.is_empty() {
+            "/".to_string()
+        } else {
+            format!("/{}/", self.mount)
+        }
+    }
+}
+
+/// A project directory with an explicitly given mount point, like `/docs=./build/docs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    /// URL path prefix, without leading or trailing slashes. Empty for `/`.
+    pub mount: String,
+    /// Path to the project directory, as given.
+    pub dir: String,
+}
+
+impl FromStr for Mount {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mount, dir) = s
+            .split_once('=')
+            .filter(|(mount, dir)| mount.starts_with('/') && !dir.is_empty())
+            .ok_or_else(|| Error::InvalidMount(s.to_string()))?;
+        Ok(Self {
+            mount: mount.trim_matches('/').to_string(),
+            dir: dir.to_string(),
+        })
+    }
+}
+
+/// A project directory that serves requests for a host name, like `admin.localhost=./admin`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirtualHost {
+    /// Host name, in lowercase, without port.
+    pub host: String,
+    /// Path to the project directory, as given.
+    pub dir: String,
+}
+
+impl FromStr for VirtualHost {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (host, dir) = s
+            .split_once('=')
+            .filter(|(host, dir)| !host.is_empty() && !host.contains(['/', ':']) && !dir.is_empty())
+            .ok_or_else(|| Error::InvalidVirtualHost(s.to_string()))?;
+        Ok(Self {
+            host: host.trim_end_matches('.').to_ascii_lowercase(),
+            dir: dir.to_string(),
+        })
+    }
+}
+
+/// Derive mount points for a list of project directories.
+///
+/// A single project directory is served at `/`. When there are multiple project directories,
+/// each one is served under the name of the directory, with a numeric suffix added
+/// where needed to tell apart project directories that have the same name.
+pub fn derive_mounts(dirs: &[PathBuf]) -> Vec<String> {
+    if dirs.len() == 1 {
+        return vec![String::new()];
+    }
+    let mut taken = HashSet::new();
+    dirs.iter()
+        .map(|dir| {
+            let name = dir
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "root".to_string());
+            let mut mount = name.clone();
+            let mut n = 1;
+            while !taken.insert(mount.clone()) {
+                n += 1;
+                mount = format!("{name}-{n}");
+            }
+            mount
+        })
+        .collect()
+}
+
+/// Find the project root serving the given URL path (with leading slashes trimmed),
+/// for a request for the given host name, if known.
+///
+/// Returns the project root along with the remainder of the URL path below its mount point.
+/// When mount points are nested, the most specific one wins.
+pub fn resolve<'a>(
+    roots: &'a [ProjectRoot],
+    host: Option<&str>,
+    uri_path: &'a str,
+) -> Option<(&'a ProjectRoot, &'a str)> {
+    let is_virtual_host =
+        host.is_some_and(|host| roots.iter().any(|root| root.host.as_deref() == Some(host)));
+    roots
+        .iter()
+        .filter(|root| {
+            if is_virtual_host {
+                root.host.as_deref() == host
+            } else {
+                root.host.is_none()
+            }
+        })
+        .filter_map(|root| {
+            if root.mount.is_empty() {
+                return Some((root, uri_path));
+            }
+            let rest = uri_path.strip_prefix(root.mount.as_str())?;
+            if rest.is_empty() || rest.starts_with('/') {
+                Some((root, rest.trim_start_matches('/')))
+            } else {
+                None
+            }
+        })
+        .max_by_key(|(root, _)| root.mount.len())
+}
@@ -0,0 +1,193 @@
+//! When a file or directory is moved within the project directory, we get two FS events.
+//! One for the old path, and one for the new path. Each of them is applied to the tracked tree
+//! on its own, resulting in a removal and a creation. To report the move as a single logical
+//! rename instead, we correlate the two halves by inode number.
+//!
+//! Moves into or out of the project directory only have one half that we get to see,
+//! and are reported as a creation or a removal, respectively.
+//!
+//! Many editors (Vim among them) save files atomically, by writing the new contents
+//! to a temporary file and then renaming it over the original. Seen on their own,
+//! the changes for such a save are a creation and a removal of the temporary file,
+//! and a modification of the original file, which now has the inode of the temporary file.
+//! We correlate these too, so that an atomic save is reported as a single modification.
+
+use crate::fs::project_dir::{ChangeKind, TrackedChange};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+/// How long we hold on to a change, waiting for a related change to show up.
+pub const RENAME_CORRELATION_WINDOW: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+struct Pending {
+    change: TrackedChange,
+    since: Instant,
+    /// Whether the file came and went without its creation having been reported,
+    /// in which case its removal is not reported either.
+    transient: bool,
+}
+
+/// Correlates related changes, such as the two halves of renames.
+#[derive(Debug, Default)]
+pub struct RenameCorrelator {
+    pending: Vec<Pending>,
+}
+
+/// What to do with a new change, given a held change for the same inode.
+enum Correlation {
+    /// Drop the held change, and report this change instead.
+    Replace(TrackedChange),
+    /// Drop the held change, and hold on to this one instead.
+    Hold(Pending),
+    /// Keep the held change. The new change adds nothing to it.
+    Absorb,
+    /// Drop both the held change and the new change.
+    Cancel,
+}
+
+impl RenameCorrelator {
+    /// Feed a change that was applied to the tracked tree into the correlator.
+    ///
+    /// `renamed` tells whether the FS event that resulted in the change was flagged as a rename.
+    /// Returns the changes that are ready to be reported, in order.
+    ///
+    /// Held changes that this change is not related to are held on to, so that several files
+    /// can be renamed or saved at once, until [`Self::flush_expired`] gives up on them. Only those
+    /// for the same paths as the change are reported before it, to keep order for those paths.
+    pub fn push(&mut self, change: TrackedChange, renamed: bool) -> Vec<TrackedChange> {
+        let related = self
+            .pending
+            .iter()
+            .position(|p| p.change.ino == change.ino && p.change.is_dir == change.is_dir);
+        if let Some(idx) = related {
+            if let Some(correlation) = correlate(&self.pending[idx], &change, renamed) {
+                let pending = self.pending.remove(idx);
+                match correlation {
+                    Correlation::Replace(change) => {
+                        let mut ready = self.take_same_paths(&change);
+                        ready.push(change);
+                        return ready;
+                    }
+                    Correlation::Hold(held) => self.pending.push(held),
+                    Correlation::Absorb => self.pending.insert(idx, pending),
+                    Correlation::Cancel => {}
+                }
+                return vec![];
+            }
+        }
+
+        let mut ready = self.take_same_paths(&change);
+        let hold = match change.kind {
+            ChangeKind::Removed => renamed,
+            // Newly created files are held in case they turn out to be temporary files.
+            ChangeKind::Created => renamed || !change.is_dir,
+            _ => false,
+        };
+        if hold {
+            self.pending.push(Pending {
+                change,
+                since: Instant::now(),
+                transient: false,
+            });
+        } else {
+            ready.push(change);
+        }
+        ready
+    }
+
+    /// Give up on finding related changes for the changes that we have held long enough.
+    pub fn flush_expired(&mut self) -> Vec<TrackedChange> {
+        let (expired, pending) = self
+            .pending
+            .drain(..)
+            .partition::<Vec<_>, _>(|p| p.since.elapsed() >= RENAME_CORRELATION_WINDOW);
+        self.pending = pending;
+        expired
+            .into_iter()
+            .filter(|p| !p.transient)
+            .map(|p| p.change)
+            .collect()
+    }
+
+    /// Give up on finding related changes for all held changes.
+    pub fn flush(&mut self) -> Vec<TrackedChange> {
+        self.pending
+            .drain(..)
+            .filter(|p| !p.transient)
+            .map(|p| p.change)
+            .collect()
+    }
+
+    /// Give up on the held changes for any of the paths of a change, which have to be reported
+    /// before it, so that changes to a path are reported in the order they happened.
+    fn take_same_paths(&mut self, change: &TrackedChange) -> Vec<TrackedChange> {
+        let (same, other) = self.pending.drain(..).partition::<Vec<_>, _>(|p| {
+            paths(&p.change).any(|path| paths(change).any(|other| other == path))
+        });
+        self.pending = other;
+        same.into_iter()
+            .filter(|p| !p.transient)
+            .map(|p| p.change)
+            .collect()
+    }
+}
+
+/// The path of a change, and the path it was renamed from, if any.
+fn paths(change: &TrackedChange) -> impl Iterator<Item = &Path> {
+    let from = match &change.kind {
+        ChangeKind::Renamed { from } => Some(from.as_path()),
+        _ => None,
+    };
+    std::iter::once(change.path.as_path()).chain(from)
+}
+
+fn correlate(pending: &Pending, change: &TrackedChange, renamed: bool) -> Option<Correlation> {
+    let same_path = pending.change.path == change.path;
+    match (&pending.change.kind, &change.kind) {
+        // The two halves of a rename.
+        (ChangeKind::Removed, ChangeKind::Created) if !same_path => {
+            Some(Correlation::Replace(TrackedChange {
+                kind: ChangeKind::Renamed {
+                    from: pending.change.path.clone(),
+                },
+                ..change.clone()
+            }))
+        }
+        (ChangeKind::Created, ChangeKind::Removed) if !same_path => {
+            Some(Correlation::Replace(TrackedChange {
+                kind: ChangeKind::Renamed {
+                    from: change.path.clone(),
+                },
+                ..pending.change.clone()
+            }))
+        }
+        // A file that is still being written to after its creation.
+        (ChangeKind::Created, ChangeKind::Modified) if same_path => Some(Correlation::Absorb),
+        // A temporary file that went away again. If it was renamed, it may be the first half
+        // of an atomic save, so we hold on to its removal for a while longer.
+        (ChangeKind::Created, ChangeKind::Removed) if renamed => Some(Correlation::Hold(Pending {
+            change: change.clone(),
+            since: Instant::now(),
+            transient: true,
+        })),
+        (ChangeKind::Created, ChangeKind::Removed) => Some(Correlation::Cancel),
+        // A file was renamed over a tracked file, which now has the inode of the renamed file.
+        // When the renamed file was a temporary file, this is an atomic save.
+        (ChangeKind::Removed, ChangeKind::Modified) if !change.is_dir => {
+            if pending.transient {
+                Some(Correlation::Replace(change.clone()))
+            } else {
+                Some(Correlation::Replace(TrackedChange {
+                    kind: ChangeKind::Renamed {
+                        from: pending.change.path.clone(),
+                    },
+                    ..change.clone()
+                }))
+            }
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,177 @@
+//! Full rescans of the project directory are deliberate, controlled operations.
+//!
+//! The tracked project directory tree is owned by a [`RescanManager`], which tags each
+//! full scan result with a generation number and atomically swaps it in, so that anyone
+//! reading the tree (the serving and status layers) sees either the old tree or the new one,
+//! and never a partially rebuilt one. In between full rescans, the tree is kept up to date
+//! incrementally from FS events.
+
+use crate::fs::{
+    index::PathIndex,
+    project_dir::{scan_project_dir, Error, ScanProgress, TrackedChange, TrackedProjectDir},
+};
+use smol::lock::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+use tracing::{debug, info};
+
+/// A tracked project directory tree, tagged with the generation of the full scan it came from.
+#[derive(Debug)]
+pub struct GenerationalTree {
+    /// Generation number. The initial full scan is generation 1, and each subsequent
+    /// full rescan increments the generation by one. Incremental updates from FS events
+    /// do not change the generation.
+    pub generation: u64,
+    pub tree: TrackedProjectDir,
+    /// Index of the tree by path. See [`GenerationalTree::apply_fs_change`].
+    pub index: PathIndex,
+}
+
+impl GenerationalTree {
+    /// Update the tree to match what is currently on disk at the given path, and the index
+    /// along with it. Incremental updates should go through here rather than through
+    /// [`TrackedProjectDir::apply_fs_change`], so that the index stays up to date.
+    pub async fn apply_fs_change(&mut self, path: &Path) -> Result<Option<TrackedChange>, Error> {
+        let change = self.tree.apply_fs_change(path).await?;
+        match &change {
+            Some(change) => self.index.reindex(&self.tree, &change.path),
+            // A file whose contents are unchanged may still have had its permissions changed.
+            None if self.tree.find_file(path).is_some() => self.index.reindex(&self.tree, path),
+            None => {}
+        }
+        Ok(change)
+    }
+}
+
+/// The outcome of a full rescan.
+#[derive(Debug)]
+pub struct Rescanned {
+    /// Generation of the tree that the rescan swapped in.
+    pub generation: u64,
+    /// What changed between the tree that was swapped out and the one swapped in.
+    /// See [`TrackedProjectDir::changes_since`].
+    pub changes: Vec<TrackedChange>,
+}
+
+/// A rescan asked for by someone other than the FS event transformer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum RescanRequest {
+    /// Swap in the rescanned tree, leaving what changed unreported,
+    /// as for when watching is resumed after being paused.
+    Quiet = 1,
+    /// Swap in the rescanned tree, and report what changed as if it had been
+    /// observed through FS events, for when the tree got out of sync.
+    Reconciling = 2,
+}
+
+/// Owner of the tracked project directory tree.
+#[derive(Debug)]
+pub struct RescanManager {
+    project_dir: PathBuf,
+    /// Whether the project directory is on a case-insensitive file system.
+    case_insensitive: bool,
+    current: RwLock<GenerationalTree>,
+    /// Held for the duration of a rescan, so that rescans do not overlap.
+    rescan_in_progress: Mutex<()>,
+    /// The [`RescanRequest`] pending, as its discriminant, or 0 if none is.
+    rescan_requested: AtomicU8,
+    /// Progress of full scans, shared with whoever reports it.
+    progress: Arc<ScanProgress>,
+}
+
+impl RescanManager {
+    /// Perform the initial full scan of the project directory, keeping track of its progress,
+    /// and of that of later rescans, in `progress`.
+    pub async fn new(
+        project_dir: PathBuf,
+        case_insensitive: bool,
+        progress: Arc<ScanProgress>,
+    ) -> Result<Self, Error> {
+        let tree = scan_project_dir(project_dir.clone(), &progress).await?;
+        let index = PathIndex::new(&tree, case_insensitive);
+        Ok(Self {
+            project_dir,
+            case_insensitive,
+            current: RwLock::new(GenerationalTree {
+                generation: 1,
+                tree,
+                index,
+            }),
+            rescan_in_progress: Mutex::new(()),
+            rescan_requested: AtomicU8::new(0),
+            progress,
+        })
+    }
+
+    /// Rescan the project directory in full and swap in the result as a new generation.
+    ///
+    /// The scan itself is performed without holding the lock on the current tree,
+    /// so readers are only blocked for the duration of the swap.
+    ///
+    /// Note that this alone does not line up the new tree with the FS event stream.
+    /// Outside of the FS event transformer, use [`RescanManager::request_rescan`] instead.
+    pub async fn rescan(&self) -> Result<Rescanned, Error> {
+        let _rescan_guard = self.rescan_in_progress.lock().await;
+        let t_start_rescan = Instant::now();
+        let tree = scan_project_dir(self.project_dir.clone(), &self.progress).await?;
+        let index = PathIndex::new(&tree, self.case_insensitive);
+        let mut current = self.current.write().await;
+        current.generation += 1;
+        current.index = index;
+        let earlier = std::mem::replace(&mut current.tree, tree);
+        let generation = current.generation;
+        let changes = current.tree.changes_since(&earlier);
+        drop(current);
+        let t_spent_rescanning = Instant::now() - t_start_rescan;
+        info!(
+            generation,
+            ?t_spent_rescanning,
+            n_changes = changes.len(),
+            "Finished full rescan of project directory."
+        );
+        Ok(Rescanned {
+            generation,
+            changes,
+        })
+    }
+
+    /// Ask for a full rescan of the project directory, to be carried out by the FS event
+    /// transformer, which takes care of lining up the new tree with the FS event stream.
+    /// Of several requests made before the rescan, the reconciling kind wins.
+    pub fn request_rescan(&self, request: RescanRequest) {
+        debug!(?request, "Full rescan of project directory requested.");
+        self.rescan_requested
+            .fetch_max(request as u8, Ordering::SeqCst);
+    }
+
+    /// Check whether a rescan was requested, clearing the request.
+    pub fn take_rescan_request(&self) -> Option<RescanRequest> {
+        match self.rescan_requested.swap(0, Ordering::SeqCst) {
+            0 => None,
+            1 => Some(RescanRequest::Quiet),
+            _ => Some(RescanRequest::Reconciling),
+        }
+    }
+
+    /// Read access to the current tree.
+    pub async fn read(&self) -> RwLockReadGuard<'_, GenerationalTree> {
+        self.current.read().await
+    }
+
+    /// Write access to the current tree, for incremental updates.
+    pub async fn write(&self) -> RwLockWriteGuard<'_, GenerationalTree> {
+        self.current.write().await
+    }
+
+    /// Generation of the current tree.
+    pub async fn generation(&self) -> u64 {
+        self.current.read().await.generation
+    }
+}
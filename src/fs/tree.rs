@@ -0,0 +1,109 @@
+//! The tracked project directory tree as shown to the status web-ui, with the URL paths
+//! that files and directories are served under.
+
+use crate::{
+    fs::project_dir::{TrackedProjectDir, TrackedProjectFile},
+    sniff::ContentClass,
+};
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// A tracked file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TreeFile {
+    /// Absolute path of the file.
+    pub path: PathBuf,
+    /// URL path that the file is served under, if any.
+    pub url_path: Option<String>,
+    /// Size in bytes.
+    pub size: u64,
+    /// Last modification time of the file contents, in milliseconds since the Unix epoch.
+    pub mtime_ms: u64,
+    /// XXH3 hash of the file contents, in hex.
+    pub hash: String,
+    /// Permission bits, in octal, like `0644`.
+    pub mode: String,
+    /// Number of hard links. Files with more than one are polled for changes.
+    pub nlink: u64,
+    /// What the contents of the file are.
+    pub class: ContentClass,
+    /// MIME type that the file is served with.
+    pub mime_type: &'static str,
+}
+
+/// A tracked directory, with everything in it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TreeDir {
+    /// Absolute path of the directory.
+    pub path: PathBuf,
+    /// URL path that the directory is served under, if any.
+    pub url_path: Option<String>,
+    pub files: Vec<TreeFile>,
+    pub dirs: Vec<TreeDir>,
+}
+
+/// A tracked file or directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum TreeNode {
+    File(TreeFile),
+    Dir(TreeDir),
+}
+
+impl TreeFile {
+    pub fn new(file: &TrackedProjectFile, url_path_of: &impl Fn(&Path) -> Option<String>) -> Self {
+        let mtime_ms = file
+            .mtime
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Self {
+            path: file.fpath.clone(),
+            url_path: url_path_of(&file.fpath),
+            size: file.size,
+            mtime_ms,
+            hash: format!("{:016x}", file.hash),
+            mode: format!("{:04o}", file.mode),
+            nlink: file.nlink,
+            class: file.class,
+            mime_type: file.mime_type,
+        }
+    }
+}
+
+impl TreeDir {
+    pub fn new(dir: &TrackedProjectDir, url_path_of: &impl Fn(&Path) -> Option<String>) -> Self {
+        Self {
+            path: dir.dpath.clone(),
+            url_path: url_path_of(&dir.dpath),
+            files: dir
+                .tracked_files
+                .iter()
+                .map(|file| TreeFile::new(file, url_path_of))
+                .collect(),
+            dirs: dir
+                .tracked_dirs
+                .iter()
+                .map(|dir| TreeDir::new(dir, url_path_of))
+                .collect(),
+        }
+    }
+}
+
+impl TreeNode {
+    /// What is tracked at an absolute path in a tree, if anything.
+    pub fn find(
+        tree: &TrackedProjectDir,
+        path: &Path,
+        url_path_of: &impl Fn(&Path) -> Option<String>,
+    ) -> Option<Self> {
+        if let Some(file) = tree.find_file(path) {
+            return Some(Self::File(TreeFile::new(file, url_path_of)));
+        }
+        tree.find_dir(path)
+            .map(|dir| Self::Dir(TreeDir::new(dir, url_path_of)))
+    }
+}
@@ -0,0 +1,37 @@
+//! File names can be in either of two Unicode normalization forms: with accented letters
+//! composed into one code point (NFC), or as a letter followed by a combining accent (NFD).
+//! macOS file systems hand out file names in NFD, whereas URLs, editors, and most everything
+//! else use NFC. So the same name, like `café.html`, can come in two different byte strings,
+//! and comparisons of file names and paths have to be made on a normalized form of them.
+
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// A file name in NFC. Names that are not valid UTF-8 are left as they are.
+pub fn nfc(name: &OsStr) -> Cow<'_, OsStr> {
+    match name.to_str() {
+        Some(s) if is_nfc_quick(s.chars()) != IsNormalized::Yes => {
+            Cow::Owned(OsString::from(s.nfc().collect::<String>()))
+        }
+        _ => Cow::Borrowed(name),
+    }
+}
+
+/// A path in NFC. Paths that are not valid UTF-8 are left as they are.
+pub fn nfc_path(path: &Path) -> Cow<'_, Path> {
+    match path.to_str() {
+        Some(s) if is_nfc_quick(s.chars()) != IsNormalized::Yes => {
+            Cow::Owned(PathBuf::from(s.nfc().collect::<String>()))
+        }
+        _ => Cow::Borrowed(path),
+    }
+}
+
+/// Check whether two file names are the same, in whichever normalization forms they are.
+pub fn same_name(a: &OsStr, b: &OsStr) -> bool {
+    a == b || nfc(a) == nfc(b)
+}
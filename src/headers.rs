@@ -0,0 +1,148 @@
+//! Custom response headers for URL paths matching a pattern.
+//!
+//! Rules come from the config file, and from a Netlify-style `_headers` file at the top
+//! of each project directory. A `_headers` file lists URL path patterns, each followed
+//! by indented `Name: value` lines with the headers to add to responses for matching paths:
+//!
+//! ```text
+//! # Needed for SharedArrayBuffer
+//! /*
+//!   Cross-Origin-Opener-Policy: same-origin
+//!   Cross-Origin-Embedder-Policy: require-corp
+//!
+//! /admin/:page
+//!   Content-Security-Policy: default-src 'self'
+//! ```
+//!
+//! See [`crate::pattern`] for the syntax of patterns.
+
+use crate::pattern;
+use hyper::header::{HeaderName, HeaderValue};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// Name of the file with header rules at the top of a project directory.
+pub const HEADERS_FILE_NAME: &str = "_headers";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Line {line}: Header {text:?} is not preceded by a path pattern")]
+    HeaderWithoutPattern { line: usize, text: String },
+    #[error("Line {line}: Expected a header on the form `Name: value`, got {text:?}")]
+    InvalidHeader { line: usize, text: String },
+    #[error("Invalid header name {0:?}")]
+    InvalidHeaderName(String),
+    #[error("Invalid value for header {0:?}")]
+    InvalidHeaderValue(String),
+}
+
+/// Headers to add to responses for URL paths matching a pattern.
+#[derive(Debug, Clone)]
+pub struct HeaderRule {
+    /// URL path pattern, with leading slash.
+    pub pattern: String,
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+/// A header rule as written in the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeaderRuleConfig {
+    /// URL path pattern, with leading slash.
+    #[serde(rename = "for")]
+    pub pattern: String,
+    pub values: BTreeMap<String, String>,
+}
+
+impl HeaderRule {
+    fn new(pattern: String) -> Self {
+        Self {
+            pattern,
+            headers: vec![],
+        }
+    }
+
+    fn push(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        let name = HeaderName::try_from(name.trim())
+            .map_err(|_| Error::InvalidHeaderName(name.to_string()))?;
+        let value = HeaderValue::try_from(value.trim())
+            .map_err(|_| Error::InvalidHeaderValue(name.to_string()))?;
+        self.headers.push((name, value));
+        Ok(())
+    }
+
+    /// Check whether this rule applies to a URL path (with leading slash).
+    pub fn matches(&self, url_path: &str) -> bool {
+        pattern::matches(&self.pattern, url_path)
+    }
+}
+
+impl TryFrom<HeaderRuleConfig> for HeaderRule {
+    type Error = Error;
+
+    fn try_from(config: HeaderRuleConfig) -> Result<Self, Self::Error> {
+        let mut rule = HeaderRule::new(config.pattern);
+        for (name, value) in &config.values {
+            rule.push(name, value)?;
+        }
+        Ok(rule)
+    }
+}
+
+/// Parse the contents of a `_headers` file.
+pub fn parse_headers_file(contents: &str) -> Result<Vec<HeaderRule>, Error> {
+    let mut rules: Vec<HeaderRule> = vec![];
+    for (i, line) in contents.lines().enumerate() {
+        let line_no = i + 1;
+        let text = line.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            rules.push(HeaderRule::new(text.to_string()));
+            continue;
+        }
+        let Some(rule) = rules.last_mut() else {
+            return Err(Error::HeaderWithoutPattern {
+                line: line_no,
+                text: text.to_string(),
+            });
+        };
+        let Some((name, value)) = text.split_once(':') else {
+            return Err(Error::InvalidHeader {
+                line: line_no,
+                text: text.to_string(),
+            });
+        };
+        rule.push(name, value)?;
+    }
+    Ok(rules)
+}
+
+/// Load the `_headers` file at the top of a project directory, if there is one.
+///
+/// A missing file means no rules. A file that fails to parse is reported, and gives no rules,
+/// so that a typo made while editing the file does not take down the server.
+pub fn load_headers_file(project_dir: &Path) -> Vec<HeaderRule> {
+    let fpath = project_dir.join(HEADERS_FILE_NAME);
+    let contents = match std::fs::read_to_string(&fpath) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return vec![],
+        Err(e) => {
+            warn!(err = ?e, ?fpath, "Failed to read headers file.");
+            return vec![];
+        }
+    };
+    match parse_headers_file(&contents) {
+        Ok(rules) => {
+            debug!(?fpath, n_rules = rules.len(), "Loaded headers file.");
+            rules
+        }
+        Err(e) => {
+            warn!(err = %e, ?fpath, "Failed to parse headers file. Ignoring it.");
+            vec![]
+        }
+    }
+}
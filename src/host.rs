@@ -0,0 +1,170 @@
+//! Validation of the `Host` header of requests, against DNS rebinding.
+//!
+//! In a DNS rebinding attack, a web page on a domain controlled by the attacker has the domain
+//! name resolve to 127.0.0.1, after which the browser lets the page read responses from our servers
+//! as if they were its own. Such requests still carry the attacker's domain name in the `Host`
+//! header, so we only serve requests for the addresses the servers are bound to,
+//! for `localhost`, and for host names that have been explicitly allowed.
+
+use std::net::IpAddr;
+
+/// Decides which host names and addresses requests may be for.
+#[derive(Debug, Clone, Default)]
+pub struct HostCheck {
+    /// Addresses the servers are bound to.
+    bound: Vec<IpAddr>,
+    /// Allowed host names, in lowercase. A leading `*.` allows any subdomain,
+    /// and `*` on its own allows any host.
+    allowed: Vec<String>,
+}
+
+impl HostCheck {
+    pub fn new(bound: Vec<IpAddr>, allowed: Vec<String>) -> Self {
+        let allowed = allowed
+            .into_iter()
+            .map(|host| host.trim_end_matches('.').to_ascii_lowercase())
+            .collect();
+        Self { bound, allowed }
+    }
+
+    /// Whether a request with the given `Host` header value, with or without port, may be served.
+    /// Requests without a `Host` header do not come from browsers, and are allowed.
+    pub fn is_allowed(&self, host: Option<&str>) -> bool {
+        let Some(host) = host else {
+            return true;
+        };
+        let host = host_name(host);
+        // Browsers send internationalized domain names in punycode, so other host names
+        // do not come from them, and are not matched against the allowed ones at all.
+        if !host.is_ascii() {
+            return false;
+        }
+        if let Ok(ip) = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+        {
+            // A domain name cannot pass for an IP address, so IP addresses are safe as long as
+            // they are ones we can be reached at.
+            return ip.is_loopback()
+                || self
+                    .bound
+                    .iter()
+                    .any(|bound| bound.is_unspecified() || *bound == ip);
+        }
+        host == "localhost"
+            || host.ends_with(".localhost")
+            || self.allowed.iter().any(|allowed| {
+                allowed == "*"
+                    || *allowed == host
+                    || allowed
+                        .strip_prefix("*.")
+                        .is_some_and(|domain| host.ends_with(&format!(".{domain}")))
+            })
+    }
+}
+
+/// The host name of a `Host` header value, without port or trailing dot, in lowercase.
+pub fn host_name(host: &str) -> String {
+    strip_port(host).trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Strip the port, if any, from a `Host` header value.
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        return host.find(']').map_or(host, |end| &host[..=end]);
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn check(allowed: &[&str]) -> HostCheck {
+        HostCheck::new(
+            vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))],
+            allowed.iter().map(|host| host.to_string()).collect(),
+        )
+    }
+
+    #[test]
+    fn strips_port() {
+        assert_eq!(strip_port("localhost:8080"), "localhost");
+        assert_eq!(strip_port("localhost"), "localhost");
+        assert_eq!(strip_port("[::1]:8080"), "[::1]");
+        assert_eq!(strip_port("[::1]"), "[::1]");
+        assert_eq!(strip_port("example.test:"), "example.test");
+        assert_eq!(strip_port("example.test:http"), "example.test:http");
+    }
+
+    #[test]
+    fn allows_ipv6_loopback_with_port() {
+        let check = check(&[]);
+        assert!(check.is_allowed(Some("[::1]:8080")));
+        assert!(check.is_allowed(Some("[::1]")));
+        assert!(!check.is_allowed(Some("[fe80::2]:8080")));
+    }
+
+    #[test]
+    fn allows_any_address_when_bound_to_unspecified() {
+        let check = HostCheck::new(vec![IpAddr::V6(Ipv6Addr::UNSPECIFIED)], vec![]);
+        assert!(check.is_allowed(Some("[fe80::2]:8080")));
+        assert!(check.is_allowed(Some("192.168.1.20:8080")));
+        assert!(!check.is_allowed(Some("evil.example:8080")));
+    }
+
+    #[test]
+    fn allows_bound_addresses_and_localhost() {
+        let check = check(&[]);
+        assert!(check.is_allowed(None));
+        assert!(check.is_allowed(Some("192.168.1.10:8080")));
+        assert!(check.is_allowed(Some("127.0.0.1:8080")));
+        assert!(check.is_allowed(Some("LOCALHOST:8080")));
+        assert!(check.is_allowed(Some("app.localhost")));
+        assert!(!check.is_allowed(Some("192.168.1.20:8080")));
+        assert!(!check.is_allowed(Some("evil.example")));
+        assert!(!check.is_allowed(Some("localhost.evil.example")));
+    }
+
+    #[test]
+    fn ignores_trailing_dot() {
+        let check = check(&["my-laptop.local."]);
+        assert!(check.is_allowed(Some("localhost.:8080")));
+        assert!(check.is_allowed(Some("my-laptop.local.:8080")));
+        assert!(check.is_allowed(Some("my-laptop.local")));
+        assert_eq!(host_name("Example.Test.:8080"), "example.test");
+    }
+
+    #[test]
+    fn allows_subdomains_with_wildcard_rule() {
+        let check = check(&["*.example.test"]);
+        assert!(check.is_allowed(Some("app.example.test")));
+        assert!(check.is_allowed(Some("a.b.example.test:8080")));
+        assert!(!check.is_allowed(Some("example.test")));
+        assert!(!check.is_allowed(Some("evilexample.test")));
+        assert!(!check.is_allowed(Some("example.test.evil.example")));
+    }
+
+    #[test]
+    fn allows_any_host_with_star_rule() {
+        assert!(check(&["*"]).is_allowed(Some("evil.example")));
+    }
+
+    #[test]
+    fn rejects_non_ascii_host() {
+        let check = check(&["example.test", "*.example.test"]);
+        assert!(!check.is_allowed(Some("exämple.test")));
+        assert!(!check.is_allowed(Some("ü.exämple.test:8080")));
+        assert!(!check.is_allowed(Some("ü:8080")));
+        assert!(!check.is_allowed(Some("ü.example.test")));
+        assert!(!check.is_allowed(Some("ü.localhost")));
+        assert!(check.is_allowed(Some("xn--tda.example.test")));
+        // Only ASCII letters are lowercased, so the host names are left as they are otherwise.
+        assert_eq!(host_name("ÜBER.Example.test"), "Über.example.test");
+    }
+}
@@ -0,0 +1,242 @@
+//! A minimal scanner for the start tags of HTML documents, enough to find the resources that
+//! pages reference, without pulling in a full HTML parser.
+//!
+//! Comments are skipped, but the contents of `<script>` and `<style>` elements are not,
+//! so markup in strings in inline scripts may be picked up as tags.
+//!
+//! Documents can also be checked for obvious problems, like elements that are never closed,
+//! or end tags that do not match any open element. Elements whose end tags may be left out,
+//! like `<p>` and `<li>`, are not checked.
+
+/// A start tag, with its name and the names of its attributes in lowercase.
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub attrs: Vec<(String, String)>,
+    /// Position after the tag.
+    end: usize,
+    /// Whether the tag ends with `/>`, as void elements and elements in SVG may.
+    self_closing: bool,
+}
+
+impl Tag {
+    /// Value of an attribute, by lowercase name.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(attr_name, _)| attr_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Find the start tags of an HTML document, in document order.
+/// If `until` is the name of an element, only the tags before its end tag are returned.
+pub fn start_tags(html: &[u8], until: Option<&str>) -> Vec<Tag> {
+    let html = String::from_utf8_lossy(html);
+    let lowercase = html.to_ascii_lowercase();
+    let end = until
+        .and_then(|name| lowercase.find(&format!("</{name}")))
+        .unwrap_or(html.len());
+    let (html, lowercase) = (&html[..end], &lowercase[..end]);
+
+    let mut tags = vec![];
+    let mut pos = 0;
+    while let Some(offset) = lowercase[pos..].find('<') {
+        let start = pos + offset;
+        if lowercase[start..].starts_with("<!--") {
+            pos = lowercase[start..]
+                .find("-->")
+                .map_or(end, |offset| start + offset + 3);
+            continue;
+        }
+        let Some(tag) = parse_tag(html, start) else {
+            break;
+        };
+        pos = tag.end;
+        tags.push(tag);
+    }
+    tags
+}
+
+/// Parse the start tag at the given position. Returns `None` if the tag is not closed.
+fn parse_tag(html: &str, start: usize) -> Option<Tag> {
+    let bytes = html.as_bytes();
+    let mut i = start + 1;
+    let name_start = i;
+    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !matches!(bytes[i], b'>' | b'/') {
+        i += 1;
+    }
+    let name = html[name_start..i].to_ascii_lowercase();
+    let mut attrs = vec![];
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+        if *bytes.get(i)? == b'>' {
+            return Some(Tag {
+                name,
+                attrs,
+                end: i + 1,
+                self_closing: bytes[i - 1] == b'/',
+            });
+        }
+        let attr_start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !matches!(bytes[i], b'>' | b'=')
+        {
+            i += 1;
+        }
+        let attr_name = html[attr_start..i].to_ascii_lowercase();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let mut value = String::new();
+        if bytes.get(i) == Some(&b'=') {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            match bytes.get(i)? {
+                quote @ (b'"' | b'\'') => {
+                    let value_start = i + 1;
+                    let value_end = value_start + html[value_start..].find(*quote as char)?;
+                    value = html[value_start..value_end].to_string();
+                    i = value_end + 1;
+                }
+                _ => {
+                    let value_start = i;
+                    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                        i += 1;
+                    }
+                    value = html[value_start..i].to_string();
+                }
+            }
+        }
+        attrs.push((attr_name, value.replace("&amp;", "&")));
+    }
+}
+
+/// Elements that have no contents, and so no end tag.
+const VOID_ELEMENTS: [&str; 15] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "keygen", "link", "meta", "param",
+    "source", "track", "wbr",
+];
+
+/// Elements whose end tags may be left out.
+const OPTIONAL_END_TAG_ELEMENTS: [&str; 18] = [
+    "body", "caption", "colgroup", "dd", "dt", "head", "html", "li", "optgroup", "option", "p",
+    "rp", "rt", "tbody", "td", "tfoot", "th", "thead",
+];
+
+/// Elements whose contents are text, and never markup.
+const RAW_TEXT_ELEMENTS: [&str; 4] = ["script", "style", "textarea", "title"];
+
+/// How many problems are reported at most, as later ones tend to follow from earlier ones.
+const MAX_PROBLEMS: usize = 10;
+
+/// An obvious problem with an HTML document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    /// Line of the document that the problem is at, counting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+/// Check an HTML document for obvious problems, in document order.
+pub fn problems(html: &[u8]) -> Vec<Problem> {
+    let html = String::from_utf8_lossy(html);
+    let lowercase = html.to_ascii_lowercase();
+    let line_at = |pos: usize| html[..pos].matches('\n').count() + 1;
+
+    let mut problems = vec![];
+    // Open elements, with the position of their start tag.
+    let mut open: Vec<(String, usize)> = vec![];
+    let mut pos = 0;
+    while let Some(offset) = lowercase[pos..].find('<') {
+        let start = pos + offset;
+        let rest = &lowercase[start..];
+        if rest.starts_with("<!--") {
+            let Some(offset) = rest.find("-->") else {
+                problems.push(Problem {
+                    line: line_at(start),
+                    message: "Comment is never closed with `-->`.".to_string(),
+                });
+                return problems;
+            };
+            pos = start + offset + 3;
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            pos = rest
+                .find('>')
+                .map_or(html.len(), |offset| start + offset + 1);
+        } else if let Some(name) = rest.strip_prefix("</") {
+            let name_len = name
+                .find(|c: char| c.is_ascii_whitespace() || c == '>')
+                .unwrap_or(name.len());
+            let name = &name[..name_len];
+            pos = rest
+                .find('>')
+                .map_or(html.len(), |offset| start + offset + 1);
+            if VOID_ELEMENTS.contains(&name) || OPTIONAL_END_TAG_ELEMENTS.contains(&name) {
+                continue;
+            }
+            match open.iter().rposition(|(open_name, _)| open_name == name) {
+                Some(index) => {
+                    for (unclosed, unclosed_start) in open.drain(index..).skip(1) {
+                        problems.push(Problem {
+                            line: line_at(unclosed_start),
+                            message: format!(
+                                "`<{unclosed}>` is not closed before `</{name}>` on line {}.",
+                                line_at(start)
+                            ),
+                        });
+                    }
+                }
+                None => problems.push(Problem {
+                    line: line_at(start),
+                    message: format!("`</{name}>` does not match any open element."),
+                }),
+            }
+        } else if rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            let Some(tag) = parse_tag(&html, start) else {
+                problems.push(Problem {
+                    line: line_at(start),
+                    message: "Start tag is never closed with `>`.".to_string(),
+                });
+                return problems;
+            };
+            pos = tag.end;
+            if RAW_TEXT_ELEMENTS.contains(&tag.name.as_str()) {
+                match lowercase[pos..].find(&format!("</{}", tag.name)) {
+                    Some(offset) => pos += offset,
+                    None => {
+                        problems.push(Problem {
+                            line: line_at(start),
+                            message: format!("`<{}>` is never closed.", tag.name),
+                        });
+                        return problems;
+                    }
+                }
+            }
+            if !tag.self_closing
+                && !VOID_ELEMENTS.contains(&tag.name.as_str())
+                && !OPTIONAL_END_TAG_ELEMENTS.contains(&tag.name.as_str())
+            {
+                open.push((tag.name, start));
+            }
+        } else {
+            // A less-than sign in text.
+            pos = start + 1;
+        }
+        if problems.len() >= MAX_PROBLEMS {
+            problems.truncate(MAX_PROBLEMS);
+            return problems;
+        }
+    }
+    for (unclosed, unclosed_start) in open.into_iter().take(MAX_PROBLEMS) {
+        problems.push(Problem {
+            line: line_at(unclosed_start),
+            message: format!("`<{unclosed}>` is never closed."),
+        });
+    }
+    problems.truncate(MAX_PROBLEMS);
+    problems
+}
@@ -0,0 +1,291 @@
+//! Translations of the status web-ui. The language is chosen by the `Accept-Language`
+//! request header, unless the `lang` query parameter names one.
+//!
+//! To add a translation, add a variant to [`Language`], with the language subtags that it is
+//! chosen for, and a [`Messages`] with the translated user interface strings.
+
+use serde::Serialize;
+
+/// Query parameter that chooses the language of the status web-ui, overriding `Accept-Language`.
+pub const LANG_PARAM: &str = "lang";
+
+/// A language that the status web-ui is translated into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    NorwegianBokmal,
+}
+
+impl Language {
+    /// Every language, the fallback first.
+    pub const ALL: [Self; 2] = [Self::English, Self::NorwegianBokmal];
+
+    /// Language tag, as for the `lang` attribute of pages.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::NorwegianBokmal => "nb",
+        }
+    }
+
+    /// Primary language subtags that the language is chosen for.
+    fn primary_subtags(self) -> &'static [&'static str] {
+        match self {
+            Self::English => &["en"],
+            // Norwegian Nynorsk readers are better served by Bokmål than by English.
+            Self::NorwegianBokmal => &["nb", "no", "nn"],
+        }
+    }
+
+    pub fn messages(self) -> &'static Messages {
+        match self {
+            Self::English => &ENGLISH,
+            Self::NorwegianBokmal => &NORWEGIAN_BOKMAL,
+        }
+    }
+
+    /// The language for a language tag, like `nb` or `en-GB`, going by its primary subtag.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let primary_subtag = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        Self::ALL.into_iter().find(|language| {
+            language
+                .primary_subtags()
+                .contains(&primary_subtag.as_str())
+        })
+    }
+}
+
+/// Choose the language according to the value of an `Accept-Language` request header.
+/// Among languages that are equally acceptable, the one that the client names first is chosen.
+/// English is the fallback, including when the client accepts none of the languages.
+pub fn negotiate_language(accept_language: &str) -> Language {
+    accept_language
+        .split(',')
+        .enumerate()
+        .filter_map(|(position, item)| {
+            let mut params = item.split(';').map(str::trim);
+            let language = Language::from_tag(params.next()?)?;
+            let q = params
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            (q > 0.0).then_some((language, q, position))
+        })
+        .max_by(|(_, a_q, a_pos), (_, b_q, b_pos)| a_q.total_cmp(b_q).then(b_pos.cmp(a_pos)))
+        .map_or(Language::English, |(language, ..)| language)
+}
+
+/// User interface strings of the status web-ui, in one language.
+/// They are used both by the index page template and, as JSON, by the script of the page.
+#[derive(Debug, Serialize)]
+pub struct Messages {
+    pub project: &'static str,
+    pub pause_watching: &'static str,
+    pub resume_watching: &'static str,
+    pub pause_serving: &'static str,
+    pub resume_serving: &'static str,
+    pub pause_serving_title: &'static str,
+    pub reload_pages: &'static str,
+    pub reload_pages_title: &'static str,
+    pub color_scheme: &'static str,
+    pub pages_and_resources: &'static str,
+    pub connected_clients: &'static str,
+    pub request_log: &'static str,
+    pub history: &'static str,
+    /// What a reload of every page is about, in the history.
+    pub every_page: &'static str,
+    /// Button that shows the diff of a change.
+    pub diff: &'static str,
+    pub no_diff: &'static str,
+    /// Between the kind of an error in a page and the page, as in "error in /index.html".
+    pub error_in: &'static str,
+    /// Before the time that a client connected.
+    pub connected: &'static str,
+    pub reloads_paused: &'static str,
+    /// Before the time of the last heartbeat of a client.
+    pub last_heartbeat: &'static str,
+    /// Link to the project preview page, and its title.
+    pub preview: &'static str,
+    pub preview_title: &'static str,
+    /// Link from the project preview page back to the status page.
+    pub status: &'static str,
+    /// Label of the URL path of the previewed page.
+    pub page: &'static str,
+    pub show: &'static str,
+    pub reload_previews: &'static str,
+    pub phone: &'static str,
+    pub tablet: &'static str,
+    pub desktop: &'static str,
+    /// Progress of a full scan of a project directory, as in
+    /// "Scanning /path/to/dir: 120 directories, 4031 files".
+    pub scanning: &'static str,
+    pub directories: &'static str,
+    pub files: &'static str,
+    /// Warning about a project directory that is only partly tracked, as in
+    /// "Not every file is tracked for changes in /path/to/dir, past the limit on number of files".
+    pub partly_tracked: &'static str,
+    pub past_limit_on: &'static str,
+    pub limit_files: &'static str,
+    pub limit_total_size: &'static str,
+    pub limit_depth: &'static str,
+    /// Note about files with more than one hard link, as in
+    /// "3 files with multiple hard links in /path/to/dir are polled for changes".
+    pub hardlinked: &'static str,
+    pub polled_for_changes: &'static str,
+    /// State of the build command, as in "Running build command npm run build"
+    /// or "Build command failed: npm run build (exit code 1)".
+    pub running_build: &'static str,
+    pub build_failed: &'static str,
+    pub exit_code: &'static str,
+    /// Outcomes of runs of the build command, in the history.
+    pub succeeded: &'static str,
+    pub cancelled: &'static str,
+    pub build_output: &'static str,
+    /// States of the steps of the build pipeline.
+    pub step_pending: &'static str,
+    pub step_running: &'static str,
+    pub step_succeeded: &'static str,
+    pub step_failed: &'static str,
+    pub step_cancelled: &'static str,
+    pub step_skipped: &'static str,
+    /// Share of the runs in the build timeline that succeeded, as in "Success rate: 90 %".
+    pub success_rate: &'static str,
+    /// Size of the output of a run, in the build timeline.
+    pub bytes_of_output: &'static str,
+    pub companions: &'static str,
+    /// States of companion processes.
+    pub companion_starting: &'static str,
+    pub companion_running: &'static str,
+    pub companion_restarting: &'static str,
+    pub companion_exited: &'static str,
+    /// Number of times a companion process was restarted, as in "3 restarts".
+    pub restarts: &'static str,
+    /// Button that restarts a companion process.
+    pub restart: &'static str,
+}
+
+static ENGLISH: Messages = Messages {
+    project: "Project",
+    pause_watching: "Pause watching",
+    resume_watching: "Resume watching",
+    pause_serving: "Pause serving",
+    resume_serving: "Resume serving",
+    pause_serving_title: "Answer every request to the project server with 503",
+    reload_pages: "Reload pages",
+    reload_pages_title: "Reload every page open in browsers",
+    color_scheme: "Color scheme",
+    pages_and_resources: "Pages and their referenced resources",
+    connected_clients: "Connected clients",
+    request_log: "Request log",
+    history: "Recent file system event and page error history",
+    every_page: "every page",
+    diff: "diff",
+    no_diff: "No diff was kept for this change.",
+    error_in: "in",
+    connected: "connected",
+    reloads_paused: "reloads paused",
+    last_heartbeat: "Last heartbeat",
+    preview: "Preview",
+    preview_title: "Preview the project at several viewport sizes side by side",
+    status: "Status",
+    page: "Page",
+    show: "Show",
+    reload_previews: "Reload previews",
+    phone: "Phone",
+    tablet: "Tablet",
+    desktop: "Desktop",
+    scanning: "Scanning",
+    directories: "directories",
+    files: "files",
+    partly_tracked: "Not every file is tracked for changes in",
+    past_limit_on: "past the limit on",
+    limit_files: "number of files",
+    limit_total_size: "total size",
+    limit_depth: "depth",
+    hardlinked: "files with multiple hard links in",
+    polled_for_changes: "are polled for changes",
+    running_build: "Running build command",
+    build_failed: "Build command failed",
+    exit_code: "exit code",
+    succeeded: "succeeded",
+    cancelled: "cancelled",
+    build_output: "Build output",
+    step_pending: "pending",
+    step_running: "running",
+    step_succeeded: "succeeded",
+    step_failed: "failed",
+    step_cancelled: "cancelled",
+    step_skipped: "skipped",
+    success_rate: "Success rate",
+    bytes_of_output: "bytes of output",
+    companions: "Companion processes",
+    companion_starting: "starting",
+    companion_running: "running",
+    companion_restarting: "restarting",
+    companion_exited: "exited",
+    restarts: "restarts",
+    restart: "Restart",
+};
+
+static NORWEGIAN_BOKMAL: Messages = Messages {
+    project: "Prosjekt",
+    pause_watching: "Sett overvåking på pause",
+    resume_watching: "Gjenoppta overvåking",
+    pause_serving: "Sett servering på pause",
+    resume_serving: "Gjenoppta servering",
+    pause_serving_title: "Svar på alle forespørsler til prosjektserveren med 503",
+    reload_pages: "Last inn sider på nytt",
+    reload_pages_title: "Last inn alle sider som er åpne i nettlesere på nytt",
+    color_scheme: "Fargetema",
+    pages_and_resources: "Sider og ressursene de refererer til",
+    connected_clients: "Tilkoblede klienter",
+    request_log: "Forespørselslogg",
+    history: "Nylige filsystemhendelser og feil på sider",
+    every_page: "alle sider",
+    diff: "diff",
+    no_diff: "Ingen diff ble tatt vare på for denne endringen.",
+    error_in: "i",
+    connected: "tilkoblet",
+    reloads_paused: "innlasting satt på pause",
+    last_heartbeat: "Siste livstegn",
+    preview: "Forhåndsvisning",
+    preview_title: "Forhåndsvis prosjektet i flere visningsstørrelser side om side",
+    status: "Status",
+    page: "Side",
+    show: "Vis",
+    reload_previews: "Last inn forhåndsvisninger på nytt",
+    phone: "Telefon",
+    tablet: "Nettbrett",
+    desktop: "Skrivebord",
+    scanning: "Skanner",
+    directories: "mapper",
+    files: "filer",
+    partly_tracked: "Ikke alle filer overvåkes for endringer i",
+    past_limit_on: "forbi grensen for",
+    limit_files: "antall filer",
+    limit_total_size: "total størrelse",
+    limit_depth: "dybde",
+    hardlinked: "filer med flere harde lenker i",
+    polled_for_changes: "sjekkes jevnlig for endringer",
+    running_build: "Kjører byggekommando",
+    build_failed: "Byggekommandoen feilet",
+    exit_code: "avslutningskode",
+    succeeded: "vellykket",
+    cancelled: "avbrutt",
+    build_output: "Byggeutdata",
+    step_pending: "venter",
+    step_running: "kjører",
+    step_succeeded: "vellykket",
+    step_failed: "feilet",
+    step_cancelled: "avbrutt",
+    step_skipped: "hoppet over",
+    success_rate: "Andel vellykket",
+    bytes_of_output: "byte utdata",
+    companions: "Følgeprosesser",
+    companion_starting: "starter",
+    companion_running: "kjører",
+    companion_restarting: "starter på nytt",
+    companion_exited: "avsluttet",
+    restarts: "omstarter",
+    restart: "Start på nytt",
+};
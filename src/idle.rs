@@ -0,0 +1,203 @@
+//! Closing of connections that have been idle or stuck for too long.
+//!
+//! hyper only times out reading request headers once it has started reading them,
+//! so it leaves a kept-alive connection open for as long as the client does not send anything.
+//! Instead, we keep track of when bytes last went over a connection, and when it has been quiet
+//! for long enough, we ask hyper to shut the connection down gracefully. That closes it right away
+//! if it is between requests, and otherwise once the response in progress has been sent,
+//! so that long-lived responses like event streams are left alone.
+//!
+//! A client that stops receiving a response would still hold on to its connection forever,
+//! so a connection is dropped outright when sending to it has made no progress for too long.
+
+use smol::{
+    future,
+    io::{AsyncRead, AsyncWrite},
+    Timer,
+};
+use std::{
+    future::Future,
+    io,
+    pin::{pin, Pin},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// The client did not receive any of the response being sent to it for too long.
+#[derive(Debug, Error)]
+#[error("Sending to client timed out")]
+pub struct SendTimedOut;
+
+/// How long a connection may go without activity.
+#[derive(Debug, Clone, Copy)]
+pub struct Timeouts {
+    /// Time without bytes going either way, after which the connection is shut down gracefully.
+    pub idle: Duration,
+    /// Time that sending may be blocked on the client, after which the connection is dropped.
+    pub send: Duration,
+}
+
+/// When bytes were last read from or written to a connection.
+#[derive(Debug, Clone)]
+pub struct Activity {
+    start: Instant,
+    /// Milliseconds since `start`.
+    last_ms: Arc<AtomicU64>,
+    /// Milliseconds since `start`, plus one, of when a write started waiting on the client.
+    /// Zero while writes are not blocked.
+    write_blocked_ms: Arc<AtomicU64>,
+}
+
+impl Activity {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            last_ms: Arc::new(AtomicU64::new(0)),
+            write_blocked_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn elapsed_ms(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    fn touch(&self) {
+        self.last_ms.fetch_max(self.elapsed_ms(), Ordering::Relaxed);
+    }
+
+    fn last(&self) -> Instant {
+        self.start + Duration::from_millis(self.last_ms.load(Ordering::Relaxed))
+    }
+
+    fn set_write_blocked(&self, blocked: bool) {
+        if blocked {
+            let ms = self.elapsed_ms() + 1;
+            let _ =
+                self.write_blocked_ms
+                    .compare_exchange(0, ms, Ordering::Relaxed, Ordering::Relaxed);
+        } else {
+            self.write_blocked_ms.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn write_blocked_since(&self) -> Option<Instant> {
+        match self.write_blocked_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(self.start + Duration::from_millis(ms - 1)),
+        }
+    }
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A stream that records its reads and writes in an [`Activity`].
+#[derive(Debug)]
+pub struct ActivityStream<S> {
+    inner: S,
+    activity: Activity,
+}
+
+impl<S> ActivityStream<S> {
+    pub fn new(inner: S, activity: Activity) -> Self {
+        Self { inner, activity }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for ActivityStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(n)) = res {
+            if n > 0 {
+                self.activity.touch();
+            }
+        }
+        res
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for ActivityStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+        match res {
+            Poll::Pending => self.activity.set_write_blocked(true),
+            Poll::Ready(ref res) => {
+                self.activity.set_write_blocked(false);
+                if matches!(res, Ok(n) if *n > 0) {
+                    self.activity.touch();
+                }
+            }
+        }
+        res
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+/// Drive a connection to completion, calling `graceful_shutdown` on it once its stream
+/// has seen no activity for the idle timeout, and dropping it if sending is blocked
+/// for longer than the send timeout.
+pub async fn serve_until_idle<C, T>(
+    conn: C,
+    activity: &Activity,
+    timeouts: Timeouts,
+    graceful_shutdown: impl FnOnce(Pin<&mut C>),
+) -> Result<T, SendTimedOut>
+where
+    C: Future<Output = T>,
+{
+    let mut conn = pin!(conn);
+    let mut graceful_shutdown = Some(graceful_shutdown);
+    loop {
+        let idle_deadline = graceful_shutdown
+            .is_some()
+            .then(|| activity.last() + timeouts.idle);
+        let send_deadline = activity
+            .write_blocked_since()
+            .map(|since| since + timeouts.send);
+        // Writes may become blocked while we wait, so check again at least this often.
+        let recheck = Instant::now() + timeouts.send;
+        let deadline = [idle_deadline, send_deadline]
+            .into_iter()
+            .flatten()
+            .fold(recheck, Instant::min);
+        let timed_out = async {
+            Timer::at(deadline).await;
+            None
+        };
+        if let Some(res) = future::or(async { Some(conn.as_mut().await) }, timed_out).await {
+            return Ok(res);
+        }
+        let now = Instant::now();
+        if matches!(activity.write_blocked_since(), Some(since) if since + timeouts.send <= now) {
+            return Err(SendTimedOut);
+        }
+        if activity.last() + timeouts.idle <= now {
+            if let Some(graceful_shutdown) = graceful_shutdown.take() {
+                graceful_shutdown(conn.as_mut());
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Allowing and denying connections by the IP address of the peer.
+//!
+//! Rules are CIDR ranges like `192.168.1.0/24`, or single addresses like `192.168.1.23`.
+//! A peer in any of the denied ranges is refused. When there are allowed ranges, a peer must be
+//! in one of them, or be on a loopback address, so that the machine http-horse runs on keeps
+//! access. Connections that are refused are closed right after they are accepted.
+
+use std::{fmt, net::IpAddr, str::FromStr};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid IP address in {0:?}")]
+    InvalidAddress(String),
+    #[error("Invalid prefix length in {0:?}")]
+    InvalidPrefixLength(String),
+}
+
+/// A range of IP addresses, given by an address and the number of leading bits that matter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, canonical(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_eq(&net.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_eq(&net.octets(), &ip.octets(), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()
+            .map(canonical)
+            .map_err(|_| Error::InvalidAddress(s.to_string()))?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse::<u8>()
+                .ok()
+                .filter(|&len| len <= max_prefix_len)
+                .ok_or_else(|| Error::InvalidPrefixLength(s.to_string()))?,
+            None => max_prefix_len,
+        };
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// Treat IPv4 addresses mapped into IPv6, as seen on dual-stack sockets, as IPv4 addresses.
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(IpAddr::V6(v6), IpAddr::V4),
+        ip => ip,
+    }
+}
+
+fn prefix_eq(a: &[u8], b: &[u8], prefix_len: u8) -> bool {
+    let (n_bytes, n_bits) = (usize::from(prefix_len / 8), prefix_len % 8);
+    if a[..n_bytes] != b[..n_bytes] {
+        return false;
+    }
+    if n_bits == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - n_bits);
+    a[n_bytes] & mask == b[n_bytes] & mask
+}
+
+/// Decides which peers may connect.
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    pub allow: Vec<IpNet>,
+    pub deny: Vec<IpNet>,
+}
+
+impl IpFilter {
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        if self.deny.iter().any(|net| net.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty()
+            || canonical(ip).is_loopback()
+            || self.allow.iter().any(|net| net.contains(ip))
+    }
+}
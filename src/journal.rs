@@ -0,0 +1,368 @@
+//! Persistent history of the changes applied to the tracked project directory trees.
+//!
+//! Every change is appended as one line of JSON to a journal file in the cache directory,
+//! so that the history survives restarts of http-horse. Each entry is given a sequential id,
+//! which lets clients that have seen part of the history ask for only what they missed.
+//!
+//! The journal file is specific to the set of project directories being served.
+//! It is compacted when opened, and when it has grown to twice [`MAX_JOURNAL_ENTRIES`] lines
+//! while open, keeping only the most recent [`MAX_JOURNAL_ENTRIES`] entries.
+
+use crate::fs::project_dir::{ChangeKind, TrackedChange};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    ops::RangeBounds,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+use xxhash_rust::xxh3::xxh3_64;
+
+/// Number of entries kept in the journal, both on disk and in memory.
+pub const MAX_JOURNAL_ENTRIES: usize = 10_000;
+
+const JOURNAL_SUBDIR_NAME: &str = "journal";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Failed to persist compacted journal file: {0}")]
+    Persist(#[from] tempfile::PersistError),
+    #[error("Could not determine cache directory")]
+    NoCacheDir,
+}
+
+/// Kind of change recorded in the journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EntryKind {
+    Created,
+    Modified,
+    Removed,
+    Renamed,
+}
+
+/// A change that was applied to a tracked project directory tree, as recorded in the journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Sequential id. Ids keep increasing across restarts.
+    pub id: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp_ms: u64,
+    pub kind: EntryKind,
+    /// Absolute path of the file or directory that changed.
+    pub path: PathBuf,
+    /// Previous path, for renames.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<PathBuf>,
+    pub is_dir: bool,
+}
+
+impl JournalEntry {
+    /// An entry for a change that happened just now. Changes that are not recorded
+    /// in a journal, like when it could not be opened, are given id zero.
+    pub fn new(id: u64, change: &TrackedChange) -> Self {
+        let (kind, from) = match &change.kind {
+            ChangeKind::Created => (EntryKind::Created, None),
+            ChangeKind::Modified => (EntryKind::Modified, None),
+            ChangeKind::Removed => (EntryKind::Removed, None),
+            ChangeKind::Renamed { from } => (EntryKind::Renamed, Some(from.clone())),
+        };
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Self {
+            id,
+            timestamp_ms,
+            kind,
+            path: change.path.clone(),
+            from,
+            is_dir: change.is_dir,
+        }
+    }
+}
+
+impl EntryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Modified => "modified",
+            Self::Removed => "removed",
+            Self::Renamed => "renamed",
+        }
+    }
+}
+
+/// Format entries as CSV, with a header row, for spreadsheets and the like.
+pub fn to_csv(entries: &[JournalEntry]) -> String {
+    let mut csv = String::from("id,timestamp_ms,kind,path,from,is_dir\r\n");
+    for entry in entries {
+        let from = entry
+            .from
+            .as_ref()
+            .map(|from| csv_field(&from.display().to_string()))
+            .unwrap_or_default();
+        let _ = write!(
+            csv,
+            "{},{},{},{},{},{}\r\n",
+            entry.id,
+            entry.timestamp_ms,
+            entry.kind.as_str(),
+            csv_field(&entry.path.display().to_string()),
+            from,
+            entry.is_dir
+        );
+    }
+    csv
+}
+
+/// Quote a CSV field if it has to be, per RFC 4180.
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    file: File,
+    /// Number of lines in the journal file.
+    n_lines: usize,
+    entries: VecDeque<JournalEntry>,
+    next_id: u64,
+}
+
+/// Append-only journal of changes, backed by a JSON Lines file.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    inner: Mutex<Inner>,
+}
+
+/// Find the default cache directory for http-horse.
+///
+/// Uses `$XDG_CACHE_HOME` when set, and otherwise the platform cache directory
+/// under the home directory of the user.
+pub fn default_cache_dir() -> Result<PathBuf, Error> {
+    if let Some(xdg_cache_home) = std::env::var_os("XDG_CACHE_HOME").filter(|v| !v.is_empty()) {
+        return Ok(PathBuf::from(xdg_cache_home).join("http-horse"));
+    }
+    let home = std::env::var_os("HOME")
+        .filter(|v| !v.is_empty())
+        .ok_or(Error::NoCacheDir)?;
+    let cache_home = if cfg!(target_os = "macos") {
+        PathBuf::from(home).join("Library").join("Caches")
+    } else {
+        PathBuf::from(home).join(".cache")
+    };
+    Ok(cache_home.join("http-horse"))
+}
+
+/// Name for files in the cache directory that are specific to a set of project directories,
+/// from the name of the first of them and a hash of all of them.
+///
+/// The hash has to stay the same across builds of http-horse, as the files outlive it,
+/// which that of `DefaultHasher` is not guaranteed to.
+pub fn project_set_file_stem(project_dirs: &[PathBuf]) -> String {
+    // Paths cannot have NUL bytes in them, so separating them with one keeps them apart.
+    let mut bytes = Vec::new();
+    for dir in project_dirs {
+        bytes.extend_from_slice(dir.as_os_str().as_bytes());
+        bytes.push(0);
+    }
+    let name = project_dirs
+        .first()
+        .and_then(|dir| dir.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "root".to_string());
+    format!("{name}-{:016x}", xxh3_64(&bytes))
+}
+
+/// Replace the journal file with one holding only the given entries.
+fn write_compacted(path: &Path, entries: &VecDeque<JournalEntry>) -> Result<(), Error> {
+    let journal_dir = path
+        .parent()
+        .expect("journal file path always has a parent");
+    let mut compacted = tempfile::NamedTempFile::new_in(journal_dir)?;
+    for entry in entries {
+        serde_json::to_writer(&mut compacted, entry)?;
+        compacted.write_all(b"\n")?;
+    }
+    compacted.as_file().sync_all()?;
+    compacted.persist(path)?;
+    Ok(())
+}
+
+/// Path of the journal file for a set of project directories.
+fn journal_file_path(cache_dir: &Path, project_dirs: &[PathBuf]) -> PathBuf {
+    cache_dir
+        .join(JOURNAL_SUBDIR_NAME)
+        .join(format!("{}.jsonl", project_set_file_stem(project_dirs)))
+}
+
+impl Journal {
+    /// Open the journal for a set of project directories, creating it if it does not exist.
+    ///
+    /// Existing entries are loaded, and the journal file is compacted if it has grown too long.
+    /// Lines that cannot be parsed (for example, a last line that was only partially written)
+    /// are skipped.
+    pub fn open(cache_dir: &Path, project_dirs: &[PathBuf]) -> Result<Self, Error> {
+        let path = journal_file_path(cache_dir, project_dirs);
+        let journal_dir = path
+            .parent()
+            .expect("journal file path always has a parent");
+        std::fs::create_dir_all(journal_dir)?;
+
+        let mut entries = VecDeque::new();
+        let mut n_lines = 0;
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    n_lines += 1;
+                    match serde_json::from_str::<JournalEntry>(&line) {
+                        Ok(entry) => {
+                            if entries.len() == MAX_JOURNAL_ENTRIES {
+                                entries.pop_front();
+                            }
+                            entries.push_back(entry);
+                        }
+                        Err(e) => warn!(err = ?e, ?path, "Skipping unparseable journal entry."),
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        let next_id = entries.back().map(|entry| entry.id + 1).unwrap_or(1);
+
+        if n_lines > entries.len() {
+            debug!(
+                n_lines,
+                n_kept = entries.len(),
+                ?path,
+                "Compacting journal file."
+            );
+            write_compacted(&path, &entries)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        info!(
+            ?path,
+            n_entries = entries.len(),
+            next_id,
+            "Opened event history journal."
+        );
+        Ok(Self {
+            path,
+            inner: Mutex::new(Inner {
+                file,
+                n_lines: entries.len(),
+                entries,
+                next_id,
+            }),
+        })
+    }
+
+    /// Path of the journal file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record a change that was applied to a tracked project directory tree.
+    pub fn append(&self, change: &TrackedChange) -> Result<JournalEntry, Error> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = JournalEntry::new(inner.next_id, change);
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        inner.file.write_all(&line)?;
+        inner.n_lines += 1;
+        inner.next_id += 1;
+        if inner.entries.len() == MAX_JOURNAL_ENTRIES {
+            inner.entries.pop_front();
+        }
+        inner.entries.push_back(entry.clone());
+        // The file is compacted now and then, rather than only when opened,
+        // so that it does not grow without bound while http-horse keeps running.
+        if inner.n_lines >= 2 * MAX_JOURNAL_ENTRIES {
+            debug!(
+                n_lines = inner.n_lines,
+                n_kept = inner.entries.len(),
+                path = ?self.path,
+                "Compacting journal file."
+            );
+            let compacted = write_compacted(&self.path, &inner.entries).and_then(|()| {
+                OpenOptions::new()
+                    .append(true)
+                    .open(&self.path)
+                    .map_err(Error::from)
+            });
+            match compacted {
+                Ok(file) => {
+                    inner.file = file;
+                    inner.n_lines = inner.entries.len();
+                }
+                // The entry has been recorded all the same, so compaction is tried again later.
+                Err(e) => warn!(err = ?e, path = ?self.path, "Failed to compact journal file."),
+            }
+        }
+        Ok(entry)
+    }
+
+    /// The most recent entries, oldest first.
+    pub fn recent(&self, n: usize) -> Vec<JournalEntry> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let skip = inner.entries.len().saturating_sub(n);
+        inner.entries.iter().skip(skip).cloned().collect()
+    }
+
+    /// All retained entries with an id greater than the given id, oldest first.
+    pub fn since(&self, id: u64) -> Vec<JournalEntry> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let start = inner.entries.partition_point(|entry| entry.id <= id);
+        inner.entries.iter().skip(start).cloned().collect()
+    }
+
+    /// Up to `limit` of the retained entries with an id less than the given id, or the most recent
+    /// ones if no id is given, oldest first. For paging backwards through the history.
+    pub fn before(&self, id: Option<u64>, limit: usize) -> Vec<JournalEntry> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let end = match id {
+            Some(id) => inner.entries.partition_point(|entry| entry.id < id),
+            None => inner.entries.len(),
+        };
+        let start = end.saturating_sub(limit);
+        inner.entries.range(start..end).cloned().collect()
+    }
+
+    /// All retained entries recorded within a range of times, in milliseconds since the Unix epoch,
+    /// oldest first.
+    pub fn recorded_between(&self, range: impl RangeBounds<u64>) -> Vec<JournalEntry> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner
+            .entries
+            .iter()
+            .filter(|entry| range.contains(&entry.timestamp_ms))
+            .cloned()
+            .collect()
+    }
+
+    /// Id of the most recent entry, or zero when the journal is empty.
+    pub fn last_id(&self) -> u64 {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.next_id - 1
+    }
+}
@@ -1 +1,46 @@
+pub mod access_log;
+pub mod auth;
+pub mod broadcast;
+pub mod build;
+pub mod client_errors;
+pub mod companion;
+pub mod compression;
+pub mod conditional;
+pub mod config;
+pub mod deps;
+pub mod diagnostics;
+pub mod diff;
+pub mod editor;
+pub mod error_page;
+pub mod event;
+pub mod file_cache;
 pub mod fs;
+pub mod headers;
+pub mod host;
+pub mod html;
+pub mod i18n;
+pub mod idle;
+pub mod ip_filter;
+pub mod journal;
+pub mod limit;
+pub mod livereload;
+pub mod mime;
+pub mod mmap;
+pub mod mocks;
+pub mod notify;
+pub mod overlay;
+pub mod pattern;
+pub mod preload;
+pub mod presence;
+pub mod proxy;
+pub mod record;
+pub mod redirects;
+pub mod reload;
+pub mod sniff;
+pub mod stats;
+pub mod stream;
+pub mod sync;
+pub mod throttle;
+pub mod tls;
+pub mod url;
+pub mod usage;
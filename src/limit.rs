@@ -0,0 +1,61 @@
+//! Limit on the number of connections that a server serves at the same time.
+//!
+//! Each live-reload client keeps an event stream open, and browsers open several connections
+//! per page, so a few forgotten tabs or a misbehaving client can otherwise pile up connections
+//! without bound. Connections past the limit are answered with 503 Service Unavailable
+//! and then closed, which tells the client to come back later, rather than being left hanging.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+/// Counts the connections being served, up to a maximum.
+#[derive(Debug, Clone)]
+pub struct ConnectionLimit {
+    max: usize,
+    active: Arc<AtomicUsize>,
+}
+
+impl ConnectionLimit {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            active: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Reserve a place for a connection, unless the limit has been reached.
+    /// The place is given back when the returned permit is dropped.
+    pub fn try_acquire(&self) -> Option<ConnectionPermit> {
+        self.active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+                (active < self.max).then_some(active + 1)
+            })
+            .ok()
+            .map(|_| ConnectionPermit {
+                active: self.active.clone(),
+            })
+    }
+
+    /// Number of connections currently being served.
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Acquire)
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+
+/// A place held by a connection within a [`ConnectionLimit`].
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
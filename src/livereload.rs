@@ -0,0 +1,278 @@
+//! Compatibility listener speaking the LiveReload protocol, so that LiveReload browser extensions,
+//! and pages that load `livereload.js` from a LiveReload server, get reloaded by http-horse.
+//!
+//! The protocol runs over a WebSocket at `/livereload`. The client greets the server with
+//! a `hello` command naming the protocols that it speaks, the server answers with its own,
+//! and from then on sends a `reload` command with the URL path of each changed file. It is up to
+//! the client to swap stylesheets and images in place, or to reload the page.
+//!
+//! Only as much of WebSocket as the protocol needs is implemented: text messages, ping and close.
+//! See <http://livereload.com/api/protocol/>.
+
+use crate::auth::base64_encode;
+use crate::event::Event;
+use futures_util::future::try_join;
+use hyper::{
+    header::{self, HeaderMap, HeaderValue},
+    rt::ReadBuf,
+    upgrade::Upgraded,
+};
+use serde_json::{json, Value};
+use smol::{
+    channel::{unbounded, Receiver},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+};
+use std::{
+    io,
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{ready, Context, Poll},
+};
+use tracing::{debug, trace};
+
+/// Port that LiveReload clients connect to by default.
+pub const DEFAULT_PORT: u16 = 35729;
+
+/// URL path of the LiveReload WebSocket.
+pub const WEBSOCKET_URL_PATH: &str = "/livereload";
+
+/// URL path of the LiveReload client script.
+pub const CLIENT_SCRIPT_URL_PATH: &str = "/livereload.js";
+
+/// The version of the LiveReload protocol that we speak.
+const PROTOCOL: &str = "http://livereload.com/protocols/official-7";
+
+/// Appended to the key of a WebSocket handshake request to derive the accept key.
+/// See <https://datatracker.ietf.org/doc/html/rfc6455#section-1.3>.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest message that we accept from clients. LiveReload clients only send short commands.
+const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// The `Sec-WebSocket-Accept` header value for a WebSocket handshake request,
+/// or `None` if the request is not one.
+pub fn websocket_accept(headers: &HeaderMap) -> Option<HeaderValue> {
+    let has_token = |name: header::HeaderName, token: &str| {
+        headers.get_all(name).iter().any(|value| {
+            value
+                .to_str()
+                .unwrap_or_default()
+                .split(',')
+                .any(|t| t.trim().eq_ignore_ascii_case(token))
+        })
+    };
+    if !has_token(header::UPGRADE, "websocket") || !has_token(header::CONNECTION, "upgrade") {
+        return None;
+    }
+    if headers.get(header::SEC_WEBSOCKET_VERSION)? != "13" {
+        return None;
+    }
+    let key = headers.get(header::SEC_WEBSOCKET_KEY)?.as_bytes();
+    let digest = ring::digest::digest(
+        &ring::digest::SHA1_FOR_LEGACY_USE_ONLY,
+        &[key, WEBSOCKET_GUID.as_bytes()].concat(),
+    );
+    HeaderValue::try_from(base64_encode(digest.as_ref())).ok()
+}
+
+/// Speak the LiveReload protocol over an upgraded connection, until the client goes away.
+pub async fn serve(upgraded: Upgraded, events: Receiver<Event>) -> io::Result<()> {
+    let (mut reader, mut writer) = smol::io::split(UpgradedIo(upgraded));
+    let (replies_tx, replies_rx) = unbounded::<(u8, Vec<u8>)>();
+    // Reload commands may only be sent once the client has said hello.
+    let greeted = &AtomicBool::new(false);
+
+    let receive = async move {
+        let mut message = vec![];
+        loop {
+            let (fin, opcode, payload) = read_frame(&mut reader).await?;
+            match opcode {
+                OPCODE_CLOSE => {
+                    // Echo the status code of the client, as the closing handshake requires.
+                    let _ = replies_tx.send((OPCODE_CLOSE, payload)).await;
+                    return Ok(());
+                }
+                OPCODE_PING => {
+                    let _ = replies_tx.send((OPCODE_PONG, payload)).await;
+                }
+                OPCODE_TEXT | OPCODE_CONTINUATION => {
+                    message.extend_from_slice(&payload);
+                    if message.len() > MAX_MESSAGE_LEN {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "LiveReload message too long",
+                        ));
+                    }
+                    if !fin {
+                        continue;
+                    }
+                    let command = serde_json::from_slice::<Value>(&message).unwrap_or_default();
+                    message.clear();
+                    match command["command"].as_str() {
+                        Some("hello") => {
+                            debug!(?command, "LiveReload client said hello.");
+                            let hello = json!({
+                                "command": "hello",
+                                "protocols": [PROTOCOL],
+                                "serverName": "http-horse",
+                            });
+                            let _ = replies_tx
+                                .send((OPCODE_TEXT, hello.to_string().into_bytes()))
+                                .await;
+                            greeted.store(true, Ordering::Relaxed);
+                        }
+                        _ => trace!(?command, "Ignoring LiveReload command."),
+                    }
+                }
+                _ => trace!(opcode, "Ignoring WebSocket frame."),
+            }
+        }
+    };
+
+    let send = async move {
+        loop {
+            let outgoing =
+                smol::future::or(async { replies_rx.recv().await.ok().map(Ok) }, async {
+                    events.recv().await.ok().map(Err)
+                })
+                .await;
+            match outgoing {
+                None => return Ok::<_, io::Error>(()),
+                Some(Ok((opcode, payload))) => {
+                    write_frame(&mut writer, opcode, &payload).await?;
+                    if opcode == OPCODE_CLOSE {
+                        return Ok(());
+                    }
+                }
+                Some(Err(event)) if greeted.load(Ordering::Relaxed) => {
+                    // Requested reloads are of pages, which LiveReload clients reload as a whole.
+                    let path = match &event {
+                        Event::ReloadRequested { page } => page.as_deref().unwrap_or("/"),
+                        _ => match event.paths().next() {
+                            Some(path) => path,
+                            None => continue,
+                        },
+                    };
+                    let reload = json!({
+                        "command": "reload",
+                        "path": path,
+                        "liveCSS": true,
+                        "liveImg": true,
+                    });
+                    write_frame(&mut writer, OPCODE_TEXT, reload.to_string().as_bytes()).await?;
+                }
+                Some(Err(_)) => {}
+            }
+        }
+    };
+
+    try_join(receive, send).await.map(|_| ())
+}
+
+/// Read a frame sent by a client, returning whether it is the final frame of its message,
+/// its opcode, and its unmasked payload.
+async fn read_frame(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<(bool, u8, Vec<u8>)> {
+    let mut head = [0; 2];
+    reader.read_exact(&mut head).await?;
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0f;
+    if head[1] & 0x80 == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "WebSocket frame from client is not masked",
+        ));
+    }
+    let len = match head[1] & 0x7f {
+        126 => {
+            let mut len = [0; 2];
+            reader.read_exact(&mut len).await?;
+            u64::from(u16::from_be_bytes(len))
+        }
+        127 => {
+            let mut len = [0; 8];
+            reader.read_exact(&mut len).await?;
+            u64::from_be_bytes(len)
+        }
+        len => u64::from(len),
+    };
+    if len > MAX_MESSAGE_LEN as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "WebSocket frame too long",
+        ));
+    }
+    let mut mask = [0; 4];
+    reader.read_exact(&mut mask).await?;
+    let mut payload = vec![0; len as usize];
+    reader.read_exact(&mut payload).await?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok((fin, opcode, payload))
+}
+
+/// Write an unfragmented, unmasked frame, as servers send them.
+async fn write_frame(
+    writer: &mut (impl AsyncWrite + Unpin),
+    opcode: u8,
+    payload: &[u8],
+) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(len as u8),
+        len @ 126..=0xffff => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
+
+/// Adapts an upgraded connection, which implements the IO traits of hyper, to those of futures.
+struct UpgradedIo(Upgraded);
+
+impl AsyncRead for UpgradedIo {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut read_buf = ReadBuf::new(buf);
+        ready!(hyper::rt::Read::poll_read(
+            Pin::new(&mut self.0),
+            cx,
+            read_buf.unfilled()
+        ))?;
+        Poll::Ready(Ok(read_buf.filled().len()))
+    }
+}
+
+impl AsyncWrite for UpgradedIo {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        hyper::rt::Write::poll_write(Pin::new(&mut self.0), cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        hyper::rt::Write::poll_flush(Pin::new(&mut self.0), cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        hyper::rt::Write::poll_shutdown(Pin::new(&mut self.0), cx)
+    }
+}
@@ -3,33 +3,120 @@ use askama::Template;
 use async_stream::stream;
 use bytes::Bytes;
 use clap::{crate_version, Parser, ValueEnum};
+use fsevent::StreamFlags;
 use futures_util::{select, FutureExt, TryStreamExt};
-use http_body_util::{combinators::BoxBody, BodyExt, Either, Full, StreamBody};
+use http_body_util::{combinators::BoxBody, BodyExt, Either, Empty, Full, StreamBody};
+use http_horse::access_log::{AccessLog, AccessLogEntry, LoggedBody};
+use http_horse::auth::{
+    AuthServers, BasicAuth, Credentials, Server, StatusToken, TokenSource, STATUS_TOKEN_PARAM,
+};
+use http_horse::build::{
+    BuildPolicy, BuildRunner, BuildSettings, BuildStep, BUILD_LOG_SUBDIR_NAME, BUILD_RUNNER,
+    DEFAULT_BUILD_DEBOUNCE,
+};
+use http_horse::client_errors::{ClientError, CLIENT_ERRORS, MAX_REPORT_LEN};
+use http_horse::companion::{Companions, COMPANIONS};
+use http_horse::compression::{
+    accepted_encodings, encode_bytes, encode_reader, is_compressible, negotiate, CompressionLevels,
+    Encoding, COMPRESSION_LEVELS, MIN_COMPRESS_SIZE,
+};
+use http_horse::conditional::{etag_for_hash, if_modified_since_is_fresh, if_none_match};
+use http_horse::config::{find_project_config, Config};
+use http_horse::deps::{css_dependencies, html_dependencies, DEPENDENCY_GRAPH};
+use http_horse::diff::DiffCapture;
+use http_horse::editor::{
+    OpenRequest, EDITOR_COMMAND, MAX_REQUEST_LEN as MAX_OPEN_IN_EDITOR_LEN, OPEN_IN_EDITOR_URL_PATH,
+};
+use http_horse::error_page::{error_json, error_message, negotiate_error_format, ErrorFormat};
+use http_horse::event::{publish_status, Event, StampedEvent, STATUS_EVENTS};
+use http_horse::file_cache::{CacheKey, FileCache};
 use http_horse::fs::{
-    exclude::{exclude, EXCLUDE_FILES_BY_NAME},
-    project_dir::scan_project_dir,
+    case::on_disk_path,
+    exclude::{exclude, is_excluded, EXCLUDE_FILES_BY_NAME},
+    gitignore::{is_gitignore_event, load_gitignores, respect_gitignore, RESPECT_GITIGNORE},
+    ignore_file::{
+        check_patterns, is_ignore_file, load_ignore_file, runtime_patterns,
+        update_runtime_patterns, CONFIG_PATTERNS,
+    },
+    limits::{parse_size, ScanLimits, SCAN_LIMITS},
+    marker::{create_marker_tempfile, fast_forward_to_marker, is_marker_event, requires_rescan},
+    project_dir::{ChangeKind, TrackedChange},
+    project_root::{derive_mounts, resolve, Mount, ProjectRoot, VirtualHost},
+    rename::{RenameCorrelator, RENAME_CORRELATION_WINDOW},
+    rescan::{RescanManager, RescanRequest, Rescanned},
+    tree::{TreeDir, TreeNode},
+};
+use http_horse::headers::{HeaderRule, HEADERS_FILE_NAME};
+use http_horse::host::{host_name, HostCheck};
+use http_horse::html;
+use http_horse::i18n::{negotiate_language, Language, Messages, LANG_PARAM};
+use http_horse::idle::{serve_until_idle, Activity, ActivityStream, Timeouts};
+use http_horse::ip_filter::{IpFilter, IpNet};
+use http_horse::journal::{default_cache_dir, to_csv, Journal, JournalEntry};
+use http_horse::limit::ConnectionLimit;
+use http_horse::livereload;
+use http_horse::mime::{mime_type_for_path, parse_mime_mapping, EXTRA_MIME_TYPES};
+use http_horse::mmap::{mapped_body, MappedFile};
+use http_horse::mocks::Mocks;
+use http_horse::overlay::{ErrorReport, ERROR_REPORTS};
+use http_horse::preload::link_header_value;
+use http_horse::presence::{
+    Heartbeat, Registration, HEARTBEAT_URL_PATH, MAX_HEARTBEAT_LEN, PRESENCE,
+};
+use http_horse::proxy::ProxyRule;
+use http_horse::record::Recorder;
+use http_horse::redirects::{RedirectRule, RuleOutcome, REDIRECTS_FILE_NAME};
+use http_horse::reload::{
+    event_name, inject_client_script, CLIENT_SCRIPT_URL_PATH, ERROR_OVERLAY_EVENT, RELOAD_CHANNEL,
+    RESERVED_PATH_PREFIX, SYNC_EVENT,
+};
+use http_horse::sniff::is_compressible_class;
+use http_horse::stats::{Stats, MAX_WINDOW_MINUTES};
+use http_horse::stream::{with_keep_alive, ReaderStream};
+use http_horse::sync::{
+    SyncAction, SyncEvent, MAX_EVENT_LEN as MAX_SYNC_EVENT_LEN, SYNC_CHANNEL, SYNC_URL_PATH,
+};
+use http_horse::throttle::{
+    parse_bandwidth, parse_duration, throttle_body, NetworkConditions, Preset, ThrottleRule,
 };
+use http_horse::tls::{run_mkcert, MaybeTlsStream, TlsSetup};
+use http_horse::url::{decode_path, encode_path_segment, encode_unsafe_chars};
+use http_horse::usage::{counted, EVENT_STREAMS, RESOURCE_USAGE, SAMPLE_INTERVAL};
 use hyper::{
-    body::{Frame, Incoming},
+    body::{Body, Frame, Incoming},
     header,
-    header::HeaderValue,
+    header::{HeaderMap, HeaderName, HeaderValue},
     http::{response::Builder as ResponseBuilder, Result as HttpResult},
     service::service_fn,
     Method, Request, Response, StatusCode,
 };
+use hyper_util::{rt::TokioExecutor, server::conn::auto::Builder as ConnBuilder};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use smol::stream::StreamExt;
-use smol::{block_on, fs::File, io::AsyncReadExt, net::TcpListener, Executor, Timer};
+use smol::{
+    block_on,
+    fs::File,
+    io::{AsyncReadExt, BufReader},
+    net::{TcpListener, TcpStream},
+    Executor, Timer,
+};
 use smol_hyper::rt::{FuturesIo, SmolExecutor, SmolTimer};
-use std::sync::{Arc, Barrier};
+use socket2::{SockRef, TcpKeepalive};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Barrier,
+};
 use std::time::Instant;
 use std::{
+    collections::HashSet,
+    future::Future,
     io::ErrorKind,
     net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
     pin::pin,
-    sync::OnceLock,
-    time::Duration,
+    sync::{mpsc::RecvTimeoutError, OnceLock, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use thiserror::Error;
 use tracing::{debug, error, info, info_span, trace, warn, Instrument};
@@ -38,31 +125,161 @@ use tracing::{debug, error, info, info_span, trace, warn, Instrument};
 #[template(path = "status-webui/index.htm")]
 struct StatusWebUiIndex<'a> {
     project_dir: &'a str,
+    language: Language,
+    messages: &'static Messages,
+    color_scheme: ColorScheme,
+    /// Every color scheme, for the color scheme switcher.
+    color_schemes: Vec<ColorSchemeOption>,
+}
+
+/// A color scheme as offered by the color scheme switcher of the status web-ui.
+struct ColorSchemeOption {
+    /// Command-line argument value of the color scheme.
+    value: String,
+    label: String,
+    selected: bool,
+}
+
+#[derive(Template)]
+#[template(path = "status-webui/preview.htm")]
+struct StatusWebUiPreview<'a> {
+    project_url: &'a str,
+    /// URL path of the project page that is previewed.
+    path: &'a str,
+    language: Language,
+    messages: &'static Messages,
     color_scheme: ColorScheme,
+    presets: Vec<PreviewPreset>,
 }
 
-static INTERNAL_INDEX_PAGE: OnceLock<Vec<u8>> = OnceLock::new();
+/// A viewport size that the project can be previewed at.
+struct PreviewPreset {
+    name: &'static str,
+    label: &'static str,
+    width: u32,
+    height: u32,
+    selected: bool,
+}
+
+/// Viewport sizes of the project preview, by name, width and height in CSS pixels.
+const PREVIEW_PRESETS: [(&str, u32, u32); 3] = [
+    ("phone", 390, 844),
+    ("tablet", 820, 1180),
+    ("desktop", 1440, 900),
+];
+
+/// Project directories as listed by the status web-ui.
+static STATUS_WEBUI_PROJECT_DIRS: OnceLock<String> = OnceLock::new();
+
+#[derive(Template)]
+#[template(path = "error-page.htm")]
+struct ErrorPage<'a> {
+    status: u16,
+    reason: &'a str,
+    message: &'a str,
+    color_scheme: ColorScheme,
+    stylesheet: &'a str,
+}
+
+/// Color theme of the status web-ui, which error pages are styled with too.
+/// Can be switched at runtime through the status API.
+static COLOR_SCHEME: RwLock<ColorScheme> = RwLock::new(ColorScheme::GraphiteAndCopper);
 
+#[derive(Template)]
+#[template(path = "project/dir-listing.htm")]
+struct DirListing<'a> {
+    /// Percent-encoded, to match the paths in reload events.
+    url_path: &'a str,
+    /// Percent-decoded, for showing.
+    display_url_path: &'a str,
+    has_parent: bool,
+    entries: Vec<DirListingEntry>,
+    client_script_url_path: &'a str,
+}
+
+struct DirListingEntry {
+    name: String,
+    href: String,
+    is_dir: bool,
+    size: Option<String>,
+    modified: String,
+}
+
+static BAD_REQUEST_BODY_TEXT: &[u8] = b"HTTP 400. Bad request.";
+static FORBIDDEN_HOST_BODY_TEXT: &[u8] = b"HTTP 403. Host not allowed.";
+static UNAUTHORIZED_BODY_TEXT: &[u8] = b"HTTP 401. Unauthorized.";
+static STATUS_TOKEN_REQUIRED_BODY_TEXT: &[u8] =
+    b"HTTP 401. Open the status URL that http-horse printed at startup, which has the status token.";
 static NOT_FOUND_BODY_TEXT: &[u8] = b"HTTP 404. File not found.";
 static METHOD_NOT_ALLOWED_BODY_TEXT: &[u8] = b"HTTP 405. Method not allowed.";
+static UPGRADE_REQUIRED_BODY_TEXT: &[u8] = b"HTTP 426. WebSocket upgrade required.";
 static INTERNAL_SERVER_ERROR_BODY_TEXT: &[u8] = b"HTTP 500. Internal server error.";
+static BAD_GATEWAY_BODY_TEXT: &[u8] = b"HTTP 502. Bad gateway.";
+static SERVICE_UNAVAILABLE_BODY_TEXT: &[u8] = b"HTTP 503. Too many connections.";
+static SERVING_PAUSED_BODY_TEXT: &[u8] = b"HTTP 503. Serving is paused.";
 
 static INTERNAL_STYLESHEET: &[u8] = include_bytes!("../webui-src/style/main.css");
 static INTERNAL_JAVASCRIPT: &[u8] = include_bytes!("../webui-src/js/main.js");
+static PREVIEW_JAVASCRIPT: &[u8] = include_bytes!("../webui-src/js/preview.js");
+static WEB_APP_MANIFEST: &[u8] = include_bytes!("../webui-src/manifest.webmanifest");
+static FAVICON: &[u8] = include_bytes!("../webui-src/icons/favicon.ico");
+
+/// Icons of the status web-ui, by file name under `icons/`, with their MIME type.
+static STATUS_ICONS: [(&str, &str, &[u8]); 5] = [
+    (
+        "icon.svg",
+        IMAGE_SVG_XML,
+        include_bytes!("../webui-src/icons/icon.svg"),
+    ),
+    (
+        "icon-180.png",
+        IMAGE_PNG,
+        include_bytes!("../webui-src/icons/icon-180.png"),
+    ),
+    (
+        "icon-192.png",
+        IMAGE_PNG,
+        include_bytes!("../webui-src/icons/icon-192.png"),
+    ),
+    (
+        "icon-512.png",
+        IMAGE_PNG,
+        include_bytes!("../webui-src/icons/icon-512.png"),
+    ),
+    (
+        "icon-maskable-512.png",
+        IMAGE_PNG,
+        include_bytes!("../webui-src/icons/icon-maskable-512.png"),
+    ),
+];
+static RELOAD_CLIENT_JAVASCRIPT: &[u8] = include_bytes!("../webui-src/js/reload-client.js");
+static HMR_RUNTIME_JAVASCRIPT: &[u8] = include_bytes!("../webui-src/js/hmr.js");
+static LIVERELOAD_CLIENT_JAVASCRIPT: &[u8] = include_bytes!("../webui-src/js/livereload.js");
 
 // XXX: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Cache-Control#Directives
 static CACHE_CONTROL_VALUE_NO_STORE: &str = "no-store";
+static CACHE_CONTROL_VALUE_NO_CACHE: &str = "no-cache";
 
 // MIME type for Server-Sent Events
 // XXX: https://html.spec.whatwg.org/multipage/server-sent-events.html#server-sent-events
 static TEXT_EVENT_STREAM: &str = "text/event-stream";
 
+static APPLICATION_JSON: &str = "application/json";
+static APPLICATION_MANIFEST_JSON: &str = "application/manifest+json";
+static IMAGE_PNG: &str = "image/png";
+static IMAGE_SVG_XML: &str = "image/svg+xml";
 static IMAGE_X_ICON: &str = "image/x-icon";
 static TEXT_CSS: &str = "text/css";
+static TEXT_CSV: &str = "text/csv";
 static TEXT_HTML: &str = "text/html";
 static TEXT_JAVASCRIPT: &str = "text/javascript";
 static TEXT_PLAIN: &str = "text/plain";
 
+// Values of the `Allow` header, for OPTIONS requests and 405 responses.
+static ALLOW_GET: &str = "GET, HEAD, OPTIONS";
+static ALLOW_POST: &str = "POST, OPTIONS";
+static ALLOW_GET_POST: &str = "GET, HEAD, POST, OPTIONS";
+
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Cli {
@@ -72,6 +289,24 @@ struct Cli {
     /// Open the project and status pages in a web browser.
     #[arg(short = 'o', long)]
     open: bool,
+    /// Exclude files matched by .gitignore files in the project directory.
+    #[arg(long)]
+    respect_gitignore: bool,
+    /// Serve project and status pages over HTTPS, with a self-signed certificate generated at startup.
+    #[arg(long)]
+    tls: bool,
+    /// Serve over HTTPS with a locally trusted certificate made by mkcert.
+    #[arg(long, conflicts_with = "tls_cert")]
+    mkcert: bool,
+    /// Send `Link: rel=preload` headers with HTML pages, for the stylesheets and scripts in their head.
+    #[arg(long)]
+    preload_links: bool,
+    /// Also speak the LiveReload protocol, for browser extensions and tools that expect a LiveReload server.
+    #[arg(long)]
+    livereload: bool,
+    /// Serve status pages from the project server, under `/.http-horse/status/`, instead of on a port of their own.
+    #[arg(long, conflicts_with_all = ["status_listen_addr", "status_listen_port"])]
+    single_port: bool,
     /*
      * Options
      */
@@ -87,19 +322,202 @@ struct Cli {
     /// Port to serve status on
     #[arg(short = 'q', long, default_value_t = 0)]
     status_listen_port: u16,
+    /// Port to speak the LiveReload protocol on, with --livereload. Uses the project address.
+    #[arg(long, default_value_t = livereload::DEFAULT_PORT)]
+    livereload_port: u16,
     /// Color theme to use for status web-ui
     #[arg(value_enum, short = 'c', long, default_value_t = ColorScheme::GraphiteAndCopper)]
     color_scheme: ColorScheme,
+    /// Directory to keep the event history journal and build logs in. Defaults to the user cache directory.
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+    /// Caching behavior to ask of browsers for project files
+    #[arg(value_enum, long, default_value_t = CacheMode::NoStore)]
+    cache_mode: CacheMode,
+    /// Memory to use for keeping small project files in memory, in MiB. 0 disables this.
+    #[arg(long, value_name = "MIB", default_value_t = 32)]
+    file_cache_size: usize,
+    /// Largest project file to keep in memory, in KiB
+    #[arg(long, value_name = "KIB", default_value_t = 256)]
+    file_cache_max_file_size: u64,
+    /// Smallest project file to serve from a memory map rather than with buffered reads, in KiB.
+    /// 0 disables memory maps.
+    #[arg(long, value_name = "KIB", default_value_t = 1024)]
+    mmap_min_size: u64,
+    /// Compression level for gzip (1-9)
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..=9))]
+    gzip_level: Option<i32>,
+    /// Compression level for brotli (0-11)
+    #[arg(long, value_parser = clap::value_parser!(i32).range(0..=11))]
+    brotli_level: Option<i32>,
+    /// Compression level for zstd (1-22)
+    #[arg(long, value_parser = clap::value_parser!(i32).range(1..=22))]
+    zstd_level: Option<i32>,
+    /// Additional MIME type for files with a given extension, e.g. `glsl=text/plain`. Can be repeated.
+    #[arg(long = "mime-type", value_name = "EXT=TYPE", value_parser = parse_mime_mapping)]
+    mime_types: Vec<(String, String)>,
+    /// File name to serve for requests for a directory, in order of preference. Can be repeated.
+    #[arg(long = "index-file", value_name = "NAME", default_values = ["index.htm", "index.html"])]
+    index_files: Vec<String>,
+    /// Page to serve for project files that are not found, relative to the project directory
+    #[arg(long, value_name = "PATH", default_value = "404.html")]
+    not_found_page: PathBuf,
+    /// Hold reloads until this file is touched, to signal that a build has finished.
+    /// Must be inside a project directory.
+    #[arg(long, value_name = "FILE")]
+    build_marker: Option<PathBuf>,
+    /// Run this build command in a shell when source files change, like `"npm run build"`,
+    /// and hold reloads until it has succeeded. Requires --src-dir.
+    /// Replaces the build steps of the config file, if any.
+    #[arg(
+        long,
+        value_name = "COMMAND",
+        requires = "src_dir",
+        conflicts_with = "build_marker"
+    )]
+    exec: Option<String>,
+    /// What to do about source changes that come in while the build is running.
+    /// Defaults to the policy of the config file, if any, or else `queue`.
+    #[arg(long, value_name = "POLICY", value_enum)]
+    exec_policy: Option<BuildPolicy>,
+    /// Wait until source changes have stopped coming in for this long before running
+    /// the build. Defaults to the debounce of the config file, if any, or else `100ms`.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    exec_debounce: Option<Duration>,
+    /// Open files at errors in the build output from the error overlay, with this command,
+    /// like `"code --goto {file}:{line}:{column}"`. The placeholders are not to be quoted.
+    #[arg(long, value_name = "COMMAND")]
+    editor: Option<String>,
+    /// Mirror scrolls, clicks and navigation across all pages open in browsers,
+    /// like on a laptop, a phone and a tablet at once.
+    #[arg(long)]
+    sync: bool,
+    /// Keep the previous contents of changed text files, to show what changed in each change
+    #[arg(long)]
+    diffs: bool,
+    /// Show desktop notifications for errors in the build output, for file system watching
+    /// having stopped, and for the addresses of the servers once they are up
+    #[arg(long)]
+    notify: bool,
+    /// Wait until changes have stopped coming in for this long before reloading, like `300ms`,
+    /// so that a build that writes many files results in a single reload.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    reload_delay: Option<Duration>,
+    /// Send events to the status web-ui at most this often, like `250ms`, with the events that
+    /// come in between batched into one message. 0 sends every event by itself, as it comes.
+    #[arg(long, value_name = "DURATION", default_value = "250ms", value_parser = parse_duration)]
+    status_event_interval: Duration,
+    /// Send TCP keepalive probes on connections that have been idle for this many seconds
+    #[arg(long, value_name = "SECS")]
+    tcp_keepalive: Option<u64>,
+    /// Close idle HTTP keep-alive connections after this many seconds. 0 disables HTTP keep-alive.
+    #[arg(long, value_name = "SECS", default_value_t = 30)]
+    keep_alive_timeout: u64,
+    /// Close connections that take longer than this many seconds to send the headers of a request,
+    /// or to finish the TLS handshake
+    #[arg(long, value_name = "SECS", default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+    header_timeout: u64,
+    /// Drop connections whose client has not received any of a response for this many seconds
+    #[arg(long, value_name = "SECS", default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    send_timeout: u64,
+    /// Maximum number of connections that each server serves at the same time.
+    /// Connections past the limit are answered with 503 Service Unavailable.
+    #[arg(long, value_name = "N", default_value_t = 256)]
+    max_connections: usize,
+    /// Track at most this many files of each project directory. Files past the limit are served,
+    /// but changes to them do not reload pages. 0 is no limit.
+    #[arg(long, value_name = "N", default_value_t = 100_000)]
+    max_files: u64,
+    /// Track files of each project directory up to this total size, like `500M` or `2G`.
+    /// Files past the limit are served, but changes to them do not reload pages.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_total_size: Option<u64>,
+    /// Track directories of each project directory down to this depth, with 0 for only the files
+    /// at the top. What is deeper is served, but changes to it do not reload pages.
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+    /// Certificate chain PEM file to serve over HTTPS with. Reloaded when it changes.
+    #[arg(long, value_name = "FILE", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// Private key PEM file for the certificate given with --tls-cert. Reloaded when it changes.
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// Config file, for settings that are not available as command-line arguments.
+    /// Takes precedence over the `.http-horse.toml` file of the project, with --project-config.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Read the `.http-horse.toml` file of the project, in the project directory or the directories
+    /// above it, up to the root of the git repo. The build steps and companion processes in it are
+    /// run, so only use this with projects that you trust.
+    #[arg(long)]
+    project_config: bool,
+    /// Write a line per request to this file in the Combined Log Format, or to stdout if `-`
+    #[arg(long, value_name = "FILE")]
+    access_log: Option<PathBuf>,
+    /// Require HTTP Basic authentication with these credentials. Can be repeated.
+    #[arg(long = "auth", value_name = "USER:PASSWORD")]
+    auth_users: Vec<Credentials>,
+    /// Which servers require authentication, when users are given with --auth or in the config file
+    #[arg(long, value_enum)]
+    auth_servers: Option<AuthServers>,
+    /// Token required to access the status server, instead of a random one generated at startup.
+    /// Part of the status URL that is printed at startup.
+    #[arg(long, value_name = "TOKEN")]
+    status_token: Option<String>,
+    /// Only accept connections from this IP address or CIDR range, and from loopback addresses.
+    /// Can be repeated.
+    #[arg(long = "allow-ip", value_name = "CIDR")]
+    allow_ips: Vec<IpNet>,
+    /// Refuse connections from this IP address or CIDR range. Can be repeated.
+    #[arg(long = "deny-ip", value_name = "CIDR")]
+    deny_ips: Vec<IpNet>,
+    /// Serve requests for this host name, in addition to localhost and the listen addresses.
+    /// `*.example.test` allows subdomains, and `*` allows any host. Can be repeated.
+    #[arg(long = "allowed-host", value_name = "HOST")]
+    allowed_hosts: Vec<String>,
+    /// Simulate a slow network connection, by delaying and rate-limiting project responses
+    #[arg(long, value_enum, value_name = "PRESET")]
+    throttle: Option<Preset>,
+    /// Delay project responses by this long, like `200ms` or `1.5s`. Overrides the --throttle preset.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    latency: Option<Duration>,
+    /// Send project responses at this rate, like `400kbps` or `1.5mbps`. Overrides the --throttle preset.
+    #[arg(long, value_name = "RATE", value_parser = parse_bandwidth)]
+    bandwidth: Option<u64>,
+    /// Forward requests for URL paths under a prefix to a backend server, like `/api=http://localhost:8000`. Can be repeated.
+    #[arg(long = "proxy", value_name = "PREFIX=URL")]
+    proxy_rules: Vec<ProxyRule>,
+    /// Directory with fixture files to answer API requests with, like `mocks/api/users/GET.json`
+    /// for `GET /api/users`. Fixtures take precedence over proxy rules and project files.
+    #[arg(long, value_name = "DIR")]
+    mocks: Option<String>,
+    /// Record responses from proxied backends as fixture files in this directory,
+    /// which can then be replayed with --mocks.
+    #[arg(long, value_name = "DIR")]
+    record: Option<String>,
+    /// Serve a project directory under a URL path prefix, like `/docs=./build/docs`. Can be repeated.
+    #[arg(long = "mount", value_name = "PREFIX=DIR")]
+    mounts: Vec<Mount>,
+    /// Serve a project directory for requests for a host name, like `admin.localhost=./admin`.
+    /// Can be repeated. Requests for other host names are served from the other project directories.
+    #[arg(long = "vhost", value_name = "HOST=DIR")]
+    virtual_hosts: Vec<VirtualHost>,
+    /// Source directory to watch for changes. Can be repeated.
+    ///
+    /// Source directories are watched but not served. Changes inside of project directories
+    /// are not considered source changes, even if a project directory is inside a source directory.
+    #[arg(short = 'w', long)]
+    src_dir: Vec<String>,
     /*
      * Positional arguments
      */
-    /// Project directory
-    #[arg(default_value = ".")]
-    dir: String,
+    /// Project directories. When more than one is given, each is served under the name of the directory.
+    /// Defaults to the current directory, unless project directories are given with --mount.
+    dirs: Vec<String>,
 }
 
 /// Color theme to use for status web-ui
-#[derive(ValueEnum, Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 enum ColorScheme {
     /// Midnight Purple (Dark Mode)
@@ -112,19 +530,255 @@ enum ColorScheme {
     GraphiteAndCopper,
     /// Crimson & Charcoal (Dark Mode)
     CrimsonAndCharcoal,
+    /// Linen & Copper (Light Mode)
+    LinenAndCopper,
+    /// Porcelain Blue (Light Mode)
+    PorcelainBlue,
+    /// Auto (Graphite & Copper or Linen & Copper, following the system)
+    Auto,
+}
+
+/// The current color scheme of the status web-ui.
+fn color_scheme() -> ColorScheme {
+    *COLOR_SCHEME.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn set_color_scheme(color_scheme: ColorScheme) {
+    *COLOR_SCHEME.write().unwrap_or_else(|e| e.into_inner()) = color_scheme;
+}
+
+/// The status web-ui index page, in a language, as of the current color scheme.
+fn render_status_webui_index(language: Language) -> askama::Result<String> {
+    let current = color_scheme();
+    let color_schemes = ColorScheme::value_variants()
+        .iter()
+        .filter_map(|color_scheme| {
+            let value = color_scheme.to_possible_value()?;
+            Some(ColorSchemeOption {
+                value: value.get_name().to_string(),
+                label: value
+                    .get_help()
+                    .map(ToString::to_string)
+                    .unwrap_or_default(),
+                selected: *color_scheme == current,
+            })
+        })
+        .collect();
+    StatusWebUiIndex {
+        project_dir: STATUS_WEBUI_PROJECT_DIRS
+            .get()
+            .map(String::as_str)
+            .unwrap_or_default(),
+        language,
+        messages: language.messages(),
+        color_scheme: current,
+        color_schemes,
+    }
+    .render()
+}
+
+/// Render the project preview page of the status web-ui, for the project page at a URL path,
+/// at the viewport sizes named by a comma-separated list, or at every size if none are named.
+fn render_status_webui_preview(
+    language: Language,
+    path: &str,
+    presets: Option<&str>,
+) -> askama::Result<String> {
+    let messages = language.messages();
+    let selected = |name: &str| presets.is_none_or(|presets| presets.split(',').any(|p| p == name));
+    let presets = PREVIEW_PRESETS
+        .into_iter()
+        .map(|(name, width, height)| PreviewPreset {
+            name,
+            label: match name {
+                "phone" => messages.phone,
+                "tablet" => messages.tablet,
+                _ => messages.desktop,
+            },
+            width,
+            height,
+            selected: selected(name),
+        })
+        .collect();
+    StatusWebUiPreview {
+        project_url: SERVER_INFO
+            .get()
+            .map(|server_info| server_info.project_url.as_str())
+            .unwrap_or_default(),
+        // Anything but a URL path would frame some other page than one of the project.
+        path: if path.starts_with('/') { path } else { "/" },
+        language,
+        messages,
+        color_scheme: color_scheme(),
+        presets,
+    }
+    .render()
+}
+
+/// Caching behavior to ask of browsers for project files
+#[derive(ValueEnum, Debug, Copy, Clone)]
+enum CacheMode {
+    /// Never cache anything, so that every page load fetches every file again
+    NoStore,
+    /// Cache files, but revalidate them with the project server on every use
+    Revalidate,
+}
+
+impl CacheMode {
+    fn cache_control_value(self) -> &'static str {
+        match self {
+            CacheMode::NoStore => CACHE_CONTROL_VALUE_NO_STORE,
+            CacheMode::Revalidate => CACHE_CONTROL_VALUE_NO_CACHE,
+        }
+    }
 }
 
-static PROJECT_DIR: OnceLock<PathBuf> = OnceLock::new();
+static CACHE_MODE: OnceLock<CacheMode> = OnceLock::new();
+
+/// Small project files, kept in memory as they are sent. Not set if disabled.
+static FILE_CACHE: OnceLock<FileCache> = OnceLock::new();
+
+/// Smallest file size, in bytes, that files are served from memory maps at. 0 if disabled.
+static MMAP_MIN_SIZE: OnceLock<u64> = OnceLock::new();
+
+/// Whether HTML pages are sent with `Link: rel=preload` headers for their critical resources.
+static PRELOAD_LINKS: OnceLock<bool> = OnceLock::new();
+
+/// Whether status pages are served from the project server, under [`STATUS_ROOT_PATH`].
+static SINGLE_PORT: OnceLock<bool> = OnceLock::new();
+
+/// How often events are sent to the status web-ui at most. Events that come in between
+/// are batched into one event stream message.
+static STATUS_EVENT_INTERVAL: OnceLock<Duration> = OnceLock::new();
+
+/// URL path that status pages are served under by the project server, with `--single-port`.
+/// Within [`RESERVED_PATH_PREFIX`], so that it does not shadow anything in the project.
+static STATUS_ROOT_PATH: &str = "/.http-horse/status/";
+
+/// The project directories being served and watched. The tracked tree of each project directory
+/// is shared between its FS event transformer, and the serving and status layers.
+static PROJECT_ROOTS: OnceLock<Vec<ProjectRoot>> = OnceLock::new();
+
+/// How often the progress of full scans of project directories is reported while they go on.
+const SCAN_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often tracked files with more than one hard link are checked for changes.
+/// See [`poll_hardlinked_files`].
+const HARDLINK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Persistent history of changes applied to the tracked project directory trees.
+/// Left unset when the journal could not be opened, in which case history is not recorded.
+static JOURNAL: OnceLock<Journal> = OnceLock::new();
+
+/// Page to serve for project files that are not found, relative to the project directory.
+static NOT_FOUND_PAGE: OnceLock<PathBuf> = OnceLock::new();
+
+/// File that is touched when a build has finished, if reloads are gated on builds.
+static BUILD_MARKER: OnceLock<PathBuf> = OnceLock::new();
+
+/// Extra response headers from the config file.
+static CONFIG_HEADER_RULES: OnceLock<Vec<HeaderRule>> = OnceLock::new();
+
+/// Redirect and rewrite rules from the config file.
+static CONFIG_REDIRECT_RULES: OnceLock<Vec<RedirectRule>> = OnceLock::new();
+
+/// Mock responses from fixture files, if a mocks directory was given.
+static MOCKS: OnceLock<Mocks> = OnceLock::new();
+
+/// Recorder of responses from proxied backends, if a recording directory was given.
+static RECORDER: OnceLock<Recorder> = OnceLock::new();
+
+/// Rules for forwarding requests to backend servers.
+static PROXY_RULES: OnceLock<Vec<ProxyRule>> = OnceLock::new();
+
+/// Network conditions to simulate for project responses.
+static NETWORK_CONDITIONS: OnceLock<NetworkConditions> = OnceLock::new();
+
+/// Network conditions for URL paths matching a pattern, from the config file.
+static THROTTLE_RULES: OnceLock<Vec<ThrottleRule>> = OnceLock::new();
+
+/// Check of the hosts that requests are for, against DNS rebinding.
+static HOST_CHECK: OnceLock<HostCheck> = OnceLock::new();
+
+/// HTTP Basic authentication, if any users were given.
+static BASIC_AUTH: OnceLock<BasicAuth> = OnceLock::new();
+
+/// Token that requests to the status server have to give.
+static STATUS_TOKEN: OnceLock<StatusToken> = OnceLock::new();
+
+/// Access log, if one was asked for.
+static ACCESS_LOG: OnceLock<AccessLog> = OnceLock::new();
+
+/// Snapshots of text files and the diffs between them, if they were asked for.
+static DIFFS: OnceLock<DiffCapture> = OnceLock::new();
+
+/// File names to serve for requests for a directory, in order of preference.
+static INDEX_FILES: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Addresses that the servers ended up listening on, and when they started, for the status API.
+static SERVER_INFO: OnceLock<ServerInfo> = OnceLock::new();
+
+/// Whether watching is paused. While paused, FS events are ignored, and the project dir trees
+/// are brought back up to date by a full rescan when watching is resumed.
+static WATCHING_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Whether serving is paused. While paused, the project server answers every request with 503,
+/// for trying out how pages behave when the server is unavailable.
+static SERVING_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// How often to check whether TLS certificate files have changed.
+const TLS_CERT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long a connection over the connection limit may take to send its request,
+/// or to receive the response, before it is closed.
+const REJECTED_CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Seconds that clients are asked to wait before trying again, when over the connection limit.
+static RETRY_AFTER_SECS: &str = "1";
+
+/// Number of history entries shown by the status web-ui.
+const STATUS_HISTORY_LEN: usize = 100;
 
 /// Values from synchronous portion of program setup.
 struct SynchronousSetupValues {
     ctrl_c: smol::channel::Receiver<()>,
-    project_dir: PathBuf,
     open_pages_in_browser: bool,
     status_addr: SocketAddr,
     project_addr: SocketAddr,
+    /// Address of the LiveReload compatibility listener, if enabled.
+    livereload_addr: Option<SocketAddr>,
+    project_out_watches: Vec<ProjectOutWatch>,
+    project_src_watch: Option<ProjectSrcWatch>,
+    mocks_watch: Option<MocksWatch>,
+    tls_setup: Option<TlsSetup>,
+    /// Certificate and key files that the TLS setup was loaded from, if any.
+    tls_cert_files: Option<(PathBuf, PathBuf)>,
+    tcp_keepalive: Option<Duration>,
+    keep_alive_timeout: Duration,
+    header_timeout: Duration,
+    send_timeout: Duration,
+    max_connections: usize,
+    ip_filter: IpFilter,
+}
+
+/// FS event observation for source directories.
+struct ProjectSrcWatch {
+    project_src_fs_event_observer_handle: std::thread::JoinHandle<()>,
+    project_src_fs_event_transformer_handle: std::thread::JoinHandle<()>,
+}
+
+/// FS event observation for the mocks directory.
+struct MocksWatch {
+    mocks_fs_event_observer_handle: std::thread::JoinHandle<()>,
+    mocks_fs_event_transformer_handle: std::thread::JoinHandle<()>,
+}
+
+/// FS event observation for a project directory, set up during the synchronous portion of program setup.
+struct ProjectOutWatch {
+    project_dir: PathBuf,
     project_out_fs_event_rx: std::sync::mpsc::Receiver<fsevent::Event>,
     project_out_fs_event_observer_handle: std::thread::JoinHandle<()>,
+    tmpfile_marker_a: tempfile::NamedTempFile,
 }
 
 /// This `main` function is part synchronous and part async.
@@ -146,7 +800,7 @@ fn main() -> anyhow::Result<()> {
             info_span!("Synchronous portion of program setup");
 
         outer_span_for_synchronous_setup_portion.in_scope(|| {
-            // Ctrl-C handler
+            // Ctrl-C handler, which also handles SIGTERM and SIGHUP
             let ctrl_c = {
                 let span = info_span!("Ctrl-C handler setup");
                 span.in_scope(|| {
@@ -176,6 +830,46 @@ fn main() -> anyhow::Result<()> {
                 })
             };
 
+            let config = {
+                let span = info_span!("Loading of config file");
+                span.in_scope(|| match &args.config {
+                    Some(config_path) => Config::load(config_path)
+                        .inspect_err(|e| error!(err = ?e, ?config_path, "Fatal: Failed to load config file."))
+                        .with_context(|| format!("Failed to load config file: {config_path:?}")),
+                    None => Ok(Config::default()),
+                })
+            }?;
+
+            let config = {
+                let span = info_span!("Loading of project config file");
+                span.in_scope(|| {
+                    // The project config file is looked for from the first project directory.
+                    let project_dir = args
+                        .dirs
+                        .first()
+                        .or(args.mounts.first().map(|mount| &mount.dir))
+                        .or(args.virtual_hosts.first().map(|vhost| &vhost.dir))
+                        .map_or(".", String::as_str);
+                    // Project directories that do not exist are reported when they are canonicalized.
+                    let Some(config_path) = std::fs::canonicalize(project_dir)
+                        .ok()
+                        .and_then(|project_dir| find_project_config(&project_dir))
+                    else {
+                        debug!(project_dir, "No project config file found.");
+                        return Ok(config);
+                    };
+                    if !args.project_config {
+                        info!(?config_path, "Found project config file. Not reading it, since --project-config was not given.");
+                        return Ok(config);
+                    }
+                    let project_config = Config::load_project(&config_path)
+                        .inspect_err(|e| error!(err = ?e, ?config_path, "Fatal: Failed to load project config file."))
+                        .with_context(|| format!("Failed to load project config file: {config_path:?}"))?;
+                    info!(?config_path, "Loaded project config file.");
+                    Ok::<_, anyhow::Error>(config.merge(project_config))
+                })
+            }?;
+
             // Values taken from command-line arguments.
             // In the future we may wish to additionally be able to read these from config file instead, etc.
             // So it makes sense to gather all accesses to `args` in one place, so that we don't have to jump
@@ -183,38 +877,167 @@ fn main() -> anyhow::Result<()> {
             // sources with some preference order.
             // For example, a preference order like: Command line args > Environment variables > Config file.
             // (Where "a > b > c" means "a" is preferred over "b", is preferred over "c".)
-            let project_dir = args.dir;
+            let explicit_mounts = args.mounts;
+            let virtual_hosts = args.virtual_hosts;
+            let project_dirs = if args.dirs.is_empty()
+                && explicit_mounts.is_empty()
+                && virtual_hosts.is_empty()
+            {
+                vec![".".to_string()]
+            } else {
+                args.dirs
+            };
+            let src_dirs = args.src_dir;
             let open_pages_in_browser = args.open;
             let status_addr = SocketAddr::new(args.status_listen_addr, args.status_listen_port);
             let project_addr = SocketAddr::new(args.project_listen_addr, args.project_listen_port);
+            let livereload_addr = args
+                .livereload
+                .then(|| SocketAddr::new(args.project_listen_addr, args.livereload_port));
             let color_scheme = args.color_scheme;
+            let respect_gitignore = args.respect_gitignore;
+            let scan_limits = ScanLimits {
+                max_files: Some(args.max_files).filter(|&max_files| max_files > 0),
+                max_total_size: args.max_total_size,
+                max_depth: args.max_depth,
+            };
+            let tls = args.tls;
+            let mkcert = args.mkcert;
+            let tls_cert_files = args.tls_cert.zip(args.tls_key);
+            let tcp_keepalive = args.tcp_keepalive.map(Duration::from_secs);
+            let keep_alive_timeout = Duration::from_secs(args.keep_alive_timeout);
+            let header_timeout = Duration::from_secs(args.header_timeout);
+            let send_timeout = Duration::from_secs(args.send_timeout);
+            let max_connections = args.max_connections;
+            let ip_filter = IpFilter {
+                allow: args.allow_ips,
+                deny: args.deny_ips,
+            };
+            let cache_dir = args.cache_dir;
+            let mime_types = args.mime_types;
+            let index_files = args.index_files;
+            let not_found_page = args.not_found_page;
+            let build_marker = args.build_marker;
+            let build_steps = match args.exec {
+                Some(command) => vec![BuildStep {
+                    name: "build".to_string(),
+                    command,
+                    ..BuildStep::default()
+                }],
+                None => config.build.steps,
+            };
+            let build_policy = args.exec_policy.or(config.build.policy).unwrap_or_default();
+            let build_debounce = args.exec_debounce;
+            let config_build_debounce = config.build.debounce;
+            let companion_configs = config.companions;
+            let sync = args.sync;
+            let editor = args.editor;
+            let diffs = args.diffs;
+            let notify = args.notify;
+            let reload_delay = args.reload_delay;
+            let status_event_interval = args.status_event_interval;
+            let access_log = args.access_log;
+            let cache_mode = args.cache_mode;
+            let file_cache_size = args
+                .file_cache_size
+                .checked_mul(1024 * 1024)
+                .ok_or_else(|| anyhow!("--file-cache-size is too large: {} MiB", args.file_cache_size))
+                .inspect_err(|e| error!(err = ?e, "Fatal: Invalid --file-cache-size."))?;
+            let file_cache_max_file_size = args
+                .file_cache_max_file_size
+                .checked_mul(1024)
+                .ok_or_else(|| anyhow!("--file-cache-max-file-size is too large: {} KiB", args.file_cache_max_file_size))
+                .inspect_err(|e| error!(err = ?e, "Fatal: Invalid --file-cache-max-file-size."))?;
+            let mmap_min_size = args
+                .mmap_min_size
+                .checked_mul(1024)
+                .ok_or_else(|| anyhow!("--mmap-min-size is too large: {} KiB", args.mmap_min_size))
+                .inspect_err(|e| error!(err = ?e, "Fatal: Invalid --mmap-min-size."))?;
+            let preload_links = args.preload_links;
+            let single_port = args.single_port;
+            let header_rules = config.headers;
+            let redirect_rules = config.redirects;
+            let auth_users = args.auth_users;
+            let status_token = args.status_token;
+            let allowed_hosts = [args.allowed_hosts, config.allowed_hosts].concat();
+            // Virtual hosts would be of little use if requests for them were rejected.
+            let allowed_hosts = allowed_hosts
+                .into_iter()
+                .chain(virtual_hosts.iter().map(|vhost| vhost.host.clone()))
+                .collect::<Vec<_>>();
+            let network_conditions = NetworkConditions::new(args.throttle, args.latency, args.bandwidth);
+            let throttle_rules = config.throttle;
+            let config_proxy_rules = config
+                .proxies
+                .iter()
+                .map(|rule| rule.parse::<ProxyRule>())
+                .collect::<Result<Vec<_>, _>>()
+                .inspect_err(|e| error!(err = ?e, "Fatal: Invalid proxy rule in config file."))
+                .context("Invalid proxy rule in config file")?;
+            // Rules given on the command line are evaluated first.
+            let proxy_rules = args
+                .proxy_rules
+                .into_iter()
+                .chain(config_proxy_rules)
+                .collect::<Vec<_>>();
+            let config_exclude = config.exclude;
+            let mocks_dir = args.mocks;
+            let record_dir = args.record;
+            let auth_servers = args.auth_servers.or(config.auth.servers).unwrap_or_default();
+            let config_auth_users = config.auth.users;
+            let compression_levels = CompressionLevels {
+                gzip: args.gzip_level,
+                brotli: args.brotli_level,
+                zstd: args.zstd_level,
+            };
 
-            let project_dir = {
+            let project_dirs = {
                 let span = info_span!("Project directory path canonicalization");
                 span.in_scope(|| {
-                    let project_dir = PathBuf::from(project_dir);
-                    let project_dir = project_dir
-                        .canonicalize()
-                        .inspect_err(
-                            |e| error!(err = ?e, ?project_dir, "Fatal: Failed to canonicalize project dir path."),
-                        )
-                        .with_context(|| format!("Failed to canonicalize project dir path: {project_dir:?}"))?;
+                    // Project directories with explicit mount points come first,
+                    // followed by those of virtual hosts.
+                    explicit_mounts
+                        .iter()
+                        .map(|mount| mount.dir.clone())
+                        .chain(virtual_hosts.iter().map(|vhost| vhost.dir.clone()))
+                        .chain(project_dirs)
+                        .map(|project_dir| canonicalize_dir_path(project_dir, "Project dir"))
+                        .collect::<anyhow::Result<Vec<_>>>()
+                })
+            }?;
 
-                    if !project_dir.is_dir() {
-                        error!(?project_dir, "Fatal: File is not a directory: Project dir path.");
-                        Err(anyhow!("File is not a directory: Project dir path: {project_dir:?}"))
-                    } else {
-                        debug!(?project_dir, "Successfully canonicalized project dir path.");
-                        Ok(project_dir)
-                    }
+            let src_dirs = {
+                let span = info_span!("Source directory path canonicalization");
+                span.in_scope(|| {
+                    src_dirs
+                        .into_iter()
+                        .map(|src_dir| canonicalize_dir_path(src_dir, "Source dir"))
+                        .collect::<anyhow::Result<Vec<_>>>()
                 })
             }?;
 
-            {
-                let span = info_span!("Initialization of OnceLock holding project directory path");
+            if sync {
+                info!("Scrolls, clicks and navigation are mirrored across pages open in browsers.");
+                http_horse::sync::enable();
+            }
+
+            if notify {
+                http_horse::notify::enable();
+            }
+
+            if let Some(editor) = editor {
+                info!(editor, "Errors in the error overlay open in the editor when clicked.");
+                EDITOR_COMMAND
+                    .set(editor)
+                    .inspect_err(|e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."))
+                    .map_err(|_| anyhow!("Failed to set value of OnceLock."))?;
+            }
+
+            if diffs {
+                let span = info_span!("Initialization of OnceLock holding diff capture");
                 span.in_scope(|| {
-                    PROJECT_DIR
-                        .set(project_dir.clone())
+                    DIFFS
+                        .set(DiffCapture::new())
                         .inspect_err(
                             |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
                         )
@@ -222,11 +1045,35 @@ fn main() -> anyhow::Result<()> {
                 })?;
             }
 
-            {
-                let span = info_span!("Initialization of OnceLock holding file names to exclude");
+            if let Some(reload_delay) = reload_delay {
+                info!(?reload_delay, "Reloads are held until changes have stopped for {reload_delay:?}.");
+                RELOAD_CHANNEL.set_delay(reload_delay);
+            }
+
+            if let Some(build_marker) = build_marker {
+                let span = info_span!("Initialization of OnceLock holding build marker path");
                 span.in_scope(|| {
-                    EXCLUDE_FILES_BY_NAME
-                        .set(exclude())
+                    // The marker file itself need not exist yet, only the directory that it is in.
+                    let file_name = build_marker
+                        .file_name()
+                        .ok_or_else(|| anyhow!("Build marker path has no file name: {build_marker:?}"))?;
+                    let dir = match build_marker.parent() {
+                        Some(dir) if !dir.as_os_str().is_empty() => dir,
+                        _ => Path::new("."),
+                    };
+                    let dir = dir
+                        .canonicalize()
+                        .inspect_err(|e| error!(err = ?e, ?build_marker, "Fatal: Failed to canonicalize build marker path."))
+                        .with_context(|| format!("Failed to canonicalize build marker path: {build_marker:?}"))?;
+                    let build_marker = dir.join(file_name);
+                    if !project_dirs.iter().any(|project_dir| build_marker.starts_with(project_dir)) {
+                        error!(?build_marker, "Fatal: Build marker is not inside a project directory.");
+                        return Err(anyhow!("Build marker is not inside a project directory: {build_marker:?}"));
+                    }
+                    info!(?build_marker, "Reloads are held until the build marker is touched.");
+                    RELOAD_CHANNEL.gate_on_builds();
+                    BUILD_MARKER
+                        .set(build_marker)
                         .inspect_err(
                             |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
                         )
@@ -234,189 +1081,770 @@ fn main() -> anyhow::Result<()> {
                 })?;
             }
 
-            // FsEvent takes strings as arguments. We always want to use the canonical path,
-            // and because of that we have to convert back to String from PathBuf.
-            let pdir = project_dir
-                .clone()
-                .into_os_string()
-                .into_string()
-                .inspect_err(|e| error!(os_string = ?e, "Fatal: Failed to convert PathBuf to String."))
-                .map_err(|_| anyhow!("Failed to convert PathBuf to String."))?;
-
-            /*
-             * We monitor FS events in the project dir using the
-             * Apple File System Events API via the fsevent crate.
-             *
-             * XXX: Hardlink creation does not result in any corresponding event.
-             *      Issue for this filed at https://github.com/octplane/fsevent-rust/issues/27
-             *
-             * XXX: When files are moved, two events are generated. One for the source file path,
-             *      and one for the target file path. Because we are choosing to subscribe to events
-             *      for the project directory only, we don't see "the other half" of a pair of events
-             *      where a file is moved into or out of the project directory. Now, we could of course
-             *      monitor the whole file system, and do our best to correlate all moves that affect us.
-             *      But really, that's a lot of work for little actual benefit.
-             *
-             *      So what we are going to do is, anytime a file or directory is moved into, within, or out
-             *      of the project directory, we create a temporary file, recursively rescan the project
-             *      directory and "fast-forward" to the point in the stream where we see the creation of
-             *      our temporary file. We do that same temporary file thing for the initial scan as well.
-             *
-             *      And if you think it's weird to do it that way, keep in mind that:
-             *
-             *        1. The information provided by the FSE API is only advisory anyway, and
-             *
-             *        2. Our use-case revolves mainly around files being written to most of the
-             *           time, and sometimes being created or deleted, and normally not being moved.
-             *           So, whereas in contexts where there is a lot of moving going on it might
-             *           not make so much sense to do it like this, it does in our case and also
-             *           helps keep the picture that we have of our project dir over time robust
-             *           (i.e. correctly corresponding to actual reality).
-             *
-             *      So all in all this is actually a good solution we have here, I think.
-             */
-
-            let (project_out_fs_event_tx, project_out_fs_event_rx) = std::sync::mpsc::channel();
-            let barrier = Arc::new(Barrier::new(2));
-
-            let project_out_fs_event_observer_handle = {
-                let pdir = pdir.clone();
-                let barrier = barrier.clone();
+            if !build_steps.is_empty() {
+                let span = info_span!("Initialization of OnceLock holding build runner");
+                let trigger_rx = span.in_scope(|| {
+                    // Only `--exec` is checked for these by the argument parser.
+                    if src_dirs.is_empty() {
+                        error!("Fatal: Build steps in config file require a source directory.");
+                        return Err(anyhow!("Build steps in config file require a source directory, given with --src-dir"));
+                    }
+                    if BUILD_MARKER.get().is_some() {
+                        error!("Fatal: Build steps in config file cannot be combined with a build marker.");
+                        return Err(anyhow!("Build steps in config file cannot be combined with --build-marker"));
+                    }
+                    let debounce = match build_debounce {
+                        Some(debounce) => debounce,
+                        None => config_build_debounce
+                            .as_deref()
+                            .map(parse_duration)
+                            .transpose()
+                            .inspect_err(|e| error!(err = ?e, "Fatal: Invalid build debounce in config file."))
+                            .context("Invalid build debounce in config file")?
+                            .unwrap_or(DEFAULT_BUILD_DEBOUNCE),
+                    };
+                    let build_settings = BuildSettings {
+                        steps: build_steps,
+                        policy: build_policy,
+                        debounce,
+                    };
+                    // Build logs are a nice-to-have, like the journal, so they are not kept if there is no cache directory.
+                    let log_dir = cache_dir
+                        .clone()
+                        .map(Ok)
+                        .unwrap_or_else(default_cache_dir)
+                        .inspect_err(|e| warn!(err = ?e, "Failed to determine cache directory. Build logs will not be kept."))
+                        .ok()
+                        .map(|cache_dir| cache_dir.join(BUILD_LOG_SUBDIR_NAME));
+                    // The steps are not logged in full, for the secrets in their environments.
+                    info!(
+                        steps = ?build_settings.steps.iter().map(|step| &step.name).collect::<Vec<_>>(),
+                        policy = ?build_settings.policy,
+                        debounce = ?build_settings.debounce,
+                        "Source changes run the build. Reloads are held until it has succeeded."
+                    );
+                    let (build_runner, trigger_rx) = BuildRunner::new(build_settings, project_dirs.clone(), log_dir);
+                    RELOAD_CHANNEL.gate_on_builds();
+                    BUILD_RUNNER
+                        .set(build_runner)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))?;
+                    Ok::<_, anyhow::Error>(trigger_rx)
+                })?;
                 std::thread::spawn(move || {
-                    let span = info_span!("FS event observer thread");
+                    let span = info_span!("Build runner thread");
                     span.in_scope(|| {
-                        debug!("FS event observer thread started.");
-                        let project_out_fs_observer = fsevent::FsEvent::new(vec![pdir]);
-
-                        // Rendezvous with main thread, so that main thread will wait before proceeding to create marker tempfile A.
-                        debug!("About to rendezvous with main thread");
-                        barrier.wait();
-
-                        project_out_fs_observer.observe(project_out_fs_event_tx);
-                        // Log at warn level so that we can spot in logs if FS observer thread stops before we expect it to.
-                        warn!("FS event observer thread stopping.");
+                        debug!("Build runner thread started.");
+                        if let Some(build_runner) = BUILD_RUNNER.get() {
+                            build_runner.run(&trigger_rx);
+                        }
+                        // Log at warn level so that we can spot in logs if build runner thread stops before we expect it to.
+                        warn!("Build runner thread stopping.");
                     })
-                })
-            };
-
-            // Create a unique temporary file in project dir, that we will use for figuring out
-            // what to do with events occurring around the time between the start and end
-            // of our initial full scan of the project directory.
-            let tmpfile_marker_a = {
-                let span = info_span!("Create marker tempfile A");
-
-                span.in_scope(|| {
-                    // Rendezvous with FS observer thread, so that main thread will wait before proceeding to create marker tempfile A.
-                    debug!("About to rendezvous with FS observer thread");
-                    barrier.wait();
-
-                    // Sleep a little bit extra, to give time for FS observer in FS observer thread to have started.
-                    // Because the FS observer is a third-party crate, we don't have the ability to set a barrier
-                    // exactly where the FS observer has actually started observing FS events.
-                    // Therefore, we have this little sleep to help us increase the likelihood of the FS observer having
-                    // started to observe FS events, so that in turn the file creation we are about to do from here
-                    // will be seen by the FS observer.
-                    debug!("Initiating brief sleep for main thread");
-                    std::thread::sleep(Duration::from_millis(250));
-
-                    let tmpfile_marker_a = tempfile::tempfile_in(&project_dir)
-                        .inspect_err(|e| error!(err = ?e, "Failed to create temporary file."))?;
-                    debug!(?tmpfile_marker_a, "Created marker tempfile A.");
-                    Ok::<_, std::io::Error>(tmpfile_marker_a)
-                })
-            }?;
+                });
+            }
 
-            {
-                let span = info_span!("Render internal index page");
+            if !companion_configs.is_empty() {
+                let span = info_span!("Initialization of OnceLock holding companion processes");
                 span.in_scope(|| {
-                    let internal_index_page = StatusWebUiIndex {
-                        project_dir: &pdir,
-                        color_scheme,
-                    };
-                    let internal_index_page_rendered =
-                        internal_index_page.render()?.as_bytes().to_vec();
-                    INTERNAL_INDEX_PAGE
-                        .set(internal_index_page_rendered)
+                    let mut names = HashSet::new();
+                    if let Some(companion) = companion_configs.iter().find(|companion| !names.insert(companion.name.as_str())) {
+                        error!(name = companion.name, "Fatal: Name of companion process is used more than once in config file.");
+                        return Err(anyhow!("Name of companion process {:?} is used more than once in config file", companion.name));
+                    }
+                    info!(companions = ?names, "Starting companion processes.");
+                    COMPANIONS
+                        .set(Companions::new(companion_configs))
                         .inspect_err(
                             |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
                         )
                         .map_err(|_| anyhow!("Failed to set value of OnceLock."))?;
-                    debug!("Successfully rendered internal index page.");
                     Ok::<_, anyhow::Error>(())
-                })
-            }?;
+                })?;
+                if let Some(companions) = COMPANIONS.get() {
+                    companions.start();
+                }
+            }
 
-            let duration_synchronous_setup = Instant::now() - t_start_synchronous_setup;
-            debug!(?duration_synchronous_setup, "Finished synchronous portion of program setup.");
+            {
+                let span = info_span!("Initialization of OnceLock holding project roots");
+                span.in_scope(|| {
+                    let n_explicit = explicit_mounts.len() + virtual_hosts.len();
+                    let mounts = explicit_mounts
+                        .iter()
+                        .map(|mount| (None, mount.mount.clone()))
+                        .chain(virtual_hosts.iter().map(|vhost| (Some(vhost.host.clone()), String::new())))
+                        .chain(derive_mounts(&project_dirs[n_explicit..]).into_iter().map(|mount| (None, mount)))
+                        .collect::<Vec<_>>();
+                    let mut taken = HashSet::new();
+                    if let Some((host, mount)) = mounts.iter().find(|mount| !taken.insert(*mount)) {
+                        error!(host, mount, "Fatal: Multiple project directories have the same mount point.");
+                        return Err(anyhow!("Multiple project directories are served under /{mount}"));
+                    }
+                    let project_roots = mounts
+                        .into_iter()
+                        .zip(project_dirs.iter().cloned())
+                        .map(|((host, mount), project_dir)| {
+                            let project_root = ProjectRoot::new(mount, project_dir);
+                            let project_root = match host {
+                                Some(host) => {
+                                    info!(project_dir = ?project_root.dir, host, "Project directory will be served for requests for {host}");
+                                    project_root.with_host(host)
+                                }
+                                None => {
+                                    info!(project_dir = ?project_root.dir, project_root.mount, "Project directory will be served under /{}", project_root.mount);
+                                    project_root
+                                }
+                            };
+                            project_root.load_rules();
+                            project_root
+                        })
+                        .collect();
+                    PROJECT_ROOTS
+                        .set(project_roots)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
 
-            Ok::<_, anyhow::Error>(SynchronousSetupValues {
-                ctrl_c,
-                project_dir,
-                project_out_fs_event_rx,
-                open_pages_in_browser,
-                status_addr,
-                project_addr,
-                project_out_fs_event_observer_handle,
-            })
-        })
-    }?;
+            {
+                let span = info_span!("Opening of event history journal");
+                span.in_scope(|| {
+                    // The journal is a nice-to-have, so failing to open it is not fatal.
+                    let journal = cache_dir
+                        .clone()
+                        .map(Ok)
+                        .unwrap_or_else(default_cache_dir)
+                        .and_then(|cache_dir| Journal::open(&cache_dir, &project_dirs));
+                    match journal {
+                        Ok(journal) => JOURNAL
+                            .set(journal)
+                            .inspect_err(
+                                |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                            )
+                            .map_err(|_| anyhow!("Failed to set value of OnceLock.")),
+                        Err(e) => {
+                            warn!(err = ?e, "Failed to open event history journal. History will not be recorded.");
+                            Ok(())
+                        }
+                    }
+                })?;
+            }
 
-    let SynchronousSetupValues {
-        ctrl_c,
-        project_dir,
-        project_out_fs_event_rx,
-        open_pages_in_browser,
-        status_addr,
-        project_addr,
-        project_out_fs_event_observer_handle,
-    } = synchronous_setup;
+            {
+                let span = info_span!("Initialization of OnceLock holding extra MIME types");
+                span.in_scope(|| {
+                    EXTRA_MIME_TYPES
+                        .set(mime_types.into_iter().collect())
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
 
-    /*
-     * Anything async goes here.
-     */
-    let ex = Executor::new();
-    block_on(ex.run(async {
-        let project_dir_tree = {
-            let span = info_span!("Initial full scan of project directory");
-            let instant_start_scan = Instant::now();
-            let project_dir_tree = ex
-                .spawn(scan_project_dir(project_dir.clone()).instrument(span.clone()))
-                .await?;
-            let t_spent_scanning = Instant::now() - instant_start_scan;
-            span.in_scope(|| {
-                info!(
+            {
+                let span = info_span!("Initialization of OnceLock holding compression levels");
+                span.in_scope(|| {
+                    COMPRESSION_LEVELS
+                        .set(compression_levels)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            if file_cache_size > 0 {
+                let span = info_span!("Initialization of OnceLock holding file cache");
+                span.in_scope(|| {
+                    FILE_CACHE
+                        .set(FileCache::new(file_cache_size, file_cache_max_file_size))
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding preload links flag");
+                span.in_scope(|| {
+                    PRELOAD_LINKS
+                        .set(preload_links)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding single port flag");
+                span.in_scope(|| {
+                    SINGLE_PORT
+                        .set(single_port)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding status event interval");
+                span.in_scope(|| {
+                    STATUS_EVENT_INTERVAL
+                        .set(status_event_interval)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding mmap min size");
+                span.in_scope(|| {
+                    MMAP_MIN_SIZE
+                        .set(mmap_min_size)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding cache mode");
+                span.in_scope(|| {
+                    CACHE_MODE
+                        .set(cache_mode)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding header rules from config file");
+                span.in_scope(|| {
+                    let header_rules = header_rules
+                        .into_iter()
+                        .map(HeaderRule::try_from)
+                        .collect::<Result<Vec<_>, _>>()
+                        .inspect_err(|e| error!(err = ?e, "Fatal: Invalid header rule in config file."))
+                        .with_context(|| "Invalid header rule in config file.")?;
+                    CONFIG_HEADER_RULES
+                        .set(header_rules)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding redirect rules from config file");
+                span.in_scope(|| {
+                    let redirect_rules = redirect_rules
+                        .into_iter()
+                        .map(RedirectRule::try_from)
+                        .collect::<Result<Vec<_>, _>>()
+                        .inspect_err(|e| error!(err = ?e, "Fatal: Invalid redirect rule in config file."))
+                        .with_context(|| "Invalid redirect rule in config file.")?;
+                    CONFIG_REDIRECT_RULES
+                        .set(redirect_rules)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            let mocks_dir = mocks_dir
+                .map(|mocks_dir| canonicalize_dir_path(mocks_dir, "Mocks dir"))
+                .transpose()?;
+
+            if let Some(mocks_dir) = &mocks_dir {
+                let span = info_span!("Initialization of OnceLock holding mocks");
+                span.in_scope(|| {
+                    info!(?mocks_dir, "Serving mock responses from fixture files.");
+                    MOCKS
+                        .set(Mocks::new(mocks_dir.clone()))
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            if let Some(record_dir) = record_dir {
+                let span = info_span!("Initialization of OnceLock holding recorder");
+                span.in_scope(|| {
+                    std::fs::create_dir_all(&record_dir)
+                        .inspect_err(|e| error!(err = ?e, record_dir, "Fatal: Failed to create recording dir."))
+                        .with_context(|| format!("Failed to create recording dir: {record_dir:?}"))?;
+                    let record_dir = canonicalize_dir_path(record_dir, "Recording dir")?;
+                    if proxy_rules.is_empty() {
+                        warn!("Recording responses, but there are no proxy rules. Use --proxy to forward requests to a backend.");
+                    }
+                    info!(?record_dir, "Recording responses from proxied backends.");
+                    RECORDER
+                        .set(Recorder::new(record_dir))
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding proxy rules");
+                span.in_scope(|| {
+                    PROXY_RULES
+                        .set(proxy_rules)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding network conditions");
+                span.in_scope(|| {
+                    if !network_conditions.is_unthrottled() {
+                        info!(?network_conditions, "Simulating network conditions for project responses.");
+                    }
+                    NETWORK_CONDITIONS
+                        .set(network_conditions)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding throttle rules from config file");
+                span.in_scope(|| {
+                    let throttle_rules = throttle_rules
+                        .into_iter()
+                        .map(ThrottleRule::try_from)
+                        .collect::<Result<Vec<_>, _>>()
+                        .inspect_err(|e| error!(err = ?e, "Fatal: Invalid throttle rule in config file."))
+                        .with_context(|| "Invalid throttle rule in config file.")?;
+                    THROTTLE_RULES
+                        .set(throttle_rules)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding host check");
+                span.in_scope(|| {
+                    HOST_CHECK
+                        .set(HostCheck::new(vec![status_addr.ip(), project_addr.ip()], allowed_hosts))
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding basic auth");
+                span.in_scope(|| {
+                    let config_auth_users = config_auth_users
+                        .iter()
+                        .map(|user| user.parse::<Credentials>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .inspect_err(|e| error!(err = ?e, "Fatal: Invalid auth user in config file."))
+                        .with_context(|| "Invalid auth user in config file.")?;
+                    let users = [auth_users, config_auth_users].concat();
+                    if users.is_empty() {
+                        return Ok(());
+                    }
+                    info!(n_users = users.len(), ?auth_servers, "Requiring HTTP Basic authentication.");
+                    BASIC_AUTH
+                        .set(BasicAuth::new(&users, auth_servers))
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding status token");
+                span.in_scope(|| {
+                    let status_token = match status_token {
+                        Some(status_token) => StatusToken::new(status_token),
+                        None => StatusToken::generate()
+                            .inspect_err(|e| error!(err = ?e, "Fatal: Failed to generate status token."))
+                            .with_context(|| "Failed to generate status token.")?,
+                    };
+                    STATUS_TOKEN
+                        .set(status_token)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding index file names");
+                span.in_scope(|| {
+                    INDEX_FILES
+                        .set(index_files)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding not found page path");
+                span.in_scope(|| {
+                    NOT_FOUND_PAGE
+                        .set(not_found_page)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            if let Some(access_log) = access_log {
+                let span = info_span!("Initialization of OnceLock holding access log");
+                span.in_scope(|| {
+                    let access_log = AccessLog::open(&access_log)
+                        .with_context(|| format!("Failed to open access log: {access_log:?}"))?;
+                    ACCESS_LOG
+                        .set(access_log)
+                        .inspect_err(|_| error!("Fatal: OnceLock has existing value."))
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding file names to exclude");
+                span.in_scope(|| {
+                    EXCLUDE_FILES_BY_NAME
+                        .set(exclude())
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding limits of tracked trees");
+                span.in_scope(|| {
+                    SCAN_LIMITS
+                        .set(scan_limits)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Initialization of OnceLock holding exclusion patterns from config file");
+                span.in_scope(|| {
+                    check_patterns(&config_exclude)
+                        .inspect_err(|e| error!(err = ?e, "Fatal: Invalid exclusion pattern in config file."))
+                        .with_context(|| "Invalid exclusion pattern in config file.")?;
+                    CONFIG_PATTERNS
+                        .set(config_exclude)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+                })?;
+            }
+
+            {
+                let span = info_span!("Loading of ignore files of project directories");
+                span.in_scope(|| {
+                    for project_dir in &project_dirs {
+                        load_ignore_file(project_dir);
+                    }
+                });
+            }
+
+            {
+                let span = info_span!("Loading of .gitignore rules");
+                span.in_scope(|| {
+                    RESPECT_GITIGNORE
+                        .set(respect_gitignore)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))?;
+                    if respect_gitignore {
+                        for project_dir in project_dirs.iter().chain(&src_dirs) {
+                            load_gitignores(project_dir)
+                                .inspect_err(|e| error!(err = ?e, ?project_dir, "Fatal: Failed to load .gitignore files."))
+                                .with_context(|| format!("Failed to load .gitignore files: {project_dir:?}"))?;
+                        }
+                    }
+                    Ok::<_, anyhow::Error>(())
+                })?;
+            }
+
+            /*
+             * We monitor FS events in the project dir using the
+             * Apple File System Events API via the fsevent crate.
+             *
+             * XXX: Hardlink creation does not result in any corresponding event.
+             *      Issue for this filed at https://github.com/octplane/fsevent-rust/issues/27
+             *      Files that full scans find to have multiple hard links are polled for changes,
+             *      since changes made through their other links go unseen too.
+             *
+             * XXX: When files are moved, two events are generated. One for the source file path,
+             *      and one for the target file path. Because we are choosing to subscribe to events
+             *      for the project directory only, we don't see "the other half" of a pair of events
+             *      where a file is moved into or out of the project directory. Now, we could of course
+             *      monitor the whole file system, and do our best to correlate all moves that affect us.
+             *      But really, that's a lot of work for little actual benefit.
+             *
+             *      So what we are going to do is, for each event we look at what is actually on disk
+             *      at the path of the event, and update our picture of the project directory to match.
+             *      That way, each half of a move is dealt with on its own. And anytime the FSE API tells
+             *      us that events were dropped, we create a temporary file, recursively rescan the project
+             *      directory and "fast-forward" to the point in the stream where we see the creation of
+             *      our temporary file. We do that same temporary file thing for the initial scan as well.
+             *
+             *      And if you think it's weird to do it that way, keep in mind that:
+             *
+             *        1. The information provided by the FSE API is only advisory anyway, and
+             *
+             *        2. Our use-case revolves mainly around files being written to most of the
+             *           time, and sometimes being created or deleted, and normally not being moved.
+             *           So, whereas in contexts where there is a lot of moving going on it might
+             *           not make so much sense to do it like this, it does in our case and also
+             *           helps keep the picture that we have of our project dir over time robust
+             *           (i.e. correctly corresponding to actual reality).
+             *
+             *      So all in all this is actually a good solution we have here, I think.
+             */
+
+            let project_out_watches = project_dirs
+                .iter()
+                .map(|project_dir| watch_project_out_dir(project_dir))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let project_src_watch = if src_dirs.is_empty() {
+                None
+            } else {
+                Some(watch_project_src_dirs(src_dirs, project_dirs.clone())?)
+            };
+
+            let mocks_watch = mocks_dir.map(watch_mocks_dir).transpose()?;
+
+            set_color_scheme(color_scheme);
+
+            {
+                let span = info_span!("Render internal index page");
+                span.in_scope(|| {
+                    let pdirs = project_dirs
+                        .iter()
+                        .map(|project_dir| project_dir.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    STATUS_WEBUI_PROJECT_DIRS
+                        .set(pdirs)
+                        .inspect_err(
+                            |e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."),
+                        )
+                        .map_err(|_| anyhow!("Failed to set value of OnceLock."))?;
+                    // The page is rendered for each request, as of the current color scheme,
+                    // but rendered once up front too, so that it is known to render.
+                    for language in Language::ALL {
+                        render_status_webui_index(language)?;
+                    }
+                    debug!("Successfully rendered internal index page.");
+                    Ok::<_, anyhow::Error>(())
+                })
+            }?;
+
+            let mut subject_alt_names = vec!["localhost".to_string(), "127.0.0.1".to_string(), "::1".to_string()];
+            for addr in [project_addr.ip(), status_addr.ip()] {
+                let addr = addr.to_string();
+                if !addr_is_unspecified(&addr) && !subject_alt_names.contains(&addr) {
+                    subject_alt_names.push(addr);
+                }
+            }
+
+            let tls_cert_files = if mkcert {
+                let span = info_span!("Creation of locally trusted TLS certificate with mkcert");
+                span.in_scope(|| {
+                    let tls_dir = cache_dir
+                        .map(Ok)
+                        .unwrap_or_else(default_cache_dir)
+                        .inspect_err(|e| error!(err = ?e, "Fatal: Failed to find cache directory for mkcert certificate."))
+                        .with_context(|| "Failed to find cache directory for mkcert certificate.")?
+                        .join("tls");
+                    let tls_cert_files = run_mkcert(&tls_dir, &subject_alt_names)
+                        .inspect_err(|e| error!(err = ?e, "Fatal: Failed to make certificate with mkcert."))
+                        .with_context(|| "Failed to make certificate with mkcert. Is mkcert installed?")?;
+                    Ok::<_, anyhow::Error>(Some(tls_cert_files))
+                })
+            } else {
+                Ok(tls_cert_files)
+            }?;
+
+            let tls_setup = if let Some((cert_fpath, key_fpath)) = &tls_cert_files {
+                let span = info_span!("Loading of TLS certificate");
+                span.in_scope(|| {
+                    let tls_setup = TlsSetup::from_pem_files(cert_fpath, key_fpath)
+                        .inspect_err(|e| error!(err = ?e, ?cert_fpath, ?key_fpath, "Fatal: Failed to load TLS certificate."))
+                        .with_context(|| format!("Failed to load TLS certificate: {cert_fpath:?}"))?;
+                    let fingerprint = tls_setup.fingerprint();
+                    info!(?cert_fpath, fingerprint, "Loaded TLS certificate with SHA-256 fingerprint {fingerprint}.");
+                    Ok::<_, anyhow::Error>(Some(tls_setup))
+                })
+            } else if tls {
+                let span = info_span!("Generation of self-signed TLS certificate");
+                span.in_scope(|| {
+                    let tls_setup = TlsSetup::self_signed(subject_alt_names.clone())
+                        .inspect_err(|e| error!(err = ?e, "Fatal: Failed to generate self-signed TLS certificate."))
+                        .with_context(|| "Failed to generate self-signed TLS certificate.")?;
+                    let fingerprint = tls_setup.fingerprint();
+                    info!(?subject_alt_names, fingerprint, "Generated self-signed TLS certificate with SHA-256 fingerprint {fingerprint}.");
+                    Ok::<_, anyhow::Error>(Some(tls_setup))
+                })
+            } else {
+                Ok(None)
+            }?;
+
+            let duration_synchronous_setup = Instant::now() - t_start_synchronous_setup;
+            debug!(?duration_synchronous_setup, "Finished synchronous portion of program setup.");
+
+            Ok::<_, anyhow::Error>(SynchronousSetupValues {
+                ctrl_c,
+                open_pages_in_browser,
+                status_addr,
+                project_addr,
+                livereload_addr,
+                project_out_watches,
+                project_src_watch,
+                mocks_watch,
+                tls_setup,
+                tls_cert_files,
+                tcp_keepalive,
+                keep_alive_timeout,
+                header_timeout,
+                send_timeout,
+                max_connections,
+                ip_filter,
+            })
+        })
+    }?;
+
+    let SynchronousSetupValues {
+        ctrl_c,
+        open_pages_in_browser,
+        status_addr,
+        project_addr,
+        livereload_addr,
+        project_out_watches,
+        project_src_watch,
+        mocks_watch,
+        tls_setup,
+        tls_cert_files,
+        tcp_keepalive,
+        keep_alive_timeout,
+        header_timeout,
+        send_timeout,
+        max_connections,
+        ip_filter,
+    } = synchronous_setup;
+
+    /*
+     * Anything async goes here.
+     */
+    let ex = Executor::new();
+    block_on(ex.run(async {
+        let project_roots = PROJECT_ROOTS
+            .get()
+            .ok_or_else(|| anyhow!("Failed to get value of OnceLock."))?;
+
+        ex.spawn(counted(report_scan_progress(project_roots))).detach();
+
+        for project_root in project_roots {
+            let span = info_span!("Initial full scan of project directory", project_dir = ?project_root.dir);
+            let instant_start_scan = Instant::now();
+            let project_dir_tree = ex
+                .spawn(counted(
+                    RescanManager::new(
+                        project_root.dir.clone(),
+                        project_root.case_insensitive,
+                        project_root.scan_progress.clone(),
+                    )
+                    .instrument(span.clone()),
+                ))
+                .await?;
+            let t_spent_scanning = Instant::now() - instant_start_scan;
+            span.in_scope(|| {
+                info!(
                     ?t_spent_scanning,
                     "Finished initial full scan of project directory."
                 );
                 trace!(?project_dir_tree, "Project dir tree.");
-                project_dir_tree
-            })
-        };
+                project_root
+                    .tree
+                    .set(project_dir_tree)
+                    .inspect_err(|_| error!("Fatal: OnceLock has existing value."))
+                    .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+            })?;
+        }
 
-        let status_tcp = TcpListener::bind(status_addr)
-            .await
-            .inspect_err(|e| {
-                error!(
-                    err = ?e,
-                    ?status_addr,
-                    "Fatal: Failed to bind TCP listener for status server."
-                )
-            })
-            .with_context(|| "Failed to bind TCP listener for status server.")?;
-        let status_addr = status_tcp
-            .local_addr()
-            .inspect_err(|e| {
-                error!(
-                    err = ?e,
-                    ?status_addr,
-                    ?status_tcp,
-                    "Fatal: Failed to get local address that status server is bound to."
-                )
-            })
-            .with_context(|| "Failed to get local address that status server is bound to.")?;
-        let status_url_s = format!("http://{status_addr}");
-        let status_url = &status_url_s;
-        info!(status_url, "Status pages will be served on <{status_url}>.");
+        // With `--single-port`, status pages are served by the project server instead.
+        let status_tcp = if single_port() {
+            None
+        } else {
+            let status_tcp = TcpListener::bind(status_addr)
+                .await
+                .inspect_err(|e| {
+                    error!(
+                        err = ?e,
+                        ?status_addr,
+                        "Fatal: Failed to bind TCP listener for status server."
+                    )
+                })
+                .with_context(|| "Failed to bind TCP listener for status server.")?;
+            let status_addr = status_tcp
+                .local_addr()
+                .inspect_err(|e| {
+                    error!(
+                        err = ?e,
+                        ?status_addr,
+                        ?status_tcp,
+                        "Fatal: Failed to get local address that status server is bound to."
+                    )
+                })
+                .with_context(|| "Failed to get local address that status server is bound to.")?;
+            Some((status_tcp, status_addr))
+        };
+        if let (Some(tls_setup), Some((cert_fpath, key_fpath))) = (&tls_setup, tls_cert_files) {
+            ex.spawn(counted(watch_tls_cert_files(tls_setup.clone(), cert_fpath, key_fpath)))
+                .detach();
+        }
 
         let project_tcp = TcpListener::bind(project_addr)
             .await
@@ -439,75 +1867,131 @@ fn main() -> anyhow::Result<()> {
                 )
             })
             .with_context(|| "Failed to get local address that project server is bound to.")?;
-        let project_url_s = format!("http://{project_addr}");
+        let scheme = if tls_setup.is_some() { "https" } else { "http" };
+        let project_url_s = format!("{scheme}://{project_addr}");
         let project_url = &project_url_s;
         info!(
             project_url,
             "Project pages will be served on <{project_url}>."
         );
 
-        let project_out_fs_event_transformer_handle = std::thread::spawn(move || {
-            std::thread::sleep(Duration::from_millis(15));
-            // TODO: Create initial temp file in project dir
-            // TODO: Start a timer so we can check how long has passed since we created initial temp file.
-            // TODO: Integrate with initial scan of project dir
-            'skip_up_to_temp_file: loop {
-                match project_out_fs_event_rx.recv() {
-                    Ok(fs_ev) => {
-                        debug!(?fs_ev, "fs event");
-                        if false
-                        // TODO: If this event corresponds to the creation of the initial temp file
-                        {
-                            break 'skip_up_to_temp_file;
-                        } else {
-                            // TODO: Check how much time has passed since initial temp file was created
-                            // TODO: If more than 30 seconds has passed, create a new temp file
-                            //       and rescan project dir. Skip all events up to new temp file.
-                        }
-                    }
-                    Err(e) => error!(err = ?e, "fs event recv error!"),
-                };
+        let status_token = STATUS_TOKEN
+            .get()
+            .map(|status_token| encode_path_segment(status_token.as_str()))
+            .unwrap_or_default();
+        let status_url_s = match &status_tcp {
+            Some((_, status_addr)) => {
+                format!("{scheme}://{status_addr}/?{STATUS_TOKEN_PARAM}={status_token}")
+            }
+            None => format!("{project_url}{STATUS_ROOT_PATH}?{STATUS_TOKEN_PARAM}={status_token}"),
+        };
+        let status_url = &status_url_s;
+        info!(status_url, "Status pages will be served on <{status_url}>.");
+
+
+        {
+            let span = info_span!("Initialization of OnceLock holding server info");
+            span.in_scope(|| {
+                SERVER_INFO
+                    .set(ServerInfo {
+                        status_url: status_url_s.clone(),
+                        project_url: project_url_s.clone(),
+                        started: Instant::now(),
+                        started_ms: SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map(|d| d.as_millis() as u64)
+                            .unwrap_or(0),
+                    })
+                    .inspect_err(|e| error!(existing_value = ?e, "Fatal: OnceLock has existing value."))
+                    .map_err(|_| anyhow!("Failed to set value of OnceLock."))
+            })?;
+        }
+
+        let livereload_tcp = match livereload_addr {
+            Some(livereload_addr) => {
+                let livereload_tcp = TcpListener::bind(livereload_addr)
+                    .await
+                    .inspect_err(|e| {
+                        error!(
+                            err = ?e,
+                            ?livereload_addr,
+                            "Fatal: Failed to bind TCP listener for LiveReload compatibility listener."
+                        )
+                    })
+                    .with_context(|| {
+                        "Failed to bind TCP listener for LiveReload compatibility listener."
+                    })?;
+                info!(
+                    ?livereload_addr,
+                    "LiveReload protocol will be spoken on {livereload_addr}."
+                );
+                Some(livereload_tcp)
             }
-            loop {
-                match project_out_fs_event_rx.recv() {
-                    Ok(fs_ev) => {
-                        if false
-                        // TODO: If event type is move
-                        {
-                            // TODO: Create temp file in project dir
-                            // TODO: Start a timer so we can check how long has passed since we created temp file.
-                            // TODO: Rescan of project dir
-                            'skip_up_to_temp_file: loop {
-                                match project_out_fs_event_rx.recv() {
-                                    Ok(fs_ev) => {
-                                        debug!(?fs_ev, "fs event");
-                                        if false
-                                        // TODO: If this event corresponds to the creation of the temp file
-                                        {
-                                            break 'skip_up_to_temp_file;
-                                        } else {
-                                            // TODO: Check how much time has passed since temp file was created
-                                            // TODO: If more than n seconds has passed, create a new temp file
-                                            //       and rescan project dir. Skip all events up to new temp file.
-                                            //       n is exponentially increasing for each time this happens,
-                                            //       up to an upper limit of 10 minutes.
-                                        }
-                                    }
-                                    Err(e) => error!(err = ?e, "fs event recv error!"),
-                                };
-                            }
-                        } else {
-                            info!(?fs_ev, "fs event")
-                        }
+            None => None,
+        };
+
+        let mut project_out_fs_event_observer_handles = vec![];
+        let mut project_out_fs_event_transformer_handles = vec![];
+        for (project_out_watch, project_root) in project_out_watches.into_iter().zip(project_roots) {
+            let ProjectOutWatch {
+                project_dir,
+                project_out_fs_event_rx,
+                project_out_fs_event_observer_handle,
+                tmpfile_marker_a,
+            } = project_out_watch;
+            let project_dir_tree = project_root
+                .tree
+                .get()
+                .ok_or_else(|| anyhow!("Failed to get value of OnceLock."))?;
+            project_out_fs_event_observer_handles.push(project_out_fs_event_observer_handle);
+            project_out_fs_event_transformer_handles.push(std::thread::spawn(move || {
+                let span = info_span!("FS event transformer thread", ?project_dir);
+                span.in_scope(|| {
+                    debug!("FS event transformer thread started.");
+                    if let Err(e) = transform_project_out_fs_events(
+                        &project_out_fs_event_rx,
+                        tmpfile_marker_a,
+                        &project_dir,
+                        project_dir_tree,
+                    ) {
+                        error!(err = ?e, "FS event transformer failed.");
+                        http_horse::notify::notify(
+                            "Stopped watching for changes",
+                            &format!("{}: {e}", project_dir.display()),
+                        );
                     }
-                    Err(e) => error!(err = ?e, "fs event recv error!"),
-                };
-            }
-        });
+                    // Log at warn level so that we can spot in logs if FS event transformer thread stops before we expect it to.
+                    warn!("FS event transformer thread stopping.");
+                })
+            }));
+        }
 
-        let server =
-            hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new());
-        let graceful = hyper_util::server::graceful::GracefulShutdown::new();
+        let mut server = ConnBuilder::new(TokioExecutor::new());
+        // Idle connections are closed by `serve_until_idle`, but hyper times out reading headers.
+        server
+            .http1()
+            .timer(SmolTimer::new())
+            .keep_alive(!keep_alive_timeout.is_zero())
+            .header_read_timeout(header_timeout);
+        let timeouts = Timeouts {
+            // Without keep-alive, the only time a connection is idle is before its request.
+            idle: if keep_alive_timeout.is_zero() {
+                header_timeout
+            } else {
+                keep_alive_timeout
+            },
+            send: send_timeout,
+        };
+        // Connections over the connection limit get a single response before they are closed.
+        let mut rejecting_server = server.clone();
+        rejecting_server.http1().keep_alive(false);
+        let project_connection_limit = ConnectionLimit::new(max_connections);
+        let status_connection_limit = ConnectionLimit::new(max_connections);
+        ex.spawn(counted(sample_resource_usage(
+            project_connection_limit.clone(),
+            status_connection_limit.clone(),
+        )))
+        .detach();
 
         info!("Starting status and project servers.");
         // Skip printing hints if we are going to attempt to open the web browser for the user.
@@ -519,6 +2003,11 @@ fn main() -> anyhow::Result<()> {
             );
         }
 
+        http_horse::notify::notify(
+            "Serving project",
+            &format!("Project: {project_url}\nStatus: {status_url}"),
+        );
+
         // Attempt to open web browser for the user if they supplied the flag for doing so.
         // If we fail to open any of the URLs, print corresponding error and instruct the user
         // to manually open each of the URLs that we failed to open for them.
@@ -536,142 +2025,1084 @@ fn main() -> anyhow::Result<()> {
             }
         }
 
-        let mut spawned_tasks = vec![];
-
-        // XXX: https://github.com/hyperium/hyper-util/blob/df55abac42d0cc1e1577f771d8a1fc91f4bcd0dd/examples/server_graceful.rs
-        loop {
-            select! {
-                /*
-                 * TODO: Enable TCP_NODELAY for accepted connections.
-                 *
-                 * XXX: For details about TCP_NODELAY, see
-                 *      https://github.com/hyperium/hyper/issues/1997
-                 *      https://en.wikipedia.org/wiki/Nagle%27s_algorithm
-                 *      https://www.extrahop.com/company/blog/2016/tcp-nodelay-nagle-quickack-best-practices/
-                 */
-
-                /*
-                 * Serving of files for the project that the user is working on.
-                 */
-                project_conn = project_tcp.accept().fuse() => {
-                    let (stream, peer_addr) = match project_conn {
-                        Ok(conn) => conn,
-                        Err(e) => {
-                            error!(err = ?e, "Accept error");
-                            Timer::after(Duration::from_secs(1)).await;
-                            continue;
-                        }
-                    };
-                    debug!(?peer_addr, "Incoming connection accepted on project_tcp");
-                    let stream = FuturesIo::new(stream);
-                    let conn = server.serve_connection_with_upgrades(stream, service_fn(request_handler_project));
-                    let conn = graceful.watch(conn.into_owned());
-                    let task = ex.spawn(async move {
-                        debug!("Spawned task for connection on connection from project_tcp.");
-                        if let Err(e) = conn.await {
-                            // We log this error at debug level because it is usually not interesting.
-                            // Known, uninteresting things (from error level logs perspective)
-                            // that trigger this error:
-                            // - In the case where user closes browser tab, we get a connection error
-                            //   if a message was still in progress of being sent.
-                            // - If the user agent is sends just `GET /` without specifying HTTP version,
-                            //   as they used to do for what we now sometimes refer to as HTTP/0.9,
-                            //   we get an "invalid URI" error.
-                            //   Conversely:
-                            //   * A client that sends `GET / HTTP/1.1` gets a HTTP/1.1 response
-                            //     as one would expect.
-                            //   * A client that sends `GET / HTTP/1.0` gets a HTTP/1.0 response
-                            //     as one might expect.
-                            // TODO: Any cases that would warrant logging this at level error?
-                            debug!(err = e, "Connection error");
-                        }
-                        debug!(?peer_addr, "Connection dropped");
-                    });
-                    spawned_tasks.push(task);
-                },
-
-                /*
-                 * Serving of status pages, showing status and history.
-                 */
-                status_conn = status_tcp.accept().fuse() => {
-                    let (stream, peer_addr) = match status_conn {
-                        Ok(conn) => conn,
-                        Err(e) => {
-                            error!(err = ?e, "Accept error");
-                            Timer::after(Duration::from_secs(1)).await;
-                            continue;
-                        }
-                    };
-                    debug!(?peer_addr, "Incoming connection accepted on status_tcp");
-                    let stream = FuturesIo::new(stream);
-                    let conn = server.serve_connection_with_upgrades(stream, service_fn(request_handler_status));
-                    let conn = graceful.watch(conn.into_owned());
-                    let task = ex.spawn(async move {
-                        debug!("Spawned task for connection on connection from status_tcp.");
-                        if let Err(e) = conn.await {
-                            // We log this error at debug level because it is usually not interesting.
-                            // Known, uninteresting things (from error level logs perspective)
-                            // that trigger this error:
-                            // - In the case where user closes browser tab, we get a connection error
-                            //   if a message was still in progress of being sent.
-                            // - If the user agent is sends just `GET /` without specifying HTTP version,
-                            //   as they used to do for what we now sometimes refer to as HTTP/0.9,
-                            //   we get an "invalid URI" error.
-                            //   Conversely:
-                            //   * A client that sends `GET / HTTP/1.1` gets a HTTP/1.1 response
-                            //     as one would expect.
-                            //   * A client that sends `GET / HTTP/1.0` gets a HTTP/1.0 response
-                            //     as one might expect.
-                            // TODO: Any cases that would warrant logging this at level error?
-                            debug!(err = e, "Connection error");
-                        }
-                        debug!(?peer_addr, "Connection dropped");
-                    });
-                    spawned_tasks.push(task);
-                },
+        // XXX: https://github.com/hyperium/hyper-util/blob/df55abac42d0cc1e1577f771d8a1fc91f4bcd0dd/examples/server_graceful.rs
+        loop {
+            select! {
+                /*
+                 * Serving of files for the project that the user is working on.
+                 */
+                project_conn = project_tcp.accept().fuse() => {
+                    let (stream, peer_addr) = match project_conn {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            error!(err = ?e, "Accept error");
+                            Timer::after(Duration::from_secs(1)).await;
+                            continue;
+                        }
+                    };
+                    debug!(?peer_addr, "Incoming connection accepted on project_tcp");
+                    if !ip_filter.is_allowed(peer_addr.ip()) {
+                        info!(?peer_addr, "Refusing connection on project_tcp from IP address that is not allowed.");
+                        continue;
+                    }
+                    configure_tcp_stream(&stream, tcp_keepalive);
+                    let connection_limit = project_connection_limit.clone();
+                    let server = server.clone();
+                    let rejecting_server = rejecting_server.clone();
+                    let tls_setup = tls_setup.clone();
+                    let task = ex.spawn(counted(async move {
+                        debug!("Spawned task for connection on connection from project_tcp.");
+                        let stream = match MaybeTlsStream::accept(stream, tls_setup.as_ref(), header_timeout).await {
+                            Ok(stream) => stream,
+                            Err(e) => {
+                                // Clients that do not trust our self-signed certificate
+                                // routinely abort the handshake, so this is not very interesting either.
+                                debug!(err = ?e, ?peer_addr, "TLS handshake failed");
+                                return;
+                            }
+                        };
+                        // The place is only taken once the handshake is done, so that connections
+                        // that never finish it cannot use up the limit.
+                        let permit = connection_limit.try_acquire();
+                        if permit.is_none() {
+                            warn!(?peer_addr, max_connections, "Too many connections on project_tcp. Answering with 503.");
+                        }
+                        let activity = Activity::new();
+                        let stream = FuturesIo::new(ActivityStream::new(stream, activity.clone()));
+                        let Some(_permit) = permit else {
+                            reject_connection(&rejecting_server, stream, &activity).await;
+                            debug!(?peer_addr, "Connection dropped");
+                            return;
+                        };
+                        let conn = server.serve_connection_with_upgrades(stream, service_fn(move |req| access_logged(true, peer_addr, req, move |req| request_handler_project(peer_addr, req))));
+                        let res = match serve_until_idle(conn, &activity, timeouts, |conn| conn.graceful_shutdown()).await {
+                            Ok(res) => res,
+                            Err(e) => {
+                                debug!(err = %e, ?peer_addr, "Connection timed out");
+                                Ok(())
+                            }
+                        };
+                        if let Err(e) = res {
+                            // We log this error at debug level because it is usually not interesting.
+                            // Known, uninteresting things (from error level logs perspective)
+                            // that trigger this error:
+                            // - In the case where user closes browser tab, we get a connection error
+                            //   if a message was still in progress of being sent.
+                            // - If the user agent is sends just `GET /` without specifying HTTP version,
+                            //   as they used to do for what we now sometimes refer to as HTTP/0.9,
+                            //   we get an "invalid URI" error.
+                            //   Conversely:
+                            //   * A client that sends `GET / HTTP/1.1` gets a HTTP/1.1 response
+                            //     as one would expect.
+                            //   * A client that sends `GET / HTTP/1.0` gets a HTTP/1.0 response
+                            //     as one might expect.
+                            // TODO: Any cases that would warrant logging this at level error?
+                            debug!(err = e, "Connection error");
+                        }
+                        debug!(?peer_addr, "Connection dropped");
+                    }));
+                    task.detach();
+                },
+
+                /*
+                 * Serving of status pages, showing status and history.
+                 */
+                status_conn = async {
+                    match &status_tcp {
+                        Some((status_tcp, _)) => status_tcp.accept().await,
+                        None => std::future::pending().await,
+                    }
+                }.fuse() => {
+                    let (stream, peer_addr) = match status_conn {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            error!(err = ?e, "Accept error");
+                            Timer::after(Duration::from_secs(1)).await;
+                            continue;
+                        }
+                    };
+                    debug!(?peer_addr, "Incoming connection accepted on status_tcp");
+                    if !ip_filter.is_allowed(peer_addr.ip()) {
+                        info!(?peer_addr, "Refusing connection on status_tcp from IP address that is not allowed.");
+                        continue;
+                    }
+                    configure_tcp_stream(&stream, tcp_keepalive);
+                    let connection_limit = status_connection_limit.clone();
+                    let server = server.clone();
+                    let rejecting_server = rejecting_server.clone();
+                    let tls_setup = tls_setup.clone();
+                    let task = ex.spawn(counted(async move {
+                        debug!("Spawned task for connection on connection from status_tcp.");
+                        let stream = match MaybeTlsStream::accept(stream, tls_setup.as_ref(), header_timeout).await {
+                            Ok(stream) => stream,
+                            Err(e) => {
+                                // Clients that do not trust our self-signed certificate
+                                // routinely abort the handshake, so this is not very interesting either.
+                                debug!(err = ?e, ?peer_addr, "TLS handshake failed");
+                                return;
+                            }
+                        };
+                        // The place is only taken once the handshake is done, so that connections
+                        // that never finish it cannot use up the limit.
+                        let permit = connection_limit.try_acquire();
+                        if permit.is_none() {
+                            warn!(?peer_addr, max_connections, "Too many connections on status_tcp. Answering with 503.");
+                        }
+                        let activity = Activity::new();
+                        let stream = FuturesIo::new(ActivityStream::new(stream, activity.clone()));
+                        let Some(_permit) = permit else {
+                            reject_connection(&rejecting_server, stream, &activity).await;
+                            debug!(?peer_addr, "Connection dropped");
+                            return;
+                        };
+                        let conn = server.serve_connection_with_upgrades(stream, service_fn(move |req| access_logged(false, peer_addr, req, request_handler_status)));
+                        let res = match serve_until_idle(conn, &activity, timeouts, |conn| conn.graceful_shutdown()).await {
+                            Ok(res) => res,
+                            Err(e) => {
+                                debug!(err = %e, ?peer_addr, "Connection timed out");
+                                Ok(())
+                            }
+                        };
+                        if let Err(e) = res {
+                            // We log this error at debug level because it is usually not interesting.
+                            // Known, uninteresting things (from error level logs perspective)
+                            // that trigger this error:
+                            // - In the case where user closes browser tab, we get a connection error
+                            //   if a message was still in progress of being sent.
+                            // - If the user agent is sends just `GET /` without specifying HTTP version,
+                            //   as they used to do for what we now sometimes refer to as HTTP/0.9,
+                            //   we get an "invalid URI" error.
+                            //   Conversely:
+                            //   * A client that sends `GET / HTTP/1.1` gets a HTTP/1.1 response
+                            //     as one would expect.
+                            //   * A client that sends `GET / HTTP/1.0` gets a HTTP/1.0 response
+                            //     as one might expect.
+                            // TODO: Any cases that would warrant logging this at level error?
+                            debug!(err = e, "Connection error");
+                        }
+                        debug!(?peer_addr, "Connection dropped");
+                    }));
+                    task.detach();
+                },
+
+                /*
+                 * LiveReload compatibility listener, for LiveReload browser extensions and tools.
+                 */
+                livereload_conn = async {
+                    match &livereload_tcp {
+                        Some(livereload_tcp) => livereload_tcp.accept().await,
+                        None => std::future::pending().await,
+                    }
+                }.fuse() => {
+                    let (stream, peer_addr) = match livereload_conn {
+                        Ok(conn) => conn,
+                        Err(e) => {
+                            error!(err = ?e, "Accept error");
+                            Timer::after(Duration::from_secs(1)).await;
+                            continue;
+                        }
+                    };
+                    debug!(?peer_addr, "Incoming connection accepted on livereload_tcp");
+                    if !ip_filter.is_allowed(peer_addr.ip()) {
+                        info!(?peer_addr, "Refusing connection on livereload_tcp from IP address that is not allowed.");
+                        continue;
+                    }
+                    configure_tcp_stream(&stream, tcp_keepalive);
+                    let server = server.clone();
+                    let tls_setup = tls_setup.clone();
+                    let task = ex.spawn(counted(async move {
+                        let stream = match MaybeTlsStream::accept(stream, tls_setup.as_ref(), header_timeout).await {
+                            Ok(stream) => stream,
+                            Err(e) => {
+                                debug!(err = ?e, ?peer_addr, "TLS handshake failed");
+                                return;
+                            }
+                        };
+                        let activity = Activity::new();
+                        let stream = FuturesIo::new(ActivityStream::new(stream, activity.clone()));
+                        // Once the connection is upgraded to a WebSocket, it is no longer served
+                        // by hyper, and so no longer subject to the idle timeouts.
+                        let conn = server.serve_connection_with_upgrades(stream, service_fn(move |req| access_logged(false, peer_addr, req, request_handler_livereload)));
+                        match serve_until_idle(conn, &activity, timeouts, |conn| conn.graceful_shutdown()).await {
+                            Ok(Err(e)) => debug!(err = e, "Connection error"),
+                            Err(e) => debug!(err = %e, ?peer_addr, "Connection timed out"),
+                            Ok(Ok(())) => {}
+                        }
+                        debug!(?peer_addr, "Connection dropped");
+                    }));
+                    task.detach();
+                },
+
+                _ = ctrl_c.recv().fuse() => {
+                    drop(project_tcp);
+                    drop(status_tcp);
+                    drop(livereload_tcp);
+                    info!("Ctrl-C or termination signal received, starting shutdown");
+                    break;
+                }
+            }
+        }
+
+        // Build commands run in process groups of their own, which a Ctrl-C in the terminal
+        // does not reach, so they are stopped here, rather than left running.
+        if let Some(build_runner) = BUILD_RUNNER.get() {
+            info!("Stopping build runner.");
+            build_runner.shutdown();
+        }
+        if let Some(companions) = COMPANIONS.get() {
+            info!("Stopping companion processes.");
+            companions.shutdown();
+        }
+
+        info!("Shutting down FS event observer threads for project out dirs.");
+        drop(project_out_fs_event_observer_handles);
+
+        info!("Shutting down FS event transformer threads for project out dirs.");
+        drop(project_out_fs_event_transformer_handles);
+
+        if let Some(ProjectSrcWatch {
+            project_src_fs_event_observer_handle,
+            project_src_fs_event_transformer_handle,
+        }) = project_src_watch
+        {
+            info!("Shutting down FS event observer thread for project source dirs.");
+            drop(project_src_fs_event_observer_handle);
+
+            info!("Shutting down FS event transformer thread for project source dirs.");
+            drop(project_src_fs_event_transformer_handle);
+        }
+
+        if let Some(MocksWatch {
+            mocks_fs_event_observer_handle,
+            mocks_fs_event_transformer_handle,
+        }) = mocks_watch
+        {
+            info!("Shutting down FS event observer thread for mocks dir.");
+            drop(mocks_fs_event_observer_handle);
+
+            info!("Shutting down FS event transformer thread for mocks dir.");
+            drop(mocks_fs_event_transformer_handle);
+        }
+
+        Ok(())
+    }))
+}
+
+/// Set socket options for an accepted connection.
+///
+/// A live-reload workflow produces many tiny responses, which should not be held back
+/// waiting for more data to send, so we disable Nagle's algorithm with TCP_NODELAY.
+///
+/// XXX: For details about TCP_NODELAY, see
+///      https://github.com/hyperium/hyper/issues/1997
+///      https://en.wikipedia.org/wiki/Nagle%27s_algorithm
+///      https://www.extrahop.com/company/blog/2016/tcp-nodelay-nagle-quickack-best-practices/
+fn configure_tcp_stream(stream: &TcpStream, tcp_keepalive: Option<Duration>) {
+    if let Err(e) = stream.set_nodelay(true) {
+        warn!(err = ?e, "Failed to set TCP_NODELAY on connection.");
+    }
+    if let Some(tcp_keepalive) = tcp_keepalive {
+        let keepalive = TcpKeepalive::new().with_time(tcp_keepalive);
+        if let Err(e) = SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+            warn!(err = ?e, "Failed to enable TCP keepalive on connection.");
+        }
+    }
+}
+
+/// Report the progress of full scans of the project directories, to the log and the status
+/// web-ui, for as long as they are going on. Scans that are over within a
+/// [`SCAN_PROGRESS_INTERVAL`] go unreported.
+async fn report_scan_progress(project_roots: &'static [ProjectRoot]) {
+    let mut reported = vec![false; project_roots.len()];
+    loop {
+        Timer::after(SCAN_PROGRESS_INTERVAL).await;
+        for (root, reported) in project_roots.iter().zip(&mut reported) {
+            let scanning = root.scan_progress.is_scanning();
+            if !scanning && !*reported {
+                continue;
+            }
+            let dirs = root.scan_progress.dirs();
+            let files = root.scan_progress.files();
+            if scanning {
+                info!(project_dir = ?root.dir, dirs, files, "Scanning project directory.");
+            }
+            publish_status(Event::ScanProgress {
+                root: root.dir.display().to_string(),
+                dirs,
+                files,
+                done: !scanning,
+                limits_reached: root.scan_progress.limits_reached(),
+                hardlinked: root.scan_progress.hardlinked(),
+            });
+            *reported = scanning;
+        }
+    }
+}
+
+/// Sample the resource usage of http-horse every so often, for the status web-ui.
+async fn sample_resource_usage(project: ConnectionLimit, status: ConnectionLimit) {
+    loop {
+        let connections = (project.active() + status.active()) as u64;
+        publish_status(Event::ResourceUsage(RESOURCE_USAGE.sample(connections)));
+        Timer::after(SAMPLE_INTERVAL).await;
+    }
+}
+
+/// Reload the TLS certificate when its certificate or key file changes.
+///
+/// The files are usually outside of the watched directories, so we poll their modification times.
+async fn watch_tls_cert_files(tls_setup: TlsSetup, cert_fpath: PathBuf, key_fpath: PathBuf) {
+    async fn mtimes(cert_fpath: &Path, key_fpath: &Path) -> Option<(SystemTime, SystemTime)> {
+        let cert_mtime = smol::fs::metadata(cert_fpath).await.ok()?.modified().ok()?;
+        let key_mtime = smol::fs::metadata(key_fpath).await.ok()?.modified().ok()?;
+        Some((cert_mtime, key_mtime))
+    }
+    let mut last_mtimes = mtimes(&cert_fpath, &key_fpath).await;
+    loop {
+        Timer::after(TLS_CERT_POLL_INTERVAL).await;
+        let current_mtimes = mtimes(&cert_fpath, &key_fpath).await;
+        if current_mtimes.is_none() || current_mtimes == last_mtimes {
+            continue;
+        }
+        last_mtimes = current_mtimes;
+        match tls_setup.reload_pem_files(&cert_fpath, &key_fpath) {
+            Ok(()) => {
+                let fingerprint = tls_setup.fingerprint();
+                info!(
+                    ?cert_fpath,
+                    fingerprint,
+                    "TLS certificate changed. Reloaded it, with SHA-256 fingerprint {fingerprint}."
+                );
+            }
+            Err(e) => {
+                // The certificate and key files are often written one after the other,
+                // so this may resolve itself once the second one has been written.
+                warn!(err = ?e, ?cert_fpath, ?key_fpath, "Failed to reload TLS certificate. Keeping the current one.");
+            }
+        }
+    }
+}
+
+/// Check whether the string form of an IP address is the unspecified address of IPv4 or IPv6.
+fn addr_is_unspecified(addr: &str) -> bool {
+    addr.parse::<IpAddr>()
+        .is_ok_and(|addr| addr.is_unspecified())
+}
+
+/// Canonicalize a directory path given by the user, ensuring that it is a directory.
+fn canonicalize_dir_path(dir: String, what: &str) -> anyhow::Result<PathBuf> {
+    let dir = PathBuf::from(dir);
+    let dir = dir
+        .canonicalize()
+        .inspect_err(|e| error!(err = ?e, ?dir, "Fatal: Failed to canonicalize {what} path."))
+        .with_context(|| format!("Failed to canonicalize {what} path: {dir:?}"))?;
+
+    if !dir.is_dir() {
+        error!(?dir, "Fatal: File is not a directory: {what} path.");
+        Err(anyhow!("File is not a directory: {what} path: {dir:?}"))
+    } else {
+        debug!(?dir, "Successfully canonicalized {what} path.");
+        Ok(dir)
+    }
+}
+
+/// Start observing FS events for source directories.
+///
+/// Unlike project directories, we do not keep track of the contents of source directories.
+/// We only care about *that* something changed in them, so that we can trigger builds.
+fn watch_project_src_dirs(
+    src_dirs: Vec<PathBuf>,
+    project_dirs: Vec<PathBuf>,
+) -> anyhow::Result<ProjectSrcWatch> {
+    let sdirs = src_dirs
+        .iter()
+        .map(|src_dir| {
+            src_dir
+                .to_path_buf()
+                .into_os_string()
+                .into_string()
+                .inspect_err(
+                    |e| error!(os_string = ?e, "Fatal: Failed to convert PathBuf to String."),
+                )
+                .map_err(|_| anyhow!("Failed to convert PathBuf to String."))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let (project_src_fs_event_tx, project_src_fs_event_rx) = std::sync::mpsc::channel();
+
+    let project_src_fs_event_observer_handle = std::thread::spawn(move || {
+        let span = info_span!("FS event observer thread for source dirs");
+        span.in_scope(|| {
+            debug!("FS event observer thread started.");
+            let project_src_fs_observer = fsevent::FsEvent::new(sdirs);
+            project_src_fs_observer.observe(project_src_fs_event_tx);
+            // Log at warn level so that we can spot in logs if FS observer thread stops before we expect it to.
+            warn!("FS event observer thread stopping.");
+        })
+    });
+
+    let project_src_fs_event_transformer_handle = std::thread::spawn(move || {
+        let span = info_span!("FS event transformer thread for source dirs");
+        span.in_scope(|| {
+            debug!("FS event transformer thread started.");
+            while let Ok(fs_ev) = project_src_fs_event_rx.recv() {
+                let path = Path::new(&fs_ev.path);
+                // Changes to the output of a build must not be treated as changes to the input
+                // of a build. Otherwise, we would end up in a build loop.
+                if project_dirs
+                    .iter()
+                    .any(|project_dir| path.starts_with(project_dir))
+                {
+                    trace!(
+                        ?fs_ev,
+                        "Ignoring FS event for project dir inside source dir."
+                    );
+                    continue;
+                }
+                let Some(src_dir) = src_dirs.iter().find(|src_dir| path.starts_with(src_dir))
+                else {
+                    trace!(?fs_ev, "Ignoring FS event for path outside of source dirs.");
+                    continue;
+                };
+                // `.gitignore` files are themselves excluded, so changes to them are looked for first.
+                if respect_gitignore() && is_gitignore_event(&fs_ev) {
+                    info!(
+                        ?fs_ev,
+                        "A .gitignore file changed. Reloading .gitignore rules."
+                    );
+                    load_gitignores(src_dir)
+                        .inspect_err(|e| error!(err = ?e, "Failed to reload .gitignore files."))
+                        .ok();
+                    continue;
+                }
+                if is_marker_event(&fs_ev)
+                    || is_excluded(src_dir, path, fs_ev.flag.contains(StreamFlags::IS_DIR))
+                {
+                    trace!(?fs_ev, "Ignoring FS event for excluded path in source dir.");
+                    continue;
+                }
+                if WATCHING_PAUSED.load(Ordering::SeqCst) {
+                    trace!(
+                        ?fs_ev,
+                        "Watching is paused. Ignoring FS event in source dir."
+                    );
+                    continue;
+                }
+                info!(?path, "Source file changed.");
+                if let Some(build_runner) = BUILD_RUNNER.get() {
+                    build_runner.trigger(path);
+                }
+            }
+            // Log at warn level so that we can spot in logs if FS event transformer thread stops before we expect it to.
+            warn!("FS event transformer thread stopping.");
+        })
+    });
+
+    Ok(ProjectSrcWatch {
+        project_src_fs_event_observer_handle,
+        project_src_fs_event_transformer_handle,
+    })
+}
+
+/// Start observing FS events for the mocks directory, so that pages using the mock responses
+/// are reloaded when fixture files change.
+fn watch_mocks_dir(mocks_dir: PathBuf) -> anyhow::Result<MocksWatch> {
+    let mdir = mocks_dir
+        .clone()
+        .into_os_string()
+        .into_string()
+        .inspect_err(|e| error!(os_string = ?e, "Fatal: Failed to convert PathBuf to String."))
+        .map_err(|_| anyhow!("Failed to convert PathBuf to String."))?;
+
+    let (mocks_fs_event_tx, mocks_fs_event_rx) = std::sync::mpsc::channel();
+
+    let mocks_fs_event_observer_handle = std::thread::spawn(move || {
+        let span = info_span!("FS event observer thread for mocks dir");
+        span.in_scope(|| {
+            debug!("FS event observer thread started.");
+            let mocks_fs_observer = fsevent::FsEvent::new(vec![mdir]);
+            mocks_fs_observer.observe(mocks_fs_event_tx);
+            // Log at warn level so that we can spot in logs if FS observer thread stops before we expect it to.
+            warn!("FS event observer thread stopping.");
+        })
+    });
+
+    let mocks_fs_event_transformer_handle = std::thread::spawn(move || {
+        let span = info_span!("FS event transformer thread for mocks dir");
+        span.in_scope(|| {
+            debug!("FS event transformer thread started.");
+            while let Ok(fs_ev) = mocks_fs_event_rx.recv() {
+                let path = Path::new(&fs_ev.path);
+                if fs_ev.flag.contains(StreamFlags::IS_DIR) || is_excluded(&mocks_dir, path, false)
+                {
+                    trace!(
+                        ?fs_ev,
+                        "Ignoring FS event for directory or excluded path in mocks dir."
+                    );
+                    continue;
+                }
+                if WATCHING_PAUSED.load(Ordering::SeqCst) {
+                    trace!(
+                        ?fs_ev,
+                        "Watching is paused. Ignoring FS event in mocks dir."
+                    );
+                    continue;
+                }
+                let Some(url_path) = MOCKS.get().and_then(|mocks| mocks.url_path_for(path)) else {
+                    continue;
+                };
+                info!(?path, url_path, "Fixture file changed.");
+                RELOAD_CHANNEL.publish(Event::FileModified {
+                    path: url_path,
+                    is_dir: false,
+                });
+            }
+            // Log at warn level so that we can spot in logs if FS event transformer thread stops before we expect it to.
+            warn!("FS event transformer thread stopping.");
+        })
+    });
+
+    Ok(MocksWatch {
+        mocks_fs_event_observer_handle,
+        mocks_fs_event_transformer_handle,
+    })
+}
+
+/// Start observing FS events for a project directory, and create marker tempfile A for it.
+///
+/// See the block comment about FS events in `main`.
+fn watch_project_out_dir(project_dir: &Path) -> anyhow::Result<ProjectOutWatch> {
+    // FsEvent takes strings as arguments. We always want to use the canonical path,
+    // and because of that we have to convert back to String from PathBuf.
+    let pdir = project_dir
+        .to_path_buf()
+        .into_os_string()
+        .into_string()
+        .inspect_err(|e| error!(os_string = ?e, "Fatal: Failed to convert PathBuf to String."))
+        .map_err(|_| anyhow!("Failed to convert PathBuf to String."))?;
+
+    let (project_out_fs_event_tx, project_out_fs_event_rx) = std::sync::mpsc::channel();
+    let barrier = Arc::new(Barrier::new(2));
+
+    let project_out_fs_event_observer_handle = {
+        let barrier = barrier.clone();
+        std::thread::spawn(move || {
+            let span = info_span!("FS event observer thread");
+            span.in_scope(|| {
+                debug!("FS event observer thread started.");
+                let project_out_fs_observer = fsevent::FsEvent::new(vec![pdir]);
+
+                // Rendezvous with main thread, so that main thread will wait before proceeding to create marker tempfile A.
+                debug!("About to rendezvous with main thread");
+                barrier.wait();
+
+                project_out_fs_observer.observe(project_out_fs_event_tx);
+                // Log at warn level so that we can spot in logs if FS observer thread stops before we expect it to.
+                warn!("FS event observer thread stopping.");
+            })
+        })
+    };
+
+    // Create a unique temporary file in project dir, that we will use for figuring out
+    // what to do with events occurring around the time between the start and end
+    // of our initial full scan of the project directory.
+    let tmpfile_marker_a = {
+        let span = info_span!("Create marker tempfile A");
+
+        span.in_scope(|| {
+            // Rendezvous with FS observer thread, so that main thread will wait before proceeding to create marker tempfile A.
+            debug!("About to rendezvous with FS observer thread");
+            barrier.wait();
+
+            // Sleep a little bit extra, to give time for FS observer in FS observer thread to have started.
+            // Because the FS observer is a third-party crate, we don't have the ability to set a barrier
+            // exactly where the FS observer has actually started observing FS events.
+            // Therefore, we have this little sleep to help us increase the likelihood of the FS observer having
+            // started to observe FS events, so that in turn the file creation we are about to do from here
+            // will be seen by the FS observer.
+            debug!("Initiating brief sleep for main thread");
+            std::thread::sleep(Duration::from_millis(250));
+
+            let tmpfile_marker_a = create_marker_tempfile(project_dir)
+                .inspect_err(|e| error!(err = ?e, "Failed to create temporary file."))?;
+            debug!(?tmpfile_marker_a, "Created marker tempfile A.");
+            Ok::<_, std::io::Error>(tmpfile_marker_a)
+        })
+    }?;
+
+    Ok(ProjectOutWatch {
+        project_dir: project_dir.to_path_buf(),
+        project_out_fs_event_rx,
+        project_out_fs_event_observer_handle,
+        tmpfile_marker_a,
+    })
+}
+
+/// Consume FS events for the project out dir, keeping our picture of the project directory
+/// in sync with reality. See the block comment about FS events in `main` for the big picture.
+///
+/// Marker tempfile A must have been created before the initial full scan of the project directory.
+fn transform_project_out_fs_events(
+    fs_event_rx: &std::sync::mpsc::Receiver<fsevent::Event>,
+    tmpfile_marker_a: tempfile::NamedTempFile,
+    project_dir: &Path,
+    project_dir_tree: &RescanManager,
+) -> Result<(), http_horse::fs::marker::Error> {
+    // Events that occurred before the initial full scan are already reflected in the initial scan.
+    fast_forward_to_marker(fs_event_rx, tmpfile_marker_a, project_dir, || {
+        rescan(project_dir_tree).map(drop)
+    })?;
+
+    if let Some(diffs) = DIFFS.get() {
+        diffs.seed(&block_on(project_dir_tree.read()).tree);
+    }
+
+    let mut rename_correlator = RenameCorrelator::default();
+    let mut t_last_hardlink_poll = Instant::now();
+
+    loop {
+        rename_correlator
+            .flush_expired()
+            .into_iter()
+            .for_each(report_change);
+        if t_last_hardlink_poll.elapsed() >= HARDLINK_POLL_INTERVAL
+            && !WATCHING_PAUSED.load(Ordering::SeqCst)
+        {
+            t_last_hardlink_poll = Instant::now();
+            poll_hardlinked_files(project_dir_tree)
+                .into_iter()
+                .for_each(report_change);
+        }
+        if let Some(request) = project_dir_tree.take_rescan_request() {
+            info!(
+                ?request,
+                "Carrying out requested rescan of project directory."
+            );
+            rename_correlator
+                .flush()
+                .into_iter()
+                .for_each(report_change);
+            let changes = resync(fs_event_rx, project_dir, project_dir_tree)?;
+            if request == RescanRequest::Reconciling {
+                info!(
+                    n_changes = changes.len(),
+                    "Reporting changes found by reconciling rescan."
+                );
+                changes.into_iter().for_each(report_change);
+            }
+        }
+        // We wait for events with a timeout, so that we get to check for rescan requests
+        // and pending halves of renames regularly.
+        let fs_ev = match fs_event_rx.recv_timeout(RENAME_CORRELATION_WINDOW) {
+            Ok(fs_ev) => fs_ev,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(http_horse::fs::marker::Error::FsEventObserverDisconnected)
+            }
+        };
+        if is_marker_event(&fs_ev) {
+            let path = Path::new(&fs_ev.path);
+            match BUILD_RUNNER.get() {
+                Some(build_runner) if build_runner.is_settling(path) => {
+                    // Pending halves of renames were written by the build too,
+                    // so they have to be reported before the held reloads are released.
+                    rename_correlator
+                        .flush()
+                        .into_iter()
+                        .for_each(report_change);
+                    build_runner.marker_seen(path);
+                }
+                _ => trace!(?fs_ev, "Ignoring FS event for marker tempfile."),
+            }
+        } else if WATCHING_PAUSED.load(Ordering::SeqCst) {
+            // A rescan is requested when watching is resumed, so we can safely drop the event.
+            trace!(?fs_ev, "Watching is paused. Ignoring FS event.");
+        } else if is_ignore_file(project_dir, Path::new(&fs_ev.path)) {
+            // As with .gitignore files, what is and is not ignored may have changed anywhere.
+            info!(
+                ?fs_ev,
+                "The ignore file of the project directory changed. Reloading its rules."
+            );
+            load_ignore_file(project_dir);
+            rename_correlator
+                .flush()
+                .into_iter()
+                .for_each(report_change);
+            resync(fs_event_rx, project_dir, project_dir_tree)?;
+        } else if respect_gitignore() && is_gitignore_event(&fs_ev) {
+            // What is and is not ignored may have changed anywhere below the directory
+            // of the .gitignore file, so we reload the rules and rescan.
+            info!(
+                ?fs_ev,
+                "A .gitignore file changed. Reloading .gitignore rules."
+            );
+            load_gitignores(project_dir)
+                .inspect_err(|e| error!(err = ?e, "Failed to reload .gitignore files."))
+                .ok();
+            rename_correlator
+                .flush()
+                .into_iter()
+                .for_each(report_change);
+            resync(fs_event_rx, project_dir, project_dir_tree)?;
+        } else if requires_rescan(&fs_ev) {
+            info!(?fs_ev, "FS event requires rescan of project directory.");
+            rename_correlator
+                .flush()
+                .into_iter()
+                .for_each(report_change);
+            resync(fs_event_rx, project_dir, project_dir_tree)?;
+        } else {
+            let res = block_on(async {
+                let mut current = project_dir_tree.write().await;
+                current.apply_fs_change(Path::new(&fs_ev.path)).await
+            });
+            match res {
+                Ok(Some(change)) => {
+                    let renamed = fs_ev.flag.contains(StreamFlags::ITEM_RENAMED);
+                    rename_correlator
+                        .push(change, renamed)
+                        .into_iter()
+                        .for_each(report_change);
+                }
+                Ok(None) => debug!(
+                    ?fs_ev,
+                    "FS event did not result in change to project dir tree."
+                ),
+                Err(e) => {
+                    warn!(err = ?e, ?fs_ev, "Failed to apply FS event to project dir tree. Rescanning project directory.");
+                    rename_correlator
+                        .flush()
+                        .into_iter()
+                        .for_each(report_change);
+                    resync(fs_event_rx, project_dir, project_dir_tree)?;
+                }
+            }
+        }
+    }
+}
+
+/// Check the tracked files that have more than one hard link for changes, by hashing them anew.
+///
+/// Changes made through the other links of a file result in FS events for the paths of those
+/// links only, if any, and not for the path that we track the file by. Neither does the creation
+/// of a hard link result in any FS event, so files that get a second link are only polled
+/// from the next full scan on.
+fn poll_hardlinked_files(project_dir_tree: &RescanManager) -> Vec<TrackedChange> {
+    block_on(async {
+        let mut current = project_dir_tree.write().await;
+        let mut changes = vec![];
+        for fpath in current.tree.hardlinked_files() {
+            match current.apply_fs_change(&fpath).await {
+                Ok(Some(change)) => changes.push(change),
+                Ok(None) => {}
+                Err(e) => warn!(err = ?e, ?fpath, "Failed to check hard-linked file for changes."),
+            }
+        }
+        changes
+    })
+}
 
-                _ = ctrl_c.recv().fuse() => {
-                    drop(project_tcp);
-                    drop(status_tcp);
-                    info!("Ctrl-C received, starting shutdown");
-                    break;
+/// Report a change that was applied to the tracked project directory tree.
+fn report_change(change: TrackedChange) {
+    info!(?change, "Applied FS change to project dir tree.");
+    if let Some(file_cache) = FILE_CACHE.get() {
+        file_cache.invalidate(&change.path);
+        if let ChangeKind::Renamed { from } = &change.kind {
+            file_cache.invalidate(from);
+        }
+    }
+    if let Some(project_roots) = PROJECT_ROOTS.get() {
+        let from = match &change.kind {
+            ChangeKind::Renamed { from } => Some(from),
+            _ => None,
+        };
+        for root in project_roots {
+            for rules_fpath in
+                [HEADERS_FILE_NAME, REDIRECTS_FILE_NAME].map(|name| root.dir.join(name))
+            {
+                if change.path == rules_fpath || from == Some(&rules_fpath) {
+                    info!(?rules_fpath, "Rules file changed. Reloading rules.");
+                    root.load_rules();
                 }
             }
         }
+    }
+    if BUILD_MARKER.get() == Some(&change.path) {
+        // The build marker is not reloaded for, but releases the reloads for the build.
+        if !matches!(change.kind, ChangeKind::Removed) {
+            info!("Build marker touched. Build has finished.");
+            RELOAD_CHANNEL.build_finished();
+        }
+    } else if let Some(event) = Event::file_change(&change, url_path_for) {
+        // What the file references is parsed anew when it is next served.
+        for url_path in event.paths() {
+            DEPENDENCY_GRAPH.remove(url_path);
+        }
+        // Problems are checked anew when the file is next served, unless it is gone.
+        match &event {
+            Event::FileDeleted { path, .. } | Event::Renamed { from: path, .. } => {
+                ERROR_REPORTS.set(path, None)
+            }
+            _ => {}
+        }
+        RELOAD_CHANNEL.publish_build_output(event);
+    }
+    let entry = JOURNAL
+        .get()
+        .and_then(|journal| {
+            journal
+                .append(&change)
+                .inspect_err(|e| warn!(err = ?e, ?change, "Failed to record change in event history journal."))
+                .ok()
+        })
+        .unwrap_or_else(|| JournalEntry::new(0, &change));
+    if let Some(diffs) = DIFFS.get() {
+        diffs.record(&change, &entry);
+    }
+    STATUS_EVENTS.send(StampedEvent::from(&entry));
+    publish_tree_updates(&change);
+}
 
-        info!("Shutting down FS event observer thread for project out dir.");
-        drop(project_out_fs_event_observer_handle);
-
-        info!("Shutting down FS event transformer thread for project out dir.");
-        drop(project_out_fs_event_transformer_handle);
+/// Send what is tracked now at the paths of a change to the status web-ui, so that it can keep
+/// its view of the project directory trees up to date without fetching them anew.
+fn publish_tree_updates(change: &TrackedChange) {
+    let from = match &change.kind {
+        ChangeKind::Renamed { from } => Some(from),
+        _ => None,
+    };
+    for root in PROJECT_ROOTS.get().into_iter().flatten() {
+        let Some(tree) = root.tree.get() else {
+            continue;
+        };
+        let current = block_on(tree.read());
+        for path in from.into_iter().chain([&change.path]) {
+            if !path.starts_with(&root.dir) {
+                continue;
+            }
+            publish_status(Event::TreeUpdated {
+                root: root.dir.display().to_string(),
+                path: path.display().to_string(),
+                node: TreeNode::find(&current.tree, path, &url_path_for),
+            });
+        }
+    }
+}
 
+/// Create a marker tempfile, rescan the project directory,
+/// and fast-forward the FS event stream to the creation of the marker tempfile.
+///
+/// Returns what the rescans found to have changed, which is left to the caller to report, if wanted.
+fn resync(
+    fs_event_rx: &std::sync::mpsc::Receiver<fsevent::Event>,
+    project_dir: &Path,
+    project_dir_tree: &RescanManager,
+) -> Result<Vec<TrackedChange>, http_horse::fs::marker::Error> {
+    let marker = create_marker_tempfile(project_dir)?;
+    let mut changes = rescan(project_dir_tree)?;
+    fast_forward_to_marker(fs_event_rx, marker, project_dir, || {
+        changes.extend(rescan(project_dir_tree)?);
         Ok(())
-    }))
+    })?;
+    publish_status(Event::TreeRescanned {
+        root: project_dir.display().to_string(),
+    });
+    Ok(changes)
+}
+
+fn rescan(
+    project_dir_tree: &RescanManager,
+) -> Result<Vec<TrackedChange>, http_horse::fs::marker::Error> {
+    let Rescanned {
+        generation,
+        changes,
+    } = block_on(project_dir_tree.rescan())?;
+    trace!(generation, tree = ?block_on(project_dir_tree.read()).tree, "Project dir tree.");
+    Ok(changes)
 }
 
 #[derive(Error, Debug)]
 #[error("FS Event Observer has disconnected")]
 pub struct FSEventObserverDisconnectedError;
 
-fn event_stream() -> BoxBody<Bytes, FSEventObserverDisconnectedError> {
-    // TODO: Connect the thing
+/// Request header that browsers send when they reconnect to an event stream.
+const LAST_EVENT_ID: HeaderName = HeaderName::from_static("last-event-id");
+
+/// Event stream message for a batch of status events, or `None` if they were all already sent.
+/// A batch of one event is sent as the event itself, and a larger batch as an array of events.
+fn status_message(batch: &[StampedEvent], last_id: &mut u64) -> Option<Bytes> {
+    // Changes that were both looked up as missed and received live are only sent once.
+    let unsent = batch
+        .iter()
+        .filter(|entry| entry.id == 0 || entry.id > *last_id)
+        .collect::<Vec<_>>();
+    let data = match unsent.as_slice() {
+        [] => return None,
+        [entry] => serde_json::to_string(entry),
+        entries => serde_json::to_string(entries),
+    }
+    .inspect_err(|e| error!(err = ?e, ?unsent, "Failed to serialize status events."))
+    .ok()?;
+    let id = unsent.iter().map(|entry| entry.id).max().unwrap_or(0);
+    Some(Bytes::from(if id != 0 {
+        *last_id = id;
+        format!("id: {id}\ndata: {data}\n\n")
+    } else {
+        format!("data: {data}\n\n")
+    }))
+}
+
+/// Stream the changes applied to the project dir trees, and other events, to the status web-ui.
+///
+/// Changes are sent with their journal entry id as event id. A client that reconnects
+/// with the id of the last event it got is first sent the changes that it missed,
+/// as far as the journal still has them.
+fn event_stream(last_event_id: Option<u64>) -> BoxBody<Bytes, FSEventObserverDisconnectedError> {
+    // Subscribe before looking up missed changes, so that none fall in between.
+    let status_rx = http_horse::event::subscribe_status();
+    let missed = match (last_event_id, JOURNAL.get()) {
+        (Some(id), Some(journal)) => journal.since(id),
+        _ => vec![],
+    };
+    let interval = status_event_interval();
     let stream = stream! {
-        let mut i = 0;
-        loop {
-            // Sleep 250ms between each iteration so we don't overwhelm the web page with events.
-            Timer::after(Duration::from_millis(250)).await;
-            yield Ok(Bytes::from(format!("data: {{\"elem\": {i}}}\n\n")));
-            i += 1;
+        let _stream = EVENT_STREAMS.enter();
+        let mut last_id = last_event_id.unwrap_or(0);
+        for entry in &missed {
+            if let Some(message) = status_message(&[entry.into()], &mut last_id) {
+                yield Ok(message);
+            }
+        }
+        // The first event after a quiet spell is sent right away. Events that come in less than
+        // the interval after the last message are batched, and sent once the interval is up.
+        let mut batch = vec![];
+        let mut ready_at = Instant::now();
+        let mut disconnected = false;
+        while !disconnected {
+            let received = if batch.is_empty() {
+                Some(status_rx.recv().await)
+            } else {
+                smol::future::or(async { Some(status_rx.recv().await) }, async {
+                    Timer::at(ready_at).await;
+                    None
+                })
+                .await
+            };
+            match received {
+                Some(Ok(event)) => batch.push(event),
+                Some(Err(_)) => disconnected = true,
+                None => {}
+            }
+            if !batch.is_empty() && (disconnected || Instant::now() >= ready_at) {
+                if let Some(message) = status_message(&std::mem::take(&mut batch), &mut last_id) {
+                    yield Ok(message);
+                }
+                ready_at = Instant::now() + interval;
+            }
         }
+        yield Err(FSEventObserverDisconnectedError);
     };
-    let stream_body = StreamBody::new(stream.map_ok(Frame::data));
+    let stream_body = StreamBody::new(with_keep_alive(stream).map_ok(Frame::data));
     BodyExt::boxed(stream_body)
 }
 
 async fn request_handler_status(
     req: Request<Incoming>,
 ) -> HttpResult<Response<Either<Full<Bytes>, BoxBody<Bytes, FSEventObserverDisconnectedError>>>> {
-    let (method, uri_path) = (req.method(), req.uri().path());
+    let is_head = req.method() == Method::HEAD;
+    let req_headers = req.headers().clone();
+    if !is_allowed_host(&req) {
+        return Ok(negotiated_error(&req_headers, forbidden_host()?).await);
+    }
+    if !is_authorized(Server::Status, req.headers()) {
+        return Ok(negotiated_error(&req_headers, unauthorized()?).await);
+    }
+    let token_source = STATUS_TOKEN
+        .get()
+        .and_then(|status_token| status_token.given_in(req.uri(), req.headers()));
+    if token_source.is_none() && !is_public_status_path(req.uri().path()) {
+        return Ok(negotiated_error(&req_headers, status_token_required()?).await);
+    }
+    let mut resp = negotiated_error(&req_headers, handle_status_request(req).await?).await;
+    // The status web-ui is opened with the token in the URL, and makes further requests without it.
+    if token_source == Some(TokenSource::Query) {
+        if let Some(cookie) = STATUS_TOKEN.get().and_then(|status_token| {
+            let path = if single_port() { STATUS_ROOT_PATH } else { "/" };
+            HeaderValue::from_str(&status_token.cookie(path)).ok()
+        }) {
+            resp.headers_mut().append(header::SET_COOKIE, cookie);
+        }
+    }
+    Ok(if is_head { without_body(resp) } else { resp })
+}
+
+/// Handle a request to the LiveReload compatibility listener: either for the WebSocket that
+/// the LiveReload protocol is spoken over, or for the client script.
+async fn request_handler_livereload(req: Request<Incoming>) -> HttpResult<ProjectResponse> {
+    let is_head = req.method() == Method::HEAD;
+    let req_headers = req.headers().clone();
+    if !is_allowed_host(&req) {
+        return Ok(negotiated_error(&req_headers, forbidden_host()?).await);
+    }
+    let response_builder = Response::builder().header(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(CACHE_CONTROL_VALUE_NO_STORE),
+    );
+    let resp = match (req.method(), req.uri().path()) {
+        (&Method::GET, livereload::WEBSOCKET_URL_PATH) => {
+            let Some(accept) = livereload::websocket_accept(req.headers()) else {
+                let (status, content_type, body) = upgrade_required();
+                let resp = response_builder
+                    .status(status)
+                    .header(header::UPGRADE, HeaderValue::from_static("websocket"))
+                    .header(header::CONTENT_TYPE, content_type)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body))?;
+                return Ok(negotiated_error(&req_headers, resp).await);
+            };
+            // Subscribe before answering, so that no change is missed in between.
+            let events = RELOAD_CHANNEL.subscribe();
+            smol::spawn(counted(async move {
+                let upgraded = match hyper::upgrade::on(req).await {
+                    Ok(upgraded) => upgraded,
+                    Err(e) => {
+                        debug!(err = ?e, "Failed to upgrade LiveReload connection.");
+                        return;
+                    }
+                };
+                if let Err(e) = livereload::serve(upgraded, events).await {
+                    debug!(err = ?e, "LiveReload connection error");
+                }
+                debug!("LiveReload client went away.");
+            }))
+            .detach();
+            response_builder
+                .status(StatusCode::SWITCHING_PROTOCOLS)
+                .header(header::UPGRADE, HeaderValue::from_static("websocket"))
+                .header(header::CONNECTION, HeaderValue::from_static("upgrade"))
+                .header(header::SEC_WEBSOCKET_ACCEPT, accept)
+                .body(Either::Left(Full::default()))?
+        }
+        (&Method::GET | &Method::HEAD, livereload::CLIENT_SCRIPT_URL_PATH) => {
+            let body = Bytes::from_static(LIVERELOAD_CLIENT_JAVASCRIPT);
+            full_body(&req_headers, response_builder, TEXT_JAVASCRIPT, body).await?
+        }
+        _ => {
+            let (status, content_type, body) = not_found();
+            response_builder
+                .status(status)
+                .header(header::CONTENT_TYPE, content_type)
+                .extension(ErrorResponse)
+                .body(Either::Left(body))?
+        }
+    };
+    let resp = negotiated_error(&req_headers, resp).await;
+    Ok(if is_head { without_body(resp) } else { resp })
+}
+
+async fn handle_status_request(
+    req: Request<Incoming>,
+) -> HttpResult<Response<Either<Full<Bytes>, BoxBody<Bytes, FSEventObserverDisconnectedError>>>> {
+    let (method, uri_path) = (get_for_head(req.method()), req.uri().path());
     let uri_path_trimmed = uri_path.trim_start_matches('/');
     debug!(
         ?method,
@@ -689,38 +3120,581 @@ async fn request_handler_status(
     );
 
     match (method, uri_path) {
-        (&Method::GET, "") => match INTERNAL_INDEX_PAGE.get() {
-            None => {
-                error!("Failed to get rendered index page for status web-ui!");
+        (&Method::GET, "") => match render_status_webui_index(status_webui_language(&req)) {
+            Err(e) => {
+                error!(err = ?e, "Failed to render index page for status web-ui!");
                 let (status, content_type, body) = server_error();
                 response_builder
                     .header(header::CONTENT_TYPE, content_type)
                     .status(status)
-                    .body(Either::Left(body))
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body))
+            }
+            Ok(internal_index_page) => {
+                let body = Bytes::from(internal_index_page);
+                full_body(req.headers(), response_builder, TEXT_HTML, body).await
+            }
+        },
+        (&Method::GET, "preview") => {
+            let path = query_param(req.uri(), "path").unwrap_or_default();
+            let presets = query_param(req.uri(), "presets");
+            match render_status_webui_preview(
+                status_webui_language(&req),
+                &path,
+                presets.as_deref(),
+            ) {
+                Err(e) => {
+                    error!(err = ?e, "Failed to render preview page for status web-ui!");
+                    let (status, content_type, body) = server_error();
+                    response_builder
+                        .header(header::CONTENT_TYPE, content_type)
+                        .status(status)
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body))
+                }
+                Ok(preview_page) => {
+                    let body = Bytes::from(preview_page);
+                    full_body(req.headers(), response_builder, TEXT_HTML, body).await
+                }
+            }
+        }
+        (&Method::GET, "favicon.ico") => {
+            let body = Bytes::from_static(FAVICON);
+            full_body(req.headers(), response_builder, IMAGE_X_ICON, body).await
+        }
+        (&Method::GET, "manifest.webmanifest") => {
+            let body = Bytes::from_static(WEB_APP_MANIFEST);
+            full_body(
+                req.headers(),
+                response_builder,
+                APPLICATION_MANIFEST_JSON,
+                body,
+            )
+            .await
+        }
+        (&Method::GET, icon_path) if status_icon(icon_path).is_some() => {
+            let (content_type, icon) = status_icon(icon_path).unwrap_or_default();
+            let body = Bytes::from_static(icon);
+            full_body(req.headers(), response_builder, content_type, body).await
+        }
+        (&Method::GET, "style/main.css") => {
+            let body = Bytes::from_static(INTERNAL_STYLESHEET);
+            full_body(req.headers(), response_builder, TEXT_CSS, body).await
+        }
+        (&Method::GET, "js/main.js") => {
+            let body = Bytes::from_static(INTERNAL_JAVASCRIPT);
+            full_body(req.headers(), response_builder, TEXT_JAVASCRIPT, body).await
+        }
+        (&Method::GET, "js/preview.js") => {
+            let body = Bytes::from_static(PREVIEW_JAVASCRIPT);
+            full_body(req.headers(), response_builder, TEXT_JAVASCRIPT, body).await
+        }
+        (&Method::GET, "history/") => {
+            let entries = JOURNAL
+                .get()
+                .map(|journal| journal.recent(STATUS_HISTORY_LEN))
+                .unwrap_or_default();
+            let events = entries.iter().map(StampedEvent::from).collect::<Vec<_>>();
+            match serde_json::to_vec(&events) {
+                Ok(body) => response_builder
+                    .header(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static(APPLICATION_JSON),
+                    )
+                    .body(Either::Left(body.into())),
+                Err(e) => {
+                    error!(err = ?e, "Failed to serialize event history.");
+                    let (status, content_type, body) = server_error();
+                    response_builder
+                        .header(header::CONTENT_TYPE, content_type)
+                        .status(status)
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body))
+                }
+            }
+        }
+        (&Method::GET, "client-errors/") => {
+            let errors = CLIENT_ERRORS.recent(STATUS_HISTORY_LEN);
+            match serde_json::to_vec(&errors) {
+                Ok(body) => response_builder
+                    .header(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static(APPLICATION_JSON),
+                    )
+                    .body(Either::Left(body.into())),
+                Err(e) => {
+                    error!(err = ?e, "Failed to serialize client errors.");
+                    let (status, content_type, body) = server_error();
+                    response_builder
+                        .header(header::CONTENT_TYPE, content_type)
+                        .status(status)
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body))
+                }
+            }
+        }
+        (&Method::GET, "api/info") => {
+            let Some(server_info) = SERVER_INFO.get() else {
+                error!("Failed to get server info for status API!");
+                let (status, content_type, body) = server_error();
+                return response_builder
+                    .header(header::CONTENT_TYPE, content_type)
+                    .status(status)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body));
+            };
+            let project_roots = PROJECT_ROOTS
+                .get()
+                .into_iter()
+                .flatten()
+                .map(|root| {
+                    json!({
+                        "url_path": root.mount_url_path(),
+                        "dir": root.dir,
+                        "host": root.host,
+                        "scan": {
+                            "scanning": root.scan_progress.is_scanning(),
+                            "dirs": root.scan_progress.dirs(),
+                            "files": root.scan_progress.files(),
+                            "bytes": root.scan_progress.bytes(),
+                            "limits_reached": root.scan_progress.limits_reached(),
+                            "hardlinked": root.scan_progress.hardlinked(),
+                        },
+                    })
+                })
+                .collect::<Vec<_>>();
+            let info = json!({
+                "version": env!("CARGO_PKG_VERSION"),
+                "status_url": server_info.status_url,
+                "project_url": server_info.project_url,
+                "started_ms": server_info.started_ms,
+                "uptime_secs": server_info.started.elapsed().as_secs(),
+                "project_roots": project_roots,
+                "watching_paused": WATCHING_PAUSED.load(Ordering::SeqCst),
+                "serving_paused": SERVING_PAUSED.load(Ordering::SeqCst),
+                "diffs": DIFFS.get().is_some(),
+            });
+            json_response(response_builder, &info)
+        }
+        (&Method::GET, "api/usage") => json_response(
+            response_builder,
+            &json!({
+                "interval_secs": SAMPLE_INTERVAL.as_secs(),
+                "samples": RESOURCE_USAGE.samples(),
+            }),
+        ),
+        (&Method::GET, "api/tree") => {
+            let mut trees = vec![];
+            for root in PROJECT_ROOTS.get().into_iter().flatten() {
+                // The tree of a project directory is only there once its initial scan is done.
+                let tree = match root.tree.get() {
+                    Some(tree) => Some(TreeDir::new(&tree.read().await.tree, &url_path_for)),
+                    None => None,
+                };
+                trees.push(json!({
+                    "url_path": root.mount_url_path(),
+                    "dir": root.dir,
+                    "tree": tree,
+                }));
+            }
+            json_response(response_builder, &trees)
+        }
+        (&Method::GET, "api/events") => {
+            let before = query_param(req.uri(), "before").and_then(|id| id.parse().ok());
+            let limit = query_param(req.uri(), "limit")
+                .and_then(|limit| limit.parse().ok())
+                .unwrap_or(STATUS_HISTORY_LEN)
+                .min(MAX_EVENTS_PAGE_LEN);
+            let entries = JOURNAL
+                .get()
+                .map(|journal| journal.before(before, limit))
+                .unwrap_or_default();
+            // Newest first, with the id to ask for the page before this one with.
+            let before = entries
+                .first()
+                .map(|entry| entry.id)
+                .filter(|_| entries.len() == limit);
+            let events = entries
+                .iter()
+                .rev()
+                .map(StampedEvent::from)
+                .collect::<Vec<_>>();
+            json_response(
+                response_builder,
+                &json!({ "events": events, "before": before }),
+            )
+        }
+        (&Method::GET, "api/clients") => json_response(response_builder, &PRESENCE.live()),
+        (&Method::GET, "api/events/export") => {
+            let time_param =
+                |name| query_param(req.uri(), name).map(|time| time.parse::<u64>().map_err(|_| ()));
+            let (Ok(from), Ok(to)) = (time_param("from").transpose(), time_param("to").transpose())
+            else {
+                let (status, content_type, body) = bad_request();
+                return response_builder
+                    .header(header::CONTENT_TYPE, content_type)
+                    .status(status)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body));
+            };
+            let entries = JOURNAL
+                .get()
+                .map(|journal| journal.recorded_between(from.unwrap_or(0)..=to.unwrap_or(u64::MAX)))
+                .unwrap_or_default();
+            let format = query_param(req.uri(), "format");
+            match format.as_deref() {
+                None | Some("json") => json_response(
+                    response_builder.header(
+                        header::CONTENT_DISPOSITION,
+                        HeaderValue::from_static(
+                            "attachment; filename=\"http-horse-history.json\"",
+                        ),
+                    ),
+                    &entries,
+                ),
+                Some("csv") => response_builder
+                    .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_CSV))
+                    .header(
+                        header::CONTENT_DISPOSITION,
+                        HeaderValue::from_static("attachment; filename=\"http-horse-history.csv\""),
+                    )
+                    .body(Either::Left(to_csv(&entries).into())),
+                Some(_) => {
+                    let (status, content_type, body) = bad_request();
+                    response_builder
+                        .header(header::CONTENT_TYPE, content_type)
+                        .status(status)
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body))
+                }
+            }
+        }
+        (&Method::GET, "api/diff") => {
+            let diff = query_param(req.uri(), "id")
+                .and_then(|id| id.parse().ok())
+                .and_then(|id| DIFFS.get()?.diff(id));
+            let Some(diff) = diff else {
+                let (status, content_type, body) = not_found();
+                return response_builder
+                    .header(header::CONTENT_TYPE, content_type)
+                    .status(status)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body));
+            };
+            response_builder
+                .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_PLAIN))
+                .body(Either::Left(diff.into()))
+        }
+        (&Method::GET, "api/builds") => {
+            let runs = BUILD_RUNNER
+                .get()
+                .map(|build_runner| build_runner.runs())
+                .unwrap_or_default();
+            match serde_json::to_vec(&runs) {
+                Ok(body) => response_builder
+                    .header(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static(APPLICATION_JSON),
+                    )
+                    .body(Either::Left(body.into())),
+                Err(e) => {
+                    error!(err = ?e, "Failed to serialize build runs.");
+                    let (status, content_type, body) = server_error();
+                    response_builder
+                        .header(header::CONTENT_TYPE, content_type)
+                        .status(status)
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body))
+                }
+            }
+        }
+        (&Method::GET, "api/build-output") => {
+            let output = query_param(req.uri(), "run")
+                .and_then(|run| run.parse().ok())
+                .and_then(|run| BUILD_RUNNER.get()?.output(run));
+            let Some(output) = output else {
+                let (status, content_type, body) = not_found();
+                return response_builder
+                    .header(header::CONTENT_TYPE, content_type)
+                    .status(status)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body));
+            };
+            response_builder
+                .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_PLAIN))
+                .body(Either::Left(output.into()))
+        }
+        (&Method::GET, "api/companions") => {
+            let statuses = COMPANIONS
+                .get()
+                .map(|companions| companions.statuses())
+                .unwrap_or_default();
+            json_response(response_builder, &statuses)
+        }
+        (&Method::GET, "api/companion-output") => {
+            let output =
+                query_param(req.uri(), "name").and_then(|name| COMPANIONS.get()?.output(&name));
+            let Some(output) = output else {
+                let (status, content_type, body) = not_found();
+                return response_builder
+                    .header(header::CONTENT_TYPE, content_type)
+                    .status(status)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body));
+            };
+            response_builder
+                .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_PLAIN))
+                .body(Either::Left(output.into()))
+        }
+        (&Method::POST, "api/companions/restart") => {
+            let restarted = query_param(req.uri(), "name").is_some_and(|name| {
+                COMPANIONS
+                    .get()
+                    .is_some_and(|companions| companions.restart(&name))
+            });
+            if !restarted {
+                let (status, content_type, body) = not_found();
+                return response_builder
+                    .header(header::CONTENT_TYPE, content_type)
+                    .status(status)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body));
+            }
+            response_builder
+                .status(StatusCode::NO_CONTENT)
+                .body(Either::Left("".into()))
+        }
+        (&Method::GET, "api/stats") => {
+            let window_minutes = query_param(req.uri(), "minutes")
+                .and_then(|minutes| minutes.parse().ok())
+                .unwrap_or(STATS_WINDOW_MINUTES)
+                .clamp(1, MAX_WINDOW_MINUTES);
+            let n_hot_files = query_param(req.uri(), "top")
+                .and_then(|top| top.parse().ok())
+                .unwrap_or(STATS_HOT_FILES_LEN)
+                .min(MAX_STATS_HOT_FILES_LEN);
+            let now_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+            let window_start_ms = now_ms.saturating_sub(window_minutes * 60_000);
+            let entries = JOURNAL
+                .get()
+                .map(|journal| journal.recorded_between(window_start_ms..))
+                .unwrap_or_default();
+            let build_runs = BUILD_RUNNER.get().map(|build_runner| build_runner.runs());
+            let stats = Stats::new(
+                &entries,
+                &RELOAD_CHANNEL.stats(),
+                build_runs.as_deref(),
+                now_ms,
+                window_minutes,
+                n_hot_files,
+            );
+            json_response(response_builder, &stats)
+        }
+        (&Method::GET, "watching/") => {
+            let paused = WATCHING_PAUSED.load(Ordering::SeqCst);
+            response_builder
+                .header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(APPLICATION_JSON),
+                )
+                .body(Either::Left(format!("{{\"paused\": {paused}}}").into()))
+        }
+        (&Method::POST, "watching/pause") => {
+            if !WATCHING_PAUSED.swap(true, Ordering::SeqCst) {
+                info!("Watching paused.");
+            }
+            response_builder
+                .status(StatusCode::NO_CONTENT)
+                .body(Either::Left("".into()))
+        }
+        (&Method::POST, "watching/resume") => {
+            if WATCHING_PAUSED.swap(false, Ordering::SeqCst) {
+                info!("Watching resumed. Requesting rescan of project directories.");
+                resume_watching();
+            }
+            response_builder
+                .status(StatusCode::NO_CONTENT)
+                .body(Either::Left("".into()))
+        }
+        (&Method::GET, "serving/") => {
+            let paused = SERVING_PAUSED.load(Ordering::SeqCst);
+            response_builder
+                .header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(APPLICATION_JSON),
+                )
+                .body(Either::Left(format!("{{\"paused\": {paused}}}").into()))
+        }
+        (&Method::POST, "serving/pause") => {
+            if !SERVING_PAUSED.swap(true, Ordering::SeqCst) {
+                info!("Serving paused. The project server answers every request with 503.");
+            }
+            response_builder
+                .status(StatusCode::NO_CONTENT)
+                .body(Either::Left("".into()))
+        }
+        (&Method::POST, "serving/resume") => {
+            if SERVING_PAUSED.swap(false, Ordering::SeqCst) {
+                info!("Serving resumed.");
+            }
+            response_builder
+                .status(StatusCode::NO_CONTENT)
+                .body(Either::Left("".into()))
+        }
+        (&Method::POST, "api/rescan") => {
+            info!("Reconciling rescan of project directories requested.");
+            for root in PROJECT_ROOTS.get().into_iter().flatten() {
+                if let Some(tree) = root.tree.get() {
+                    tree.request_rescan(RescanRequest::Reconciling);
+                }
+            }
+            // The rescans are carried out by the FS event transformers, shortly.
+            response_builder
+                .status(StatusCode::ACCEPTED)
+                .body(Either::Left("".into()))
+        }
+        (&Method::GET, "api/exclusions") => {
+            json_response(response_builder, &json!({ "patterns": runtime_patterns() }))
+        }
+        (&Method::POST, "api/exclusions") => {
+            let body = http_body_util::Limited::new(req.into_body(), MAX_EXCLUSIONS_REQUEST_LEN)
+                .collect()
+                .await
+                .map(|body| body.to_bytes());
+            let exclusions_request = match body {
+                Ok(body) => serde_json::from_slice::<ExclusionsRequest>(&body).ok(),
+                Err(e) => {
+                    debug!(err = ?e, "Failed to read exclusions request.");
+                    None
+                }
+            };
+            let patterns = exclusions_request.and_then(|ExclusionsRequest { add, remove }| {
+                update_runtime_patterns(&add, &remove)
+                    .inspect_err(|e| warn!(err = ?e, "Rejected exclusions request."))
+                    .ok()
+            });
+            let Some(patterns) = patterns else {
+                let (status, content_type, body) = bad_request();
+                return response_builder
+                    .header(header::CONTENT_TYPE, content_type)
+                    .status(status)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body));
+            };
+            info!(
+                ?patterns,
+                "Runtime exclusion patterns changed. Reconciling project directories with them."
+            );
+            for root in PROJECT_ROOTS.get().into_iter().flatten() {
+                load_ignore_file(&root.dir);
+                if let Some(tree) = root.tree.get() {
+                    tree.request_rescan(RescanRequest::Reconciling);
+                }
             }
-            Some(internal_index_page) => response_builder
-                .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_HTML))
-                .body(Either::Left(internal_index_page.as_slice().into())),
-        },
-        (&Method::GET, "favicon.ico") => response_builder
-            .header(header::CONTENT_TYPE, HeaderValue::from_static(IMAGE_X_ICON))
-            .status(StatusCode::NO_CONTENT)
-            .body(Either::Left("".into())),
-        (&Method::GET, "style/main.css") => response_builder
-            .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_CSS))
-            .body(Either::Left(INTERNAL_STYLESHEET.into())),
-        (&Method::GET, "js/main.js") => response_builder
-            .header(
-                header::CONTENT_TYPE,
-                HeaderValue::from_static(TEXT_JAVASCRIPT),
-            )
-            .body(Either::Left(INTERNAL_JAVASCRIPT.into())),
-        (&Method::GET, "event-stream/") => response_builder
-            .header(
-                header::CONTENT_TYPE,
-                HeaderValue::from_static(TEXT_EVENT_STREAM),
+            // What is now excluded is reported as removed, and what no longer is as created,
+            // by the rescans that the FS event transformers carry out shortly.
+            json_response(response_builder, &json!({ "patterns": patterns }))
+        }
+        (&Method::POST, "api/reload") => {
+            let body = http_body_util::Limited::new(req.into_body(), MAX_RELOAD_REQUEST_LEN)
+                .collect()
+                .await
+                .map(|body| body.to_bytes());
+            let reload_request = match body {
+                Ok(body) if body.is_empty() => Some(ReloadRequest::default()),
+                Ok(body) => serde_json::from_slice::<ReloadRequest>(&body).ok(),
+                Err(e) => {
+                    debug!(err = ?e, "Failed to read reload request.");
+                    None
+                }
+            };
+            let Some(reload_request) = reload_request else {
+                let (status, content_type, body) = bad_request();
+                return response_builder
+                    .header(header::CONTENT_TYPE, content_type)
+                    .status(status)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body));
+            };
+            let pages = if reload_request.pages.is_empty() {
+                vec![None]
+            } else {
+                reload_request.pages.into_iter().map(Some).collect()
+            };
+            for page in pages {
+                info!(?page, "Reloading, as requested.");
+                let event = Event::ReloadRequested { page };
+                RELOAD_CHANNEL.publish(event.clone());
+                publish_status(event);
+            }
+            response_builder
+                .status(StatusCode::NO_CONTENT)
+                .body(Either::Left("".into()))
+        }
+        (&Method::GET, "api/color-scheme") => {
+            let body = json!({ "color_scheme": color_scheme() }).to_string();
+            full_body(
+                req.headers(),
+                response_builder,
+                APPLICATION_JSON,
+                body.into(),
             )
-            .body(Either::Right(event_stream())),
+            .await
+        }
+        (&Method::POST, "api/color-scheme") => {
+            let body = http_body_util::Limited::new(req.into_body(), MAX_COLOR_SCHEME_REQUEST_LEN)
+                .collect()
+                .await
+                .map(|body| body.to_bytes());
+            let color_scheme_request = match body {
+                Ok(body) => serde_json::from_slice::<ColorSchemeRequest>(&body).ok(),
+                Err(e) => {
+                    debug!(err = ?e, "Failed to read color scheme request.");
+                    None
+                }
+            };
+            let Some(ColorSchemeRequest { color_scheme }) = color_scheme_request else {
+                let (status, content_type, body) = bad_request();
+                return response_builder
+                    .header(header::CONTENT_TYPE, content_type)
+                    .status(status)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body));
+            };
+            info!(?color_scheme, "Switching color scheme, as requested.");
+            set_color_scheme(color_scheme);
+            if let Some(value) = color_scheme.to_possible_value() {
+                publish_status(Event::ColorSchemeChanged {
+                    color_scheme: value.get_name().to_string(),
+                });
+            }
+            response_builder
+                .status(StatusCode::NO_CONTENT)
+                .body(Either::Left("".into()))
+        }
+        (&Method::GET, "event-stream/") => {
+            // Sent by browsers when they reconnect.
+            let last_event_id = req
+                .headers()
+                .get(LAST_EVENT_ID)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok());
+            response_builder
+                .header(
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static(TEXT_EVENT_STREAM),
+                )
+                .body(Either::Right(event_stream(last_event_id)))
+        }
+        (&Method::OPTIONS, _) => options_response(
+            req.headers(),
+            response_builder,
+            status_allow(uri_path),
+            false,
+        ),
         (&Method::GET, _) => {
             warn!(
                 uri_path,
@@ -730,6 +3704,7 @@ async fn request_handler_status(
             response_builder
                 .header(header::CONTENT_TYPE, content_type)
                 .status(status)
+                .extension(ErrorResponse)
                 .body(Either::Left(body))
         }
         _ => {
@@ -738,18 +3713,556 @@ async fn request_handler_status(
                 "Status server got request with unexpected request method. Returning 405."
             );
             let (status, content_type, body) = method_not_allowed();
+            response_builder
+                .header(
+                    header::ALLOW,
+                    HeaderValue::from_static(status_allow(uri_path)),
+                )
+                .header(header::CONTENT_TYPE, content_type)
+                .status(status)
+                .extension(ErrorResponse)
+                .body(Either::Left(body))
+        }
+    }
+}
+
+/// Largest number of events returned at once by the status API.
+const MAX_EVENTS_PAGE_LEN: usize = 1_000;
+
+/// Number of minutes that change statistics are computed over by default.
+const STATS_WINDOW_MINUTES: u64 = 60;
+
+/// Number of most often changed files listed in change statistics by default.
+const STATS_HOT_FILES_LEN: usize = 20;
+
+/// Largest number of most often changed files listed in change statistics.
+const MAX_STATS_HOT_FILES_LEN: usize = 1_000;
+
+/// Addresses that the servers ended up listening on, and when they started.
+#[derive(Debug)]
+struct ServerInfo {
+    status_url: String,
+    project_url: String,
+    started: Instant,
+    /// Milliseconds since the Unix epoch.
+    started_ms: u64,
+}
+
+/// Respond with a value as JSON.
+fn json_response<B>(
+    response_builder: ResponseBuilder,
+    value: &impl Serialize,
+) -> HttpResult<Response<Either<Full<Bytes>, B>>> {
+    match serde_json::to_vec(value) {
+        Ok(body) => response_builder
+            .header(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(APPLICATION_JSON),
+            )
+            .body(Either::Left(body.into())),
+        Err(e) => {
+            error!(err = ?e, "Failed to serialize JSON response.");
+            let (status, content_type, body) = server_error();
             response_builder
                 .header(header::CONTENT_TYPE, content_type)
                 .status(status)
+                .extension(ErrorResponse)
                 .body(Either::Left(body))
         }
     }
 }
 
+/// Largest body accepted for a request to reload pages, in bytes.
+const MAX_RELOAD_REQUEST_LEN: usize = 64 * 1024;
+
+/// Request to reload the pages open in browsers, as posted to the status server.
+#[derive(Debug, Default, Deserialize)]
+struct ReloadRequest {
+    /// URL paths of the pages to reload. Every page is reloaded if there are none.
+    #[serde(default)]
+    pages: Vec<String>,
+}
+
+/// Largest body accepted for a request to switch color scheme, in bytes.
+const MAX_COLOR_SCHEME_REQUEST_LEN: usize = 1024;
+
+/// Largest body accepted for a request to change the runtime exclusion patterns, in bytes.
+const MAX_EXCLUSIONS_REQUEST_LEN: usize = 64 * 1024;
+
+/// Request to add and remove runtime exclusion patterns, as posted to the status server.
+/// See [`http_horse::fs::ignore_file`].
+#[derive(Debug, Deserialize)]
+struct ExclusionsRequest {
+    #[serde(default)]
+    add: Vec<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+/// Request to switch the color scheme of the status web-ui, as posted to the status server.
+#[derive(Debug, Deserialize)]
+struct ColorSchemeRequest {
+    color_scheme: ColorScheme,
+}
+
+/// Bring the project dir trees back up to date after watching was paused.
+fn resume_watching() {
+    let Some(project_roots) = PROJECT_ROOTS.get() else {
+        return;
+    };
+    for root in project_roots {
+        root.load_rules();
+        load_ignore_file(&root.dir);
+        if respect_gitignore() {
+            load_gitignores(&root.dir)
+                .inspect_err(|e| error!(err = ?e, "Failed to reload .gitignore files."))
+                .ok();
+        }
+        if let Some(tree) = root.tree.get() {
+            tree.request_rescan(RescanRequest::Quiet);
+        }
+    }
+}
+
+/// The path, as it is on disk, of what is tracked at a path in a project directory,
+/// going by the path in either Unicode normalization form, and in any case
+/// on case-insensitive file systems.
+async fn tracked_path(project_root: &ProjectRoot, path: &Path) -> Option<PathBuf> {
+    let tree = project_root.tree.get()?.read().await;
+    tree.index.get(path).map(|entry| entry.path().to_path_buf())
+}
+
+/// Find the URL path that a file or directory in one of the project directories is served at.
+fn url_path_for(path: &Path) -> Option<String> {
+    let project_roots = PROJECT_ROOTS.get()?;
+    let (project_root, rel_path) = project_roots
+        .iter()
+        .filter_map(|root| Some((root, path.strip_prefix(&root.dir).ok()?)))
+        .min_by_key(|(_, rel_path)| rel_path.components().count())?;
+    let mut url_path = project_root.mount_url_path();
+    let rel_path = rel_path
+        .iter()
+        .map(|name| encode_path_segment(&name.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join("/");
+    url_path.push_str(&rel_path);
+    Some(url_path)
+}
+
+/// Value of a query parameter of a request URI, percent-decoded.
+fn query_param(uri: &hyper::Uri, name: &str) -> Option<String> {
+    uri.query()?
+        .split('&')
+        .find_map(|param| param.strip_prefix(name)?.strip_prefix('='))
+        .and_then(decode_path)
+}
+
+/// What a page served by the project server is sent over its event stream.
+enum PageEvent {
+    Reload(Event),
+    Errors(Vec<ErrorReport>),
+    Sync(SyncEvent),
+}
+
+/// Stream reload events to a page served by the project server.
+/// If the URL path of the page is given, only changes that affect the page are streamed.
+/// The client stays registered as connected for as long as the stream is.
+fn reload_event_stream(
+    page: Option<String>,
+    registration: Option<Registration>,
+) -> BoxBody<Bytes, std::io::Error> {
+    let reload_rx = RELOAD_CHANNEL.subscribe();
+    let errors_rx = ERROR_REPORTS.subscribe();
+    let sync_rx = http_horse::sync::subscribe();
+    let stream = stream! {
+        let _registration = registration;
+        let _stream = EVENT_STREAMS.enter();
+        // Errors shown on the page, so that it is only sent changes to those.
+        let mut shown_errors = vec![];
+        let mut changed_errors = Some(ERROR_REPORTS.current());
+        loop {
+            if let Some(mut errors) = changed_errors.take() {
+                errors.retain(|report| report.is_for_page(page.as_deref()));
+                if errors != shown_errors {
+                    let data = serde_json::to_string(&errors).map_err(std::io::Error::other)?;
+                    yield Ok(Bytes::from(format!("event: {ERROR_OVERLAY_EVENT}\ndata: {data}\n\n")));
+                    shown_errors = errors;
+                }
+            }
+            let event = match smol::future::or(
+                async { reload_rx.recv().await.map(PageEvent::Reload) },
+                smol::future::or(
+                    async { errors_rx.recv().await.map(PageEvent::Errors) },
+                    async { sync_rx.recv().await.map(PageEvent::Sync) },
+                ),
+            )
+            .await
+            {
+                Ok(PageEvent::Reload(event)) => event,
+                Ok(PageEvent::Errors(errors)) => {
+                    changed_errors = Some(errors);
+                    continue;
+                }
+                Ok(PageEvent::Sync(event)) => {
+                    // Scrolls and clicks only make sense on the same page. Navigation is mirrored everywhere.
+                    let same_page = page.as_ref().is_none_or(|page| *page == event.page);
+                    if same_page || matches!(event.action, SyncAction::Navigate { .. }) {
+                        let data = serde_json::to_string(&event).map_err(std::io::Error::other)?;
+                        yield Ok(Bytes::from(format!("event: {SYNC_EVENT}\ndata: {data}\n\n")));
+                    }
+                    continue;
+                }
+                Err(_) => break,
+            };
+            if let Some(page) = &page {
+                // Requested reloads are for every page, or for one page in particular.
+                let affected = match &event {
+                    // Requested reloads are for every page, or for one page in particular.
+                    Event::ReloadRequested { page: requested } => {
+                        requested.as_ref().is_none_or(|requested| requested == page)
+                    }
+                    _ => {
+                        event.is_dir()
+                            || event
+                                .paths()
+                                .any(|url_path| DEPENDENCY_GRAPH.affects(page, url_path))
+                    }
+                };
+                if !affected {
+                    trace!(page, ?event, "Change does not affect page. Not sending it.");
+                    continue;
+                }
+            }
+            let data = serde_json::to_string(&event).map_err(std::io::Error::other)?;
+            let message = match event_name(&event) {
+                Some(name) => format!("event: {name}\ndata: {data}\n\n"),
+                None => format!("data: {data}\n\n"),
+            };
+            yield Ok(Bytes::from(message));
+        }
+    };
+    let stream_body = StreamBody::new(with_keep_alive(stream).map_ok(Frame::data));
+    BodyExt::boxed(stream_body)
+}
+
+/// Response from the project server. Either a full body, or a streamed body.
+type ProjectResponse = Response<Either<Full<Bytes>, BoxBody<Bytes, std::io::Error>>>;
+
+/// Handle a request, writing a line about it to the access log, if there is one,
+/// once the response body has been sent. Requests to the project server are also
+/// sent to the request log of the status web-ui, if `is_project` is set.
+async fn access_logged<F, Fut, B>(
+    is_project: bool,
+    peer_addr: SocketAddr,
+    req: Request<Incoming>,
+    handler: F,
+) -> HttpResult<Response<LoggedBody<B>>>
+where
+    F: FnOnce(Request<Incoming>) -> Fut,
+    Fut: Future<Output = HttpResult<Response<B>>>,
+{
+    let access_log = ACCESS_LOG.get();
+    let publish = is_project && is_shown_in_request_log(req.method(), req.uri());
+    let entry = (access_log.is_some() || publish).then(|| {
+        AccessLogEntry::for_request(
+            peer_addr.ip(),
+            req.method(),
+            req.uri(),
+            req.version(),
+            req.headers(),
+        )
+    });
+    let resp = handler(req).await?;
+    let status = resp.status().as_u16();
+    let entry = entry.map(|entry| AccessLogEntry { status, ..entry });
+    Ok(resp.map(|body| LoggedBody::new(body, entry, access_log, publish)))
+}
+
+/// Whether a request to the project server is shown in the request log of the status web-ui.
+/// The event streams of pages and what the client script posts are left out, since they are
+/// there for every page, and would drown out what the pages themselves fetched.
+fn is_shown_in_request_log(method: &Method, uri: &hyper::Uri) -> bool {
+    match uri
+        .path()
+        .strip_prefix('/')
+        .and_then(|path| path.strip_prefix(RESERVED_PATH_PREFIX))
+    {
+        Some(reserved_path) => {
+            method == Method::GET && reserved_path != "events" && status_path(uri.path()).is_none()
+        }
+        None => true,
+    }
+}
+
+/// What a URL path of the project server is within [`STATUS_ROOT_PATH`], with its leading slash,
+/// or an empty string for the status root path without its trailing slash.
+/// `None` for paths outside of it.
+fn status_path(uri_path: &str) -> Option<&str> {
+    let status_path = uri_path.strip_prefix(STATUS_ROOT_PATH.trim_end_matches('/'))?;
+    (status_path.is_empty() || status_path.starts_with('/')).then_some(status_path)
+}
+
+/// Handle a request for a status page made to the project server, with `--single-port`,
+/// as the status server would have handled it.
+async fn request_handler_status_in_project(
+    req: Request<Incoming>,
+    status_path: String,
+) -> HttpResult<ProjectResponse> {
+    let query = req.uri().query().map(|query| format!("?{query}"));
+    let query = query.unwrap_or_default();
+    // Status pages refer to what they use by relative URLs, which need the trailing slash.
+    if status_path.is_empty() {
+        return Response::builder()
+            .status(StatusCode::PERMANENT_REDIRECT)
+            .header(header::LOCATION, format!("{STATUS_ROOT_PATH}{query}"))
+            .header(
+                header::CACHE_CONTROL,
+                HeaderValue::from_static(CACHE_CONTROL_VALUE_NO_STORE),
+            )
+            .body(Either::Left(Full::default()));
+    }
+    let (mut parts, body) = req.into_parts();
+    parts.uri = match hyper::Uri::try_from(format!("{status_path}{query}")) {
+        Ok(uri) => uri,
+        Err(e) => {
+            debug!(err = ?e, status_path, "Failed to make status server URI. Returning 400.");
+            let (status, content_type, body) = bad_request();
+            return Response::builder()
+                .header(header::CONTENT_TYPE, content_type)
+                .status(status)
+                .extension(ErrorResponse)
+                .body(Either::Left(body));
+        }
+    };
+    let resp = request_handler_status(Request::from_parts(parts, body)).await?;
+    Ok(resp.map(|body| match body {
+        Either::Left(body) => Either::Left(body),
+        Either::Right(body) => Either::Right(body.map_err(std::io::Error::other).boxed()),
+    }))
+}
+
 async fn request_handler_project(
+    peer_addr: SocketAddr,
+    req: Request<Incoming>,
+) -> HttpResult<ProjectResponse> {
+    if single_port() {
+        if let Some(status_path) = status_path(req.uri().path()) {
+            let status_path = status_path.to_string();
+            return request_handler_status_in_project(req, status_path).await;
+        }
+    }
+    let is_head = req.method() == Method::HEAD;
+    let req_headers = req.headers().clone();
+    if SERVING_PAUSED.load(Ordering::SeqCst) {
+        let resp = negotiated_error(&req_headers, serving_paused()?).await;
+        return Ok(if is_head { without_body(resp) } else { resp });
+    }
+    if !is_allowed_host(&req) {
+        return Ok(negotiated_error(&req_headers, forbidden_host()?).await);
+    }
+    if !is_authorized(Server::Project, req.headers()) {
+        return Ok(negotiated_error(&req_headers, unauthorized()?).await);
+    }
+    let uri_path = req.uri().path().to_string();
+    let host = request_host(&req);
+    if let Some(resp) = mocked(req.method(), &uri_path).await {
+        let resp = negotiated_error(&req_headers, resp?).await;
+        let resp = if is_head { without_body(resp) } else { resp };
+        return Ok(throttled(resp, network_conditions(&uri_path)).await);
+    }
+    if let Some(rule) = proxy_rule_for(&uri_path) {
+        let resp = negotiated_error(&req_headers, proxied(rule, peer_addr, req).await?).await;
+        return Ok(throttled(resp, network_conditions(&uri_path)).await);
+    }
+    let mut resp = handle_project_request(req).await?;
+    if resp.status() == StatusCode::NOT_FOUND {
+        resp = with_not_found_page(host.as_deref(), &uri_path, resp).await;
+    }
+    let resp = negotiated_error(&req_headers, resp).await;
+    let resp = if is_head { without_body(resp) } else { resp };
+    Ok(throttled(resp, network_conditions(&uri_path)).await)
+}
+
+/// The host name that a request is for, from the `Host` header, or the URI for HTTP/2 requests.
+fn request_host<B>(req: &Request<B>) -> Option<String> {
+    req.headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| req.uri().host())
+        .map(host_name)
+}
+
+/// Answer a request with a mock response, if there is a fixture for it.
+async fn mocked(method: &Method, uri_path: &str) -> Option<HttpResult<ProjectResponse>> {
+    let mocks = MOCKS.get()?;
+    let uri_path = uri_path.trim_start_matches('/');
+    if uri_path.starts_with(RESERVED_PATH_PREFIX) {
+        return None;
+    }
+    let url_path = decode_path(uri_path)?;
+    let mock = match mocks.find(method, &url_path).await {
+        Ok(mock) => mock?,
+        Err(e) => {
+            error!(err = ?e, url_path, "Failed to read mock response. Returning 500.");
+            let (status, content_type, body) = server_error();
+            return Some(
+                Response::builder()
+                    .header(header::CONTENT_TYPE, content_type)
+                    .status(status)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body)),
+            );
+        }
+    };
+    debug!(fixture = ?mock.fixture, url_path, "Serving mock response.");
+    let mut response_builder = Response::builder()
+        .status(mock.status)
+        .header(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(mime_type_for_path(&mock.fixture)),
+        )
+        .header(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(CACHE_CONTROL_VALUE_NO_STORE),
+        );
+    if let Some(headers) = response_builder.headers_mut() {
+        // Headers from the fixture replace our defaults, and may be repeated.
+        for (name, _) in &mock.headers {
+            headers.remove(name);
+        }
+        for (name, value) in mock.headers {
+            headers.append(name, value);
+        }
+    }
+    Some(response_builder.body(Either::Left(mock.body.into())))
+}
+
+/// The first proxy rule that applies to a request for the given URL path, if any.
+fn proxy_rule_for(uri_path: &str) -> Option<&'static ProxyRule> {
+    // Live reload is always served by us.
+    if uri_path
+        .trim_start_matches('/')
+        .starts_with(RESERVED_PATH_PREFIX)
+    {
+        return None;
+    }
+    PROXY_RULES
+        .get()?
+        .iter()
+        .find(|rule| rule.matches(uri_path))
+}
+
+/// Forward a request to a backend server, answering with 502 if that fails.
+async fn proxied(
+    rule: &ProxyRule,
+    peer_addr: SocketAddr,
     req: Request<Incoming>,
-) -> HttpResult<Response<Either<Full<Bytes>, BoxBody<Bytes, std::io::Error>>>> {
-    let (method, uri_path) = (req.method(), req.uri().path());
+) -> HttpResult<ProjectResponse> {
+    let (method, uri) = (req.method().clone(), req.uri().clone());
+    match rule.forward(req, peer_addr.ip()).await {
+        Ok(resp) => {
+            let url_path = decode_path(uri.path().trim_start_matches('/'));
+            let resp = match (RECORDER.get(), url_path) {
+                (Some(recorder), Some(url_path)) => recorder.record(&method, &url_path, resp),
+                _ => resp,
+            };
+            Ok(resp.map(Either::Right))
+        }
+        Err(e) => {
+            warn!(err = ?e, %uri, "Failed to proxy request. Returning 502.");
+            Response::builder()
+                .status(StatusCode::BAD_GATEWAY)
+                .extension(ErrorResponse)
+                .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_PLAIN))
+                .header(
+                    header::CACHE_CONTROL,
+                    HeaderValue::from_static(CACHE_CONTROL_VALUE_NO_STORE),
+                )
+                .body(Either::Left(BAD_GATEWAY_BODY_TEXT.into()))
+        }
+    }
+}
+
+/// Network conditions to simulate for a request for the given URL path.
+fn network_conditions(uri_path: &str) -> NetworkConditions {
+    let uri_path = uri_path.trim_start_matches('/');
+    // Keep live reload snappy, whatever the conditions.
+    if uri_path.starts_with(RESERVED_PATH_PREFIX) {
+        return NetworkConditions::default();
+    }
+    let url_path = format!(
+        "/{}",
+        decode_path(uri_path).unwrap_or_else(|| uri_path.to_string())
+    );
+    THROTTLE_RULES
+        .get()
+        .and_then(|rules| rules.iter().find(|rule| rule.matches(&url_path)))
+        .map(|rule| rule.conditions)
+        .or_else(|| NETWORK_CONDITIONS.get().copied())
+        .unwrap_or_default()
+}
+
+/// Delay a response, and rate-limit its body, according to the given network conditions.
+async fn throttled(resp: ProjectResponse, conditions: NetworkConditions) -> ProjectResponse {
+    if !conditions.latency.is_zero() {
+        Timer::after(conditions.latency).await;
+    }
+    let Some(bytes_per_sec) = conditions.bytes_per_sec else {
+        return resp;
+    };
+    resp.map(|body| {
+        let body = match body {
+            Either::Left(full) => full.map_err(|never| match never {}).boxed(),
+            Either::Right(body) => body,
+        };
+        Either::Right(throttle_body(body, bytes_per_sec))
+    })
+}
+
+/// Replace the body of a 404 response with the not found page of the project directory
+/// that the request was for, if it has one. The 404 status is kept.
+async fn with_not_found_page(
+    host: Option<&str>,
+    uri_path: &str,
+    resp: ProjectResponse,
+) -> ProjectResponse {
+    let uri_path = uri_path.trim_start_matches('/');
+    let uri_path = decode_path(uri_path).unwrap_or_else(|| uri_path.to_string());
+    let (Some(project_roots), Some(not_found_page)) = (PROJECT_ROOTS.get(), NOT_FOUND_PAGE.get())
+    else {
+        return resp;
+    };
+    let Some((project_root, _)) = resolve(project_roots, host, &uri_path) else {
+        return resp;
+    };
+    let fpath = project_root.dir.join(not_found_page);
+    let mut contents = match smol::fs::read(&fpath).await {
+        Ok(contents) => contents,
+        Err(e) => {
+            if e.kind() != ErrorKind::NotFound {
+                warn!(err = ?e, ?fpath, "Failed to read not found page.");
+            }
+            return resp;
+        }
+    };
+    let content_type = mime_type_for_path(&fpath);
+    if content_type.starts_with(TEXT_HTML) {
+        // Not found pages get every change, as the requested page could appear at any time.
+        inject_client_script(&mut contents, None);
+    }
+    debug!(?fpath, "Serving not found page of project directory.");
+    let (mut parts, _) = resp.into_parts();
+    parts.extensions.remove::<ErrorResponse>();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::CONTENT_ENCODING);
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    Response::from_parts(parts, Either::Left(contents.into()))
+}
+
+async fn handle_project_request(req: Request<Incoming>) -> HttpResult<ProjectResponse> {
+    let (method, uri_path) = (get_for_head(req.method()), req.uri().path());
     let uri_path_trimmed = uri_path.trim_start_matches('/');
     debug!(
         ?method,
@@ -761,24 +4274,278 @@ async fn request_handler_project(
     //      It is therefore essential that we only use the path that has leading slashes stripped.
     let uri_path = uri_path_trimmed;
 
+    let cache_mode = CACHE_MODE.get().copied().unwrap_or(CacheMode::NoStore);
     let response_builder = Response::builder().header(
         header::CACHE_CONTROL,
-        HeaderValue::from_static(CACHE_CONTROL_VALUE_NO_STORE),
+        HeaderValue::from_static(cache_mode.cache_control_value()),
     );
 
-    let Some(project_dir) = PROJECT_DIR.get() else {
+    let Some(uri_path_decoded) = decode_path(uri_path) else {
+        warn!(
+            uri_path,
+            "Project server got request with malformed uri path. Returning 404."
+        );
+        let (status, content_type, body) = not_found();
+        return response_builder
+            .header(header::CONTENT_TYPE, content_type)
+            .status(status)
+            .extension(ErrorResponse)
+            .body(Either::Left(body));
+    };
+    let uri_path_trimmed = uri_path_decoded.as_str();
+    let uri_path = uri_path_trimmed;
+
+    if method == Method::OPTIONS {
+        return options_response(req.headers(), response_builder, ALLOW_GET, true);
+    }
+
+    if let Some(reserved_path) = uri_path.strip_prefix(RESERVED_PATH_PREFIX) {
+        return match (method, reserved_path) {
+            (&Method::GET, "client.js") => {
+                let body = Bytes::from_static(RELOAD_CLIENT_JAVASCRIPT);
+                full_body(req.headers(), response_builder, TEXT_JAVASCRIPT, body).await
+            }
+            (&Method::GET, "hmr.js") => {
+                let body = Bytes::from_static(HMR_RUNTIME_JAVASCRIPT);
+                full_body(req.headers(), response_builder, TEXT_JAVASCRIPT, body).await
+            }
+            (&Method::GET, "events") => {
+                let page = query_param(req.uri(), "page");
+                // Clients are listed by the page that they are on, which is the page that opened the event stream.
+                let registration = query_param(req.uri(), "client").map(|client| {
+                    let header_value = |name| {
+                        req.headers()
+                            .get(name)
+                            .and_then(|value| value.to_str().ok())
+                            .map(str::to_string)
+                    };
+                    let url = header_value(header::REFERER)
+                        .or_else(|| page.clone())
+                        .unwrap_or_default();
+                    PRESENCE.connect(client, url, header_value(header::USER_AGENT))
+                });
+                response_builder
+                    .header(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static(TEXT_EVENT_STREAM),
+                    )
+                    .body(Either::Right(reload_event_stream(page, registration)))
+            }
+            (&Method::POST, HEARTBEAT_URL_PATH) => {
+                let body = http_body_util::Limited::new(req.into_body(), MAX_HEARTBEAT_LEN)
+                    .collect()
+                    .await
+                    .map(|body| body.to_bytes());
+                let heartbeat = match body {
+                    Ok(body) => serde_json::from_slice::<Heartbeat>(&body).ok(),
+                    Err(e) => {
+                        debug!(err = ?e, "Failed to read heartbeat.");
+                        None
+                    }
+                };
+                let Some(heartbeat) = heartbeat else {
+                    let (status, content_type, body) = bad_request();
+                    return response_builder
+                        .header(header::CONTENT_TYPE, content_type)
+                        .status(status)
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body));
+                };
+                if !PRESENCE.heartbeat(heartbeat) {
+                    // The event stream of the client has not connected yet, or has been closed.
+                    trace!("Heartbeat from client that is not connected.");
+                }
+                response_builder
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Either::Left(Full::default()))
+            }
+            (&Method::POST, "client-errors") => {
+                let body = http_body_util::Limited::new(req.into_body(), MAX_REPORT_LEN)
+                    .collect()
+                    .await
+                    .map(|body| body.to_bytes());
+                let error = match body {
+                    Ok(body) => serde_json::from_slice::<ClientError>(&body).ok(),
+                    Err(e) => {
+                        debug!(err = ?e, "Failed to read client error report.");
+                        None
+                    }
+                };
+                let Some(error) = error else {
+                    let (status, content_type, body) = bad_request();
+                    return response_builder
+                        .header(header::CONTENT_TYPE, content_type)
+                        .status(status)
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body));
+                };
+                info!(
+                    kind = ?error.kind,
+                    page = error.page,
+                    message = error.message,
+                    "Error in page open in browser."
+                );
+                CLIENT_ERRORS.record(error);
+                response_builder
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Either::Left(Full::default()))
+            }
+            (&Method::POST, OPEN_IN_EDITOR_URL_PATH) if http_horse::editor::is_enabled() => {
+                let body = http_body_util::Limited::new(req.into_body(), MAX_OPEN_IN_EDITOR_LEN)
+                    .collect()
+                    .await
+                    .map(|body| body.to_bytes());
+                let request = match body {
+                    Ok(body) => serde_json::from_slice::<OpenRequest>(&body).ok(),
+                    Err(e) => {
+                        debug!(err = ?e, "Failed to read request to open file in editor.");
+                        None
+                    }
+                };
+                let Some(request) = request else {
+                    let (status, content_type, body) = bad_request();
+                    return response_builder
+                        .header(header::CONTENT_TYPE, content_type)
+                        .status(status)
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body));
+                };
+                // Only files with errors in them can be opened, and others are as good as not there.
+                if !ERROR_REPORTS.has_diagnostic_in(&request.file) {
+                    warn!(file = ?request.file, "Request to open file without errors in editor. Returning 404.");
+                    let (status, content_type, body) = not_found();
+                    return response_builder
+                        .header(header::CONTENT_TYPE, content_type)
+                        .status(status)
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body));
+                }
+                if let Err(e) = http_horse::editor::open(&request) {
+                    error!(err = ?e, ?request, "Failed to open file in editor.");
+                    let (status, content_type, body) = server_error();
+                    return response_builder
+                        .header(header::CONTENT_TYPE, content_type)
+                        .status(status)
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body));
+                }
+                response_builder
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Either::Left(Full::default()))
+            }
+            (&Method::POST, SYNC_URL_PATH) if http_horse::sync::is_enabled() => {
+                let body = http_body_util::Limited::new(req.into_body(), MAX_SYNC_EVENT_LEN)
+                    .collect()
+                    .await
+                    .map(|body| body.to_bytes());
+                let event = match body {
+                    Ok(body) => serde_json::from_slice::<SyncEvent>(&body).ok(),
+                    Err(e) => {
+                        debug!(err = ?e, "Failed to read sync event.");
+                        None
+                    }
+                };
+                let Some(event) = event.filter(SyncEvent::is_valid) else {
+                    let (status, content_type, body) = bad_request();
+                    return response_builder
+                        .header(header::CONTENT_TYPE, content_type)
+                        .status(status)
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body));
+                };
+                trace!(?event, "Mirroring event to other clients.");
+                SYNC_CHANNEL.send(event);
+                response_builder
+                    .status(StatusCode::NO_CONTENT)
+                    .body(Either::Left(Full::default()))
+            }
+            _ => {
+                warn!(
+                    uri_path,
+                    "Project server got request for unknown reserved path. Returning 404."
+                );
+                let (status, content_type, body) = not_found();
+                response_builder
+                    .header(header::CONTENT_TYPE, content_type)
+                    .status(status)
+                    .extension(ErrorResponse)
+                    .body(Either::Left(body))
+            }
+        };
+    }
+
+    let Some(project_roots) = PROJECT_ROOTS.get() else {
         let (status, content_type, body) = server_error();
         let resp = response_builder
             .header(header::CONTENT_TYPE, content_type)
             .status(status)
+            .extension(ErrorResponse)
             .body(Either::Left(body));
         return resp;
     };
 
+    let rewritten_uri_path;
+    let host = request_host(&req);
+    let uri_path_trimmed = match apply_redirect_rules(project_roots, host.as_deref(), uri_path) {
+        None => uri_path_trimmed,
+        Some(RuleOutcome::Redirect { location, status }) => {
+            let location = match req.uri().query() {
+                Some(query) if !location.contains('?') => format!("{location}?{query}"),
+                _ => location,
+            };
+            let status = StatusCode::from_u16(status).unwrap_or(StatusCode::MOVED_PERMANENTLY);
+            info!(uri_path, location, %status, "Redirecting according to redirect rule.");
+            return response_builder
+                .header(header::LOCATION, encode_unsafe_chars(&location))
+                .status(status)
+                .body(Either::Left(Full::default()));
+        }
+        Some(RuleOutcome::Rewrite(target)) => {
+            info!(uri_path, target, "Rewriting according to redirect rule.");
+            rewritten_uri_path = target;
+            let target_path = rewritten_uri_path.split('?').next().unwrap_or_default();
+            target_path.trim_start_matches('/')
+        }
+    };
+    let uri_path = uri_path_trimmed;
+
+    let Some((project_root, uri_path)) = resolve(project_roots, host.as_deref(), uri_path) else {
+        warn!(
+            uri_path,
+            "Project server got request for path outside of any mount point. Returning 404."
+        );
+        let (status, content_type, body) = not_found();
+        return response_builder
+            .header(header::CONTENT_TYPE, content_type)
+            .status(status)
+            .extension(ErrorResponse)
+            .body(Either::Left(body));
+    };
+    let project_dir = &project_root.dir;
+
+    // Requests for a mount point without trailing slash are redirected, so that relative links work.
+    if uri_path.is_empty() && !project_root.mount.is_empty() && !uri_path_trimmed.ends_with('/') {
+        return response_builder
+            .header(header::LOCATION, project_root.mount_url_path())
+            .status(StatusCode::MOVED_PERMANENTLY)
+            .body(Either::Left(Full::default()));
+    }
+
+    let response_builder =
+        with_header_rules(project_root, uri_path_trimmed, uri_path, response_builder);
+
     match (method, uri_path) {
         (&Method::GET, _) => {
             if uri_path.is_empty() {
-                handle_dir_request(project_dir, response_builder).await
+                let dir_url_path = project_root.mount_url_path();
+                handle_dir_request(
+                    project_root,
+                    req.headers(),
+                    project_dir,
+                    &dir_url_path,
+                    response_builder,
+                )
+                .await
             } else {
                 let uri_path = uri_path.trim_start_matches('/');
                 let req_path = Path::join(project_dir.as_ref(), uri_path);
@@ -797,7 +4564,18 @@ async fn request_handler_project(
                     );
                 }
 
-                let Ok(req_path) = req_path.canonicalize().inspect_err(|e| match e.kind() {
+                // The file name may be in another Unicode normalization form on disk than in
+                // the request, which only the file systems of macOS see past by themselves.
+                let canonical = match req_path.canonicalize() {
+                    Err(e) if e.kind() == ErrorKind::NotFound => {
+                        match tracked_path(project_root, &req_path).await {
+                            Some(tracked_path) => tracked_path.canonicalize(),
+                            None => Err(e),
+                        }
+                    }
+                    res => res,
+                };
+                let Ok(req_path) = canonical.inspect_err(|e| match e.kind() {
                     ErrorKind::NotFound => {
                         // Note: We explicitly log that we did not find file, because we actually went looking for it.
                         warn!(err = ?e, uri_path, ?req_path, "File not found on file system.");
@@ -817,9 +4595,33 @@ async fn request_handler_project(
                     return response_builder
                         .header(header::CONTENT_TYPE, content_type)
                         .status(status)
+                        .extension(ErrorResponse)
                         .body(Either::Left(body));
                 };
 
+                // On case-insensitive file systems, the canonical path may have names in the case
+                // of the request rather than as they are on disk, which would get past the checks
+                // below that go by name. So we go on with the path as it is on disk.
+                let req_path = if project_root.case_insensitive {
+                    match tracked_path(project_root, &req_path).await {
+                        Some(tracked_path) => tracked_path,
+                        None => match on_disk_path(project_dir, &req_path) {
+                            Ok(on_disk) => on_disk,
+                            Err(e) => {
+                                error!(err = ?e, uri_path, ?req_path, "Unexpected I/O error");
+                                let (status, content_type, body) = not_found();
+                                return response_builder
+                                    .header(header::CONTENT_TYPE, content_type)
+                                    .status(status)
+                                    .extension(ErrorResponse)
+                                    .body(Either::Left(body));
+                            }
+                        },
+                    }
+                } else {
+                    req_path
+                };
+
                 // We disallow traversing up above the project dir.
                 //
                 // Sidenote: Well-behaved user-agents like Firefox or curl
@@ -851,19 +4653,56 @@ async fn request_handler_project(
                     return response_builder
                         .header(header::CONTENT_TYPE, content_type)
                         .status(status)
+                        .extension(ErrorResponse)
                         .body(Either::Left(body));
                 }
                 let req_path_checked = req_path;
 
-                if req_path_checked.is_dir() {
-                    handle_dir_request(req_path_checked, response_builder).await
-                } else {
-                    // TODO: Look for the file
+                if is_excluded(project_dir, &req_path_checked, req_path_checked.is_dir())
+                    || [HEADERS_FILE_NAME, REDIRECTS_FILE_NAME]
+                        .iter()
+                        .any(|name| req_path_checked == project_dir.join(name))
+                {
+                    warn!(
+                        uri_path,
+                        ?req_path_checked,
+                        "Client requested excluded path. Returning 404."
+                    );
                     let (status, content_type, body) = not_found();
-                    response_builder
+                    return response_builder
                         .header(header::CONTENT_TYPE, content_type)
                         .status(status)
-                        .body(Either::Left(body))
+                        .extension(ErrorResponse)
+                        .body(Either::Left(body));
+                }
+
+                if req_path_checked.is_dir() {
+                    // Requests for a directory without trailing slash are redirected,
+                    // so that relative links in the index file work.
+                    if !uri_path.ends_with('/') {
+                        return response_builder
+                            .header(header::LOCATION, format!("{}/", req.uri().path()))
+                            .status(StatusCode::MOVED_PERMANENTLY)
+                            .body(Either::Left(Full::default()));
+                    }
+                    // As requested, percent-encoded, like the paths in reload events are.
+                    let dir_url_path = req.uri().path();
+                    handle_dir_request(
+                        project_root,
+                        req.headers(),
+                        req_path_checked,
+                        dir_url_path,
+                        response_builder,
+                    )
+                    .await
+                } else {
+                    handle_file_request(
+                        project_root,
+                        req.headers(),
+                        req_path_checked,
+                        response_builder,
+                    )
+                    .await
                 }
             }
         }
@@ -874,8 +4713,10 @@ async fn request_handler_project(
             );
             let (status, content_type, body) = method_not_allowed();
             response_builder
+                .header(header::ALLOW, HeaderValue::from_static(ALLOW_GET))
                 .header(header::CONTENT_TYPE, content_type)
                 .status(status)
+                .extension(ErrorResponse)
                 .body(Either::Left(body))
         }
     }
@@ -883,41 +4724,825 @@ async fn request_handler_project(
 
 /// Handle a dir request.
 ///
+/// Serves the first index file that exists in the directory,
+/// or a listing of the directory when there is no index file.
+///
 /// Security note: It is the responsibility of the *caller* to ensure
 /// that the requested directory is not outside the intended path.
 /// (I.e. caller has to be careful about requests like `GET /foo/../../../bar/`, etc.)
 async fn handle_dir_request<P: AsRef<Path>>(
+    project_root: &ProjectRoot,
+    req_headers: &HeaderMap,
     req_path_checked: P,
+    dir_url_path: &str,
     response_builder: ResponseBuilder,
-) -> HttpResult<Response<Either<Full<Bytes>, BoxBody<Bytes, std::io::Error>>>> {
-    // TODO: How to stream file with hyper, now that we use smol instead of tokio?
-    /*
-    // 1. Try file "index.htm"
-    if let Ok(file) = File::open(req_path_checked.as_ref().join("index.htm")).await {
-        // Based on <https://github.com/hyperium/hyper/blob/4c84e8c1c26a1464221de96b9f39816ce7251a5f/examples/send_file.rs#L81C1-L82C42>
-        let reader_stream = ReaderStream::new(file);
-        let stream_body = StreamBody::new(reader_stream.map_ok(Frame::data));
-        let boxed_body = BodyExt::boxed(stream_body);
-        return response_builder.body(Either::Right(boxed_body));
+) -> HttpResult<ProjectResponse> {
+    let project_dir = &project_root.dir;
+    let req_path_checked = req_path_checked.as_ref();
+    let index_files = INDEX_FILES.get().map(Vec::as_slice).unwrap_or_default();
+    for index_file in index_files {
+        let index_fpath = req_path_checked.join(index_file);
+        // Symlinks are not served, same as they are not tracked.
+        let is_file = smol::fs::symlink_metadata(&index_fpath)
+            .await
+            .is_ok_and(|m| m.is_file());
+        if is_file && !is_excluded(project_dir, &index_fpath, false) {
+            return handle_file_request(project_root, req_headers, index_fpath, response_builder)
+                .await;
+        }
+    }
+
+    let entries = match list_dir(project_dir, req_path_checked).await {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!(err = ?e, ?req_path_checked, "Failed to list directory.");
+            let (status, content_type, body) = server_error();
+            return response_builder
+                .header(header::CONTENT_TYPE, content_type)
+                .status(status)
+                .extension(ErrorResponse)
+                .body(Either::Left(body));
+        }
+    };
+    let display_url_path = decode_path(dir_url_path);
+    let dir_listing = DirListing {
+        url_path: dir_url_path,
+        display_url_path: display_url_path.as_deref().unwrap_or(dir_url_path),
+        has_parent: req_path_checked != project_dir,
+        entries,
+        client_script_url_path: CLIENT_SCRIPT_URL_PATH,
+    };
+    match dir_listing.render() {
+        Ok(html) => response_builder
+            .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_HTML))
+            .body(Either::Left(html.into())),
+        Err(e) => {
+            error!(err = ?e, ?req_path_checked, "Failed to render directory listing.");
+            let (status, content_type, body) = server_error();
+            response_builder
+                .header(header::CONTENT_TYPE, content_type)
+                .status(status)
+                .extension(ErrorResponse)
+                .body(Either::Left(body))
+        }
+    }
+}
+
+/// List the entries of a directory that are served, directories first.
+async fn list_dir(project_dir: &Path, dpath: &Path) -> std::io::Result<Vec<DirListingEntry>> {
+    let mut entries = vec![];
+    let mut read_dir = smol::fs::read_dir(dpath).await?;
+    while let Some(dir_entry) = read_dir.next().await {
+        let dir_entry = dir_entry?;
+        let path = dir_entry.path();
+        let metadata = smol::fs::symlink_metadata(&path).await?;
+        // Symlinks are not served, same as they are not tracked.
+        if metadata.is_symlink() || is_excluded(project_dir, &path, metadata.is_dir()) {
+            continue;
+        }
+        let name = dir_entry.file_name().to_string_lossy().into_owned();
+        let mut href = encode_path_segment(&name);
+        if metadata.is_dir() {
+            href.push('/');
+        }
+        entries.push(DirListingEntry {
+            name,
+            href,
+            is_dir: metadata.is_dir(),
+            size: metadata.is_file().then(|| format_size(metadata.len())),
+            modified: metadata
+                .modified()
+                .map(httpdate::fmt_http_date)
+                .unwrap_or_default(),
+        });
+    }
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(entries)
+}
+
+/// Format a file size for humans.
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{size} B");
+    }
+    let mut size = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{size:.1} {unit}")
+}
+
+/// Handle a file request.
+///
+/// HTML files are read in full, so that the reload client script can be injected into them.
+/// Other files are streamed.
+///
+/// Security note: It is the responsibility of the *caller* to ensure
+/// that the requested file is not outside the intended path.
+async fn handle_file_request<P: AsRef<Path>>(
+    project_root: &ProjectRoot,
+    req_headers: &HeaderMap,
+    req_path_checked: P,
+    response_builder: ResponseBuilder,
+) -> HttpResult<ProjectResponse> {
+    let req_path_checked = req_path_checked.as_ref();
+    // Tracked files have their MIME type, size and class at hand. Others are looked up,
+    // going by their file name extension only.
+    let tracked = match project_root.tree.get() {
+        Some(tree) => tree
+            .read()
+            .await
+            .index
+            .file(req_path_checked)
+            .map(|tracked_file| {
+                let compressible =
+                    is_compressible_class(tracked_file.mime_type, tracked_file.class);
+                (tracked_file.mime_type, tracked_file.size, compressible)
+            }),
+        None => None,
+    };
+    let (content_type, len, compressible) = match tracked {
+        Some(tracked) => tracked,
+        None => match smol::fs::metadata(req_path_checked).await {
+            Ok(metadata) => {
+                let content_type = mime_type_for_path(req_path_checked);
+                (content_type, metadata.len(), is_compressible(content_type))
+            }
+            Err(e) => return file_read_error(e, req_path_checked, response_builder),
+        },
+    };
+    // HTML files are not served precompressed, as the reload client script has to be injected.
+    let precompressed = if content_type.starts_with(TEXT_HTML) {
+        None
+    } else {
+        find_precompressed(&project_root.dir, req_headers, req_path_checked).await
+    };
+    let (served_fpath, encoding) = match &precompressed {
+        Some((precompressed_fpath, encoding)) => (precompressed_fpath.as_path(), Some(*encoding)),
+        None => (
+            req_path_checked,
+            compressible
+                .then(|| response_encoding(req_headers, len))
+                .flatten(),
+        ),
+    };
+    let response_builder = if compressible || precompressed.is_some() {
+        response_builder.header(header::VARY, HeaderValue::from_static("accept-encoding"))
+    } else {
+        response_builder
+    };
+
+    // Files that we are not tracking (yet) are served without validators.
+    let validators = match project_root.tree.get() {
+        Some(tree) => tree
+            .read()
+            .await
+            .index
+            .file(served_fpath)
+            .map(|tracked_file| {
+                let etag = etag_for_hash(tracked_file.hash, encoding.map(Encoding::as_str));
+                (etag, tracked_file.mtime, tracked_file.hash)
+            }),
+        None => None,
+    };
+    let response_builder = if let Some((etag, mtime, _)) = &validators {
+        let (etag, mtime) = (etag.as_str(), *mtime);
+        let response_builder = response_builder
+            .header(header::ETAG, etag)
+            .header(header::LAST_MODIFIED, httpdate::fmt_http_date(mtime));
+        // If-Modified-Since is only evaluated when there is no If-None-Match.
+        let not_modified = match req_headers.get(header::IF_NONE_MATCH) {
+            Some(v) => v.to_str().is_ok_and(|v| if_none_match(v, etag)),
+            None => req_headers
+                .get(header::IF_MODIFIED_SINCE)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|v| if_modified_since_is_fresh(v, mtime)),
+        };
+        if not_modified {
+            debug!(?req_path_checked, etag, ?mtime, "File not modified.");
+            return response_builder
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Either::Left(Full::default()));
+        }
+        response_builder
+    } else {
+        response_builder
+    };
+    let hash = validators.map(|(_, _, hash)| hash);
+    // HTML files bypass the file cache when preload links are on, as the cached body may be
+    // compressed, and the links are found in the uncompressed document.
+    let file_cache = FILE_CACHE
+        .get()
+        .filter(|_| !(preload_links() && content_type.starts_with(TEXT_HTML)));
+    if let (Some(file_cache), Some(hash)) = (file_cache, hash) {
+        if file_cache.accepts(len) {
+            let key = CacheKey {
+                path: served_fpath.to_path_buf(),
+                encoding: encoding.map(Encoding::as_str),
+            };
+            let precompressed = precompressed.is_some();
+            if let Some(body) =
+                cached_file(file_cache, key, hash, content_type, encoding, precompressed).await
+            {
+                let response_builder = response_builder
+                    .header(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+                let response_builder = match encoding {
+                    Some(encoding) => response_builder.header(
+                        header::CONTENT_ENCODING,
+                        HeaderValue::from_static(encoding.as_str()),
+                    ),
+                    None => response_builder,
+                };
+                return response_builder.body(Either::Left(body.into()));
+            }
+        }
+    }
+    serve_file(
+        served_fpath,
+        content_type,
+        encoding,
+        precompressed.is_some(),
+        response_builder,
+    )
+    .await
+}
+
+/// Get a file from the file cache, reading it and adding it to the cache if it is not there.
+/// Returns `None` if the file could not be read, which is then left to the uncached path to report.
+async fn cached_file(
+    file_cache: &FileCache,
+    key: CacheKey,
+    hash: u64,
+    content_type: &str,
+    encoding: Option<Encoding>,
+    precompressed: bool,
+) -> Option<Bytes> {
+    if let Some(body) = file_cache.get(&key, hash) {
+        trace!(?key, "Serving file from file cache.");
+        return Some(body);
+    }
+    let mut contents = smol::fs::read(&key.path)
+        .await
+        .inspect_err(|e| debug!(err = ?e, ?key, "Failed to read file for file cache."))
+        .ok()?;
+    let url_path = if precompressed {
+        None
+    } else {
+        record_dependencies(&key.path, content_type, &contents)
+    };
+    if content_type.starts_with(TEXT_HTML) {
+        inject_client_script(&mut contents, url_path.as_deref());
+    }
+    if let Some(encoding) = encoding.filter(|_| !precompressed) {
+        contents = encode_bytes(contents, encoding)
+            .await
+            .inspect_err(|e| error!(err = ?e, ?key, "Failed to compress file for file cache."))
+            .ok()?;
+    }
+    let body = Bytes::from(contents);
+    file_cache.insert(key, hash, body.clone());
+    Some(body)
+}
+
+/// Record the files that an HTML page or a stylesheet references in the dependency graph.
+/// HTML pages are also checked for obvious problems, to show in the error overlay.
+/// Returns the URL path of the file.
+fn record_dependencies(fpath: &Path, content_type: &str, contents: &[u8]) -> Option<String> {
+    let url_path = url_path_for(fpath)?;
+    let deps = if content_type.starts_with(TEXT_HTML) {
+        report_html_problems(&url_path, contents);
+        html_dependencies(&url_path, contents)
+    } else if content_type.starts_with(TEXT_CSS) {
+        css_dependencies(&url_path, contents)
+    } else {
+        return Some(url_path);
+    };
+    trace!(url_path, ?deps, "Recording dependencies.");
+    DEPENDENCY_GRAPH.set_dependencies(url_path.clone(), deps);
+    Some(url_path)
+}
+
+/// Show the obvious problems of an HTML page in the error overlay, or clear them if it has none.
+fn report_html_problems(url_path: &str, contents: &[u8]) {
+    let problems = html::problems(contents);
+    let report = (!problems.is_empty()).then(|| {
+        warn!(url_path, ?problems, "HTML page has problems.");
+        ErrorReport {
+            title: format!("Problems in HTML of {url_path}"),
+            url_path: Some(url_path.to_string()),
+            output: problems
+                .iter()
+                .map(|problem| format!("Line {}: {}", problem.line, problem.message))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            diagnostics: Vec::new(),
+        }
+    });
+    ERROR_REPORTS.set(url_path, report);
+}
+
+/// Find a precompressed sibling of a file (such as `foo.js.br` for `foo.js`),
+/// in an encoding that the client accepts.
+async fn find_precompressed(
+    project_dir: &Path,
+    req_headers: &HeaderMap,
+    fpath: &Path,
+) -> Option<(PathBuf, Encoding)> {
+    let accept_encoding = req_headers.get(header::ACCEPT_ENCODING)?.to_str().ok()?;
+    for encoding in accepted_encodings(accept_encoding) {
+        let mut precompressed_fpath = fpath.as_os_str().to_owned();
+        precompressed_fpath.push(".");
+        precompressed_fpath.push(encoding.file_extension());
+        let precompressed_fpath = PathBuf::from(precompressed_fpath);
+        // Symlinks are not served, same as they are not tracked.
+        let is_file = smol::fs::symlink_metadata(&precompressed_fpath)
+            .await
+            .is_ok_and(|m| m.is_file());
+        if is_file && !is_excluded(project_dir, &precompressed_fpath, false) {
+            return Some((precompressed_fpath, encoding));
+        }
+    }
+    None
+}
+
+/// Find what a request is to be answered with according to the first matching redirect rule,
+/// if any. Rules from the config file are evaluated before those of the project directory.
+///
+/// Rules that are not forced only apply to paths that do not exist in the project directory.
+/// Rewrites to anything other than a URL path are not supported, and are ignored.
+fn apply_redirect_rules(
+    project_roots: &[ProjectRoot],
+    host: Option<&str>,
+    uri_path: &str,
+) -> Option<RuleOutcome> {
+    let resolved = resolve(project_roots, host, uri_path);
+    let exists = resolved.is_some_and(|(root, rest)| root.has_path(rest));
+    let is_supported = |outcome: &RuleOutcome| match outcome {
+        RuleOutcome::Rewrite(target) if !target.starts_with('/') => {
+            warn!(
+                target,
+                "Rewrite to other than a URL path is not supported. Ignoring rule."
+            );
+            false
+        }
+        _ => true,
+    };
+
+    let url_path = format!("/{uri_path}");
+    let config_rules = CONFIG_REDIRECT_RULES
+        .get()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let outcome = config_rules
+        .iter()
+        .filter(|rule| rule.force || !exists)
+        .filter_map(|rule| rule.apply(&url_path))
+        .find(is_supported);
+    if outcome.is_some() {
+        return outcome;
+    }
+
+    let (project_root, uri_path_below_mount) = resolved?;
+    let url_path_below_mount = format!("/{uri_path_below_mount}");
+    let project_rules = project_root
+        .redirect_rules
+        .read()
+        .unwrap_or_else(|e| e.into_inner());
+    let outcome = project_rules
+        .iter()
+        .filter(|rule| rule.force || !exists)
+        .filter_map(|rule| rule.apply(&url_path_below_mount))
+        .find(is_supported)?;
+    // Targets of rules from a project directory are relative to its mount point.
+    let mount = project_root.mount_url_path();
+    let mount = mount.trim_end_matches('/');
+    Some(match outcome {
+        RuleOutcome::Rewrite(target) => RuleOutcome::Rewrite(format!("{mount}{target}")),
+        RuleOutcome::Redirect { location, status } if location.starts_with('/') => {
+            RuleOutcome::Redirect {
+                location: format!("{mount}{location}"),
+                status,
+            }
+        }
+        outcome => outcome,
+    })
+}
+
+/// Add the headers of the header rules that match a request.
+///
+/// Rules from the config file are matched against the full URL path, and rules from
+/// the `_headers` file of the project directory against the URL path below its mount point.
+fn with_header_rules(
+    project_root: &ProjectRoot,
+    uri_path: &str,
+    uri_path_below_mount: &str,
+    mut response_builder: ResponseBuilder,
+) -> ResponseBuilder {
+    let url_path = format!("/{uri_path}");
+    let url_path_below_mount = format!("/{uri_path_below_mount}");
+    let project_rules = project_root
+        .header_rules
+        .read()
+        .unwrap_or_else(|e| e.into_inner());
+    let config_rules = CONFIG_HEADER_RULES
+        .get()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let matching_rules = config_rules
+        .iter()
+        .filter(|rule| rule.matches(&url_path))
+        .chain(
+            project_rules
+                .iter()
+                .filter(|rule| rule.matches(&url_path_below_mount)),
+        );
+    for rule in matching_rules {
+        trace!(pattern = rule.pattern, url_path, "Applying header rule.");
+        for (name, value) in &rule.headers {
+            response_builder = response_builder.header(name, value);
+        }
+    }
+    response_builder
+}
+
+/// HEAD requests are routed like GET requests. Their responses have the body stripped afterwards.
+fn get_for_head(method: &Method) -> &Method {
+    if method == Method::HEAD {
+        &Method::GET
+    } else {
+        method
+    }
+}
+
+/// Strip the body of a response to a HEAD request, keeping the headers that a GET request
+/// would have gotten. Streamed file bodies already carry a `Content-Length` from file metadata,
+/// and bodies that we have in full get one from their size here.
+fn without_body<B>(resp: Response<Either<Full<Bytes>, B>>) -> Response<Either<Full<Bytes>, B>> {
+    let (mut parts, body) = resp.into_parts();
+    if let (Either::Left(full), false) = (body, parts.headers.contains_key(header::CONTENT_LENGTH))
+    {
+        if let Some(len) = full.size_hint().exact() {
+            parts
+                .headers
+                .insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+        }
+    }
+    Response::from_parts(parts, Either::Left(Full::default()))
+}
+
+/// Pick the encoding to compress a response body that benefits from compression with, if any.
+fn response_encoding(req_headers: &HeaderMap, len: u64) -> Option<Encoding> {
+    if len < MIN_COMPRESS_SIZE {
+        return None;
+    }
+    let accept_encoding = req_headers.get(header::ACCEPT_ENCODING)?.to_str().ok()?;
+    negotiate(accept_encoding)
+}
+
+/// Respond with a body that we have in full, compressing it if the client accepts that.
+async fn full_body<B>(
+    req_headers: &HeaderMap,
+    response_builder: ResponseBuilder,
+    content_type: &'static str,
+    body: Bytes,
+) -> HttpResult<Response<Either<Full<Bytes>, B>>> {
+    let response_builder =
+        response_builder.header(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    if !is_compressible(content_type) {
+        return response_builder.body(Either::Left(body.into()));
+    }
+    let response_builder =
+        response_builder.header(header::VARY, HeaderValue::from_static("accept-encoding"));
+    let Some(encoding) = response_encoding(req_headers, body.len() as u64) else {
+        return response_builder.body(Either::Left(body.into()));
+    };
+    match encode_bytes(body.to_vec(), encoding).await {
+        Ok(encoded) => response_builder
+            .header(
+                header::CONTENT_ENCODING,
+                HeaderValue::from_static(encoding.as_str()),
+            )
+            .body(Either::Left(encoded.into())),
+        Err(e) => {
+            warn!(err = ?e, ?encoding, "Failed to compress response body. Sending it uncompressed.");
+            response_builder.body(Either::Left(body.into()))
+        }
+    }
+}
+
+fn preload_links() -> bool {
+    PRELOAD_LINKS.get().copied().unwrap_or(false)
+}
+
+fn single_port() -> bool {
+    SINGLE_PORT.get().copied().unwrap_or(false)
+}
+
+fn status_event_interval() -> Duration {
+    STATUS_EVENT_INTERVAL.get().copied().unwrap_or_default()
+}
+
+/// Serve the contents of a file.
+async fn serve_file(
+    req_path_checked: &Path,
+    content_type: &'static str,
+    encoding: Option<Encoding>,
+    precompressed: bool,
+    response_builder: ResponseBuilder,
+) -> HttpResult<ProjectResponse> {
+    if content_type.starts_with(TEXT_HTML) {
+        return match smol::fs::read(req_path_checked).await {
+            Ok(mut contents) => {
+                let response_builder = match preload_links()
+                    .then(|| link_header_value(&contents))
+                    .flatten()
+                {
+                    Some(link) => response_builder.header(header::LINK, link),
+                    None => response_builder,
+                };
+                let url_path = record_dependencies(req_path_checked, content_type, &contents);
+                inject_client_script(&mut contents, url_path.as_deref());
+                debug!(?req_path_checked, content_type, "Serving HTML file.");
+                let response_builder = response_builder
+                    .header(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+                let Some(encoding) = encoding else {
+                    return response_builder.body(Either::Left(contents.into()));
+                };
+                match encode_bytes(contents, encoding).await {
+                    Ok(encoded) => response_builder
+                        .header(
+                            header::CONTENT_ENCODING,
+                            HeaderValue::from_static(encoding.as_str()),
+                        )
+                        .body(Either::Left(encoded.into())),
+                    Err(e) => {
+                        error!(err = ?e, ?req_path_checked, "Failed to compress HTML file.");
+                        let (status, content_type, body) = server_error();
+                        response_builder
+                            .header(header::CONTENT_TYPE, content_type)
+                            .status(status)
+                            .extension(ErrorResponse)
+                            .body(Either::Left(body))
+                    }
+                }
+            }
+            Err(e) => file_read_error(e, req_path_checked, response_builder),
+        };
     }
-    // 2. Try file "index.html"
-    if let Ok(file) = File::open(req_path_checked.as_ref().join("index.html")).await {
-        // Based on <https://github.com/hyperium/hyper/blob/4c84e8c1c26a1464221de96b9f39816ce7251a5f/examples/send_file.rs#L81C1-L82C42>
-        let reader_stream = ReaderStream::new(file);
+
+    let file = match File::open(req_path_checked).await {
+        Ok(file) => file,
+        Err(e) => return file_read_error(e, req_path_checked, response_builder),
+    };
+    let metadata = match file.metadata().await {
+        Ok(metadata) => metadata,
+        Err(e) => return file_read_error(e, req_path_checked, response_builder),
+    };
+    debug!(
+        ?req_path_checked,
+        content_type,
+        len = metadata.len(),
+        ?encoding,
+        precompressed,
+        "Streaming file."
+    );
+    let response_builder =
+        response_builder.header(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    let response_builder = match encoding {
+        Some(encoding) if precompressed => response_builder.header(
+            header::CONTENT_ENCODING,
+            HeaderValue::from_static(encoding.as_str()),
+        ),
+        _ => response_builder,
+    };
+    if let Some(encoding) = encoding.filter(|_| !precompressed) {
+        // The length of the compressed body is not known up front, so it is sent chunked.
+        let encoder = encode_reader(BufReader::new(file), encoding);
+        let reader_stream = ReaderStream::new(encoder);
         let stream_body = StreamBody::new(reader_stream.map_ok(Frame::data));
         let boxed_body = BodyExt::boxed(stream_body);
-        return response_builder.body(Either::Right(boxed_body));
+        return response_builder
+            .header(
+                header::CONTENT_ENCODING,
+                HeaderValue::from_static(encoding.as_str()),
+            )
+            .body(Either::Right(boxed_body));
     }
-     */
-    // 3. Return a directory listing. (Note: This one needs to update itself as well.)
-    // TODO: dir listing
+    let mmap_min_size = MMAP_MIN_SIZE.get().copied().unwrap_or(0);
+    if mmap_min_size > 0 && metadata.len() >= mmap_min_size {
+        let len = usize::try_from(metadata.len()).unwrap_or(usize::MAX);
+        match MappedFile::map(&file, len) {
+            Ok(Some(mapped)) => {
+                trace!(?req_path_checked, "Serving file from memory map.");
+                return response_builder
+                    .header(header::CONTENT_LENGTH, metadata.len())
+                    .body(Either::Right(mapped_body(mapped)));
+            }
+            Ok(None) => debug!(
+                ?req_path_checked,
+                "Too many files mapped. Streaming file with buffered reads."
+            ),
+            Err(e) => warn!(
+                err = ?e,
+                ?req_path_checked,
+                "Failed to map file. Streaming file with buffered reads."
+            ),
+        }
+    }
+    // Based on <https://github.com/hyperium/hyper/blob/4c84e8c1c26a1464221de96b9f39816ce7251a5f/examples/send_file.rs#L81C1-L82C42>
+    let reader_stream = ReaderStream::new(file);
+    let stream_body = StreamBody::new(reader_stream.map_ok(Frame::data));
+    let boxed_body = BodyExt::boxed(stream_body);
+    response_builder
+        .header(header::CONTENT_LENGTH, metadata.len())
+        .body(Either::Right(boxed_body))
+}
+
+/// Respond to a request for a file that could not be read.
+fn file_read_error(
+    e: std::io::Error,
+    req_path_checked: &Path,
+    response_builder: ResponseBuilder,
+) -> HttpResult<ProjectResponse> {
+    warn!(err = ?e, ?req_path_checked, "Failed to read file. Returning 404.");
     let (status, content_type, body) = not_found();
     response_builder
         .header(header::CONTENT_TYPE, content_type)
         .status(status)
+        .extension(ErrorResponse)
         .body(Either::Left(body))
 }
 
+/// Answer the request on a connection over the connection limit with 503 Service Unavailable,
+/// and close the connection.
+async fn reject_connection<I>(server: &ConnBuilder<TokioExecutor>, io: I, activity: &Activity)
+where
+    I: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+{
+    let conn = server.serve_connection(io, service_fn(service_unavailable));
+    let timeouts = Timeouts {
+        idle: REJECTED_CONNECTION_TIMEOUT,
+        send: REJECTED_CONNECTION_TIMEOUT,
+    };
+    match serve_until_idle(conn, activity, timeouts, |conn| conn.graceful_shutdown()).await {
+        Ok(Err(e)) => debug!(err = e, "Connection error"),
+        Err(e) => debug!(err = %e, "Connection timed out"),
+        Ok(Ok(())) => {}
+    }
+}
+
+async fn service_unavailable(
+    req: Request<Incoming>,
+) -> HttpResult<Response<Either<Full<Bytes>, Empty<Bytes>>>> {
+    let resp = Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .extension(ErrorResponse)
+        .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_PLAIN))
+        .header(
+            header::RETRY_AFTER,
+            HeaderValue::from_static(RETRY_AFTER_SECS),
+        )
+        .body(Either::Left(SERVICE_UNAVAILABLE_BODY_TEXT.into()))?;
+    Ok(negotiated_error(req.headers(), resp).await)
+}
+
+fn serving_paused<B>() -> HttpResult<Response<Either<Full<Bytes>, B>>> {
+    Response::builder()
+        .status(StatusCode::SERVICE_UNAVAILABLE)
+        .extension(ErrorResponse)
+        .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_PLAIN))
+        .header(
+            header::RETRY_AFTER,
+            HeaderValue::from_static(RETRY_AFTER_SECS),
+        )
+        .header(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(CACHE_CONTROL_VALUE_NO_STORE),
+        )
+        .body(Either::Left(SERVING_PAUSED_BODY_TEXT.into()))
+}
+
+/// Whether the host that a request is for is one that we serve.
+fn is_allowed_host(req: &Request<Incoming>) -> bool {
+    let host = req
+        .uri()
+        .authority()
+        .map(|authority| authority.as_str())
+        .or_else(|| {
+            req.headers()
+                .get(header::HOST)
+                .map(|host| host.to_str().unwrap_or_default())
+        });
+    let allowed = HOST_CHECK.get().is_none_or(|check| check.is_allowed(host));
+    if !allowed {
+        warn!(
+            ?host,
+            "Rejecting request for host that is not allowed. Use --allowed-host to allow it."
+        );
+    }
+    allowed
+}
+
+fn forbidden_host<B>() -> HttpResult<Response<Either<Full<Bytes>, B>>> {
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .extension(ErrorResponse)
+        .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_PLAIN))
+        .body(Either::Left(FORBIDDEN_HOST_BODY_TEXT.into()))
+}
+
+/// Whether a request to the given server is allowed, as far as authentication is concerned.
+fn is_authorized(server: Server, headers: &HeaderMap) -> bool {
+    BASIC_AUTH
+        .get()
+        .is_none_or(|auth| !auth.protects(server) || auth.is_authorized(headers))
+}
+
+fn unauthorized<B>() -> HttpResult<Response<Either<Full<Bytes>, B>>> {
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .extension(ErrorResponse)
+        .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_PLAIN))
+        .header(header::WWW_AUTHENTICATE, BasicAuth::challenge())
+        .header(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(CACHE_CONTROL_VALUE_NO_STORE),
+        )
+        .body(Either::Left(UNAUTHORIZED_BODY_TEXT.into()))
+}
+
+fn status_token_required<B>() -> HttpResult<Response<Either<Full<Bytes>, B>>> {
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .extension(ErrorResponse)
+        .header(header::CONTENT_TYPE, HeaderValue::from_static(TEXT_PLAIN))
+        .header(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(CACHE_CONTROL_VALUE_NO_STORE),
+        )
+        .body(Either::Left(STATUS_TOKEN_REQUIRED_BODY_TEXT.into()))
+}
+
+/// Marks a response as one of our own error responses, with a plain text body that
+/// [`negotiated_error`] may replace with one in a format that the client prefers.
+#[derive(Debug, Clone, Copy)]
+struct ErrorResponse;
+
+/// Replace the plain text body of an error response of our own with an HTML page or JSON,
+/// if the client prefers that according to the `Accept` request header.
+async fn negotiated_error<B>(
+    req_headers: &HeaderMap,
+    resp: Response<Either<Full<Bytes>, B>>,
+) -> Response<Either<Full<Bytes>, B>> {
+    let format = req_headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .map_or(ErrorFormat::Text, negotiate_error_format);
+    if resp.extensions().get::<ErrorResponse>().is_none() {
+        return resp;
+    }
+    let (mut parts, body) = resp.into_parts();
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("accept"));
+    let full = match body {
+        Either::Left(full) if format != ErrorFormat::Text => full,
+        body => return Response::from_parts(parts, body),
+    };
+    let Ok(text) = full.collect().await.map(|collected| collected.to_bytes());
+    let message = error_message(&String::from_utf8_lossy(&text)).to_string();
+    let (content_type, body) = match format {
+        ErrorFormat::Json => (APPLICATION_JSON, error_json(parts.status, &message)),
+        _ => {
+            let page = ErrorPage {
+                status: parts.status.as_u16(),
+                reason: parts.status.canonical_reason().unwrap_or_default(),
+                message: &message,
+                color_scheme: color_scheme(),
+                stylesheet: std::str::from_utf8(INTERNAL_STYLESHEET).unwrap_or_default(),
+            };
+            match page.render() {
+                Ok(page) => (TEXT_HTML, page),
+                Err(e) => {
+                    error!(err = ?e, "Failed to render error page.");
+                    return Response::from_parts(parts, Either::Left(text.into()));
+                }
+            }
+        }
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    Response::from_parts(parts, Either::Left(body.into()))
+}
+
 fn server_error() -> (StatusCode, HeaderValue, Full<Bytes>) {
     (
         StatusCode::INTERNAL_SERVER_ERROR,
@@ -926,6 +5551,94 @@ fn server_error() -> (StatusCode, HeaderValue, Full<Bytes>) {
     )
 }
 
+/// The language to serve the status web-ui in: the one named by the `lang` query parameter,
+/// if it is one that the status web-ui is translated into, or else the one negotiated
+/// by the `Accept-Language` request header.
+fn status_webui_language<B>(req: &Request<B>) -> Language {
+    query_param(req.uri(), LANG_PARAM)
+        .and_then(|lang| Language::from_tag(&lang))
+        .unwrap_or_else(|| {
+            let accept_language = req
+                .headers()
+                .get(header::ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok());
+            negotiate_language(accept_language.unwrap_or_default())
+        })
+}
+
+/// MIME type and contents of the icon of the status web-ui at a path, if there is one.
+fn status_icon(uri_path: &str) -> Option<(&'static str, &'static [u8])> {
+    let name = uri_path.strip_prefix("icons/")?;
+    STATUS_ICONS
+        .iter()
+        .find(|(icon_name, ..)| *icon_name == name)
+        .map(|&(_, content_type, icon)| (content_type, icon))
+}
+
+/// Whether a path on the status server is for an icon or the web app manifest, which browsers
+/// fetch without the status token, and which tell nothing about the project.
+fn is_public_status_path(uri_path: &str) -> bool {
+    matches!(
+        uri_path.trim_start_matches('/'),
+        "favicon.ico" | "manifest.webmanifest"
+    ) || uri_path.starts_with("/icons/")
+}
+
+/// Methods allowed for a given uri path on the status server.
+fn status_allow(uri_path: &str) -> &'static str {
+    match uri_path {
+        "watching/pause"
+        | "watching/resume"
+        | "serving/pause"
+        | "serving/resume"
+        | "api/reload"
+        | "api/rescan"
+        | "api/companions/restart" => ALLOW_POST,
+        "api/color-scheme" | "api/exclusions" => ALLOW_GET_POST,
+        _ => ALLOW_GET,
+    }
+}
+
+/// Answer an OPTIONS request with the methods that are allowed.
+///
+/// With `cors`, CORS preflight requests additionally get told that the request may be made from
+/// any origin, with whatever request headers the preflight asks for. That is only for the project
+/// server, as the status server has routes that change state, which other sites must not reach.
+fn options_response<B>(
+    req_headers: &HeaderMap,
+    response_builder: ResponseBuilder,
+    allow: &'static str,
+    cors: bool,
+) -> HttpResult<Response<Either<Full<Bytes>, B>>> {
+    let mut response_builder = response_builder
+        .status(StatusCode::NO_CONTENT)
+        .header(header::ALLOW, HeaderValue::from_static(allow));
+    if cors && req_headers.contains_key(header::ACCESS_CONTROL_REQUEST_METHOD) {
+        response_builder = response_builder
+            .header(
+                header::ACCESS_CONTROL_ALLOW_ORIGIN,
+                HeaderValue::from_static("*"),
+            )
+            .header(
+                header::ACCESS_CONTROL_ALLOW_METHODS,
+                HeaderValue::from_static(allow),
+            );
+        if let Some(request_headers) = req_headers.get(header::ACCESS_CONTROL_REQUEST_HEADERS) {
+            response_builder =
+                response_builder.header(header::ACCESS_CONTROL_ALLOW_HEADERS, request_headers);
+        }
+    }
+    response_builder.body(Either::Left(Full::default()))
+}
+
+fn bad_request() -> (StatusCode, HeaderValue, Full<Bytes>) {
+    (
+        StatusCode::BAD_REQUEST,
+        HeaderValue::from_static(TEXT_PLAIN),
+        BAD_REQUEST_BODY_TEXT.into(),
+    )
+}
+
 fn method_not_allowed() -> (StatusCode, HeaderValue, Full<Bytes>) {
     (
         StatusCode::METHOD_NOT_ALLOWED,
@@ -934,6 +5647,14 @@ fn method_not_allowed() -> (StatusCode, HeaderValue, Full<Bytes>) {
     )
 }
 
+fn upgrade_required() -> (StatusCode, HeaderValue, Full<Bytes>) {
+    (
+        StatusCode::UPGRADE_REQUIRED,
+        HeaderValue::from_static(TEXT_PLAIN),
+        UPGRADE_REQUIRED_BODY_TEXT.into(),
+    )
+}
+
 fn not_found() -> (StatusCode, HeaderValue, Full<Bytes>) {
     (
         StatusCode::NOT_FOUND,
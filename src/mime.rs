@@ -0,0 +1,160 @@
+//! MIME types for files served by the project server, based on file name extensions.
+//!
+//! The built-in mapping covers what is commonly found in web projects. Users can add to it,
+//! or override it, with extension to MIME type mappings of their own.
+
+use std::{collections::HashMap, path::Path, sync::OnceLock};
+use thiserror::Error;
+
+/// MIME type used for files with no extension, or an extension that we do not know of.
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// User-provided extension to MIME type mappings, keyed by lowercase extension.
+/// Set once, at program startup. These take precedence over the built-in mapping.
+pub static EXTRA_MIME_TYPES: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Expected a mapping on the form EXT=TYPE, got {0:?}")]
+    InvalidMapping(String),
+}
+
+/// Parse a user-provided mapping on the form `EXT=TYPE`, such as `glsl=text/plain`.
+///
+/// A leading dot on the extension is accepted, and the extension is lowercased.
+/// The MIME type must be visible ASCII, as it is sent as a static `Content-Type` header value.
+pub fn parse_mime_mapping(s: &str) -> Result<(String, String), Error> {
+    let Some((ext, mime_type)) = s.split_once('=') else {
+        return Err(Error::InvalidMapping(s.to_string()));
+    };
+    let ext = ext.trim().trim_start_matches('.').to_ascii_lowercase();
+    let mime_type = mime_type.trim();
+    // Stricter than `HeaderValue::from_str`, which also accepts bytes past ASCII,
+    // since `HeaderValue::from_static` panics on those.
+    let is_header_safe = mime_type
+        .bytes()
+        .all(|b| b == b'\t' || (b' '..=b'~').contains(&b));
+    if ext.is_empty() || mime_type.is_empty() || !mime_type.contains('/') || !is_header_safe {
+        return Err(Error::InvalidMapping(s.to_string()));
+    }
+    Ok((ext, mime_type.to_string()))
+}
+
+/// Find the MIME type for a file, based on the extension of its file name.
+///
+/// Text based types include a UTF-8 charset parameter.
+pub fn mime_type_for_path(path: &Path) -> &'static str {
+    let Some(ext) = path.extension().and_then(|ext| ext.to_str()) else {
+        return DEFAULT_MIME_TYPE;
+    };
+    let ext = ext.to_ascii_lowercase();
+    if let Some(mime_type) = EXTRA_MIME_TYPES.get().and_then(|extra| extra.get(&ext)) {
+        return mime_type;
+    }
+    builtin_mime_type(&ext).unwrap_or(DEFAULT_MIME_TYPE)
+}
+
+fn builtin_mime_type(ext: &str) -> Option<&'static str> {
+    let mime_type = match ext {
+        // Documents
+        "htm" | "html" => "text/html; charset=utf-8",
+        "xhtml" => "application/xhtml+xml; charset=utf-8",
+        "txt" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "xml" => "application/xml; charset=utf-8",
+        "pdf" => "application/pdf",
+        // Styles and scripts
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" | "cjs" => "text/javascript; charset=utf-8",
+        "map" => "application/json; charset=utf-8",
+        "json" => "application/json; charset=utf-8",
+        "webmanifest" => "application/manifest+json; charset=utf-8",
+        "wasm" => "application/wasm",
+        // Images
+        "svg" => "image/svg+xml; charset=utf-8",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "bmp" => "image/bmp",
+        // Fonts
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        // Audio and video
+        "mp3" => "audio/mpeg",
+        "ogg" | "oga" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "vtt" => "text/vtt; charset=utf-8",
+        // Other
+        "csv" => "text/csv; charset=utf-8",
+        "zip" => "application/zip",
+        _ => return None,
+    };
+    Some(mime_type)
+}
+
+/// Find a file name extension for a MIME type, such as a `Content-Type` header value.
+/// Parameters, such as the charset, are ignored.
+pub fn extension_for_mime_type(mime_type: &str) -> Option<&'static str> {
+    let essence = mime_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let ext = match essence.as_str() {
+        "text/html" => "html",
+        "application/javascript" => "js",
+        "application/problem+json" => "json",
+        "text/xml" => "xml",
+        essence => BUILTIN_EXTENSIONS.iter().copied().find(|ext| {
+            builtin_mime_type(ext)
+                .is_some_and(|mime_type| mime_type.split(';').next() == Some(essence))
+        })?,
+    };
+    Some(ext)
+}
+
+/// Extensions of the built-in mapping, in order of preference when going from MIME type to extension.
+const BUILTIN_EXTENSIONS: &[&str] = &[
+    "html",
+    "xhtml",
+    "txt",
+    "md",
+    "xml",
+    "pdf",
+    "css",
+    "js",
+    "json",
+    "webmanifest",
+    "wasm",
+    "svg",
+    "png",
+    "jpg",
+    "gif",
+    "webp",
+    "avif",
+    "ico",
+    "bmp",
+    "woff",
+    "woff2",
+    "ttf",
+    "otf",
+    "mp3",
+    "ogg",
+    "wav",
+    "flac",
+    "mp4",
+    "webm",
+    "vtt",
+    "csv",
+    "zip",
+];
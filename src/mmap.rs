@@ -0,0 +1,259 @@
+//! Serving of large files from memory maps, which saves the copies through the buffers of
+//! the blocking thread pool and of [`crate::stream::ReaderStream`] that buffered streaming makes.
+//!
+//! A file that is truncated while it is mapped raises `SIGBUS` when the pages that are gone
+//! are read. Build tools do truncate files that we may be serving, so we handle `SIGBUS` for
+//! faults inside our mappings by mapping zeroed pages in place of the ones that are gone,
+//! and flagging the mapping as truncated. The chunk that was being copied is then discarded,
+//! and the response body ends with an error, like it would for a failed read.
+//! Faults outside of our mappings are passed on to the handler that was installed before ours.
+
+use crate::stream::CHUNK_SIZE;
+use async_stream::stream;
+use bytes::Bytes;
+use http_body_util::{combinators::BoxBody, BodyExt, StreamBody};
+use hyper::body::Frame;
+use std::{
+    io, mem,
+    os::fd::AsRawFd,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+};
+use tracing::{debug, warn};
+
+/// How many files can be mapped at a time. Files past that are streamed the buffered way.
+const MAX_MAPPINGS: usize = 64;
+
+/// A mapping that the `SIGBUS` handler knows of. Free while `len` is 0.
+struct Slot {
+    start: AtomicUsize,
+    len: AtomicUsize,
+    truncated: AtomicBool,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const FREE_SLOT: Slot = Slot {
+    start: AtomicUsize::new(0),
+    len: AtomicUsize::new(0),
+    truncated: AtomicBool::new(false),
+};
+
+static SLOTS: [Slot; MAX_MAPPINGS] = [FREE_SLOT; MAX_MAPPINGS];
+
+/// The `SIGBUS` action that was installed before ours.
+struct PreviousAction(libc::sigaction);
+
+// SAFETY: The action is only read after it has been set, and it is not mutated.
+unsafe impl Sync for PreviousAction {}
+unsafe impl Send for PreviousAction {}
+
+static PREVIOUS_SIGBUS_ACTION: OnceLock<PreviousAction> = OnceLock::new();
+
+/// A read-only memory map of a whole file.
+pub struct MappedFile {
+    ptr: *mut libc::c_void,
+    len: usize,
+    slot: &'static Slot,
+}
+
+// SAFETY: The mapping is read-only, and is unmapped only on drop.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    /// Map a file of the given length. Returns `None` if all mapping slots are taken.
+    pub fn map(file: &impl AsRawFd, len: usize) -> io::Result<Option<Self>> {
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Cannot map an empty file",
+            ));
+        }
+        install_sigbus_handler()?;
+        let Some(slot) = SLOTS.iter().find(|slot| {
+            slot.len
+                .compare_exchange(0, usize::MAX, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        }) else {
+            return Ok(None);
+        };
+        // SAFETY: We map a file that we have open for reading, with a length that it had.
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            slot.len.store(0, Ordering::Release);
+            return Err(io::Error::last_os_error());
+        }
+        slot.truncated.store(false, Ordering::Release);
+        slot.start.store(ptr as usize, Ordering::Release);
+        slot.len.store(len, Ordering::Release);
+        Ok(Some(Self { ptr, len, slot }))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copy a range of the file. Fails if the file has been truncated while it was mapped.
+    fn copy_range(&self, start: usize, end: usize) -> io::Result<Bytes> {
+        // SAFETY: The range is within the mapping, and pages that are gone from the file
+        // are replaced by zeroed pages by our `SIGBUS` handler.
+        let data =
+            unsafe { std::slice::from_raw_parts(self.ptr.cast::<u8>().add(start), end - start) };
+        let chunk = Bytes::copy_from_slice(data);
+        if self.slot.truncated.load(Ordering::Acquire) {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "File was truncated while it was being sent",
+            ));
+        }
+        Ok(chunk)
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        // SAFETY: The mapping was created by us, and is not used after this.
+        if unsafe { libc::munmap(self.ptr, self.len) } != 0 {
+            warn!(err = ?io::Error::last_os_error(), "Failed to unmap file.");
+        }
+        self.slot.start.store(0, Ordering::Release);
+        self.slot.len.store(0, Ordering::Release);
+    }
+}
+
+/// Stream the contents of a mapped file as a response body, in chunks.
+/// Chunks are copied on the blocking thread pool, as they may have to be paged in from disk.
+pub fn mapped_body(mapped: MappedFile) -> BoxBody<Bytes, io::Error> {
+    let mapped = Arc::new(mapped);
+    let stream = stream! {
+        let mut offset = 0;
+        while offset < mapped.len() {
+            let end = (offset + CHUNK_SIZE).min(mapped.len());
+            let mapped = mapped.clone();
+            let chunk = smol::unblock(move || mapped.copy_range(offset, end)).await;
+            match chunk {
+                Ok(chunk) => yield Ok(Frame::data(chunk)),
+                Err(e) => {
+                    debug!(err = ?e, "Stopped sending mapped file.");
+                    yield Err(e);
+                    return;
+                }
+            }
+            offset = end;
+        }
+    };
+    BodyExt::boxed(StreamBody::new(stream))
+}
+
+fn install_sigbus_handler() -> io::Result<()> {
+    if PREVIOUS_SIGBUS_ACTION.get().is_some() {
+        return Ok(());
+    }
+    static INSTALL: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _guard = INSTALL.lock().unwrap_or_else(|e| e.into_inner());
+    if PREVIOUS_SIGBUS_ACTION.get().is_some() {
+        return Ok(());
+    }
+    // SAFETY: The handler only does async-signal-safe things, and the previous action
+    // is stored before any mapping that the handler could be called for exists.
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handle_sigbus as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut previous: libc::sigaction = mem::zeroed();
+        if libc::sigaction(libc::SIGBUS, &action, &mut previous) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let _ = PREVIOUS_SIGBUS_ACTION.set(PreviousAction(previous));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn fault_addr(info: *mut libc::siginfo_t) -> usize {
+    (*info).si_addr() as usize
+}
+
+#[cfg(not(target_os = "linux"))]
+unsafe fn fault_addr(info: *mut libc::siginfo_t) -> usize {
+    (*info).si_addr as usize
+}
+
+extern "C" fn handle_sigbus(
+    signum: libc::c_int,
+    info: *mut libc::siginfo_t,
+    ctx: *mut libc::c_void,
+) {
+    // SAFETY: The kernel passes a valid siginfo for handlers installed with SA_SIGINFO.
+    let addr = unsafe { fault_addr(info) };
+    for slot in &SLOTS {
+        let (start, len) = (
+            slot.start.load(Ordering::Acquire),
+            slot.len.load(Ordering::Acquire),
+        );
+        if len == 0 || len == usize::MAX || addr < start || addr >= start + len {
+            continue;
+        }
+        // SAFETY: We replace a page of our own mapping, that the file no longer backs,
+        // with a zeroed page. sysconf and mmap do not take locks.
+        unsafe {
+            let page_size = libc::sysconf(libc::_SC_PAGESIZE) as usize;
+            let page = addr & !(page_size - 1);
+            let res = libc::mmap(
+                page as *mut libc::c_void,
+                page_size,
+                libc::PROT_READ,
+                libc::MAP_FIXED | libc::MAP_PRIVATE | libc::MAP_ANON,
+                -1,
+                0,
+            );
+            if res != libc::MAP_FAILED {
+                slot.truncated.store(true, Ordering::Release);
+                return;
+            }
+        }
+    }
+    // Not ours, so pass it on.
+    if let Some(PreviousAction(previous)) = PREVIOUS_SIGBUS_ACTION.get() {
+        // SAFETY: The previous action was installed for this signal, and is called like the
+        // kernel would have called it.
+        unsafe {
+            match previous.sa_sigaction {
+                libc::SIG_IGN => {}
+                libc::SIG_DFL => {
+                    // Returning with the default action restored makes the fault happen again.
+                    libc::signal(libc::SIGBUS, libc::SIG_DFL);
+                }
+                handler if previous.sa_flags & libc::SA_SIGINFO != 0 => {
+                    let handler: extern "C" fn(
+                        libc::c_int,
+                        *mut libc::siginfo_t,
+                        *mut libc::c_void,
+                    ) = mem::transmute(handler);
+                    handler(signum, info, ctx);
+                }
+                handler => {
+                    let handler: extern "C" fn(libc::c_int) = mem::transmute(handler);
+                    handler(signum);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Mock API responses from fixture files, for frontend development before the backend exists.
+//!
+//! The mocks directory mirrors the URL paths of the API. The response to a request is given by
+//! a fixture file named after the request method, in the directory for the URL path:
+//!
+//! ```text
+//! mocks/
+//!   api/
+//!     users/
+//!       GET.json            GET /api/users
+//!       POST.json           POST /api/users
+//!       POST.headers        Status and headers of the response to POST /api/users
+//!       [id]/
+//!         GET.json          GET /api/users/42, and any other single path segment
+//! ```
+//!
+//! A directory with a name in square brackets matches any single path segment, but directories
+//! that match the segment exactly take precedence. The content type of a response is derived
+//! from the extension of its fixture file. A `.headers` file next to it can set the status
+//! and additional headers of the response, with `Name: value` lines, where the pseudo-header
+//! `Status` sets the status code, like in CGI:
+//!
+//! ```text
+//! Status: 201
+//! Location: /api/users/42
+//! ```
+//!
+//! Fixture files are read for each request, so changes to them take effect right away.
+
+use hyper::{
+    header::{HeaderName, HeaderValue},
+    Method, StatusCode,
+};
+use smol::stream::StreamExt;
+use std::{
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// Extension of files with the status and headers of a mock response.
+pub const HEADERS_EXTENSION: &str = "headers";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to read fixture {0:?}")]
+    Read(PathBuf, #[source] io::Error),
+    #[error("{path:?} line {line}: Expected a header on the form `Name: value`, got {text:?}")]
+    InvalidHeader {
+        path: PathBuf,
+        line: usize,
+        text: String,
+    },
+    #[error("{0:?}: Invalid status {1:?}")]
+    InvalidStatus(PathBuf, String),
+}
+
+/// A mock response, as given by fixture files.
+#[derive(Debug)]
+pub struct MockResponse {
+    /// The fixture file with the body of the response.
+    pub fixture: PathBuf,
+    pub status: StatusCode,
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    pub body: Vec<u8>,
+}
+
+/// Mock responses from the fixture files in a directory.
+#[derive(Debug, Clone)]
+pub struct Mocks {
+    dir: PathBuf,
+}
+
+impl Mocks {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Find the mock response for a request, if there is a fixture for it.
+    /// The URL path must be decoded, and HEAD requests are answered with the GET fixture.
+    pub async fn find(
+        &self,
+        method: &Method,
+        url_path: &str,
+    ) -> Result<Option<MockResponse>, Error> {
+        let Some(dir) = self.resolve_dir(url_path).await else {
+            return Ok(None);
+        };
+        let method = if method == Method::HEAD {
+            Method::GET.as_str()
+        } else {
+            method.as_str()
+        };
+        let Some(fixture) = find_fixture(&dir, method).await else {
+            return Ok(None);
+        };
+        let body = smol::fs::read(&fixture)
+            .await
+            .map_err(|e| Error::Read(fixture.clone(), e))?;
+        let headers_fpath = dir.join(format!("{method}.{HEADERS_EXTENSION}"));
+        let (status, headers) = match smol::fs::read_to_string(&headers_fpath).await {
+            Ok(text) => parse_headers(&headers_fpath, &text)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => (StatusCode::OK, vec![]),
+            Err(e) => return Err(Error::Read(headers_fpath, e)),
+        };
+        Ok(Some(MockResponse {
+            fixture,
+            status,
+            headers,
+            body,
+        }))
+    }
+
+    /// Find the directory for a URL path, preferring exact matches of path segments
+    /// over directories with names in square brackets.
+    async fn resolve_dir(&self, url_path: &str) -> Option<PathBuf> {
+        let mut dir = self.dir.clone();
+        for segment in url_path.split('/').filter(|segment| !segment.is_empty()) {
+            if segment == "."
+                || segment == ".."
+                || segment.contains(['/', '\\'])
+                || segment.starts_with('.')
+            {
+                return None;
+            }
+            let exact = dir.join(segment);
+            if is_dir(&exact).await {
+                dir = exact;
+                continue;
+            }
+            dir = find_param_dir(&dir).await?;
+        }
+        Some(dir)
+    }
+
+    /// The URL path that a fixture file is for, if it is in the mocks directory.
+    /// Directories with names in square brackets are kept as they are.
+    pub fn url_path_for(&self, fpath: &Path) -> Option<String> {
+        let rel_path = fpath.strip_prefix(&self.dir).ok()?;
+        let parent = rel_path.parent()?;
+        let segments = parent
+            .components()
+            .map(|c| c.as_os_str().to_str())
+            .collect::<Option<Vec<_>>>()?;
+        Some(format!("/{}", segments.join("/")))
+    }
+}
+
+async fn is_dir(path: &Path) -> bool {
+    smol::fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.is_dir())
+}
+
+/// Find the first directory, by name, with a name in square brackets.
+async fn find_param_dir(dir: &Path) -> Option<PathBuf> {
+    let names = dir_entry_names(dir).await;
+    for name in names {
+        if name.starts_with('[') && name.ends_with(']') {
+            let path = dir.join(&name);
+            if is_dir(&path).await {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+/// Find the first fixture file, by name, for a method. Any extension but that of headers files will do.
+async fn find_fixture(dir: &Path, method: &str) -> Option<PathBuf> {
+    let names = dir_entry_names(dir).await;
+    for name in names {
+        let Some((stem, ext)) = name.split_once('.') else {
+            continue;
+        };
+        if stem == method && ext != HEADERS_EXTENSION {
+            let path = dir.join(&name);
+            if smol::fs::metadata(&path)
+                .await
+                .is_ok_and(|metadata| metadata.is_file())
+            {
+                return Some(path);
+            }
+        }
+    }
+    None
+}
+
+/// Names of the entries in a directory, sorted, so that lookups do not depend on directory order.
+async fn dir_entry_names(dir: &Path) -> Vec<String> {
+    let Ok(mut entries) = smol::fs::read_dir(dir).await else {
+        return vec![];
+    };
+    let mut names = vec![];
+    while let Some(Ok(entry)) = entries.next().await {
+        if let Ok(name) = entry.file_name().into_string() {
+            names.push(name);
+        }
+    }
+    names.sort();
+    names
+}
+
+fn parse_headers(
+    path: &Path,
+    text: &str,
+) -> Result<(StatusCode, Vec<(HeaderName, HeaderValue)>), Error> {
+    let mut status = StatusCode::OK;
+    let mut headers = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || Error::InvalidHeader {
+            path: path.to_path_buf(),
+            line: i + 1,
+            text: line.to_string(),
+        };
+        let (name, value) = line.split_once(':').ok_or_else(invalid)?;
+        let (name, value) = (name.trim(), value.trim());
+        if name.eq_ignore_ascii_case("status") {
+            // Like in CGI, the status code may be followed by a reason phrase.
+            let code = value.split_whitespace().next().unwrap_or_default();
+            status = code
+                .parse::<StatusCode>()
+                .map_err(|_| Error::InvalidStatus(path.to_path_buf(), value.to_string()))?;
+            continue;
+        }
+        let name = HeaderName::try_from(name).map_err(|_| invalid())?;
+        let value = HeaderValue::try_from(value).map_err(|_| invalid())?;
+        headers.push((name, value));
+    }
+    Ok((status, headers))
+}
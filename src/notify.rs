@@ -0,0 +1,73 @@
+//! Desktop notifications, for what is worth knowing about without keeping an eye on
+//! the terminal: the addresses to open once the servers are up, errors in the build output,
+//! and file system watching having stopped.
+//!
+//! Notifications are shown through the notification center on macOS, by way of `osascript`,
+//! and through `notify-send` elsewhere.
+
+use std::{
+    process::Command,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use tracing::{debug, warn};
+
+/// Longest notification message, in characters. Longer ones are truncated.
+const MAX_MESSAGE_LEN: usize = 240;
+
+static NOTIFY_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Enable desktop notifications.
+pub fn enable() {
+    NOTIFY_ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    NOTIFY_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Show a desktop notification, if notifications are enabled.
+/// This does not wait for the notification to be shown, and failures are only logged.
+pub fn notify(title: &str, message: &str) {
+    if !is_enabled() {
+        return;
+    }
+    let mut message = message.to_string();
+    if let Some((idx, _)) = message.char_indices().nth(MAX_MESSAGE_LEN) {
+        message.truncate(idx);
+        message.push('…');
+    }
+    let mut command = notification_command(title, &message);
+    std::thread::spawn(move || match command.status() {
+        Ok(status) if status.success() => debug!(?command, "Showed desktop notification."),
+        Ok(status) => warn!(?command, ?status, "Failed to show desktop notification."),
+        Err(e) => warn!(err = ?e, ?command, "Failed to show desktop notification."),
+    });
+}
+
+#[cfg(target_os = "macos")]
+fn notification_command(title: &str, message: &str) -> Command {
+    let script = format!(
+        "display notification {} with title \"http-horse\" subtitle {}",
+        applescript_string(message),
+        applescript_string(title)
+    );
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(script);
+    command
+}
+
+#[cfg(not(target_os = "macos"))]
+fn notification_command(title: &str, message: &str) -> Command {
+    let mut command = Command::new("notify-send");
+    command
+        .arg("--app-name=http-horse")
+        .arg(format!("http-horse: {title}"))
+        .arg(message);
+    command
+}
+
+/// Quote text as an AppleScript string literal.
+#[cfg(target_os = "macos")]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,116 @@
+//! Errors to show in a full-screen overlay over the pages that are open in browsers,
+//! like obvious problems with the HTML of a page, so that they are noticed right away,
+//! rather than as a page that looks subtly off.
+//!
+//! Errors are reported under a key, and stay until they are cleared under the same key.
+//! Subscribers get the current errors whenever they change, and close the overlay
+//! once there are none.
+
+use crate::{broadcast::Broadcast, diagnostics::Diagnostic, notify};
+use serde::Serialize;
+use smol::channel::Receiver;
+use std::{collections::BTreeMap, path::Path, sync::Mutex};
+use tracing::debug;
+
+/// How many updates a subscriber can fall behind by before it starts missing updates.
+const SUBSCRIBER_CAPACITY: usize = 16;
+
+/// An error to show in the overlay.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorReport {
+    pub title: String,
+    /// URL path of the page that the error is about, or `None` if it is about every page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url_path: Option<String>,
+    /// Error output to show, as plain text.
+    pub output: String,
+    /// The errors and warnings in the output that are at places in source files, if any.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ErrorReport {
+    /// Whether the error is to be shown on a page. Pages that are not known by URL path,
+    /// like directory listings, only show errors that are about every page.
+    pub fn is_for_page(&self, page: Option<&str>) -> bool {
+        self.url_path
+            .as_deref()
+            .is_none_or(|url_path| Some(url_path) == page)
+    }
+}
+
+/// The current errors, by key, and the subscribers to changes to them.
+#[derive(Debug)]
+pub struct ErrorReports {
+    reports: Mutex<BTreeMap<String, ErrorReport>>,
+    updates: Broadcast<Vec<ErrorReport>>,
+}
+
+/// The errors to show in the overlay on pages served by the project server.
+pub static ERROR_REPORTS: ErrorReports = ErrorReports::new();
+
+impl ErrorReports {
+    pub const fn new() -> Self {
+        Self {
+            reports: Mutex::new(BTreeMap::new()),
+            updates: Broadcast::new("error overlay", SUBSCRIBER_CAPACITY),
+        }
+    }
+
+    /// The current errors.
+    pub fn current(&self) -> Vec<ErrorReport> {
+        let reports = self.reports.lock().unwrap_or_else(|e| e.into_inner());
+        reports.values().cloned().collect()
+    }
+
+    /// Report an error under a key, or clear it with `None`.
+    /// Subscribers are only notified if this changes the current errors,
+    /// and so is the desktop, if desktop notifications are enabled. See [`crate::notify`].
+    pub fn set(&self, key: &str, report: Option<ErrorReport>) {
+        let current = {
+            let mut reports = self.reports.lock().unwrap_or_else(|e| e.into_inner());
+            let changed = match report {
+                Some(report) if reports.get(key) == Some(&report) => false,
+                Some(report) => {
+                    notify::notify(&report.title, &report.output);
+                    reports.insert(key.to_string(), report);
+                    true
+                }
+                None => reports.remove(key).is_some(),
+            };
+            if !changed {
+                return;
+            }
+            reports.values().cloned().collect::<Vec<_>>()
+        };
+        debug!(
+            key,
+            n_reports = current.len(),
+            "Errors for overlay changed."
+        );
+        self.updates.send(current);
+    }
+
+    /// Whether any of the current errors is in a file. Only those files can be opened
+    /// in an editor from the overlay. See [`crate::editor`].
+    pub fn has_diagnostic_in(&self, file: &Path) -> bool {
+        let reports = self.reports.lock().unwrap_or_else(|e| e.into_inner());
+        reports.values().any(|report| {
+            report
+                .diagnostics
+                .iter()
+                .any(|diagnostic| diagnostic.file == file)
+        })
+    }
+
+    /// Subscribe to changes to the current errors. The subscription ends when the receiver is dropped.
+    pub fn subscribe(&self) -> Receiver<Vec<ErrorReport>> {
+        self.updates.subscribe()
+    }
+}
+
+impl Default for ErrorReports {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,80 @@
+//! URL path patterns, as used in `_headers` and `_redirects` files.
+//!
+//! In patterns, `*` matches anything, including slashes, and `:name` matches a single,
+//! non-empty path segment. What they match is captured, with `*` captured as `splat`.
+
+/// Name that what is matched by `*` is captured as.
+pub const SPLAT: &str = "splat";
+
+/// Match a URL path against a pattern, returning the captured placeholders if it matches.
+pub fn captures(pattern: &str, path: &str) -> Option<Vec<(String, String)>> {
+    let mut captured = vec![];
+    match_from(pattern, path, &mut captured).then_some(captured)
+}
+
+/// Check whether a URL path matches a pattern.
+pub fn matches(pattern: &str, path: &str) -> bool {
+    captures(pattern, path).is_some()
+}
+
+/// Substitute captured placeholders into a target, such as `/blog/:year/:splat`.
+pub fn substitute(target: &str, captured: &[(String, String)]) -> String {
+    let mut substituted = String::with_capacity(target.len());
+    let mut rest = target;
+    while let Some(pos) = rest.find(':') {
+        substituted.push_str(&rest[..pos]);
+        let after_colon = &rest[pos + 1..];
+        let name_len = after_colon
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(after_colon.len());
+        let name = &after_colon[..name_len];
+        match captured.iter().find(|(n, _)| n == name) {
+            Some((_, value)) if !name.is_empty() => substituted.push_str(value),
+            _ => {
+                substituted.push(':');
+                substituted.push_str(name);
+            }
+        }
+        rest = &after_colon[name_len..];
+    }
+    substituted.push_str(rest);
+    substituted
+}
+
+fn match_from(pattern: &str, path: &str, captured: &mut Vec<(String, String)>) -> bool {
+    let Some(c) = pattern.chars().next() else {
+        return path.is_empty();
+    };
+    let rest = &pattern[c.len_utf8()..];
+    match c {
+        '*' => {
+            let n_captured = captured.len();
+            for (i, _) in path.char_indices().chain([(path.len(), ' ')]) {
+                captured.push((SPLAT.to_string(), path[..i].to_string()));
+                if match_from(rest, &path[i..], captured) {
+                    return true;
+                }
+                captured.truncate(n_captured);
+            }
+            false
+        }
+        ':' => {
+            // A placeholder name runs until the end of the path segment in the pattern.
+            let name_len = rest.find('/').unwrap_or(rest.len());
+            let segment_len = path.find('/').unwrap_or(path.len());
+            if segment_len == 0 {
+                return false;
+            }
+            captured.push((
+                rest[..name_len].to_string(),
+                path[..segment_len].to_string(),
+            ));
+            if match_from(&rest[name_len..], &path[segment_len..], captured) {
+                return true;
+            }
+            captured.pop();
+            false
+        }
+        _ => path.starts_with(c) && match_from(rest, &path[c.len_utf8()..], captured),
+    }
+}
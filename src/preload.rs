@@ -0,0 +1,124 @@
+//! `Link: rel=preload` headers for the critical resources of HTML pages, so that preloading
+//! strategies can be tried out against real pages in development.
+//!
+//! The critical resources of a page are taken to be the stylesheets and the scripts that block
+//! rendering, found in the head of the document (or in the whole document if it has no head).
+//! Stylesheets for other media than screens, scripts that are `async` or `nomodule`,
+//! and resources that the page already preloads itself, are left out.
+//! Module scripts get `rel=modulepreload` rather than `rel=preload`.
+//!
+//! The headers are sent with the final response. Sending them ahead of it, in a `103 Early Hints`
+//! response, is not supported by the HTTP server library that we use.
+
+use crate::html::start_tags;
+use crate::url::encode_unsafe_chars;
+use hyper::header::HeaderValue;
+
+/// How a preloaded resource is going to be used by the page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    Style,
+    Script,
+    ModuleScript,
+}
+
+/// A resource of a page to preload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreloadLink {
+    /// URL of the resource, as written in the page.
+    pub href: String,
+    pub destination: Destination,
+}
+
+impl PreloadLink {
+    /// The link as an element of a `Link` header value.
+    fn to_link_value(&self) -> String {
+        let href = encode_unsafe_chars(&self.href).replace('>', "%3E");
+        match self.destination {
+            Destination::Style => format!("<{href}>; rel=preload; as=style"),
+            Destination::Script => format!("<{href}>; rel=preload; as=script"),
+            Destination::ModuleScript => format!("<{href}>; rel=modulepreload"),
+        }
+    }
+}
+
+/// Find the critical resources of an HTML document, in document order.
+pub fn critical_resources(html: &[u8]) -> Vec<PreloadLink> {
+    let mut links: Vec<PreloadLink> = vec![];
+    let mut already_preloaded = vec![];
+    for tag in start_tags(html, Some("head")) {
+        let attr = |name: &str| tag.attr(name);
+        let link = match tag.name.as_str() {
+            "link" => {
+                let rel = attr("rel").unwrap_or_default().to_ascii_lowercase();
+                let mut rel = rel.split_ascii_whitespace();
+                let Some(href) = attr("href") else {
+                    continue;
+                };
+                if rel
+                    .clone()
+                    .any(|rel| rel == "preload" || rel == "modulepreload")
+                {
+                    already_preloaded.push(href.to_string());
+                    continue;
+                }
+                // Stylesheets for other media, like print, do not block rendering.
+                let for_screen = attr("media").is_none_or(|media| {
+                    let media = media.trim().to_ascii_lowercase();
+                    media.is_empty() || media == "all" || media == "screen"
+                });
+                if !rel.any(|rel| rel == "stylesheet") || attr("disabled").is_some() || !for_screen
+                {
+                    continue;
+                }
+                PreloadLink {
+                    href: href.to_string(),
+                    destination: Destination::Style,
+                }
+            }
+            "script" => {
+                let Some(src) = attr("src") else {
+                    continue;
+                };
+                if attr("async").is_some() || attr("nomodule").is_some() {
+                    continue;
+                }
+                let destination = match attr("type").map(str::to_ascii_lowercase).as_deref() {
+                    None | Some("") | Some("text/javascript") | Some("application/javascript") => {
+                        Destination::Script
+                    }
+                    Some("module") => Destination::ModuleScript,
+                    // Not a script that the browser runs, like JSON data or a template.
+                    Some(_) => continue,
+                };
+                PreloadLink {
+                    href: src.to_string(),
+                    destination,
+                }
+            }
+            _ => continue,
+        };
+        if !link.href.is_empty()
+            && !link.href.starts_with("data:")
+            && !links.iter().any(|l| l.href == link.href)
+        {
+            links.push(link);
+        }
+    }
+    links.retain(|link| !already_preloaded.contains(&link.href));
+    links
+}
+
+/// The `Link` header value for the critical resources of an HTML document, if it has any.
+pub fn link_header_value(html: &[u8]) -> Option<HeaderValue> {
+    let links = critical_resources(html);
+    if links.is_empty() {
+        return None;
+    }
+    let value = links
+        .iter()
+        .map(PreloadLink::to_link_value)
+        .collect::<Vec<_>>()
+        .join(", ");
+    HeaderValue::try_from(value).ok()
+}
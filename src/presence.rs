@@ -0,0 +1,185 @@
+//! The reload clients that are connected, meaning the pages that are open in browsers,
+//! for the status web-ui to show which tabs on which devices are live.
+//!
+//! A client is registered for as long as its event stream is connected, and sends
+//! a heartbeat every so often. Clients that have not sent one in a while are left out,
+//! since their connection may be gone without the event stream having noticed yet.
+
+use crate::event::{self, Event};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// URL path, under the reserved path prefix, that the client script posts heartbeats to.
+pub const HEARTBEAT_URL_PATH: &str = "heartbeat";
+
+/// Largest heartbeat body accepted from the client script, in bytes.
+pub const MAX_HEARTBEAT_LEN: usize = 16 * 1024;
+
+/// Clients that have not sent a heartbeat for this long are considered gone.
+/// The client script sends one every 15 seconds.
+const HEARTBEAT_TIMEOUT_MS: u64 = 45_000;
+
+/// Longest URL or user agent kept, in bytes. Longer ones are truncated.
+const MAX_TEXT_LEN: usize = 2 * 1024;
+
+/// A connected reload client.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectedClient {
+    /// Random ID that the client picked, which stays the same when it reconnects.
+    pub client: String,
+    /// URL of the page, as last reported by the client.
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Milliseconds since the Unix epoch, when the client connected.
+    pub connected_ms: u64,
+    /// Milliseconds since the Unix epoch, when the client last sent a heartbeat.
+    pub last_heartbeat_ms: u64,
+    /// Whether the client has opted out of automatic reloads.
+    pub reloads_paused: bool,
+}
+
+/// Heartbeat, as posted by the client script.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Heartbeat {
+    pub client: String,
+    pub url: String,
+    #[serde(default)]
+    pub reloads_paused: bool,
+}
+
+/// The connected reload clients, by connection.
+#[derive(Debug)]
+pub struct Presence {
+    clients: Mutex<BTreeMap<u64, ConnectedClient>>,
+    next_connection: AtomicU64,
+}
+
+/// The clients connected to the project server.
+pub static PRESENCE: Presence = Presence::new();
+
+/// Registration of a connected client, which ends when this is dropped.
+#[derive(Debug)]
+pub struct Registration {
+    presence: &'static Presence,
+    connection: u64,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut clients = self
+            .presence
+            .clients
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        clients.remove(&self.connection);
+    }
+}
+
+impl Presence {
+    pub const fn new() -> Self {
+        Self {
+            clients: Mutex::new(BTreeMap::new()),
+            next_connection: AtomicU64::new(0),
+        }
+    }
+
+    /// Register a client whose event stream has connected.
+    ///
+    /// Clients reconnect under the same ID, so a client can briefly be registered for both
+    /// its old and its new connection. Only the most recent one is listed.
+    pub fn connect(
+        &'static self,
+        client: String,
+        url: String,
+        user_agent: Option<String>,
+    ) -> Registration {
+        let now = now_ms();
+        let connection = self.next_connection.fetch_add(1, Ordering::Relaxed);
+        let client = ConnectedClient {
+            client: truncated(client),
+            url: truncated(url),
+            user_agent: user_agent.map(truncated),
+            connected_ms: now,
+            last_heartbeat_ms: now,
+            reloads_paused: false,
+        };
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        // Clients that reconnect were connected all along.
+        if !clients.values().any(|c| c.client == client.client) {
+            event::publish_status(Event::ClientConnected {
+                client: client.client.clone(),
+                url: client.url.clone(),
+            });
+        }
+        clients.insert(connection, client);
+        Registration {
+            presence: self,
+            connection,
+        }
+    }
+
+    /// Note a heartbeat from a client. Returns whether the client is registered.
+    pub fn heartbeat(&self, heartbeat: Heartbeat) -> bool {
+        let now = now_ms();
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        let mut found = false;
+        for client in clients.values_mut() {
+            if client.client == heartbeat.client {
+                client.url = truncated(heartbeat.url.clone());
+                client.last_heartbeat_ms = now;
+                client.reloads_paused = heartbeat.reloads_paused;
+                found = true;
+            }
+        }
+        found
+    }
+
+    /// The clients that are live, in the order that they connected.
+    pub fn live(&self) -> Vec<ConnectedClient> {
+        let now = now_ms();
+        let clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        let mut live: Vec<ConnectedClient> = vec![];
+        for client in clients.values() {
+            if now.saturating_sub(client.last_heartbeat_ms) > HEARTBEAT_TIMEOUT_MS {
+                continue;
+            }
+            // Connections are numbered in order, so a later one replaces an earlier one.
+            live.retain(|c| c.client != client.client);
+            live.push(client.clone());
+        }
+        live
+    }
+}
+
+impl Default for Presence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn truncated(mut text: String) -> String {
+    if text.len() > MAX_TEXT_LEN {
+        let mut len = MAX_TEXT_LEN;
+        while !text.is_char_boundary(len) {
+            len -= 1;
+        }
+        text.truncate(len);
+        text.push('…');
+    }
+    text
+}
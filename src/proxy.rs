@@ -0,0 +1,213 @@
+//! Reverse proxying of requests under a URL path prefix to a backend server,
+//! so that the frontend can be served by http-horse while the API lives in a separate dev server.
+//!
+//! Rules are given like `/api=http://localhost:8000`. A request for `/api/users?page=2` is then
+//! forwarded to `http://localhost:8000/api/users?page=2`. If the target URL has a path,
+//! it replaces the prefix, so that with `/api=http://localhost:8000/v1`, the same request
+//! is forwarded to `http://localhost:8000/v1/users?page=2`.
+//!
+//! Method, headers and body are forwarded as they are, apart from hop-by-hop headers, and bodies
+//! are streamed in both directions. The `Host` header is set to that of the backend, with
+//! the original in `X-Forwarded-Host`. Each request is forwarded over a new connection,
+//! and only plain HTTP backends are supported.
+
+use crate::usage::counted;
+use bytes::Bytes;
+use http_body_util::{combinators::BoxBody, BodyExt};
+use hyper::{
+    body::Incoming,
+    client::conn::http1,
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    http::uri::{Authority, PathAndQuery},
+    Request, Response, Uri, Version,
+};
+use smol::net::TcpStream;
+use smol_hyper::rt::FuturesIo;
+use std::{io, net::IpAddr, str::FromStr};
+use thiserror::Error;
+use tracing::debug;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Expected a proxy rule on the form `/prefix=http://host:port`, got {0:?}")]
+    InvalidRule(String),
+    #[error(
+        "Invalid proxy target URL {0:?}. Expected an absolute URL like `http://localhost:8000`"
+    )]
+    InvalidTarget(String),
+    #[error("Unsupported scheme in proxy target URL {0:?}. Only `http` is supported")]
+    UnsupportedScheme(String),
+    #[error("Failed to connect to backend {0}")]
+    Connect(Authority, #[source] io::Error),
+    #[error("Failed to forward request to backend")]
+    Forward(#[from] hyper::Error),
+    #[error("Invalid URL for forwarded request")]
+    Uri(#[from] hyper::http::Error),
+}
+
+/// Headers that only apply to a single connection, and must not be forwarded.
+const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+
+/// Requests for URL paths under a prefix, forwarded to a backend server.
+#[derive(Debug, Clone)]
+pub struct ProxyRule {
+    /// URL path prefix, with leading slash and without trailing slash.
+    /// Empty if all requests are forwarded.
+    prefix: String,
+    authority: Authority,
+    /// Path that replaces the prefix, without trailing slash. Empty if the prefix is kept.
+    target_path: String,
+}
+
+impl ProxyRule {
+    /// Whether this rule applies to a URL path (with leading slash).
+    /// The prefix must match whole path segments.
+    pub fn matches(&self, url_path: &str) -> bool {
+        url_path
+            .strip_prefix(&self.prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    /// The URL that a request for the given URL is forwarded to, in origin form.
+    fn forwarded_path_and_query(&self, uri: &Uri) -> Result<PathAndQuery, Error> {
+        let path = uri.path();
+        let path = if self.target_path.is_empty() {
+            path.to_string()
+        } else {
+            let rest = path.strip_prefix(&self.prefix).unwrap_or(path);
+            format!("{}{rest}", self.target_path)
+        };
+        let path = if path.is_empty() {
+            "/".to_string()
+        } else {
+            path
+        };
+        let path_and_query = match uri.query() {
+            Some(query) => format!("{path}?{query}"),
+            None => path,
+        };
+        PathAndQuery::try_from(path_and_query).map_err(|e| Error::Uri(e.into()))
+    }
+
+    /// Forward a request to the backend, and return its response.
+    pub async fn forward(
+        &self,
+        req: Request<Incoming>,
+        peer_ip: IpAddr,
+    ) -> Result<Response<BoxBody<Bytes, io::Error>>, Error> {
+        let (mut parts, body) = req.into_parts();
+        let original_authority = parts.uri.authority().cloned();
+        parts.uri = Uri::from(self.forwarded_path_and_query(&parts.uri)?);
+        // The backend is always spoken to over HTTP/1.1, whatever the client used.
+        parts.version = Version::HTTP_11;
+        remove_hop_by_hop_headers(&mut parts.headers);
+        // Requests over HTTP/2 carry the host in the URI rather than in a header.
+        if let Some(host) = parts
+            .headers
+            .remove(header::HOST)
+            .or_else(|| original_authority.as_ref().map(authority_value))
+        {
+            parts.headers.insert(X_FORWARDED_HOST, host);
+        }
+        parts
+            .headers
+            .insert(header::HOST, authority_value(&self.authority));
+        let forwarded_for = match parts.headers.get(X_FORWARDED_FOR) {
+            Some(existing) => format!(
+                "{}, {peer_ip}",
+                String::from_utf8_lossy(existing.as_bytes())
+            ),
+            None => peer_ip.to_string(),
+        };
+        if let Ok(forwarded_for) = HeaderValue::try_from(forwarded_for) {
+            parts.headers.insert(X_FORWARDED_FOR, forwarded_for);
+        }
+
+        let host = self
+            .authority
+            .host()
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let port = self.authority.port_u16().unwrap_or(80);
+        let stream = TcpStream::connect((host, port))
+            .await
+            .map_err(|e| Error::Connect(self.authority.clone(), e))?;
+        let _ = stream.set_nodelay(true);
+        let (mut sender, conn) = http1::handshake(FuturesIo::new(stream)).await?;
+        // The connection must be driven until the response body has been read in full,
+        // which is after we have returned the response.
+        smol::spawn(counted(async move {
+            if let Err(e) = conn.await {
+                debug!(err = ?e, "Proxied connection error");
+            }
+        }))
+        .detach();
+
+        let resp = sender
+            .send_request(Request::from_parts(parts, body))
+            .await?;
+        let (mut parts, body) = resp.into_parts();
+        remove_hop_by_hop_headers(&mut parts.headers);
+        Ok(Response::from_parts(
+            parts,
+            body.map_err(io::Error::other).boxed(),
+        ))
+    }
+}
+
+impl FromStr for ProxyRule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, target) = s
+            .split_once('=')
+            .filter(|(prefix, _)| prefix.starts_with('/'))
+            .ok_or_else(|| Error::InvalidRule(s.to_string()))?;
+        let target_uri = target
+            .parse::<Uri>()
+            .map_err(|_| Error::InvalidTarget(target.to_string()))?;
+        match target_uri.scheme_str() {
+            Some("http") => {}
+            Some(_) => return Err(Error::UnsupportedScheme(target.to_string())),
+            None => return Err(Error::InvalidTarget(target.to_string())),
+        }
+        let authority = target_uri
+            .authority()
+            .cloned()
+            .ok_or_else(|| Error::InvalidTarget(target.to_string()))?;
+        Ok(Self {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            authority,
+            target_path: target_uri.path().trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+fn authority_value(authority: &Authority) -> HeaderValue {
+    HeaderValue::from_str(authority.as_str()).expect("authority is a valid header value")
+}
+
+fn remove_hop_by_hop_headers(headers: &mut HeaderMap) {
+    let listed: Vec<HeaderName> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_str(name.trim()).ok())
+        .collect();
+    for name in HOP_BY_HOP_HEADERS.iter().chain(&listed) {
+        headers.remove(name);
+    }
+}
@@ -0,0 +1,159 @@
+//! Recording of responses from proxied backends, as fixture files for [`crate::mocks`].
+//!
+//! Each response that is proxied in full is written to the recording directory, as a fixture
+//! file named after the request method in the directory for the URL path, next to a `.headers`
+//! file with its status and headers. Serving the recording directory with `--mocks` then
+//! replays the responses, so that the frontend can be used without the backend.
+//!
+//! Only the latest response for each method and URL path is kept. Query strings are not part
+//! of the recording, as fixtures are looked up by URL path alone.
+
+use crate::mime::extension_for_mime_type;
+use crate::mocks::HEADERS_EXTENSION;
+use async_stream::stream;
+use bytes::Bytes;
+use http_body_util::{combinators::BoxBody, BodyExt, StreamBody};
+use hyper::{body::Body, header, http::response::Parts, Method, Response};
+use smol::stream::StreamExt;
+use std::{fmt::Write as _, io, path::PathBuf};
+use tracing::{info, warn};
+
+/// Extension of fixture files for responses of a type that we have no extension for.
+const DEFAULT_EXTENSION: &str = "bin";
+
+/// Headers that are not recorded, because they are set anew when the recording is replayed.
+const SKIPPED_HEADERS: [header::HeaderName; 3] = [
+    header::CONTENT_LENGTH,
+    header::DATE,
+    header::TRANSFER_ENCODING,
+];
+
+/// Records responses as fixture files in a directory.
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    dir: PathBuf,
+}
+
+impl Recorder {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Record a response to a request with the given method, for the given decoded URL path.
+    /// The body is passed on as it is read, and the recording is written once it has been read
+    /// in full. Responses to HEAD requests, and for URL paths that cannot be stored, are not recorded.
+    pub fn record(
+        &self,
+        method: &Method,
+        url_path: &str,
+        resp: Response<BoxBody<Bytes, io::Error>>,
+    ) -> Response<BoxBody<Bytes, io::Error>> {
+        let Some(dir) = self
+            .fixture_dir(url_path)
+            .filter(|_| method != Method::HEAD)
+        else {
+            return resp;
+        };
+        let (parts, body) = resp.into_parts();
+        let ext = parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(extension_for_mime_type)
+            .unwrap_or(DEFAULT_EXTENSION);
+        let recording = Recording {
+            dir,
+            method: method.to_string(),
+            fixture_name: format!("{method}.{ext}"),
+            headers_name: format!("{method}.{HEADERS_EXTENSION}"),
+            headers_text: headers_text(&parts),
+        };
+        let stream = stream! {
+            let mut body = body;
+            let mut recorded = Vec::new();
+            while let Some(frame) = body.frame().await {
+                let frame = frame?;
+                if let Some(data) = frame.data_ref() {
+                    recorded.extend_from_slice(data);
+                }
+                // The body is not necessarily polled again once the last of it has been sent,
+                // so the recording is saved before the last frame is passed on.
+                if body.is_end_stream() {
+                    recording.save(&recorded).await;
+                    yield Ok(frame);
+                    return;
+                }
+                yield Ok(frame);
+            }
+            recording.save(&recorded).await;
+        };
+        Response::from_parts(parts, BodyExt::boxed(StreamBody::new(stream)))
+    }
+
+    /// The directory for the fixture files of a URL path,
+    /// if all of its segments can be stored as directory names.
+    fn fixture_dir(&self, url_path: &str) -> Option<PathBuf> {
+        let mut dir = self.dir.clone();
+        for segment in url_path.split('/').filter(|segment| !segment.is_empty()) {
+            if segment.starts_with('.') || segment.contains(['/', '\\']) {
+                return None;
+            }
+            dir.push(segment);
+        }
+        Some(dir)
+    }
+}
+
+/// Status and headers of a response, as written to `.headers` files.
+fn headers_text(parts: &Parts) -> String {
+    let mut text = format!("Status: {}\n", parts.status);
+    for (name, value) in &parts.headers {
+        if SKIPPED_HEADERS.contains(name) {
+            continue;
+        }
+        if let Ok(value) = value.to_str() {
+            let _ = writeln!(text, "{name}: {value}");
+        }
+    }
+    text
+}
+
+/// Where and how a response is recorded.
+struct Recording {
+    dir: PathBuf,
+    method: String,
+    fixture_name: String,
+    headers_name: String,
+    headers_text: String,
+}
+
+impl Recording {
+    async fn save(&self, body: &[u8]) {
+        match self.write(body).await {
+            Ok(()) => info!(fixture = ?self.dir.join(&self.fixture_name), "Recorded response."),
+            Err(e) => warn!(err = ?e, dir = ?self.dir, "Failed to record response."),
+        }
+    }
+
+    async fn write(&self, body: &[u8]) -> io::Result<()> {
+        smol::fs::create_dir_all(&self.dir).await?;
+        // Remove earlier recordings with other extensions, which would otherwise be found first.
+        let mut entries = smol::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = entries.next().await {
+            let name = entry?.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if name
+                .split_once('.')
+                .is_some_and(|(stem, _)| stem == self.method)
+                && name != self.fixture_name
+                && name != self.headers_name
+            {
+                smol::fs::remove_file(self.dir.join(name)).await?;
+            }
+        }
+        smol::fs::write(self.dir.join(&self.fixture_name), body).await?;
+        smol::fs::write(self.dir.join(&self.headers_name), &self.headers_text).await
+    }
+}
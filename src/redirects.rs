@@ -0,0 +1,174 @@
+//! Redirect and rewrite rules for URL paths matching a pattern.
+//!
+//! Rules come from the config file, and from a Netlify-style `_redirects` file at the top
+//! of each project directory. Each line of a `_redirects` file holds a rule,
+//! with a path pattern, a target, and optionally a status code:
+//!
+//! ```text
+//! # Moved pages
+//! /old-blog/*     /blog/:splat     301
+//! /docs/:page/    /docs/:page      308
+//! # Single page app
+//! /app/*          /app/index.html  200
+//! ```
+//!
+//! Status 200 means that the target is served in place of the requested path (a rewrite),
+//! while 3xx status codes redirect the client to the target. The default status is 301.
+//! Rules only apply to paths that do not exist in the project directory, unless the status
+//! is followed by `!`. The first matching rule wins.
+//!
+//! See [`crate::pattern`] for the syntax of patterns, and what is captured by them.
+
+use crate::pattern;
+use serde::Deserialize;
+use std::path::Path;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// Name of the file with redirect rules at the top of a project directory.
+pub const REDIRECTS_FILE_NAME: &str = "_redirects";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Line {line}: Expected a rule on the form `FROM TO [STATUS]`, got {text:?}")]
+    InvalidRule { line: usize, text: String },
+    #[error("Unsupported status {0:?}. Supported are 200, 301, 302, 303, 307 and 308")]
+    UnsupportedStatus(String),
+}
+
+/// A redirect or rewrite of URL paths matching a pattern.
+#[derive(Debug, Clone)]
+pub struct RedirectRule {
+    /// URL path pattern, with leading slash.
+    pub from: String,
+    /// Target URL path or URL, which may refer to placeholders captured by the pattern.
+    pub to: String,
+    /// 200 for rewrites, and a 3xx status code for redirects.
+    pub status: u16,
+    /// Whether the rule also applies to paths that exist in the project directory.
+    pub force: bool,
+}
+
+/// A redirect rule as written in the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedirectRuleConfig {
+    pub from: String,
+    pub to: String,
+    #[serde(default = "default_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub force: bool,
+}
+
+fn default_status() -> u16 {
+    301
+}
+
+/// What a request is to be answered with, according to a matching rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleOutcome {
+    /// Redirect the client to the given location, with the given status code.
+    Redirect { location: String, status: u16 },
+    /// Serve the given URL path in place of the requested one.
+    Rewrite(String),
+}
+
+impl RedirectRule {
+    /// Apply this rule to a URL path (with leading slash), if it matches.
+    pub fn apply(&self, url_path: &str) -> Option<RuleOutcome> {
+        let captured = pattern::captures(&self.from, url_path)?;
+        let target = pattern::substitute(&self.to, &captured);
+        Some(if self.status == 200 {
+            RuleOutcome::Rewrite(target)
+        } else {
+            RuleOutcome::Redirect {
+                location: target,
+                status: self.status,
+            }
+        })
+    }
+}
+
+impl TryFrom<RedirectRuleConfig> for RedirectRule {
+    type Error = Error;
+
+    fn try_from(config: RedirectRuleConfig) -> Result<Self, Self::Error> {
+        check_status(config.status)?;
+        Ok(RedirectRule {
+            from: config.from,
+            to: config.to,
+            status: config.status,
+            force: config.force,
+        })
+    }
+}
+
+fn check_status(status: u16) -> Result<u16, Error> {
+    match status {
+        200 | 301 | 302 | 303 | 307 | 308 => Ok(status),
+        _ => Err(Error::UnsupportedStatus(status.to_string())),
+    }
+}
+
+/// Parse the contents of a `_redirects` file.
+pub fn parse_redirects_file(contents: &str) -> Result<Vec<RedirectRule>, Error> {
+    let mut rules = vec![];
+    for (i, line) in contents.lines().enumerate() {
+        let text = line.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let invalid_rule = || Error::InvalidRule {
+            line: i + 1,
+            text: text.to_string(),
+        };
+        let fields = text.split_whitespace().collect::<Vec<_>>();
+        let (from, to, status) = match fields.as_slice() {
+            [from, to] => (from, to, "301"),
+            [from, to, status] => (from, to, *status),
+            _ => return Err(invalid_rule()),
+        };
+        let (status, force) = match status.strip_suffix('!') {
+            Some(status) => (status, true),
+            None => (status, false),
+        };
+        let status = status
+            .parse()
+            .map_err(|_| Error::UnsupportedStatus(status.to_string()))
+            .and_then(check_status)?;
+        rules.push(RedirectRule {
+            from: from.to_string(),
+            to: to.to_string(),
+            status,
+            force,
+        });
+    }
+    Ok(rules)
+}
+
+/// Load the `_redirects` file at the top of a project directory, if there is one.
+///
+/// A missing file means no rules. A file that fails to parse is reported, and gives no rules,
+/// so that a typo made while editing the file does not take down the server.
+pub fn load_redirects_file(project_dir: &Path) -> Vec<RedirectRule> {
+    let fpath = project_dir.join(REDIRECTS_FILE_NAME);
+    let contents = match std::fs::read_to_string(&fpath) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return vec![],
+        Err(e) => {
+            warn!(err = ?e, ?fpath, "Failed to read redirects file.");
+            return vec![];
+        }
+    };
+    match parse_redirects_file(&contents) {
+        Ok(rules) => {
+            debug!(?fpath, n_rules = rules.len(), "Loaded redirects file.");
+            rules
+        }
+        Err(e) => {
+            warn!(err = %e, ?fpath, "Failed to parse redirects file. Ignoring it.");
+            vec![]
+        }
+    }
+}
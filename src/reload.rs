@@ -0,0 +1,266 @@
+//! The reload channel carries changes to the served project directories
+//! to the pages that are open in web browsers, as [`Event`]s with URL paths.
+//!
+//! Pages served by the project server get a small client script injected into them,
+//! which subscribes to the reload channel through an event stream on a reserved URL path,
+//! and reloads the page (or the part of it that is affected) when something changes.
+//!
+//! Changes to stylesheets and images are sent as `css-update` and `asset-update` events rather
+//! than as plain messages, so that the client can swap them in place, without losing the state
+//! of the page. Changes to JavaScript modules are sent as `module-update` events, which pages that
+//! use native ES modules can opt in to handling without a reload, through a small hot module
+//! replacement runtime served at a reserved URL path. Pages that do not handle them are reloaded.
+//!
+//! Reloads can be gated on builds, in which case changes to build output are held until
+//! the build has finished, so that pages are never reloaded with half-written output.
+//! They can also be delayed until changes have stopped coming in for a while, so that a build
+//! that writes many files results in a single batch of changes, rather than a reload per file.
+
+use crate::{
+    broadcast::Broadcast,
+    event::{self, Event},
+    stats::ReloadStats,
+    usage::counted,
+};
+use smol::{channel::Receiver, Timer};
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::debug;
+
+/// URL path prefix reserved for http-horse on the project server, without leading slash.
+/// Files in the project directory are never served under this prefix.
+pub const RESERVED_PATH_PREFIX: &str = ".http-horse/";
+
+/// URL path of the reload client script.
+pub const CLIENT_SCRIPT_URL_PATH: &str = "/.http-horse/client.js";
+
+/// URL path of the hot module replacement runtime, an ES module that lets modules of the page
+/// register handlers for changes to themselves.
+pub const HMR_RUNTIME_URL_PATH: &str = "/.http-horse/hmr.js";
+
+/// Name of the event stream event for changes to stylesheets.
+pub const CSS_UPDATE_EVENT: &str = "css-update";
+
+/// Name of the event stream event for changes to images.
+pub const ASSET_UPDATE_EVENT: &str = "asset-update";
+
+/// Name of the event stream event for changes to JavaScript modules.
+pub const MODULE_UPDATE_EVENT: &str = "module-update";
+
+/// Name of the event stream event for changes to the errors shown in the error overlay.
+/// See [`crate::overlay`].
+pub const ERROR_OVERLAY_EVENT: &str = "error-overlay";
+
+/// Name of the event stream event for scrolls, clicks and navigation mirrored from other
+/// clients. See [`crate::sync`].
+pub const SYNC_EVENT: &str = "sync";
+
+/// Extensions of the image files that are swapped in place, in lowercase.
+const IMAGE_EXTENSIONS: [&str; 7] = ["avif", "gif", "jpeg", "jpg", "png", "svg", "webp"];
+
+/// Extensions of the JavaScript modules that can be replaced in place, in lowercase.
+const MODULE_EXTENSIONS: [&str; 2] = ["js", "mjs"];
+
+/// How many events a subscriber can fall behind by before it starts missing events.
+const SUBSCRIBER_CAPACITY: usize = 64;
+
+/// Name of the event stream event that an event is sent as on the reload channel,
+/// or `None` for plain messages.
+///
+/// Stylesheets, images and JavaScript modules that are modified, or that are replaced by renaming
+/// another file over them, as build tools that write files atomically do, can be swapped in place.
+pub fn event_name(event: &Event) -> Option<&'static str> {
+    let path = match event {
+        Event::FileModified {
+            path,
+            is_dir: false,
+        }
+        | Event::Renamed {
+            path,
+            is_dir: false,
+            ..
+        } => path,
+        _ => return None,
+    };
+    let (_, ext) = path.rsplit_once('.')?;
+    let ext = ext.to_ascii_lowercase();
+    if ext == "css" {
+        Some(CSS_UPDATE_EVENT)
+    } else if IMAGE_EXTENSIONS.contains(&ext.as_str()) {
+        Some(ASSET_UPDATE_EVENT)
+    } else if MODULE_EXTENSIONS.contains(&ext.as_str()) {
+        Some(MODULE_UPDATE_EVENT)
+    } else {
+        None
+    }
+}
+
+/// Broadcasts reload events to all subscribers.
+#[derive(Debug)]
+pub struct ReloadChannel {
+    events: Broadcast<Event>,
+    held: Mutex<HeldEvents>,
+    stats: Mutex<ReloadStats>,
+}
+
+/// Changes to build output that are held back, until the build has finished
+/// when reloads are gated on builds, or until changes have stopped coming in otherwise.
+#[derive(Debug)]
+struct HeldEvents {
+    events: Vec<Event>,
+    gated_on_builds: bool,
+    delay: Duration,
+    /// Incremented for each held event, so that a delayed release can tell
+    /// whether more changes have come in since it was scheduled.
+    generation: u64,
+}
+
+/// The reload channel of the project server.
+pub static RELOAD_CHANNEL: ReloadChannel = ReloadChannel::new();
+
+impl ReloadChannel {
+    pub const fn new() -> Self {
+        Self {
+            events: Broadcast::new("reload channel", SUBSCRIBER_CAPACITY),
+            held: Mutex::new(HeldEvents {
+                events: Vec::new(),
+                gated_on_builds: false,
+                delay: Duration::ZERO,
+                generation: 0,
+            }),
+            stats: Mutex::new(ReloadStats::new()),
+        }
+    }
+
+    /// Subscribe to reload events. The subscription ends when the receiver is dropped.
+    pub fn subscribe(&self) -> Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Hold changes to build output from now on, until [`Self::build_finished`] is called.
+    pub fn gate_on_builds(&self) {
+        let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+        held.gated_on_builds = true;
+    }
+
+    /// Hold changes to build output until no further changes have come in for the given delay.
+    pub fn set_delay(&self, delay: Duration) {
+        let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+        held.delay = delay;
+    }
+
+    /// Send a reload event for a change to build output to all subscribers, or hold it
+    /// until the build has finished or the reload delay has passed, as configured.
+    pub fn publish_build_output(&'static self, event: Event) {
+        let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+        if !held.gated_on_builds && held.delay.is_zero() {
+            drop(held);
+            self.publish(event);
+            return;
+        }
+        debug!(?event, "Holding reload event.");
+        if held.gated_on_builds && held.events.is_empty() {
+            event::publish_status(Event::BuildStarted);
+        }
+        if !held.events.contains(&event) {
+            held.events.push(event);
+        }
+        held.generation += 1;
+        if !held.gated_on_builds {
+            let (delay, generation) = (held.delay, held.generation);
+            smol::spawn(counted(async move {
+                Timer::after(delay).await;
+                self.release_if_quiet(generation);
+            }))
+            .detach();
+        }
+    }
+
+    /// Send the reload events that were held while the build was running.
+    pub fn build_finished(&self) {
+        let events = {
+            let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+            std::mem::take(&mut held.events)
+        };
+        debug!(
+            n_events = events.len(),
+            "Build has finished. Sending held reload events."
+        );
+        event::publish_status(Event::BuildFinished);
+        self.publish_all(events);
+    }
+
+    /// Send the held reload events, unless more changes have come in since the given generation.
+    fn release_if_quiet(&self, generation: u64) {
+        let events = {
+            let mut held = self.held.lock().unwrap_or_else(|e| e.into_inner());
+            if held.generation != generation {
+                return;
+            }
+            std::mem::take(&mut held.events)
+        };
+        debug!(
+            n_events = events.len(),
+            "No more changes within reload delay. Sending held reload events."
+        );
+        self.publish_all(events);
+    }
+
+    fn publish_all(&self, events: Vec<Event>) {
+        for event in events {
+            self.publish(event);
+        }
+    }
+
+    /// Send a reload event to all subscribers, dropping subscribers that have gone away.
+    pub fn publish(&self, event: Event) {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let mut stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        stats.record(event_name(&event), now_ms);
+        drop(stats);
+        self.events.send(event);
+    }
+
+    /// The reloads that have been sent so far.
+    pub fn stats(&self) -> ReloadStats {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+impl Default for ReloadChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Inject the reload client script into an HTML document.
+///
+/// The script tag is inserted before the closing body tag when there is one,
+/// and appended to the end of the document otherwise. The URL path of the page, if given,
+/// lets the client subscribe to only the changes that affect the page.
+/// See [`crate::deps`].
+pub fn inject_client_script(html: &mut Vec<u8>, page_url_path: Option<&str>) {
+    let mut attrs = String::new();
+    if let Some(page_url_path) = page_url_path {
+        let page_url_path = page_url_path.replace('&', "&amp;").replace('"', "&quot;");
+        attrs.push_str(&format!(" data-page=\"{page_url_path}\""));
+    }
+    if crate::sync::is_enabled() {
+        attrs.push_str(" data-sync");
+    }
+    if crate::editor::is_enabled() {
+        attrs.push_str(" data-editor");
+    }
+    let script_tag = format!("<script src={CLIENT_SCRIPT_URL_PATH}{attrs}></script>\n");
+    let closing_body_tag = b"</body";
+    let pos = html
+        .windows(closing_body_tag.len())
+        .rposition(|w| w.eq_ignore_ascii_case(closing_body_tag))
+        .unwrap_or(html.len());
+    html.splice(pos..pos, script_tag.into_bytes());
+}
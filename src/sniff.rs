@@ -0,0 +1,106 @@
+//! Classification of file contents as text or binary, by looking at the first bytes of them.
+//!
+//! File name extensions say what most files in web projects are, but not all: files like
+//! `LICENSE` or `CNAME` have no extension but are text, and text files are not always in UTF-8.
+//! Tracked files are classified as they are read during scans, and what they are classified as
+//! decides the charset parameter they are served with, whether they are compressed,
+//! and whether diffs of them are captured.
+
+use crate::compression::is_compressible;
+use crate::mime::DEFAULT_MIME_TYPE;
+use serde::Serialize;
+
+/// Bytes at the start of a file that it is classified by.
+pub const SNIFF_LEN: usize = 8 * 1024;
+
+/// What the contents of a file are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ContentClass {
+    /// Text in UTF-8, which includes ASCII.
+    Utf8Text,
+    /// Text in another encoding, like UTF-16 or a legacy 8-bit one.
+    OtherText,
+    Binary,
+}
+
+/// Signatures of binary formats, by the bytes that files of them start with.
+const MAGIC: &[&[u8]] = &[
+    b"\x89PNG\r\n\x1a\n",
+    b"\xff\xd8\xff",
+    b"GIF87a",
+    b"GIF89a",
+    b"%PDF-",
+    b"PK\x03\x04",
+    b"\x1f\x8b",
+    b"\x28\xb5\x2f\xfd",
+    b"\0asm",
+    b"wOFF",
+    b"wOF2",
+    b"\0\x01\0\0",
+    b"OggS",
+    b"fLaC",
+    b"\x1a\x45\xdf\xa3",
+];
+
+/// Classify the contents of a file by its first bytes, up to [`SNIFF_LEN`] of them.
+pub fn classify(head: &[u8]) -> ContentClass {
+    let head = &head[..head.len().min(SNIFF_LEN)];
+    if head.starts_with(b"\xef\xbb\xbf") {
+        return ContentClass::Utf8Text;
+    }
+    if head.starts_with(b"\xff\xfe") || head.starts_with(b"\xfe\xff") {
+        return ContentClass::OtherText;
+    }
+    if MAGIC.iter().any(|magic| head.starts_with(magic))
+        || head.get(4..8) == Some(&b"ftyp"[..])
+        || head.contains(&0)
+    {
+        return ContentClass::Binary;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => return ContentClass::Utf8Text,
+        // A multibyte character may have been cut off at the end of what we looked at.
+        Err(e) if e.error_len().is_none() => {
+            return ContentClass::Utf8Text;
+        }
+        Err(_) => {}
+    }
+    // Text in legacy 8-bit encodings has few control characters other than whitespace.
+    let n_control = head
+        .iter()
+        .filter(|&&b| (b < 0x20 && !b"\t\n\x0c\r\x1b".contains(&b)) || b == 0x7f)
+        .count();
+    if n_control * 10 > head.len() {
+        ContentClass::Binary
+    } else {
+        ContentClass::OtherText
+    }
+}
+
+/// The content type to serve a file with, given the MIME type that its file name extension
+/// maps to and what its contents were classified as.
+///
+/// Files whose extension says nothing about them are served as plain text if they are UTF-8 text.
+/// The UTF-8 charset parameter is kept only for files whose contents are UTF-8 text, and left out
+/// for others, so that browsers go by the byte order mark or sniff the encoding themselves.
+pub fn content_type_for(mime_type: &'static str, class: ContentClass) -> &'static str {
+    match class {
+        ContentClass::Utf8Text if mime_type == DEFAULT_MIME_TYPE => "text/plain; charset=utf-8",
+        ContentClass::Utf8Text => mime_type,
+        ContentClass::OtherText | ContentClass::Binary => {
+            mime_type.split(';').next().unwrap_or(mime_type).trim_end()
+        }
+    }
+}
+
+/// Check whether contents of a given content type and class benefit from compression.
+///
+/// Text always does. Binary contents do if their content type is one that does,
+/// like WebAssembly, and not if it is unknown.
+pub fn is_compressible_class(content_type: &str, class: ContentClass) -> bool {
+    match class {
+        ContentClass::Utf8Text | ContentClass::OtherText => true,
+        ContentClass::Binary => is_compressible(content_type),
+    }
+}
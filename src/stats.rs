@@ -0,0 +1,192 @@
+//! Statistics over the recent history of changes, reloads and builds, to help spot build loops,
+//! meaning builds that keep rebuilding because they write to what they watch,
+//! and other pathological rebuild behavior.
+
+use crate::{build::BuildRun, journal::JournalEntry};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    path::PathBuf,
+};
+
+/// Longest window that statistics are computed over, in minutes.
+pub const MAX_WINDOW_MINUTES: u64 = 24 * 60;
+
+/// Number of reload times kept, for the reloads per minute.
+const MAX_RELOAD_TIMES: usize = 10_000;
+
+/// Reloads sent on the reload channel since http-horse was started.
+#[derive(Debug, Default, Clone)]
+pub struct ReloadStats {
+    /// By event stream event name, with plain reloads under `reload`.
+    counts: BTreeMap<&'static str, u64>,
+    /// Milliseconds since the Unix epoch, oldest first.
+    times_ms: VecDeque<u64>,
+}
+
+impl ReloadStats {
+    pub const fn new() -> Self {
+        Self {
+            counts: BTreeMap::new(),
+            times_ms: VecDeque::new(),
+        }
+    }
+
+    /// Count a reload event sent as the given event stream event, or as a plain message.
+    pub fn record(&mut self, event_name: Option<&'static str>, now_ms: u64) {
+        *self
+            .counts
+            .entry(event_name.unwrap_or("reload"))
+            .or_default() += 1;
+        if self.times_ms.len() == MAX_RELOAD_TIMES {
+            self.times_ms.pop_front();
+        }
+        self.times_ms.push_back(now_ms);
+    }
+}
+
+/// A file that changed often.
+#[derive(Debug, Clone, Serialize)]
+pub struct HotFile {
+    pub path: PathBuf,
+    pub changes: u64,
+    /// Milliseconds since the Unix epoch.
+    pub last_changed_ms: u64,
+}
+
+/// Runs of the build pipeline that started within the window.
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildStats {
+    /// Finished runs.
+    pub runs: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    /// Runs that were stopped for source changes that came in as they ran.
+    pub cancelled: u64,
+    /// Share of the runs that succeeded, out of those that were not cancelled,
+    /// or `None` if there were none.
+    pub success_rate: Option<f64>,
+    pub mean_duration_ms: Option<u64>,
+    pub max_duration_ms: Option<u64>,
+    /// Finished runs in each minute of the window, oldest minute first.
+    pub runs_per_minute: Vec<u64>,
+}
+
+/// Statistics over the last few minutes.
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    pub window_minutes: u64,
+    /// Changes within the window.
+    pub changes: u64,
+    /// Changes in each minute of the window, oldest minute first.
+    pub changes_per_minute: Vec<u64>,
+    /// Reloads in each minute of the window, oldest minute first.
+    pub reloads_per_minute: Vec<u64>,
+    /// The files that changed most often within the window, most often first.
+    pub hot_files: Vec<HotFile>,
+    /// Reloads since http-horse was started, by event stream event name.
+    pub reloads: BTreeMap<&'static str, u64>,
+    /// Builds within the window, if there is a build pipeline.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub builds: Option<BuildStats>,
+}
+
+impl Stats {
+    /// Compute statistics over the given journal entries, reloads and build runs, for the minutes
+    /// up to `now_ms`. Entries and runs from outside the window are ignored.
+    pub fn new(
+        entries: &[JournalEntry],
+        reloads: &ReloadStats,
+        build_runs: Option<&[BuildRun]>,
+        now_ms: u64,
+        window_minutes: u64,
+        n_hot_files: usize,
+    ) -> Self {
+        let window_minutes = window_minutes.clamp(1, MAX_WINDOW_MINUTES);
+        // Index of the minute that a time falls in, with the current minute last.
+        let minute_of = |timestamp_ms: u64| {
+            let minutes_ago = now_ms.saturating_sub(timestamp_ms) / 60_000;
+            (minutes_ago < window_minutes).then(|| (window_minutes - 1 - minutes_ago) as usize)
+        };
+
+        let mut changes = 0;
+        let mut changes_per_minute = vec![0; window_minutes as usize];
+        let mut hot_files: HashMap<&PathBuf, HotFile> = HashMap::new();
+        for entry in entries {
+            let Some(minute) = minute_of(entry.timestamp_ms) else {
+                continue;
+            };
+            changes += 1;
+            changes_per_minute[minute] += 1;
+            if entry.is_dir {
+                continue;
+            }
+            let hot_file = hot_files.entry(&entry.path).or_insert_with(|| HotFile {
+                path: entry.path.clone(),
+                changes: 0,
+                last_changed_ms: 0,
+            });
+            hot_file.changes += 1;
+            hot_file.last_changed_ms = hot_file.last_changed_ms.max(entry.timestamp_ms);
+        }
+        let mut hot_files = hot_files.into_values().collect::<Vec<_>>();
+        hot_files.sort_by(|a, b| {
+            b.changes
+                .cmp(&a.changes)
+                .then(b.last_changed_ms.cmp(&a.last_changed_ms))
+        });
+        hot_files.truncate(n_hot_files);
+
+        let mut reloads_per_minute = vec![0; window_minutes as usize];
+        for minute in reloads.times_ms.iter().filter_map(|&t| minute_of(t)) {
+            reloads_per_minute[minute] += 1;
+        }
+
+        let builds = build_runs.map(|build_runs| {
+            let mut builds = BuildStats {
+                runs: 0,
+                succeeded: 0,
+                failed: 0,
+                cancelled: 0,
+                success_rate: None,
+                mean_duration_ms: None,
+                max_duration_ms: None,
+                runs_per_minute: vec![0; window_minutes as usize],
+            };
+            let mut total_duration_ms = 0;
+            for build_run in build_runs {
+                let (Some(minute), Some(duration_ms)) =
+                    (minute_of(build_run.started_ms), build_run.duration_ms)
+                else {
+                    continue;
+                };
+                builds.runs += 1;
+                builds.runs_per_minute[minute] += 1;
+                if build_run.success {
+                    builds.succeeded += 1;
+                } else if build_run.cancelled {
+                    builds.cancelled += 1;
+                } else {
+                    builds.failed += 1;
+                }
+                total_duration_ms += duration_ms;
+                builds.max_duration_ms = builds.max_duration_ms.max(Some(duration_ms));
+            }
+            let n_completed = builds.succeeded + builds.failed;
+            builds.success_rate =
+                (n_completed > 0).then(|| builds.succeeded as f64 / n_completed as f64);
+            builds.mean_duration_ms = (builds.runs > 0).then(|| total_duration_ms / builds.runs);
+            builds
+        });
+
+        Self {
+            window_minutes,
+            changes,
+            changes_per_minute,
+            reloads_per_minute,
+            hot_files,
+            reloads: reloads.counts.clone(),
+            builds,
+        }
+    }
+}
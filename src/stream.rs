@@ -0,0 +1,93 @@
+//! Adapters for streaming HTTP response bodies.
+//!
+//! [`ReaderStream`] fills the role that `tokio_util::io::ReaderStream` has in the hyper examples,
+//! for readers implementing the futures `AsyncRead` trait that smol uses.
+//!
+//! [`with_keep_alive`] keeps event streams from being closed for being idle.
+
+use async_stream::stream;
+use bytes::Bytes;
+use smol::{
+    io::AsyncRead,
+    stream::{Stream, StreamExt},
+    Timer,
+};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+/// How long an event stream can go without sending anything before a keep-alive comment is sent.
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long browsers wait before reconnecting to an event stream that was closed, in milliseconds.
+pub const RECONNECT_DELAY_MS: u64 = 1_000;
+
+/// Size of the chunks that readers are read in, and that response body frames are made of.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A stream of chunks read from an async reader, ending when the reader reaches EOF.
+#[derive(Debug)]
+pub struct ReaderStream<R> {
+    /// Set to `None` once EOF or an error has been reached.
+    reader: Option<R>,
+    buf: Box<[u8]>,
+}
+
+impl<R: AsyncRead + Unpin> ReaderStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: Some(reader),
+            buf: vec![0; CHUNK_SIZE].into_boxed_slice(),
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for ReaderStream<R> {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let Some(reader) = this.reader.as_mut() else {
+            return Poll::Ready(None);
+        };
+        match Pin::new(reader).poll_read(cx, &mut this.buf) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(0)) => {
+                this.reader = None;
+                Poll::Ready(None)
+            }
+            Poll::Ready(Ok(n)) => Poll::Ready(Some(Ok(Bytes::copy_from_slice(&this.buf[..n])))),
+            Poll::Ready(Err(e)) => {
+                this.reader = None;
+                Poll::Ready(Some(Err(e)))
+            }
+        }
+    }
+}
+
+/// Wrap the messages of an event stream with a `retry:` directive at the start, and with
+/// `: keep-alive` comments when it has been idle for a while, so that proxies do not close it,
+/// and so that a connection that was lost, like while a laptop was asleep, is noticed.
+pub fn with_keep_alive<E: Send + Sync + 'static>(
+    messages: impl Stream<Item = Result<Bytes, E>> + Send + Sync + 'static,
+) -> impl Stream<Item = Result<Bytes, E>> + Send + Sync + 'static {
+    stream! {
+        yield Ok(Bytes::from(format!("retry: {RECONNECT_DELAY_MS}\n\n")));
+        let mut messages = Box::pin(messages);
+        loop {
+            let next = smol::future::or(async { Some(messages.next().await) }, async {
+                Timer::after(KEEP_ALIVE_INTERVAL).await;
+                None
+            })
+            .await;
+            match next {
+                Some(Some(message)) => yield message,
+                Some(None) => break,
+                None => yield Ok(Bytes::from_static(b": keep-alive\n\n")),
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Synchronized browsing: scrolls, clicks and navigation in one of the pages that are open
+//! in browsers are mirrored in all the others, so that a page can be tried out on a laptop,
+//! a phone and a tablet at once.
+//!
+//! The reload client script posts what happens in its page to a reserved URL path,
+//! and gets what happens in the other pages as `sync` events on its event stream.
+
+use crate::broadcast::Broadcast;
+use serde::{Deserialize, Serialize};
+use smol::channel::Receiver;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// URL path, under the reserved path prefix, that the client script posts sync events to.
+pub const SYNC_URL_PATH: &str = "sync";
+
+/// Largest sync event body accepted from the client script, in bytes.
+pub const MAX_EVENT_LEN: usize = 4 * 1024;
+
+/// How many events a subscriber can fall behind by before it starts missing events.
+/// Scrolls come in quickly, so this is more than for the reload channel.
+const SUBSCRIBER_CAPACITY: usize = 256;
+
+/// Something that happened in a page, to mirror in the other pages.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncEvent {
+    /// Random ID of the client that the event happened in, so that it can ignore its own events.
+    pub client: String,
+    /// URL path of the page. Scrolls and clicks are only mirrored in pages with the same URL path.
+    pub page: String,
+    #[serde(flatten)]
+    pub action: SyncAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum SyncAction {
+    /// Scroll position, as fractions of how far the page can be scrolled, since pages
+    /// are of different sizes on different screens.
+    Scroll { x: f64, y: f64 },
+    /// Click on the element matched by a CSS selector.
+    Click { selector: String },
+    /// Navigation to another page, by URL path and query.
+    Navigate { url: String },
+}
+
+impl SyncEvent {
+    /// Whether the event is one that may be mirrored. Navigation is only allowed to pages
+    /// of the project server itself, so that it cannot send every client elsewhere.
+    pub fn is_valid(&self) -> bool {
+        match &self.action {
+            SyncAction::Scroll { x, y } => (0.0..=1.0).contains(x) && (0.0..=1.0).contains(y),
+            SyncAction::Click { selector } => !selector.is_empty(),
+            SyncAction::Navigate { url } => url.starts_with('/') && !url.starts_with("//"),
+        }
+    }
+}
+
+static SYNC_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The sync channel of the project server.
+pub static SYNC_CHANNEL: Broadcast<SyncEvent> = Broadcast::new("sync channel", SUBSCRIBER_CAPACITY);
+
+/// Enable synchronized browsing, making the reload client script post and apply sync events.
+pub fn enable() {
+    SYNC_ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    SYNC_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Subscribe to sync events. The subscription ends when the receiver is dropped.
+pub fn subscribe() -> Receiver<SyncEvent> {
+    SYNC_CHANNEL.subscribe()
+}
@@ -0,0 +1,205 @@
+//! Simulation of slow networks, by delaying and rate-limiting responses from the project server.
+//!
+//! This makes it possible to see how a project loads on a slow connection, without having to
+//! set up throttling in the developer tools of each browser. Conditions can be set for all
+//! responses, and be overridden for URL paths matching a pattern in the config file:
+//!
+//! ```toml
+//! [[throttle]]
+//! for = "/api/*"
+//! preset = "slow-3g"
+//! latency = "3s"
+//! ```
+//!
+//! See [`crate::pattern`] for the syntax of patterns.
+
+use crate::pattern;
+use async_stream::stream;
+use bytes::Bytes;
+use clap::ValueEnum;
+use http_body_util::{combinators::BoxBody, BodyExt, StreamBody};
+use hyper::body::Frame;
+use serde::Deserialize;
+use smol::Timer;
+use std::{io, time::Duration};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Invalid duration {0:?}. Expected a number of milliseconds or seconds, like `200ms` or `1.5s`")]
+    InvalidDuration(String),
+    #[error("Invalid bandwidth {0:?}. Expected a number of kilobits or megabits per second, like `400kbps` or `1.5mbps`")]
+    InvalidBandwidth(String),
+}
+
+/// Network conditions resembling those of common kinds of mobile connections,
+/// like the presets of the Chrome developer tools.
+#[derive(ValueEnum, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    /// 2 s latency, 400 kbit/s
+    #[value(name = "slow-3g")]
+    #[serde(rename = "slow-3g")]
+    Slow3g,
+    /// 560 ms latency, 1.5 Mbit/s
+    #[value(name = "3g")]
+    #[serde(rename = "3g")]
+    ThreeG,
+    /// 170 ms latency, 9 Mbit/s
+    #[value(name = "4g")]
+    #[serde(rename = "4g")]
+    FourG,
+}
+
+/// How much responses are delayed and rate-limited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NetworkConditions {
+    /// Delay before a response is sent.
+    pub latency: Duration,
+    /// Rate at which response bodies are sent. Unlimited if `None`.
+    pub bytes_per_sec: Option<u64>,
+}
+
+impl NetworkConditions {
+    /// Start from the conditions of a preset, if any, and override them with those given.
+    pub fn new(
+        preset: Option<Preset>,
+        latency: Option<Duration>,
+        bytes_per_sec: Option<u64>,
+    ) -> Self {
+        let base = preset.map(Self::from).unwrap_or_default();
+        Self {
+            latency: latency.unwrap_or(base.latency),
+            bytes_per_sec: bytes_per_sec.or(base.bytes_per_sec),
+        }
+    }
+
+    pub fn is_unthrottled(&self) -> bool {
+        self.latency.is_zero() && self.bytes_per_sec.is_none()
+    }
+}
+
+impl From<Preset> for NetworkConditions {
+    fn from(preset: Preset) -> Self {
+        let (latency_ms, kbit_per_sec) = match preset {
+            Preset::Slow3g => (2000, 400),
+            Preset::ThreeG => (560, 1500),
+            Preset::FourG => (170, 9000),
+        };
+        Self {
+            latency: Duration::from_millis(latency_ms),
+            bytes_per_sec: Some(kbit_per_sec * 1000 / 8),
+        }
+    }
+}
+
+/// Network conditions for URL paths matching a pattern.
+#[derive(Debug, Clone)]
+pub struct ThrottleRule {
+    /// URL path pattern, with leading slash.
+    pub pattern: String,
+    pub conditions: NetworkConditions,
+}
+
+impl ThrottleRule {
+    /// Check whether this rule applies to a URL path (with leading slash).
+    pub fn matches(&self, url_path: &str) -> bool {
+        pattern::matches(&self.pattern, url_path)
+    }
+}
+
+/// A throttle rule as written in the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThrottleRuleConfig {
+    /// URL path pattern, with leading slash.
+    #[serde(rename = "for")]
+    pub pattern: String,
+    pub preset: Option<Preset>,
+    pub latency: Option<String>,
+    pub bandwidth: Option<String>,
+}
+
+impl TryFrom<ThrottleRuleConfig> for ThrottleRule {
+    type Error = Error;
+
+    fn try_from(config: ThrottleRuleConfig) -> Result<Self, Self::Error> {
+        let latency = config.latency.as_deref().map(parse_duration).transpose()?;
+        let bytes_per_sec = config
+            .bandwidth
+            .as_deref()
+            .map(parse_bandwidth)
+            .transpose()?;
+        Ok(ThrottleRule {
+            pattern: config.pattern,
+            conditions: NetworkConditions::new(config.preset, latency, bytes_per_sec),
+        })
+    }
+}
+
+/// Parse a duration like `200ms` or `1.5s`. A plain number is taken as milliseconds.
+pub fn parse_duration(s: &str) -> Result<Duration, Error> {
+    let invalid = || Error::InvalidDuration(s.to_string());
+    let text = s.trim();
+    let (number, ms_per_unit) = if let Some(number) = text.strip_suffix("ms") {
+        (number, 1.0)
+    } else if let Some(number) = text.strip_suffix('s') {
+        (number, 1000.0)
+    } else {
+        (text, 1.0)
+    };
+    let number = number.trim().parse::<f64>().map_err(|_| invalid())?;
+    if !number.is_finite() || number < 0.0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs_f64(number * ms_per_unit / 1000.0))
+}
+
+/// Parse a bandwidth like `400kbps` or `1.5mbps`, into bytes per second.
+pub fn parse_bandwidth(s: &str) -> Result<u64, Error> {
+    let invalid = || Error::InvalidBandwidth(s.to_string());
+    let text = s.trim().to_ascii_lowercase();
+    let (number, bit_per_unit) = if let Some(number) = text.strip_suffix("kbps") {
+        (number, 1e3)
+    } else if let Some(number) = text.strip_suffix("mbps") {
+        (number, 1e6)
+    } else {
+        return Err(invalid());
+    };
+    let number = number.trim().parse::<f64>().map_err(|_| invalid())?;
+    let bytes_per_sec = number * bit_per_unit / 8.0;
+    if !bytes_per_sec.is_finite() || bytes_per_sec < 1.0 {
+        return Err(invalid());
+    }
+    Ok(bytes_per_sec as u64)
+}
+
+/// Send a response body at no more than the given rate,
+/// by sending it in small chunks with pauses in between.
+pub fn throttle_body(
+    body: BoxBody<Bytes, io::Error>,
+    bytes_per_sec: u64,
+) -> BoxBody<Bytes, io::Error> {
+    // Ten chunks per second keeps progress smooth, without an excessive number of timers.
+    let chunk_size = (bytes_per_sec / 10).clamp(1, 64 * 1024) as usize;
+    let pause = Duration::from_secs_f64(chunk_size as f64 / bytes_per_sec as f64);
+    let stream = stream! {
+        let mut body = body;
+        while let Some(frame) = body.frame().await {
+            let data = match frame?.into_data() {
+                Ok(data) => data,
+                Err(frame) => {
+                    yield Ok(frame);
+                    continue;
+                }
+            };
+            let mut rest = data;
+            while !rest.is_empty() {
+                let chunk = rest.split_to(chunk_size.min(rest.len()));
+                Timer::after(pause.mul_f64(chunk.len() as f64 / chunk_size as f64)).await;
+                yield Ok(Frame::data(chunk));
+            }
+        }
+    };
+    BodyExt::boxed(StreamBody::new(stream))
+}
@@ -0,0 +1,291 @@
+//! HTTPS for the project and status servers.
+//!
+//! Many Web APIs, like service workers on hosts other than localhost, and `getUserMedia`,
+//! are only available in secure contexts. To make these usable during development,
+//! http-horse can serve over HTTPS with a throwaway self-signed certificate generated at startup,
+//! with a certificate provided by the user, or with a locally trusted certificate made by mkcert.
+//!
+//! Certificates loaded from files can be replaced while the servers are running,
+//! which only affects connections made after the replacement.
+
+use futures_rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    rustls::{
+        self,
+        crypto::ring::{default_provider, sign::any_supported_type},
+        server::{ClientHello, ResolvesServerCert},
+        sign::CertifiedKey,
+        ServerConfig,
+    },
+    server::TlsStream,
+    TlsAcceptor,
+};
+use smol::{
+    io::{AsyncRead, AsyncWrite},
+    Timer,
+};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::Command,
+    sync::{Arc, RwLock},
+    task::{Context, Poll},
+    time::Duration,
+};
+use thiserror::Error;
+use tracing::{debug, info};
+
+/// Name of the mkcert executable, which is looked for in `PATH`.
+const MKCERT: &str = "mkcert";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O: {0}")]
+    IO(#[from] io::Error),
+    #[error("Certificate generation: {0}")]
+    Rcgen(#[from] rcgen::Error),
+    #[error("TLS: {0}")]
+    Rustls(#[from] rustls::Error),
+    #[error("PEM file {0:?}: {1}")]
+    Pem(PathBuf, futures_rustls::pki_types::pem::Error),
+    #[error("No certificates in PEM file {0:?}")]
+    NoCertificates(PathBuf),
+    #[error("mkcert failed: {0}")]
+    Mkcert(String),
+}
+
+/// Serves whichever certificate is current, so that certificates can be replaced at runtime.
+#[derive(Debug)]
+struct CertResolver {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(
+            self.current
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        )
+    }
+}
+
+/// A certificate and the TLS acceptor that serves it.
+#[derive(Clone)]
+pub struct TlsSetup {
+    pub acceptor: TlsAcceptor,
+    resolver: Arc<CertResolver>,
+}
+
+impl TlsSetup {
+    /// Generate a self-signed certificate for the given host names and IP addresses.
+    pub fn self_signed(subject_alt_names: Vec<String>) -> Result<Self, Error> {
+        let rcgen::CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(subject_alt_names)?;
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der()));
+        Self::new(vec![cert.der().clone()], key)
+    }
+
+    /// Load a certificate chain and its private key from PEM files.
+    pub fn from_pem_files(cert_fpath: &Path, key_fpath: &Path) -> Result<Self, Error> {
+        let (cert_chain, key) = load_pem_files(cert_fpath, key_fpath)?;
+        Self::new(cert_chain, key)
+    }
+
+    /// Set up TLS with a certificate chain and its private key.
+    pub fn new(
+        cert_chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> Result<Self, Error> {
+        let resolver = Arc::new(CertResolver {
+            current: RwLock::new(certified_key(cert_chain, key)?),
+        });
+        let mut config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_cert_resolver(resolver.clone());
+        // Our connections are served by an executor that does not support HTTP/2.
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(Self {
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+            resolver,
+        })
+    }
+
+    /// Replace the certificate with one loaded from PEM files.
+    /// If loading fails, the current certificate is kept.
+    pub fn reload_pem_files(&self, cert_fpath: &Path, key_fpath: &Path) -> Result<(), Error> {
+        let (cert_chain, key) = load_pem_files(cert_fpath, key_fpath)?;
+        let certified_key = certified_key(cert_chain, key)?;
+        *self
+            .resolver
+            .current
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = certified_key;
+        Ok(())
+    }
+
+    /// SHA-256 fingerprint of the current certificate, as colon-separated hex.
+    pub fn fingerprint(&self) -> String {
+        let current = self
+            .resolver
+            .current
+            .read()
+            .unwrap_or_else(|e| e.into_inner());
+        current
+            .end_entity_cert()
+            .map(fingerprint)
+            .unwrap_or_default()
+    }
+}
+
+fn certified_key(
+    cert_chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+) -> Result<Arc<CertifiedKey>, Error> {
+    let key = any_supported_type(&key)?;
+    Ok(Arc::new(CertifiedKey::new(cert_chain, key)))
+}
+
+fn load_pem_files(
+    cert_fpath: &Path,
+    key_fpath: &Path,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), Error> {
+    let cert_pem = std::fs::read(cert_fpath)?;
+    let cert_chain = CertificateDer::pem_slice_iter(&cert_pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Error::Pem(cert_fpath.to_path_buf(), e))?;
+    if cert_chain.is_empty() {
+        return Err(Error::NoCertificates(cert_fpath.to_path_buf()));
+    }
+    let key_pem = std::fs::read(key_fpath)?;
+    let key = PrivateKeyDer::from_pem_slice(&key_pem)
+        .map_err(|e| Error::Pem(key_fpath.to_path_buf(), e))?;
+    debug!(
+        ?cert_fpath,
+        ?key_fpath,
+        n_certs = cert_chain.len(),
+        "Loaded certificate and key from PEM files."
+    );
+    Ok((cert_chain, key))
+}
+
+/// Have mkcert make a certificate for the given host names and IP addresses, signed by its
+/// local CA, so that browsers trust it once `mkcert -install` has been run.
+///
+/// Returns the paths of the certificate and key PEM files, which are written to `out_dir`.
+pub fn run_mkcert(
+    out_dir: &Path,
+    subject_alt_names: &[String],
+) -> Result<(PathBuf, PathBuf), Error> {
+    std::fs::create_dir_all(out_dir)?;
+    let cert_fpath = out_dir.join("cert.pem");
+    let key_fpath = out_dir.join("key.pem");
+    info!(
+        ?cert_fpath,
+        ?key_fpath,
+        ?subject_alt_names,
+        "Running mkcert to make a locally trusted certificate."
+    );
+    let output = Command::new(MKCERT)
+        .arg("-cert-file")
+        .arg(&cert_fpath)
+        .arg("-key-file")
+        .arg(&key_fpath)
+        .args(subject_alt_names)
+        .output()
+        .map_err(|e| Error::Mkcert(format!("Failed to run {MKCERT}: {e}")))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Mkcert(format!(
+            "{}: {}",
+            output.status,
+            stderr.trim()
+        )));
+    }
+    Ok((cert_fpath, key_fpath))
+}
+
+/// SHA-256 fingerprint of a certificate, as colon-separated hex.
+pub fn fingerprint(cert: &CertificateDer) -> String {
+    ring::digest::digest(&ring::digest::SHA256, cert)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// A connection that may or may not be using TLS.
+pub enum MaybeTlsStream<S> {
+    Plain(S),
+    Tls(Box<TlsStream<S>>),
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> MaybeTlsStream<S> {
+    /// Carry out the TLS handshake for an accepted connection, if TLS is in use.
+    ///
+    /// Gives up after `timeout`, so that clients that connect but never send a ClientHello
+    /// do not keep their connections open for ever.
+    pub async fn accept(stream: S, tls: Option<&TlsSetup>, timeout: Duration) -> io::Result<Self> {
+        match tls {
+            Some(tls) => {
+                let handshake = async {
+                    let stream = tls.acceptor.accept(stream).await?;
+                    Ok::<_, io::Error>(Self::Tls(Box::new(stream)))
+                };
+                let deadline = async {
+                    Timer::after(timeout).await;
+                    Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "TLS handshake timed out",
+                    ))
+                };
+                smol::future::or(handshake, deadline).await
+            }
+            None => Ok(Self::Plain(stream)),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for MaybeTlsStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for MaybeTlsStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_close(cx),
+            Self::Tls(stream) => Pin::new(stream).poll_close(cx),
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Percent-encoding and -decoding of URL paths.
+
+/// Percent-encode a single URL path segment, such as a file name.
+///
+/// Everything except unreserved characters is encoded, including slashes.
+pub fn encode_path_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for &b in segment.as_bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{b:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Percent-encode the characters of a URL or URL path that are never allowed as is,
+/// namely spaces, control characters and non-ASCII characters.
+///
+/// Unlike [`encode_path_segment`], this leaves delimiters like `/`, `?` and `%` alone.
+pub fn encode_unsafe_chars(url: &str) -> String {
+    let mut encoded = String::with_capacity(url.len());
+    for &b in url.as_bytes() {
+        if b.is_ascii_graphic() {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{b:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Percent-decode a URL path.
+///
+/// Returns `None` if the path contains malformed percent-encodings,
+/// or if the decoded path is not valid UTF-8.
+pub fn decode_path(path: &str) -> Option<String> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            let hex = std::str::from_utf8(hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
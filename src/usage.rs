@@ -0,0 +1,218 @@
+//! Resource usage of http-horse itself, sampled every few seconds, so that the status web-ui
+//! can show its footprint over time, like how memory grows with the size of the project
+//! directories, or whether open file descriptors, tasks or event streams pile up.
+
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::debug;
+
+/// How often resource usage is sampled.
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Number of samples kept, which is an hour's worth.
+const MAX_SAMPLES: usize = 720;
+
+/// Resource usage at one point in time. What cannot be found out on this system is left out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResourceUsage {
+    /// Milliseconds since the Unix epoch.
+    pub sampled_ms: u64,
+    /// CPU time spent in user and system mode since http-horse was started, in milliseconds.
+    pub cpu_time_ms: u64,
+    /// CPU time spent since the previous sample, in percent of the time of one core.
+    pub cpu_percent: u64,
+    /// Resident memory, in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rss_bytes: Option<u64>,
+    /// Open file descriptors, of connections, log files and the like.
+    /// Tracked files are not kept open.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_fds: Option<u64>,
+    /// Threads of the process, which run the async executor and blocking work.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<u64>,
+    /// Connections being served by the project and status servers,
+    /// which count towards `--max-connections`.
+    pub connections: u64,
+    /// Tasks running on the async executor: those serving connections, the LiveReload ones
+    /// included, WebSocket connections, connections to proxied backends, and background tasks.
+    pub tasks: u64,
+    /// Server-sent event streams open, to pages for reloads and to the status web-ui.
+    pub event_streams: u64,
+}
+
+/// A number of things that are live, counted by the guards handed out for them.
+#[derive(Debug, Default)]
+pub struct LiveCount {
+    live: AtomicU64,
+}
+
+/// Counts towards a [`LiveCount`] until dropped.
+#[derive(Debug)]
+pub struct LiveGuard(&'static LiveCount);
+
+impl LiveCount {
+    pub const fn new() -> Self {
+        Self {
+            live: AtomicU64::new(0),
+        }
+    }
+
+    /// Count one more, until the returned guard is dropped.
+    pub fn enter(&'static self) -> LiveGuard {
+        self.live.fetch_add(1, Ordering::Relaxed);
+        LiveGuard(self)
+    }
+
+    pub fn get(&self) -> u64 {
+        self.live.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for LiveGuard {
+    fn drop(&mut self) {
+        self.0.live.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Tasks spawned on the async executor, counted with [`counted`].
+pub static TASKS: LiveCount = LiveCount::new();
+
+/// Server-sent event streams that are open.
+pub static EVENT_STREAMS: LiveCount = LiveCount::new();
+
+/// Count a future as a task in [`TASKS`] for as long as it runs. Wraps futures that are spawned.
+pub async fn counted<F: Future>(future: F) -> F::Output {
+    let _guard = TASKS.enter();
+    future.await
+}
+
+/// The recent resource usage samples, oldest first.
+#[derive(Debug, Default)]
+pub struct UsageHistory {
+    samples: Mutex<VecDeque<ResourceUsage>>,
+}
+
+/// Resource usage of this process.
+pub static RESOURCE_USAGE: UsageHistory = UsageHistory::new();
+
+impl UsageHistory {
+    pub const fn new() -> Self {
+        Self {
+            samples: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Sample the resource usage of this process, keep the sample, and return it.
+    pub fn sample(&self, connections: u64) -> ResourceUsage {
+        let sampled_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let cpu_time_ms = cpu_time_ms();
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let cpu_percent = samples.back().map_or(0, |previous| {
+            let elapsed_ms = sampled_ms.saturating_sub(previous.sampled_ms).max(1);
+            cpu_time_ms.saturating_sub(previous.cpu_time_ms) * 100 / elapsed_ms
+        });
+        let (rss_bytes, threads) = memory_and_threads();
+        let usage = ResourceUsage {
+            sampled_ms,
+            cpu_time_ms,
+            cpu_percent,
+            rss_bytes,
+            open_fds: open_fds(),
+            threads,
+            connections,
+            tasks: TASKS.get(),
+            event_streams: EVENT_STREAMS.get(),
+        };
+        if samples.len() == MAX_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(usage.clone());
+        usage
+    }
+
+    pub fn samples(&self) -> Vec<ResourceUsage> {
+        let samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        samples.iter().cloned().collect()
+    }
+}
+
+fn cpu_time_ms() -> u64 {
+    // SAFETY: getrusage only writes to the struct that it is given.
+    let usage = unsafe {
+        let mut usage: libc::rusage = std::mem::zeroed();
+        if libc::getrusage(libc::RUSAGE_SELF, &mut usage) != 0 {
+            debug!(err = ?std::io::Error::last_os_error(), "Failed to get CPU time.");
+            return 0;
+        }
+        usage
+    };
+    let ms = |time: libc::timeval| time.tv_sec as u64 * 1000 + time.tv_usec as u64 / 1000;
+    ms(usage.ru_utime) + ms(usage.ru_stime)
+}
+
+/// Open file descriptors, as listed in `/dev/fd`, less the one that it is listed through.
+fn open_fds() -> Option<u64> {
+    let entries = std::fs::read_dir("/dev/fd")
+        .inspect_err(|e| debug!(err = ?e, "Failed to list open file descriptors."))
+        .ok()?;
+    Some((entries.count() as u64).saturating_sub(1))
+}
+
+/// Resident memory in bytes, and number of threads.
+#[cfg(target_os = "macos")]
+fn memory_and_threads() -> (Option<u64>, Option<u64>) {
+    // SAFETY: proc_pidinfo writes no more than the given size to the struct that it is given.
+    let info = unsafe {
+        let mut info: libc::proc_taskinfo = std::mem::zeroed();
+        let size = std::mem::size_of::<libc::proc_taskinfo>() as libc::c_int;
+        let written = libc::proc_pidinfo(
+            libc::getpid(),
+            libc::PROC_PIDTASKINFO,
+            0,
+            &mut info as *mut _ as *mut libc::c_void,
+            size,
+        );
+        if written != size {
+            debug!(err = ?std::io::Error::last_os_error(), "Failed to get task info.");
+            return (None, None);
+        }
+        info
+    };
+    (
+        Some(info.pti_resident_size),
+        Some(info.pti_threadnum as u64),
+    )
+}
+
+/// Resident memory in bytes, and number of threads.
+#[cfg(target_os = "linux")]
+fn memory_and_threads() -> (Option<u64>, Option<u64>) {
+    let status = std::fs::read_to_string("/proc/self/status")
+        .inspect_err(|e| debug!(err = ?e, "Failed to read process status."))
+        .unwrap_or_default();
+    let field = |name: &str| {
+        status.lines().find_map(|line| {
+            let value = line.strip_prefix(name)?.strip_prefix(':')?.trim();
+            value.trim_end_matches(" kB").parse::<u64>().ok()
+        })
+    };
+    (field("VmRSS").map(|kib| kib * 1024), field("Threads"))
+}
+
+/// Resident memory in bytes, and number of threads.
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn memory_and_threads() -> (Option<u64>, Option<u64>) {
+    (None, None)
+}
@@ -0,0 +1,65 @@
+// Hot module replacement runtime of http-horse, for projects that use native ES modules.
+// Modules opt in to being replaced without a reload of the page:
+//
+//     import { hot } from "/.http-horse/hmr.js";
+//     hot(import.meta.url).accept((newModule) => { ... });
+//
+// When a module that has accept handlers changes, its dispose handlers are called, it is imported
+// anew, and its accept handlers are called with the new module. Changes to other modules reload the page.
+
+// Registered modules, by URL path.
+const modules = new Map();
+
+function urlPath(href) {
+    return new URL(href, location.href).pathname;
+}
+
+// Handle to register handlers for changes to the module at a URL, usually `import.meta.url`.
+// Importing a module anew registers it anew, replacing the handlers of the old module.
+export function hot(moduleUrl) {
+    let module = { acceptHandlers: [], disposeHandlers: [], data: {} };
+    let previous = modules.get(urlPath(moduleUrl));
+    if (previous !== undefined) {
+        // State handed over by the dispose handlers of the old module.
+        module.data = previous.data;
+    }
+    modules.set(urlPath(moduleUrl), module);
+    return {
+        // State that survives replacement of the module.
+        get data() {
+            return module.data;
+        },
+        // Call a handler with the new module when the module has been replaced.
+        accept(handler = () => {}) {
+            module.acceptHandlers.push(handler);
+        },
+        // Call a handler before the module is replaced, to clean up after it,
+        // like removing event listeners. It gets `data` to hand state over to the new module.
+        dispose(handler) {
+            module.disposeHandlers.push(handler);
+        },
+    };
+}
+
+// Replace the module at a URL path. Resolves to whether it was replaced, as opposed to
+// the page having to be reloaded. Called by the reload client script.
+async function update(changedUrlPath) {
+    let module = modules.get(changedUrlPath);
+    if (module === undefined || module.acceptHandlers.length === 0) {
+        return false;
+    }
+    let data = {};
+    for (let handler of module.disposeHandlers) {
+        handler(data);
+    }
+    module.data = data;
+    let url = new URL(changedUrlPath, location.href);
+    url.searchParams.set("http-horse-t", Date.now());
+    let newModule = await import(url.href);
+    for (let handler of module.acceptHandlers) {
+        handler(newModule);
+    }
+    return true;
+}
+
+window.__httpHorseHmr = { update };
@@ -0,0 +1,76 @@
+// Served by the LiveReload compatibility listener of http-horse, for pages that load livereload.js
+// from it, like LiveReload browser extensions make them do. Speaks the LiveReload protocol.
+(function () {
+    const PROTOCOL = "http://livereload.com/protocols/official-7";
+    const IMAGE_EXTENSIONS = ["avif", "gif", "jpeg", "jpg", "png", "svg", "webp"];
+
+    // The server is wherever this script was loaded from.
+    let scriptUrl = new URL(document.currentScript !== null ? document.currentScript.src : location.href);
+    let socketUrl = (scriptUrl.protocol === "https:" ? "wss://" : "ws://") + scriptUrl.host + "/livereload";
+
+    // Whether a URL is for the changed file. Pages may be served under another path than
+    // the one that http-horse serves the file at, so only the end of the path has to match.
+    function matchesPath(href, path) {
+        return new URL(href, location.href).pathname.endsWith(path);
+    }
+
+    function cacheBusted(href) {
+        let url = new URL(href, location.href);
+        url.searchParams.set("livereload", Date.now());
+        return url.href;
+    }
+
+    function extension(path) {
+        let match = /\.([^./]+)$/.exec(path);
+        return match !== null ? match[1].toLowerCase() : "";
+    }
+
+    // Replace stylesheet links with ones that bypass the browser cache. As LiveReload does,
+    // every stylesheet is swapped when none of them matches, since the changed one may be imported.
+    function swapStylesheets(path) {
+        let links = Array.from(document.querySelectorAll("link[rel~=stylesheet][href]"));
+        let matching = links.filter((link) => matchesPath(link.href, path));
+        for (let link of matching.length > 0 ? matching : links) {
+            let newLink = link.cloneNode();
+            newLink.href = cacheBusted(link.href);
+            newLink.onload = newLink.onerror = () => link.remove();
+            link.after(newLink);
+        }
+    }
+
+    function swapImages(path) {
+        for (let img of document.querySelectorAll("img[src]")) {
+            if (matchesPath(img.src, path)) {
+                img.src = cacheBusted(img.src);
+            }
+        }
+    }
+
+    function reload(cmd) {
+        if (cmd.liveCSS && extension(cmd.path) === "css") {
+            console.debug("livereload: Swapping stylesheets due to change", cmd.path);
+            swapStylesheets(cmd.path);
+        } else if (cmd.liveImg && IMAGE_EXTENSIONS.includes(extension(cmd.path))) {
+            console.debug("livereload: Swapping images due to change", cmd.path);
+            swapImages(cmd.path);
+        } else {
+            console.debug("livereload: Reloading page due to change", cmd.path);
+            location.reload();
+        }
+    }
+
+    function connect() {
+        let socket = new WebSocket(socketUrl);
+        socket.onopen = () => socket.send(JSON.stringify({ command: "hello", protocols: [PROTOCOL] }));
+        socket.onmessage = (msg) => {
+            let cmd = JSON.parse(msg.data);
+            if (cmd.command === "reload") {
+                reload(cmd);
+            }
+        };
+        // Reconnect when the server is restarted.
+        socket.onclose = () => setTimeout(connect, 1000);
+    }
+
+    connect();
+})();
@@ -1,6 +1,608 @@
-let eventSource = new EventSource("/event-stream/");
+// User interface strings, in the language that the page was served in.
+const messages = JSON.parse(document.getElementById("messages").textContent);
 
-eventSource.onmessage = function (evt) {
-    let data = JSON.parse(evt.data);
-    console.log("Received Server Sent Event data", data);
+// URLs are relative, so that the status pages work under the path they are served from with `--single-port` too.
+let eventSource = new EventSource("event-stream/");
+
+// Events that come in quick succession are sent as a batch, in one message.
+eventSource.onmessage = function (msg) {
+    let data = JSON.parse(msg.data);
+    for (let entry of Array.isArray(data) ? data : [data]) {
+        handleStatusEvent(entry);
+    }
 };
+
+// Changes as they happen, shown at the top of the history.
+// Requests to the project server are shown in the request log instead.
+function handleStatusEvent(entry) {
+    if (entry.type === "request") {
+        appendRequestLogEntry(renderRequest(entry));
+        return;
+    }
+    if (entry.type === "color-scheme-changed") {
+        renderColorScheme(entry.color_scheme);
+        return;
+    }
+    if (entry.type === "tree-updated" || entry.type === "tree-rescanned") {
+        // Tree updates are for keeping a view of the tree up to date. The changes are in the history already.
+        return;
+    }
+    if (entry.type === "scan-progress") {
+        renderScanProgress(entry);
+        return;
+    }
+    if (entry.type === "build-output") {
+        appendBuildOutput(entry.stream, entry.text);
+        return;
+    }
+    if (entry.type === "companion-output") {
+        appendCompanionOutput(entry.name, entry.stream, entry.text);
+        return;
+    }
+    if (entry.type === "companion-started" || entry.type === "companion-exited") {
+        refreshCompanions();
+    }
+    if (entry.type.startsWith("build-run-") || entry.type.startsWith("build-step-")) {
+        renderBuildStatus(entry);
+    }
+    if (entry.type === "build-run-started") {
+        clearBuildOutput(entry.run);
+    }
+    if (entry.type === "build-run-finished") {
+        refreshBuildRuns();
+    }
+    if (entry.type === "resource-usage") {
+        // Resource usage samples are for graphing the footprint of http-horse, not history.
+        return;
+    }
+    document.getElementById("history-entries").prepend(renderHistoryEntry(entry));
+    if (RELOADING_EVENT_TYPES.includes(entry.type)) {
+        appendRequestLogEntry(renderRequestLogMarker(entry));
+    }
+    if (entry.type === "client-connected" || entry.type === "client-disconnected") {
+        refreshConnectedClients();
+    }
+}
+
+// Full scans in progress, by project directory.
+let scansInProgress = new Map();
+
+// Show an indicator in the header for as long as any project directory is being scanned in full.
+function renderScanProgress(entry) {
+    if (entry.done) {
+        scansInProgress.delete(entry.root);
+    } else {
+        scansInProgress.set(entry.root, entry);
+    }
+    let text = Array.from(scansInProgress.values(), (scan) =>
+        `${messages.scanning} ${scan.root}: ${scan.dirs} ${messages.directories}, ${scan.files} ${messages.files}`);
+    document.getElementById("scan-progress-text").textContent = text.join("; ");
+    document.getElementById("scan-progress").hidden = scansInProgress.size === 0;
+    if (entry.done) {
+        renderScanLimits(entry.root, entry.limits_reached || []);
+        renderHardlinked(entry.root, entry.hardlinked || 0);
+    }
+}
+
+// Limits of the tracked tree that the last full scan ran into, by project directory.
+let scanLimitsReached = new Map();
+
+// Warn about project directories where changes to some files go unnoticed, for being past a limit.
+function renderScanLimits(root, limits) {
+    if (limits.length > 0) {
+        scanLimitsReached.set(root, limits);
+    } else {
+        scanLimitsReached.delete(root);
+    }
+    let text = Array.from(scanLimitsReached, ([dir, limits]) => {
+        let names = limits.map((limit) => messages["limit_" + limit.replaceAll("-", "_")]);
+        return `${messages.partly_tracked} ${dir}, ${messages.past_limit_on} ${names.join(", ")}.`;
+    });
+    let p = document.getElementById("scan-limits");
+    p.textContent = text.join(" ");
+    p.hidden = scanLimitsReached.size === 0;
+}
+
+// Number of files with more than one hard link that the last full scan found, by project directory.
+let hardlinkedFiles = new Map();
+
+// Note which project directories have files that are polled for changes, for having more than one hard link.
+function renderHardlinked(root, n) {
+    if (n > 0) {
+        hardlinkedFiles.set(root, n);
+    } else {
+        hardlinkedFiles.delete(root);
+    }
+    let text = Array.from(hardlinkedFiles, ([dir, n]) =>
+        `${n} ${messages.hardlinked} ${dir} ${messages.polled_for_changes}.`);
+    let p = document.getElementById("hardlinked");
+    p.textContent = text.join(" ");
+    p.hidden = hardlinkedFiles.size === 0;
+}
+
+// Show that the build command is running, or that its last run failed, in the header.
+// Steps of the latest run of the build pipeline, with how far each got.
+let buildSteps = [];
+
+function renderBuildStatus(entry) {
+    let p = document.getElementById("build-status");
+    switch (entry.type) {
+        case "build-run-started":
+            buildSteps = entry.steps.map((name) => ({ name, state: "pending" }));
+            break;
+        case "build-step-started":
+            buildSteps[entry.step].state = "running";
+            break;
+        case "build-step-finished":
+            buildSteps[entry.step].state = entry.state;
+            buildSteps[entry.step].duration_ms = entry.duration_ms;
+            break;
+        case "build-run-finished":
+            for (let step of buildSteps) {
+                if (step.state === "pending") {
+                    step.state = "skipped";
+                }
+            }
+            break;
+    }
+    let running = entry.type !== "build-run-finished";
+    p.querySelector("progress").hidden = !running;
+    let exitCode = entry.exit_code !== undefined ? ` (${messages.exit_code} ${entry.exit_code})` : "";
+    document.getElementById("build-status-text").textContent = running
+        ? `${messages.running_build}:`
+        : `${messages.build_failed}${exitCode}:`;
+    document.getElementById("build-steps").replaceChildren(...buildSteps.map(renderBuildStep));
+    // A cancelled run is followed by a new run right away.
+    p.hidden = !running && (entry.success || entry.cancelled);
+}
+
+function renderBuildStep(step) {
+    let span = document.createElement("span");
+    span.className = "build-step";
+    span.dataset.state = step.state;
+    span.textContent = step.duration_ms !== undefined ? `${step.name} ${step.duration_ms} ms` : step.name;
+    span.title = messages["step_" + step.state.replaceAll("-", "_")];
+    return span;
+}
+
+// Output of the latest run of the build command, as it comes.
+function clearBuildOutput(run) {
+    document.getElementById("build-output-run").textContent = `#${run}`;
+    document.getElementById("build-output-text").replaceChildren();
+    document.getElementById("build-output").hidden = false;
+}
+
+function appendBuildOutput(stream, text) {
+    let pre = document.getElementById("build-output-text");
+    // Only follow new output when scrolled to the bottom, as with the request log.
+    let following = pre.scrollTop + pre.clientHeight >= pre.scrollHeight - 1;
+    let span = document.createElement("span");
+    span.className = "build-output-" + stream;
+    span.textContent = text;
+    pre.append(span);
+    if (following) {
+        pre.scrollTop = pre.scrollHeight;
+    }
+}
+
+// Show the output of a run of the build command, for as long as it is kept.
+function showBuildOutput(run) {
+    fetch(`api/build-output?run=${run}`)
+        .then((res) => (res.ok ? res.text() : ""))
+        .then((output) => {
+            clearBuildOutput(run);
+            appendBuildOutput("stdout", output);
+        })
+        .catch((err) => console.error("Failed to fetch build output", err));
+}
+
+// Finished runs of the build command, from the build history, oldest first.
+let buildRuns = [];
+
+// Number of the most recent runs shown in the build timeline, and counted in the success rate.
+const BUILD_TIMELINE_LEN = 100;
+
+function buildOutcome(run) {
+    return run.success ? "succeeded" : run.cancelled ? "cancelled" : "failed";
+}
+
+// Runs as bars, by how long they took, colored by how they went.
+function renderBuildTimeline() {
+    let shown = buildRuns.slice(-BUILD_TIMELINE_LEN);
+    let maxDuration = Math.max(1, ...shown.map((run) => run.duration_ms));
+    document.getElementById("build-timeline").replaceChildren(...shown.map((run) => renderBuildTimelineRun(run, maxDuration)));
+    // Cancelled runs neither failed nor succeeded.
+    let completed = shown.filter((run) => !run.cancelled);
+    let succeeded = completed.filter((run) => run.success);
+    document.getElementById("build-success-rate").textContent = completed.length > 0
+        ? `${messages.success_rate}: ${Math.round((100 * succeeded.length) / completed.length)} % (${succeeded.length}/${completed.length})`
+        : "";
+    if (shown.length > 0) {
+        document.getElementById("build-output").hidden = false;
+    }
+}
+
+function renderBuildTimelineRun(run, maxDuration) {
+    let span = document.createElement("span");
+    span.className = "build-timeline-run";
+    span.dataset.outcome = buildOutcome(run);
+    // Quick runs get a sliver of a bar, so that they can be seen and clicked too.
+    span.style.height = `${Math.max(10, Math.round((100 * run.duration_ms) / maxDuration))}%`;
+    span.title = [
+        `#${run.run} ${new Date(run.started_ms).toLocaleString()}`,
+        `${describeBuildOutcome(run)} ${run.duration_ms} ms, ${run.output_len} ${messages.bytes_of_output}`,
+        run.trigger,
+    ].join("\n");
+    span.addEventListener("click", () => showBuildOutput(run.run));
+    return span;
+}
+
+function refreshBuildRuns() {
+    return fetch("api/builds")
+        .then((res) => res.json())
+        .then((runs) => {
+            buildRuns = runs.filter((run) => run.duration_ms !== null);
+            renderBuildTimeline();
+            return runs;
+        })
+        .catch((err) => console.error("Failed to fetch build runs", err));
+}
+
+// Finished runs as entries in the event history, as they would have come on the status event stream.
+function buildRunHistoryEntry(run) {
+    return { ...run, type: "build-run-finished", timestamp_ms: run.started_ms + run.duration_ms };
+}
+
+// Name of a step of the build pipeline, by its index in the latest run.
+function buildStepName(evt) {
+    let step = buildSteps[evt.step];
+    return step !== undefined ? step.name : `${evt.step + 1}`;
+}
+
+// What an event is about, by event type.
+function describeEvent(evt) {
+    switch (evt.type) {
+        case "renamed":
+            return `${evt.from} → ${evt.path}`;
+        case "reload-requested":
+            return evt.page !== undefined ? evt.page : messages.every_page;
+        case "client-connected":
+            return `${evt.client} ${evt.url}`;
+        case "client-disconnected":
+            return evt.client;
+        case "build-run-started":
+            return `#${evt.run} ${evt.steps.join(" → ")} (${evt.trigger})`;
+        case "build-step-started":
+            return `#${evt.run} ${evt.name}: ${evt.command}`;
+        case "build-step-finished":
+            return `#${evt.run} ${buildStepName(evt)} ${messages["step_" + evt.state]} ${evt.duration_ms} ms`;
+        case "build-run-finished":
+            return `#${evt.run} ${describeBuildOutcome(evt)} ${evt.duration_ms} ms`;
+        case "companion-started":
+            return `${evt.name} (pid ${evt.pid})`;
+        case "companion-exited": {
+            let exitCode = evt.exit_code !== undefined ? ` ${messages.exit_code} ${evt.exit_code}` : "";
+            let restart = evt.restart_in_ms !== undefined ? `, ${messages.companion_restarting} ${evt.restart_in_ms} ms` : "";
+            return `${evt.name}${exitCode}${restart}`;
+        }
+        default:
+            return evt.path !== undefined ? evt.path : "";
+    }
+}
+
+// How a run of the build command went. The exit code is left out of events when there is none,
+// and null in the build history.
+function describeBuildOutcome(run) {
+    return run.success
+        ? messages.succeeded
+        : run.cancelled
+          ? messages.cancelled
+          : run.exit_code != null
+            ? `${messages.exit_code} ${run.exit_code}`
+            : messages.build_failed;
+}
+
+// Whether the server keeps diffs of changed text files, as started with `--diffs`.
+let diffsEnabled = false;
+
+// Event types that diffs are kept for.
+const DIFF_EVENT_TYPES = ["file-created", "file-modified", "renamed"];
+
+function renderHistoryEntry(entry) {
+    let p = document.createElement("p");
+    p.className = "history-entry";
+    p.dataset.kind = entry.type;
+    let time = new Date(entry.timestamp_ms).toLocaleTimeString();
+    p.textContent = `${time} ${entry.type} ${describeEvent(entry)}`;
+    if (diffsEnabled && entry.id !== undefined && !entry.is_dir && DIFF_EVENT_TYPES.includes(entry.type)) {
+        p.append(" ", renderDiffToggle(p, entry.id));
+    }
+    return p;
+}
+
+// Button that shows what changed, below the history entry.
+function renderDiffToggle(p, id) {
+    let button = document.createElement("button");
+    button.type = "button";
+    button.className = "diff-toggle";
+    button.textContent = messages.diff;
+    let pre = null;
+    button.addEventListener("click", () => {
+        if (pre !== null) {
+            pre.hidden = !pre.hidden;
+            return;
+        }
+        pre = document.createElement("pre");
+        pre.className = "diff";
+        p.after(pre);
+        fetch(`api/diff?id=${id}`)
+            .then((res) => (res.ok ? res.text() : messages.no_diff))
+            .then((diff) => pre.replaceChildren(...diff.split("\n").map(renderDiffLine)))
+            .catch((err) => console.error("Failed to fetch diff", err));
+    });
+    return button;
+}
+
+function renderDiffLine(line) {
+    let span = document.createElement("span");
+    span.textContent = line + "\n";
+    if (line.startsWith("+") && !line.startsWith("+++")) {
+        span.className = "diff-insert";
+    } else if (line.startsWith("-") && !line.startsWith("---")) {
+        span.className = "diff-delete";
+    }
+    return span;
+}
+
+// Requests that pages made to the project server, oldest first, so that what a page fetched
+// after it was reloaded reads top to bottom below the change that reloaded it.
+const MAX_REQUEST_LOG_ENTRIES = 200;
+
+// Event types that pages are reloaded for, marked in the request log.
+const RELOADING_EVENT_TYPES = ["file-created", "file-modified", "file-deleted", "renamed", "reload-requested", "build-finished"];
+
+function appendRequestLogEntry(p) {
+    let requestLog = document.getElementById("request-log-entries");
+    // Only follow new entries when scrolled to the bottom, so as not to pull the log away from what is being read.
+    let following = requestLog.scrollTop + requestLog.clientHeight >= requestLog.scrollHeight - 1;
+    requestLog.append(p);
+    while (requestLog.childElementCount > MAX_REQUEST_LOG_ENTRIES) {
+        requestLog.firstElementChild.remove();
+    }
+    if (following) {
+        requestLog.scrollTop = requestLog.scrollHeight;
+    }
+}
+
+function renderRequest(request) {
+    let p = document.createElement("p");
+    p.className = "request-log-entry";
+    p.dataset.status = Math.floor(request.status / 100) + "xx";
+    let time = new Date(request.timestamp_ms).toLocaleTimeString();
+    p.textContent = `${time} ${request.status} ${request.method} ${request.path} ${request.duration_ms} ms ${request.bytes} B`;
+    return p;
+}
+
+function renderRequestLogMarker(entry) {
+    let p = document.createElement("p");
+    p.className = "request-log-marker";
+    let time = new Date(entry.timestamp_ms).toLocaleTimeString();
+    p.textContent = `${time} ${entry.type} ${describeEvent(entry)}`;
+    return p;
+}
+
+// Errors in pages open in browsers, shown in the history next to the changes that preceded them.
+function renderClientError(error) {
+    let p = document.createElement("p");
+    p.className = "history-entry client-error";
+    p.dataset.kind = error.kind;
+    let time = new Date(error.timestamp_ms).toLocaleTimeString();
+    p.textContent = `${time} ${error.kind} ${messages.error_in} ${error.page}: ${error.message}`;
+    p.title = [error.source, error.stack].filter((s) => s).join("\n");
+    return p;
+}
+
+Promise.all([
+    fetch("history/").then((res) => res.json()),
+    fetch("client-errors/").then((res) => res.json()),
+    fetch("api/info").then((res) => res.json()),
+    refreshBuildRuns(),
+])
+    .then(([entries, clientErrors, info, runs = []]) => {
+        diffsEnabled = info.diffs;
+        for (let root of info.project_roots) {
+            renderScanLimits(root.dir, root.scan.limits_reached);
+            renderHardlinked(root.dir, root.scan.hardlinked);
+        }
+        let historyEntries = document.getElementById("history-entries");
+        let rendered = [
+            ...entries.map((entry) => [entry.timestamp_ms, renderHistoryEntry(entry)]),
+            ...clientErrors.map((error) => [error.timestamp_ms, renderClientError(error)]),
+            ...buildRuns.slice(-BUILD_TIMELINE_LEN).map(buildRunHistoryEntry).map((entry) => [entry.timestamp_ms, renderHistoryEntry(entry)]),
+        ];
+        // Most recent first.
+        rendered.sort(([a], [b]) => b - a);
+        historyEntries.replaceChildren(...rendered.map(([, p]) => p));
+        // Show the output of the latest run of the build command, if there has been one.
+        let latest = runs[runs.length - 1];
+        if (latest !== undefined) {
+            showBuildOutput(latest.run);
+        }
+    })
+    .catch((err) => console.error("Failed to fetch event history", err));
+
+let toggleWatching = document.getElementById("toggle-watching");
+
+function renderWatchingState(paused) {
+    toggleWatching.dataset.paused = paused;
+    toggleWatching.textContent = paused ? messages.resume_watching : messages.pause_watching;
+}
+
+toggleWatching.addEventListener("click", () => {
+    let paused = toggleWatching.dataset.paused === "true";
+    fetch(paused ? "watching/resume" : "watching/pause", { method: "POST" })
+        .then(() => renderWatchingState(!paused))
+        .catch((err) => console.error("Failed to toggle watching", err));
+});
+
+let toggleServing = document.getElementById("toggle-serving");
+
+function renderServingState(paused) {
+    toggleServing.dataset.paused = paused;
+    toggleServing.textContent = paused ? messages.resume_serving : messages.pause_serving;
+}
+
+toggleServing.addEventListener("click", () => {
+    let paused = toggleServing.dataset.paused === "true";
+    fetch(paused ? "serving/resume" : "serving/pause", { method: "POST" })
+        .then(() => renderServingState(!paused))
+        .catch((err) => console.error("Failed to toggle serving", err));
+});
+
+document.getElementById("reload-pages").addEventListener("click", () => {
+    fetch("api/reload", { method: "POST" })
+        .catch((err) => console.error("Failed to reload pages", err));
+});
+
+fetch("watching/")
+    .then((res) => res.json())
+    .then((watching) => renderWatchingState(watching.paused))
+    .catch((err) => console.error("Failed to fetch watching state", err));
+
+fetch("serving/")
+    .then((res) => res.json())
+    .then((serving) => renderServingState(serving.paused))
+    .catch((err) => console.error("Failed to fetch serving state", err));
+
+// Color scheme of the status web-ui, switched here or in any other open status page.
+let colorSchemeSelect = document.getElementById("color-scheme");
+
+function renderColorScheme(colorScheme) {
+    document.documentElement.dataset.colorScheme = colorScheme;
+    colorSchemeSelect.value = colorScheme;
+}
+
+colorSchemeSelect.addEventListener("change", () => {
+    let colorScheme = colorSchemeSelect.value;
+    fetch("api/color-scheme", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ color_scheme: colorScheme }),
+    })
+        .then(() => renderColorScheme(colorScheme))
+        .catch((err) => console.error("Failed to switch color scheme", err));
+});
+
+// Pages open in browsers, on this and other devices.
+const CLIENTS_REFRESH_INTERVAL_MS = 5000;
+
+function renderConnectedClient(client) {
+    let li = document.createElement("li");
+    li.className = "connected-client";
+    let url = document.createElement("code");
+    url.textContent = client.url;
+    let since = new Date(client.connected_ms).toLocaleTimeString();
+    let details = document.createElement("span");
+    details.className = "connected-client-details";
+    details.textContent = ` ${messages.connected} ${since}` + (client.reloads_paused ? `, ${messages.reloads_paused}` : "");
+    li.title = [client.user_agent, `${messages.last_heartbeat} ${new Date(client.last_heartbeat_ms).toLocaleTimeString()}`]
+        .filter((s) => s)
+        .join("\n");
+    li.append(url, details);
+    return li;
+}
+
+function refreshConnectedClients() {
+    fetch("api/clients")
+        .then((res) => res.json())
+        .then((clients) => {
+            document.getElementById("list-connected-clients").replaceChildren(...clients.map(renderConnectedClient));
+        })
+        .catch((err) => console.error("Failed to fetch connected clients", err));
+}
+
+refreshConnectedClients();
+setInterval(refreshConnectedClients, CLIENTS_REFRESH_INTERVAL_MS);
+
+// Long-running processes from the config file, with the output of the one that is selected.
+let selectedCompanion;
+
+function renderCompanion(companion) {
+    let li = document.createElement("li");
+    li.className = "companion";
+    li.dataset.state = companion.state;
+    li.dataset.selected = companion.name === selectedCompanion;
+    let name = document.createElement("code");
+    name.className = "companion-name";
+    name.textContent = companion.name;
+    name.title = companion.command;
+    name.addEventListener("click", () => selectCompanion(companion.name));
+    let details = document.createElement("span");
+    details.className = "companion-details";
+    let state = messages["companion_" + companion.state];
+    let pid = companion.pid !== undefined ? ` (pid ${companion.pid})` : "";
+    let exitCode = companion.state !== "running" && companion.exit_code !== undefined
+        ? `, ${messages.exit_code} ${companion.exit_code}`
+        : "";
+    details.textContent = ` ${state}${pid}${exitCode}, ${companion.restarts} ${messages.restarts} `;
+    let restart = document.createElement("button");
+    restart.textContent = messages.restart;
+    restart.addEventListener("click", () => {
+        fetch(`api/companions/restart?name=${encodeURIComponent(companion.name)}`, { method: "POST" })
+            .catch((err) => console.error("Failed to restart companion process", err));
+    });
+    li.append(name, details, restart);
+    return li;
+}
+
+function refreshCompanions() {
+    fetch("api/companions")
+        .then((res) => res.json())
+        .then((companions) => {
+            document.getElementById("list-companions").replaceChildren(...companions.map(renderCompanion));
+            document.getElementById("companions").hidden = companions.length === 0;
+        })
+        .catch((err) => console.error("Failed to fetch companion processes", err));
+}
+
+// Show the output of a companion process, kept up to date as it comes, or hide it if it is shown already.
+function selectCompanion(name) {
+    let pre = document.getElementById("companion-output-text");
+    selectedCompanion = selectedCompanion === name ? undefined : name;
+    for (let li of document.querySelectorAll("#list-companions > li.companion")) {
+        li.dataset.selected = li.querySelector(".companion-name").textContent === selectedCompanion;
+    }
+    pre.replaceChildren();
+    pre.hidden = selectedCompanion === undefined;
+    if (selectedCompanion === undefined) {
+        return;
+    }
+    fetch(`api/companion-output?name=${encodeURIComponent(name)}`)
+        .then((res) => (res.ok ? res.text() : ""))
+        .then((output) => {
+            if (selectedCompanion === name) {
+                // Output that came on the event stream meanwhile is in what was fetched.
+                pre.replaceChildren();
+                appendCompanionOutput(name, "stdout", output);
+            }
+        })
+        .catch((err) => console.error("Failed to fetch companion process output", err));
+}
+
+function appendCompanionOutput(name, stream, text) {
+    if (name !== selectedCompanion) {
+        return;
+    }
+    let pre = document.getElementById("companion-output-text");
+    // Only follow new output when scrolled to the bottom, as with the build output.
+    let following = pre.scrollTop + pre.clientHeight >= pre.scrollHeight - 1;
+    let span = document.createElement("span");
+    span.className = "build-output-" + stream;
+    span.textContent = text;
+    pre.append(span);
+    if (following) {
+        pre.scrollTop = pre.scrollHeight;
+    }
+}
+
+refreshCompanions();
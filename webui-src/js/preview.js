@@ -0,0 +1,45 @@
+// Preview of project pages at several viewport sizes. The framed pages are reloaded by changes
+// like any other page open in a browser, so every size updates at the same time.
+let previews = document.getElementById("previews");
+let form = document.getElementById("preview-form");
+let pathInput = document.getElementById("preview-path");
+
+function frames() {
+    return Array.from(previews.querySelectorAll("iframe"));
+}
+
+// Keep the address of the preview page in step with what it shows, so that it can be reopened or shared.
+function updateLocation() {
+    let params = new URLSearchParams(location.search);
+    params.set("path", pathInput.value);
+    params.set("presets", new FormData(form).getAll("presets").join(","));
+    history.replaceState(null, "", "?" + params);
+}
+
+// Show the page at the given URL path in every frame.
+function navigate(path) {
+    if (!path.startsWith("/")) {
+        path = "/" + path;
+    }
+    pathInput.value = path;
+    for (let frame of frames()) {
+        frame.src = previews.dataset.projectUrl + path;
+    }
+    updateLocation();
+}
+
+form.onsubmit = function (evt) {
+    evt.preventDefault();
+    navigate(pathInput.value);
+};
+
+document.getElementById("preview-presets").onchange = function (evt) {
+    let figure = previews.querySelector(`figure[data-preset="${CSS.escape(evt.target.value)}"]`);
+    figure.hidden = !evt.target.checked;
+    updateLocation();
+};
+
+// Frames are reloaded together, by navigating them anew, since pages of another origin cannot be reloaded directly.
+document.getElementById("reload-previews").onclick = function () {
+    navigate(pathInput.value);
+};
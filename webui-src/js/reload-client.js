@@ -0,0 +1,567 @@
+// Injected into pages served by http-horse. Reloads the page when the project changes.
+(function () {
+    let listingUrlPath = document.documentElement.dataset.httpHorseListing;
+
+    function parentUrlPath(urlPath) {
+        return urlPath.replace(/[^/]*\/?$/, "");
+    }
+
+    const FILE_EVENT_TYPES = ["file-created", "file-modified", "file-deleted", "renamed"];
+
+    function isRelevant(evt) {
+        // Requested reloads are for every page, or for one page in particular.
+        if (evt.type === "reload-requested") {
+            return evt.page === undefined || listingUrlPath === undefined || evt.page === listingUrlPath;
+        }
+        if (!FILE_EVENT_TYPES.includes(evt.type)) {
+            return false;
+        }
+        // Directory listings only care about entries appearing and disappearing in the directory.
+        if (listingUrlPath !== undefined) {
+            return [evt.path, evt.from]
+                .filter((urlPath) => urlPath !== undefined)
+                .some((urlPath) => parentUrlPath(urlPath) === listingUrlPath);
+        }
+        return true;
+    }
+
+    // URL path of a same-origin URL, or undefined for other origins.
+    function sameOriginUrlPath(href, base = location.href) {
+        let url = new URL(href, base);
+        return url.origin === location.origin ? url.pathname : undefined;
+    }
+
+    // A URL with a query parameter that makes the browser fetch it anew.
+    function cacheBusted(href, base = location.href) {
+        let url = new URL(href, base);
+        url.searchParams.set("http-horse-t", Date.now());
+        return url.href;
+    }
+
+    // Rules of a stylesheet, or of a grouping rule like @media, or none if they cannot be read.
+    function cssRules(sheetOrRule) {
+        try {
+            return Array.from(sheetOrRule.cssRules || []);
+        } catch (e) {
+            // Rules of stylesheets from other origins cannot be read.
+            return [];
+        }
+    }
+
+    // Whether a stylesheet imports the stylesheet at a URL path, directly or indirectly.
+    function importsStylesheet(sheet, urlPath) {
+        return cssRules(sheet).some((rule) =>
+            rule instanceof CSSImportRule && rule.styleSheet !== null
+                && (sameOriginUrlPath(rule.href) === urlPath || importsStylesheet(rule.styleSheet, urlPath)));
+    }
+
+    // Stylesheet links of the page that load the stylesheet at a URL path.
+    function stylesheetLinks(urlPath) {
+        return Array.from(document.querySelectorAll("link[rel~=stylesheet][href]"))
+            .filter((link) => sameOriginUrlPath(link.href) === urlPath
+                || (link.sheet !== null && importsStylesheet(link.sheet, urlPath)));
+    }
+
+    // Replace a stylesheet link with one that bypasses the browser cache. The old link is
+    // removed once the new stylesheet has loaded, so that the page is never left unstyled.
+    function swapStylesheet(link) {
+        let newLink = link.cloneNode();
+        newLink.href = cacheBusted(link.href);
+        newLink.onload = newLink.onerror = () => link.remove();
+        link.after(newLink);
+    }
+
+    // A CSS value with the `url()` references to a URL path cache busted,
+    // or undefined if it has none. Relative URLs are resolved against the given base.
+    function withSwappedUrls(value, urlPath, base) {
+        let swapped = false;
+        let newValue = value.replace(/url\(\s*(['"]?)(.*?)\1\s*\)/g, (match, quote, href) => {
+            if (sameOriginUrlPath(href, base) !== urlPath) {
+                return match;
+            }
+            swapped = true;
+            return `url("${cacheBusted(href, base)}")`;
+        });
+        return swapped ? newValue : undefined;
+    }
+
+    // A `srcset` value with the candidates for a URL path cache busted, or undefined if it has none.
+    function withSwappedSrcset(srcset, urlPath) {
+        let swapped = false;
+        let candidates = srcset.split(",").map((candidate) => {
+            let [href, ...descriptors] = candidate.trim().split(/\s+/);
+            if (href === "" || sameOriginUrlPath(href) !== urlPath) {
+                return candidate;
+            }
+            swapped = true;
+            return [cacheBusted(href), ...descriptors].join(" ");
+        });
+        return swapped ? candidates.join(", ") : undefined;
+    }
+
+    // Make the page fetch the image at a URL path anew, wherever it references it: in images,
+    // icons, inline styles and stylesheet rules. Returns how many references were swapped.
+    function swapAsset(urlPath) {
+        let n = 0;
+        for (let el of document.querySelectorAll("img[src], link[rel~=icon][href], input[type=image][src]")) {
+            let attr = el.localName === "link" ? "href" : "src";
+            if (sameOriginUrlPath(el.getAttribute(attr)) === urlPath) {
+                el.setAttribute(attr, cacheBusted(el.getAttribute(attr)));
+                n++;
+            }
+        }
+        for (let el of document.querySelectorAll("img[srcset], source[srcset]")) {
+            let srcset = withSwappedSrcset(el.getAttribute("srcset"), urlPath);
+            if (srcset !== undefined) {
+                el.setAttribute("srcset", srcset);
+                n++;
+            }
+        }
+        for (let el of document.querySelectorAll("[style*='url(']")) {
+            let style = withSwappedUrls(el.getAttribute("style"), urlPath, location.href);
+            if (style !== undefined) {
+                el.setAttribute("style", style);
+                n++;
+            }
+        }
+        let swapInRules = (rules, base) => {
+            for (let rule of rules) {
+                if (rule instanceof CSSImportRule && rule.styleSheet !== null) {
+                    swapInRules(cssRules(rule.styleSheet), rule.styleSheet.href || base);
+                } else if (rule.style !== undefined) {
+                    for (let name of Array.from(rule.style)) {
+                        let value = withSwappedUrls(rule.style.getPropertyValue(name), urlPath, base);
+                        if (value !== undefined) {
+                            rule.style.setProperty(name, value, rule.style.getPropertyPriority(name));
+                            n++;
+                        }
+                    }
+                }
+                // Grouping rules, like @media and @supports, have rules of their own.
+                if (!(rule instanceof CSSImportRule)) {
+                    swapInRules(cssRules(rule), base);
+                }
+            }
+        };
+        for (let sheet of document.styleSheets) {
+            swapInRules(cssRules(sheet), sheet.href || location.href);
+        }
+        return n;
+    }
+
+    // Report errors in the page to http-horse, for the status web-ui to show. A page stuck
+    // in a loop of errors stops reporting them after a while, to not flood the history.
+    const MAX_REPORTED_ERRORS = 50;
+    let nReportedErrors = 0;
+
+    function reportError(kind, message, source, stack) {
+        if (nReportedErrors++ >= MAX_REPORTED_ERRORS) {
+            return;
+        }
+        let report = { kind, page: location.href, message, source, stack };
+        fetch("/.http-horse/client-errors", {
+            method: "POST",
+            headers: { "Content-Type": "application/json" },
+            body: JSON.stringify(report),
+            keepalive: true,
+        }).catch(() => {});
+    }
+
+    function formatConsoleArg(arg) {
+        if (arg instanceof Error) {
+            return arg.message;
+        }
+        if (typeof arg === "object" && arg !== null) {
+            try {
+                return JSON.stringify(arg);
+            } catch (e) {
+                // Cyclic objects, for example.
+            }
+        }
+        return String(arg);
+    }
+
+    window.addEventListener("error", function (evt) {
+        let source = evt.filename ? `${evt.filename}:${evt.lineno}:${evt.colno}` : undefined;
+        let stack = evt.error instanceof Error ? evt.error.stack : undefined;
+        reportError("uncaught-exception", evt.message, source, stack);
+    });
+
+    window.addEventListener("unhandledrejection", function (evt) {
+        let reason = evt.reason;
+        let message = reason instanceof Error ? reason.message : formatConsoleArg(reason);
+        reportError("unhandled-rejection", message, undefined, reason instanceof Error ? reason.stack : undefined);
+    });
+
+    let consoleError = console.error;
+    console.error = function (...args) {
+        let error = args.find((arg) => arg instanceof Error);
+        reportError("console-error", args.map(formatConsoleArg).join(" "), undefined, error && error.stack);
+        return consoleError.apply(this, args);
+    };
+
+    // Pages opened with `?horse-noreload`, or whose reloads were paused with the toolbar,
+    // are not reloaded by themselves, for when the state of the page is being debugged.
+    // Stylesheets and images are still swapped in place.
+    const NO_RELOAD_STORAGE_KEY = "http-horse-noreload";
+    let reloadsPaused = new URLSearchParams(location.search).has("horse-noreload")
+        || sessionStorage.getItem(NO_RELOAD_STORAGE_KEY) !== null;
+    // Whether there were changes while reloads were paused.
+    let reloadPending = false;
+    let toolbarButton;
+
+    function reloadPage(evt) {
+        if (reloadsPaused) {
+            console.debug("http-horse: Not reloading page due to change, since reloads are paused", evt);
+            reloadPending = true;
+            renderToolbar();
+            return;
+        }
+        console.debug("http-horse: Reloading page due to change", evt);
+        location.reload();
+    }
+
+    function renderToolbar() {
+        toolbarButton.textContent = reloadsPaused
+            ? (reloadPending ? "🐴 Reloads paused (changes pending)" : "🐴 Reloads paused")
+            : "🐴";
+        toolbarButton.title = reloadsPaused ? "Resume automatic reloads" : "Pause automatic reloads of this tab";
+    }
+
+    // Mini-toolbar in a corner of the page, in a shadow root like the error overlay.
+    function showToolbar() {
+        let host = document.createElement("http-horse-toolbar");
+        let shadow = host.attachShadow({ mode: "open" });
+        let style = document.createElement("style");
+        style.textContent = `
+            button { position: fixed; bottom: 0.5rem; right: 0.5rem; z-index: 2147483646;
+                font: 12px/1.5 system-ui, sans-serif; cursor: pointer; opacity: 0.6;
+                background: #222; color: #eee; border: none; border-radius: 4px; padding: 0.2rem 0.5rem; }
+            button:hover { opacity: 1; }
+            @media print { button { display: none; } }
+        `;
+        toolbarButton = document.createElement("button");
+        toolbarButton.type = "button";
+        toolbarButton.onclick = () => {
+            reloadsPaused = !reloadsPaused;
+            if (reloadsPaused) {
+                sessionStorage.setItem(NO_RELOAD_STORAGE_KEY, "");
+                sendHeartbeat();
+            } else {
+                sessionStorage.removeItem(NO_RELOAD_STORAGE_KEY);
+                if (reloadPending) {
+                    location.reload();
+                    return;
+                }
+                sendHeartbeat();
+            }
+            renderToolbar();
+        };
+        renderToolbar();
+        shadow.append(style, toolbarButton);
+        document.documentElement.append(host);
+    }
+
+    showToolbar();
+
+    // URL path of the page, for only getting the changes that affect it.
+    let pageUrlPath = document.currentScript !== null ? document.currentScript.dataset.page : undefined;
+    // Random ID of this tab, which http-horse lists it under among the connected clients.
+    let clientId = Math.random().toString(36).slice(2);
+    let eventSourceParams = new URLSearchParams({ client: clientId });
+    if (pageUrlPath !== undefined) {
+        eventSourceParams.set("page", pageUrlPath);
+    }
+    let eventSource = new EventSource("/.http-horse/events?" + eventSourceParams);
+
+    // Let http-horse know that the tab is still open, and whether its reloads are paused.
+    const HEARTBEAT_INTERVAL_MS = 15000;
+
+    function sendHeartbeat() {
+        fetch("/.http-horse/heartbeat", {
+            method: "POST",
+            headers: { "Content-Type": "application/json" },
+            body: JSON.stringify({ client: clientId, url: location.href, reloads_paused: reloadsPaused }),
+        }).catch(() => {});
+    }
+
+    eventSource.onopen = sendHeartbeat;
+    setInterval(sendHeartbeat, HEARTBEAT_INTERVAL_MS);
+
+    eventSource.onmessage = function (msg) {
+        let evt = JSON.parse(msg.data);
+        if (isRelevant(evt)) {
+            reloadPage(evt);
+        }
+    };
+
+    eventSource.addEventListener("css-update", function (msg) {
+        let evt = JSON.parse(msg.data);
+        if (listingUrlPath !== undefined) {
+            if (isRelevant(evt)) {
+                reloadPage(evt);
+            }
+            return;
+        }
+        // A stylesheet of the page that was renamed away is gone, and the page needs reloading.
+        if (evt.from !== undefined && stylesheetLinks(evt.from).length > 0) {
+            reloadPage(evt);
+            return;
+        }
+        let links = stylesheetLinks(evt.path);
+        if (links.length > 0) {
+            console.debug("http-horse: Swapping stylesheets due to change", evt);
+            links.forEach(swapStylesheet);
+        }
+    });
+
+    // Full-screen overlay showing errors, like problems with the HTML of the page.
+    // It lives in a shadow root, so that the styles of the page and of the overlay stay apart.
+    let overlayHost;
+
+    // Whether errors in the build output can be opened in an editor, as started with `--editor`.
+    let editorEnabled = document.currentScript !== null && document.currentScript.dataset.editor !== undefined;
+
+    function openInEditor(diagnostic) {
+        fetch("/.http-horse/open-in-editor", {
+            method: "POST",
+            headers: { "Content-Type": "application/json" },
+            body: JSON.stringify({ file: diagnostic.file, line: diagnostic.line, column: diagnostic.column }),
+        }).catch((err) => console.error("http-horse: Failed to open file in editor", err));
+    }
+
+    // An error or warning at a place in a source file, which opens the file there when clicked.
+    function renderDiagnostic(diagnostic) {
+        let li = document.createElement("li");
+        li.className = diagnostic.severity;
+        let location = `${diagnostic.file}:${diagnostic.line}:${diagnostic.column}`;
+        let code = diagnostic.code !== undefined ? ` [${diagnostic.code}]` : "";
+        let place;
+        if (editorEnabled) {
+            place = document.createElement("button");
+            place.className = "location";
+            place.title = "Open in editor";
+            place.onclick = () => openInEditor(diagnostic);
+        } else {
+            place = document.createElement("span");
+        }
+        place.textContent = location;
+        li.append(place, ` ${diagnostic.severity}${code}: ${diagnostic.message}`);
+        return li;
+    }
+
+    function closeOverlay() {
+        if (overlayHost !== undefined) {
+            overlayHost.remove();
+            overlayHost = undefined;
+        }
+    }
+
+    function showOverlay(reports) {
+        closeOverlay();
+        overlayHost = document.createElement("http-horse-error-overlay");
+        let shadow = overlayHost.attachShadow({ mode: "open" });
+        let style = document.createElement("style");
+        style.textContent = `
+            .overlay { position: fixed; inset: 0; z-index: 2147483647; overflow: auto;
+                background: rgba(20, 20, 20, 0.92); color: #eee; padding: 2rem;
+                font: 14px/1.5 ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; }
+            h2 { color: #ff7b72; font-size: 1.1rem; margin: 0 0 0.5rem; }
+            pre { white-space: pre-wrap; margin: 0 0 2rem; }
+            button { position: fixed; top: 1rem; right: 1rem; font: inherit; cursor: pointer;
+                background: none; color: inherit; border: 1px solid currentColor; border-radius: 4px; }
+            ul { list-style: none; margin: 0 0 1rem; padding: 0; }
+            li.error { color: #ff7b72; }
+            li.warning { color: #e3b341; }
+            button.location { position: static; border: none; padding: 0; text-decoration: underline; }
+        `;
+        let overlay = document.createElement("div");
+        overlay.className = "overlay";
+        overlay.setAttribute("role", "alertdialog");
+        let dismiss = document.createElement("button");
+        dismiss.textContent = "Dismiss (Esc)";
+        dismiss.onclick = closeOverlay;
+        overlay.append(dismiss);
+        for (let report of reports) {
+            let title = document.createElement("h2");
+            title.textContent = report.title;
+            let output = document.createElement("pre");
+            output.textContent = report.output;
+            if (report.diagnostics !== undefined) {
+                let list = document.createElement("ul");
+                list.append(...report.diagnostics.map(renderDiagnostic));
+                overlay.append(title, list, output);
+            } else {
+                overlay.append(title, output);
+            }
+        }
+        shadow.append(style, overlay);
+        document.documentElement.append(overlayHost);
+    }
+
+    document.addEventListener("keydown", function (evt) {
+        if (evt.key === "Escape") {
+            closeOverlay();
+        }
+    });
+
+    eventSource.addEventListener("error-overlay", function (msg) {
+        let reports = JSON.parse(msg.data);
+        if (reports.length > 0) {
+            console.debug("http-horse: Showing errors", reports);
+            showOverlay(reports);
+        } else {
+            closeOverlay();
+        }
+    });
+
+    // Synchronized browsing: scrolls, clicks and navigation are posted to http-horse,
+    // which mirrors them in the other pages open in browsers.
+    if (document.currentScript !== null && document.currentScript.dataset.sync !== undefined) {
+        const SCROLL_INTERVAL_MS = 50;
+        let syncPage = pageUrlPath !== undefined ? pageUrlPath : location.pathname;
+        // Set while applying an event from another client, so that it is not posted back.
+        let applying = false;
+
+        function postSync(evt) {
+            if (applying) {
+                return;
+            }
+            fetch("/.http-horse/sync", {
+                method: "POST",
+                headers: { "Content-Type": "application/json" },
+                body: JSON.stringify({ client: clientId, page: syncPage, ...evt }),
+                keepalive: true,
+            }).catch(() => {});
+        }
+
+        // A CSS selector for an element, by ID where there is one, and by position otherwise.
+        function selectorFor(el) {
+            let parts = [];
+            for (; el instanceof Element && el !== document.documentElement; el = el.parentElement) {
+                if (el.id !== "") {
+                    parts.unshift("#" + CSS.escape(el.id));
+                    break;
+                }
+                let index = Array.from(el.parentElement.children).indexOf(el) + 1;
+                parts.unshift(`${el.localName}:nth-child(${index})`);
+            }
+            if (parts.length === 0 || !parts[0].startsWith("#")) {
+                parts.unshift(":root");
+            }
+            return parts.join(" > ");
+        }
+
+        function scrollFraction(scrolled, scrollable) {
+            return scrollable > 0 ? Math.min(Math.max(scrolled / scrollable, 0), 1) : 0;
+        }
+
+        let scrollTimer;
+        window.addEventListener("scroll", function () {
+            if (applying || scrollTimer !== undefined) {
+                return;
+            }
+            scrollTimer = setTimeout(() => {
+                scrollTimer = undefined;
+                let root = document.documentElement;
+                postSync({
+                    action: "scroll",
+                    x: scrollFraction(window.scrollX, root.scrollWidth - window.innerWidth),
+                    y: scrollFraction(window.scrollY, root.scrollHeight - window.innerHeight),
+                });
+            }, SCROLL_INTERVAL_MS);
+        }, { passive: true });
+
+        document.addEventListener("click", function (evt) {
+            if (!evt.isTrusted || !(evt.target instanceof Element)
+                || evt.target.closest("http-horse-error-overlay, http-horse-toolbar") !== null) {
+                return;
+            }
+            let link = evt.target.closest("a[href]");
+            if (link !== null && sameOriginUrlPath(link.href) !== undefined) {
+                // Followed same-origin links are mirrored as navigation, once the new page has loaded.
+                return;
+            }
+            postSync({ action: "click", selector: selectorFor(evt.target) });
+        }, true);
+
+        // Navigation is posted by the page navigated to, which is how links, forms
+        // and the back button are all covered. Pages navigated to by a sync event do not post it again.
+        let navigatedBySync = sessionStorage.getItem("http-horse-sync-navigated") === location.pathname + location.search;
+        sessionStorage.removeItem("http-horse-sync-navigated");
+        if (!navigatedBySync && document.referrer !== "" && sameOriginUrlPath(document.referrer) !== undefined) {
+            postSync({ action: "navigate", url: location.pathname + location.search });
+        }
+
+        eventSource.addEventListener("sync", function (msg) {
+            let evt = JSON.parse(msg.data);
+            if (evt.client === clientId) {
+                return;
+            }
+            applying = true;
+            try {
+                if (evt.action === "navigate") {
+                    if (evt.url !== location.pathname + location.search) {
+                        sessionStorage.setItem("http-horse-sync-navigated", evt.url);
+                        location.href = evt.url;
+                    }
+                } else if (evt.page !== syncPage) {
+                    return;
+                } else if (evt.action === "scroll") {
+                    let root = document.documentElement;
+                    window.scrollTo(evt.x * (root.scrollWidth - window.innerWidth), evt.y * (root.scrollHeight - window.innerHeight));
+                } else if (evt.action === "click") {
+                    let el = document.querySelector(evt.selector);
+                    if (el !== null) {
+                        el.click();
+                    }
+                }
+            } finally {
+                // Scroll events from applying a scroll are dispatched later, so they are ignored for a moment longer.
+                setTimeout(() => { applying = false; }, SCROLL_INTERVAL_MS * 2);
+            }
+        });
+    }
+
+    // Changed JavaScript modules are replaced in place by the hot module replacement runtime,
+    // if the page loads it and the module accepts being replaced, and reload the page otherwise.
+    eventSource.addEventListener("module-update", function (msg) {
+        let evt = JSON.parse(msg.data);
+        if (listingUrlPath !== undefined) {
+            if (isRelevant(evt)) {
+                reloadPage(evt);
+            }
+            return;
+        }
+        let reload = () => reloadPage(evt);
+        let hmr = window.__httpHorseHmr;
+        if (hmr === undefined) {
+            reload();
+            return;
+        }
+        hmr.update(evt.path).then((replaced) => {
+            if (replaced) {
+                console.debug("http-horse: Replaced module due to change", evt);
+            } else {
+                reload();
+            }
+        }, (e) => {
+            console.debug("http-horse: Failed to replace module", e);
+            reload();
+        });
+    });
+
+    eventSource.addEventListener("asset-update", function (msg) {
+        let evt = JSON.parse(msg.data);
+        if (listingUrlPath !== undefined) {
+            if (isRelevant(evt)) {
+                reloadPage(evt);
+            }
+            return;
+        }
+        let n = swapAsset(evt.path);
+        if (n > 0) {
+            console.debug(`http-horse: Swapped ${n} references to image due to change`, evt);
+        }
+    });
+})();